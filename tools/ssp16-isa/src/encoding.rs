@@ -0,0 +1,400 @@
+use crate::mnemonics::SspMnemonic;
+use crate::mnemonics::SspMnemonic::*;
+use crate::mnemonics::SspMnemonicModifier::{Immediate, Reference};
+use crate::operators::{
+    SspMnemonicAccOperation, SspMnemonicCondition, SspMnemonicConditionFlag,
+    SspMnemonicFlagOperation, SspOperator,
+};
+use crate::registers::{SspGeneralRegister, SspPointerRegister};
+
+// Operand kinds. Each one packs its operand into a number that is stored in the bits of a field
+// mask (lowest bits first, see `deposit`).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Kind {
+    // Accumulator, implied by the opcode
+    A,
+    // General register number
+    Reg,
+    // ri: pointer register number (0-7)
+    Ptr,
+    // (ri) and ((ri)): bank << 4 | modifier << 2 | register
+    PtrRef,
+    PtrDoubleRef,
+    // (ri) of r0-r3 and (rj) of r4-r7 (multiply ops): modifier << 2 | register
+    BankARef,
+    BankBRef,
+    // (a), implied by the opcode
+    PtrAccumulator,
+    RamA,
+    RamB,
+    Byte,
+    // Direct RAM address, the highest bit selects the bank
+    Addr,
+    // Immediate word, stored in the second word
+    Imm,
+    // flag << 4 | condition
+    Cond,
+    // f, implied by the opcode
+    Flag,
+    FlagOp,
+    AccOp,
+}
+
+// Field masks:
+const NONE: u16 = 0;
+const LOW: u16 = 0x00FF;
+const ADDR: u16 = 0x01FF;
+const DST: u16 = 0x00F0;
+const SRC: u16 = 0x000F;
+const RI: u16 = 0x0103;
+const RI_REF: u16 = 0x010F;
+const RI_NUMBER: u16 = 0x0700;
+const RJ_DSP: u16 = 0x00F0;
+const RI_DSP: u16 = 0x000F;
+const COND: u16 = 0x01F0;
+const OP: u16 = 0x000F;
+const ACC_OP: u16 = 0x0007;
+
+use Kind::*;
+
+#[derive(Debug)]
+pub struct Form {
+    pub mnemonic: SspMnemonic,
+    pub operands: &'static [(Kind, u16)],
+    pub opcode: u16,
+    pub words: u16,
+}
+
+const fn form(
+    mnemonic: SspMnemonic,
+    operands: &'static [(Kind, u16)],
+    opcode: u16,
+    words: u16,
+) -> Form {
+    Form {
+        mnemonic,
+        operands,
+        opcode,
+        words,
+    }
+}
+
+// Every instruction form of the SSP16xx. When several forms share an encoding the first one is
+// the one decoded.
+#[rustfmt::skip]
+pub const FORMS: [Form; 67] = [
+    // RET (LD PC, STACK)
+    form(Ret, &[], 0x0065, 1),
+
+    // **** Arithmetic ops ****
+
+    // OPi simm
+    form(Sub(Immediate), &[(Byte, LOW)], 0x3800, 1),
+    form(Cmp(Immediate), &[(Byte, LOW)], 0x7800, 1),
+    form(Add(Immediate), &[(Byte, LOW)], 0x9800, 1),
+    form(And(Immediate), &[(Byte, LOW)], 0xB800, 1),
+    form(Or(Immediate), &[(Byte, LOW)], 0xD800, 1),
+    form(Eor(Immediate), &[(Byte, LOW)], 0xF800, 1),
+
+    // OPi A, imm
+    form(Sub(Immediate), &[(A, NONE), (Imm, NONE)], 0x2800, 2),
+    form(Cmp(Immediate), &[(A, NONE), (Imm, NONE)], 0x6800, 2),
+    form(Add(Immediate), &[(A, NONE), (Imm, NONE)], 0x8800, 2),
+    form(And(Immediate), &[(A, NONE), (Imm, NONE)], 0xA800, 2),
+    form(Or(Immediate), &[(A, NONE), (Imm, NONE)], 0xC800, 2),
+    form(Eor(Immediate), &[(A, NONE), (Imm, NONE)], 0xE800, 2),
+
+    // OP A, s
+    form(Sub(Reference), &[(A, NONE), (Reg, SRC)], 0x2000, 1),
+    form(Cmp(Reference), &[(A, NONE), (Reg, SRC)], 0x6000, 1),
+    form(Add(Reference), &[(A, NONE), (Reg, SRC)], 0x8000, 1),
+    form(And(Reference), &[(A, NONE), (Reg, SRC)], 0xA000, 1),
+    form(Or(Reference), &[(A, NONE), (Reg, SRC)], 0xC000, 1),
+    form(Eor(Reference), &[(A, NONE), (Reg, SRC)], 0xE000, 1),
+
+    // OP A, ri
+    form(Sub(Reference), &[(A, NONE), (Ptr, RI)], 0x3200, 1),
+    form(Cmp(Reference), &[(A, NONE), (Ptr, RI)], 0x7200, 1),
+    form(Add(Reference), &[(A, NONE), (Ptr, RI)], 0x9200, 1),
+    form(And(Reference), &[(A, NONE), (Ptr, RI)], 0xB200, 1),
+    form(Or(Reference), &[(A, NONE), (Ptr, RI)], 0xD200, 1),
+    form(Eor(Reference), &[(A, NONE), (Ptr, RI)], 0xF200, 1),
+
+    // OP A, (ri)
+    form(Sub(Reference), &[(A, NONE), (PtrRef, RI_REF)], 0x2200, 1),
+    form(Cmp(Reference), &[(A, NONE), (PtrRef, RI_REF)], 0x6200, 1),
+    form(Add(Reference), &[(A, NONE), (PtrRef, RI_REF)], 0x8200, 1),
+    form(And(Reference), &[(A, NONE), (PtrRef, RI_REF)], 0xA200, 1),
+    form(Or(Reference), &[(A, NONE), (PtrRef, RI_REF)], 0xC200, 1),
+    form(Eor(Reference), &[(A, NONE), (PtrRef, RI_REF)], 0xE200, 1),
+
+    // OP A, ((ri))
+    form(Sub(Reference), &[(A, NONE), (PtrDoubleRef, RI_REF)], 0x2A00, 1),
+    form(Cmp(Reference), &[(A, NONE), (PtrDoubleRef, RI_REF)], 0x6A00, 1),
+    form(Add(Reference), &[(A, NONE), (PtrDoubleRef, RI_REF)], 0x8A00, 1),
+    form(And(Reference), &[(A, NONE), (PtrDoubleRef, RI_REF)], 0xAA00, 1),
+    form(Or(Reference), &[(A, NONE), (PtrDoubleRef, RI_REF)], 0xCA00, 1),
+    form(Eor(Reference), &[(A, NONE), (PtrDoubleRef, RI_REF)], 0xEA00, 1),
+
+    // OP A, addr
+    form(Sub(Reference), &[(A, NONE), (Addr, ADDR)], 0x2600, 1),
+    form(Cmp(Reference), &[(A, NONE), (Addr, ADDR)], 0x6600, 1),
+    form(Add(Reference), &[(A, NONE), (Addr, ADDR)], 0x8600, 1),
+    form(And(Reference), &[(A, NONE), (Addr, ADDR)], 0xA600, 1),
+    form(Or(Reference), &[(A, NONE), (Addr, ADDR)], 0xC600, 1),
+    form(Eor(Reference), &[(A, NONE), (Addr, ADDR)], 0xE600, 1),
+
+    // MOD f, op
+    form(Mod, &[(Flag, NONE), (FlagOp, OP)], 0x9400, 1),
+
+    // **** Load instructions ****
+
+    // LD d, s
+    form(Ld(Reference), &[(Reg, DST), (Reg, SRC)], 0x0000, 1),
+    // LD d, (ri)
+    form(Ld(Reference), &[(Reg, DST), (PtrRef, RI_REF)], 0x0200, 1),
+    // LD (ri), s
+    form(Ld(Reference), &[(PtrRef, RI_REF), (Reg, DST)], 0x0400, 1),
+    // LD a, A[addr] and LD a, B[addr]
+    form(Ld(Reference), &[(A, NONE), (RamA, LOW)], 0x0600, 1),
+    form(Ld(Reference), &[(A, NONE), (RamB, LOW)], 0x0700, 1),
+    // LDI d, imm
+    form(Ld(Reference), &[(Reg, DST), (Imm, NONE)], 0x0800, 2),
+    form(Ld(Immediate), &[(Reg, DST), (Imm, NONE)], 0x0800, 2),
+    // LD d, ((ri))
+    form(Ld(Reference), &[(Reg, DST), (PtrDoubleRef, RI_REF)], 0x0A00, 1),
+    // LDI (ri), imm
+    form(Ld(Reference), &[(PtrRef, RI_REF), (Imm, NONE)], 0x0C00, 2),
+    form(Ld(Immediate), &[(PtrRef, RI_REF), (Imm, NONE)], 0x0C00, 2),
+    // LD A[addr], a and LD B[addr], a
+    form(Ld(Reference), &[(RamA, LOW), (A, NONE)], 0x0E00, 1),
+    form(Ld(Reference), &[(RamB, LOW), (A, NONE)], 0x0F00, 1),
+    // LD d, ri
+    form(Ld(Reference), &[(Reg, DST), (Ptr, RI)], 0x1200, 1),
+    // LD ri, s
+    form(Ld(Reference), &[(Ptr, RI), (Reg, DST)], 0x1400, 1),
+    // LDI ri, simm
+    form(Ld(Reference), &[(Ptr, RI_NUMBER), (Byte, LOW)], 0x1800, 1),
+    form(Ld(Immediate), &[(Ptr, RI_NUMBER), (Byte, LOW)], 0x1800, 1),
+    // LD d, (a)
+    form(Ld(Reference), &[(Reg, DST), (PtrAccumulator, NONE)], 0x4A00, 1),
+
+    // **** Program control ****
+
+    // CALL cond, addr
+    form(Call, &[(Cond, COND), (Imm, NONE)], 0x4800, 2),
+    // BRA cond, addr
+    form(Bra, &[(Cond, COND), (Imm, NONE)], 0x4C00, 2),
+    // MOD cond, op
+    form(Mod, &[(Cond, COND), (AccOp, ACC_OP)], 0x9000, 1),
+
+    // **** Multiply/accumulate ****
+
+    // MLD (rj), (ri)
+    form(Mld, &[(BankBRef, RJ_DSP), (BankARef, RI_DSP)], 0xB700, 1),
+    // MPYA (rj), (ri)
+    form(Mpya, &[(BankBRef, RJ_DSP), (BankARef, RI_DSP)], 0x9700, 1),
+    // MPYS (rj), (ri)
+    form(Mpys, &[(BankBRef, RJ_DSP), (BankARef, RI_DSP)], 0x3700, 1),
+];
+
+impl Kind {
+    // The number stored for an operand, if it is of this kind:
+    pub fn value(self, operand: &SspOperator) -> Option<u16> {
+        match (self, operand) {
+            (A, SspOperator::Reg(SspGeneralRegister::A)) => Some(0),
+            (Reg, SspOperator::Reg(register)) => Some(register.value() as u16),
+            (Ptr, SspOperator::Ptr(register)) => Some(number(register)),
+            (PtrRef, SspOperator::PtrRef(register))
+            | (PtrDoubleRef, SspOperator::PtrDoubleRef(register)) => {
+                Some((register.ram_bank() as u16) << 4 | reference(register))
+            }
+            (BankARef, SspOperator::PtrRef(register)) if register.ram_bank() == 0 => {
+                Some(reference(register))
+            }
+            (BankBRef, SspOperator::PtrRef(register)) if register.ram_bank() == 1 => {
+                Some(reference(register))
+            }
+            (PtrAccumulator, SspOperator::PtrAccumulator) => Some(0),
+            (RamA, SspOperator::RamBankAddressA(addr)) => Some(*addr as u16),
+            (RamB, SspOperator::RamBankAddressB(addr)) => Some(*addr as u16),
+            (Byte, SspOperator::Byte(value)) => Some(*value as u16),
+            (Addr, SspOperator::Byte(addr)) => Some(*addr as u16),
+            (Addr, SspOperator::Word(addr)) if *addr <= ADDR => Some(*addr),
+            (Imm, SspOperator::Word(value)) => Some(*value),
+            (Cond, SspOperator::Condition(cond)) => {
+                Some((cond.flag_value() as u16) << 4 | cond.value() as u16)
+            }
+            (Flag, SspOperator::ConditionFlag(_)) => Some(0),
+            (FlagOp, SspOperator::FlagOperation(op)) => Some(op.value() as u16),
+            (AccOp, SspOperator::AccOperation(op)) => Some(op.value() as u16),
+            _ => None,
+        }
+    }
+
+    // The operand a stored number stands for:
+    pub fn operand(self, value: u16) -> Option<SspOperator<'static>> {
+        let register = |bank: u16| {
+            SspPointerRegister::from_values((bank << 2 | value & 3) as u8, (value >> 2 & 3) as u8)
+        };
+
+        match self {
+            A => Some(SspOperator::Reg(SspGeneralRegister::A)),
+            Reg => SspGeneralRegister::from_value(value as u8).map(SspOperator::Reg),
+            Ptr => SspPointerRegister::from_values(value as u8, 0).map(SspOperator::Ptr),
+            PtrRef => register(value >> 4).map(SspOperator::PtrRef),
+            PtrDoubleRef => register(value >> 4).map(SspOperator::PtrDoubleRef),
+            BankARef => register(0).map(SspOperator::PtrRef),
+            BankBRef => register(1).map(SspOperator::PtrRef),
+            PtrAccumulator => Some(SspOperator::PtrAccumulator),
+            RamA => Some(SspOperator::RamBankAddressA(value as u8)),
+            RamB => Some(SspOperator::RamBankAddressB(value as u8)),
+            Byte => Some(SspOperator::Byte(value as u8)),
+            Addr if value <= LOW => Some(SspOperator::Byte(value as u8)),
+            Addr | Imm => Some(SspOperator::Word(value)),
+            Cond => SspMnemonicCondition::from_values((value & 0xF) as u8, (value >> 4) as u8)
+                .map(SspOperator::Condition),
+            Flag => Some(SspOperator::ConditionFlag(SspMnemonicConditionFlag::F)),
+            FlagOp => {
+                SspMnemonicFlagOperation::from_value(value as u8).map(SspOperator::FlagOperation)
+            }
+            AccOp => {
+                SspMnemonicAccOperation::from_value(value as u8).map(SspOperator::AccOperation)
+            }
+        }
+    }
+
+    // Numbers and labels, the operands whose kind depends on their value:
+    pub fn is_value(self) -> bool {
+        matches!(self, Byte | Addr | Imm)
+    }
+}
+
+fn number(register: &SspPointerRegister) -> u16 {
+    (register.ram_bank() << 2 | register.value()) as u16
+}
+
+fn reference(register: &SspPointerRegister) -> u16 {
+    (register.modifier_value() << 2 | register.value()) as u16
+}
+
+// Stores `value` in the bits of `mask`, lowest bits first. Fails if it doesn't fit.
+pub fn deposit(value: u16, mask: u16) -> Option<u16> {
+    let mut word = 0;
+    let mut remaining = value;
+    for bit in (0..16).filter(|bit| mask & 1 << bit != 0) {
+        word |= (remaining & 1) << bit;
+        remaining >>= 1;
+    }
+    if remaining == 0 {
+        Some(word)
+    } else {
+        None
+    }
+}
+
+pub fn extract(word: u16, mask: u16) -> u16 {
+    (0..16)
+        .filter(|bit| mask & 1 << bit != 0)
+        .enumerate()
+        .fold(0, |value, (index, bit)| value | (word >> bit & 1) << index)
+}
+
+impl Form {
+    // Bits taken by operands:
+    pub fn fields(&self) -> u16 {
+        self.operands
+            .iter()
+            .fold(0, |fields, (_, mask)| fields | mask)
+    }
+
+    pub fn matches(&self, mnemonic: SspMnemonic, operands: &[SspOperator]) -> bool {
+        self.mnemonic == mnemonic
+            && self.operands.len() == operands.len()
+            && self
+                .operands
+                .iter()
+                .zip(operands)
+                .all(|((kind, _), operand)| kind.value(operand).is_some())
+    }
+
+    // Shallow version of `matches`, numbers and labels fit any kind of value:
+    pub fn resembles(&self, mnemonic: SspMnemonic, operands: &[SspOperator]) -> bool {
+        self.mnemonic == mnemonic
+            && self.operands.len() == operands.len()
+            && self
+                .operands
+                .iter()
+                .zip(operands)
+                .all(|((kind, _), operand)| match operand {
+                    SspOperator::Byte(_) | SspOperator::Word(_) | SspOperator::LabelRef(_) => {
+                        kind.is_value()
+                    }
+                    _ => kind.value(operand).is_some(),
+                })
+    }
+
+    pub fn encode(&self, operands: &[SspOperator]) -> Option<Vec<u16>> {
+        let mut words = vec![self.opcode];
+        for ((kind, mask), operand) in self.operands.iter().zip(operands) {
+            let value = kind.value(operand)?;
+            if *kind == Imm {
+                words.push(value);
+            } else {
+                words[0] |= deposit(value, *mask)?;
+            }
+        }
+        Some(words)
+    }
+
+    // Operands of a first word (and the word after it) encoded with this form:
+    pub fn decode(&self, word: u16, next: u16) -> Option<Vec<SspOperator<'static>>> {
+        if word & !self.fields() != self.opcode {
+            return None;
+        }
+        self.operands
+            .iter()
+            .map(|(kind, mask)| match kind {
+                Imm => kind.operand(next),
+                _ => kind.operand(extract(word, *mask)),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod encoding_tests {
+    use super::*;
+
+    #[test]
+    fn check_fields() {
+        assert_eq!(deposit(0b101, RI), Some(0x0101));
+        assert_eq!(deposit(0x1F, COND), Some(0x01F0));
+        assert_eq!(deposit(0x8, ACC_OP), None);
+        assert_eq!(extract(0x0101, RI), 0b101);
+        assert_eq!(extract(0x4D50, COND), 0x15);
+    }
+
+    #[test]
+    fn check_forms_are_consistent() {
+        for form in FORMS.iter() {
+            assert_eq!(
+                form.operands.iter().map(|(_, mask)| mask).sum::<u16>(),
+                form.fields(),
+                "Overlapping fields: {:?}",
+                form
+            );
+            assert_eq!(form.opcode & form.fields(), 0, "{:?}", form);
+            assert_eq!(
+                form.words,
+                1 + form
+                    .operands
+                    .iter()
+                    .filter(|(kind, _)| *kind == Imm)
+                    .count() as u16,
+                "{:?}",
+                form
+            );
+        }
+    }
+}
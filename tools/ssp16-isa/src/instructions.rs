@@ -0,0 +1,1251 @@
+use crate::encoding;
+use crate::errors;
+use crate::mnemonics;
+use crate::operators;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+// An instruction as written in source: a mnemonic and up to two operands. Label references only
+// appear in instructions borrowed from source, decoded and parsed ones are `Instruction<'static>`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Instruction<'a> {
+    mnemonic: Option<mnemonics::SspMnemonic>,
+    op1: Option<operators::SspOperator<'a>>,
+    op2: Option<operators::SspOperator<'a>>,
+}
+
+impl<'a> Instruction<'a> {
+    pub fn new(mnemonic: mnemonics::SspMnemonic) -> Instruction<'a> {
+        Instruction {
+            mnemonic: Some(mnemonic),
+            op1: None,
+            op2: None,
+        }
+    }
+
+    pub fn with_operands(
+        mnemonic: mnemonics::SspMnemonic,
+        operands: &[operators::SspOperator<'a>],
+    ) -> Instruction<'a> {
+        Instruction {
+            mnemonic: Some(mnemonic),
+            op1: operands.first().copied(),
+            op2: operands.get(1).copied(),
+        }
+    }
+
+    // This function performs a shallow validation on if an instruction should be considered complete,
+    // i.e.: having all parameters in place. It won't check if the instruction is actually valid.
+    pub fn is_complete(&self) -> bool {
+        let (mnemonic, operands) = match self.operands() {
+            Some(operands) => operands,
+            None => return true,
+        };
+        let mut forms = encoding::FORMS.iter().filter(|f| f.mnemonic == mnemonic);
+        let arity = forms.clone().map(|f| f.operands.len()).max().unwrap_or(0);
+
+        operands.len() >= arity || forms.any(|f| f.resembles(mnemonic, &operands))
+    }
+
+    pub fn new_with_op(&self, op: &operators::SspOperator<'a>) -> Instruction<'a> {
+        match (self.mnemonic, self.op1, self.op2) {
+            (Some(_), Some(_), None) => Instruction {
+                mnemonic: self.mnemonic,
+                op1: self.op1,
+                op2: Some(*op),
+            },
+
+            (Some(_), None, None) => Instruction {
+                mnemonic: self.mnemonic,
+                op1: Some(*op),
+                op2: None,
+            },
+
+            _ => *self,
+        }
+    }
+
+    // The form of the encoding table the instruction matches, if it's a valid SSP16xx one:
+    pub fn form(&self) -> Option<&'static encoding::Form> {
+        let (mnemonic, operands) = self.operands()?;
+        encoding::FORMS
+            .iter()
+            .find(|f| f.matches(mnemonic, &operands))
+    }
+
+    // Size in words:
+    pub fn size(&self) -> Option<u16> {
+        self.form().map(|f| f.words)
+    }
+
+    // This function validates that the function we received is a valid SSP16xx one,
+    // and if that's the case it'll build the appropiate opcode words:
+    pub fn encode(&self) -> Result<Vec<u16>, Box<dyn Error>> {
+        let words = self
+            .operands()
+            .and_then(|(_, operands)| self.form()?.encode(&operands));
+
+        match words {
+            Some(words) => Ok(words),
+            None => Err(Box::new(errors::IsaError(format!(
+                "Invalid instruction: {:?}",
+                self
+            )))),
+        }
+    }
+
+    // Same as `encode`, as big endian bytes:
+    pub fn build(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(self
+            .encode()?
+            .into_iter()
+            .flat_map(Instruction::word_to_bytes)
+            .collect())
+    }
+
+    // The instruction starting with `word` (`next` being the word after it, for the ones taking
+    // two words):
+    pub fn decode(word: u16, next: u16) -> Option<Instruction<'static>> {
+        encoding::FORMS.iter().find_map(|f| {
+            let mut operands = f.decode(word, next)?.into_iter();
+            Some(Instruction {
+                mnemonic: Some(f.mnemonic),
+                op1: operands.next(),
+                op2: operands.next(),
+            })
+        })
+    }
+
+    // Mnemonic and operands:
+    pub fn operands(&self) -> Option<(mnemonics::SspMnemonic, Vec<operators::SspOperator<'a>>)> {
+        let operands = [self.op1, self.op2].iter().flatten().copied().collect();
+        Some((self.mnemonic?, operands))
+    }
+
+    // The same instruction, as long as it has no label references:
+    pub fn resolved(&self) -> Option<Instruction<'static>> {
+        self.resolve(|_| None)
+    }
+
+    // An owned copy of the instruction, with label references replaced by the address `address`
+    // gives for them (`None` if it doesn't know one of the labels):
+    pub fn resolve(&self, address: impl Fn(&str) -> Option<u16>) -> Option<Instruction<'static>> {
+        let (mnemonic, operands) = self.operands()?;
+        let operands = operands
+            .iter()
+            .map(|op| op.resolve(&address))
+            .collect::<Option<Vec<operators::SspOperator<'static>>>>()?;
+        Some(Instruction::with_operands(mnemonic, &operands))
+    }
+
+    // Native syntax, borrowing label references from `s` (see `resolve` to get rid of them). Only
+    // the syntax is checked, not if the operands are valid for the mnemonic:
+    pub fn parse(s: &'a str) -> Result<Instruction<'a>, errors::IsaError> {
+        let mut words = s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|word| !word.is_empty());
+        let mnemonic = words
+            .next()
+            .ok_or_else(|| errors::IsaError("Empty instruction".to_string()))?
+            .parse::<mnemonics::SspMnemonic>()?;
+        let operands = words
+            .map(|word| {
+                operators::SspOperator::new(word)
+                    .ok_or_else(|| errors::IsaError(format!("Invalid operator: {}", word)))
+            })
+            .collect::<Result<Vec<operators::SspOperator>, errors::IsaError>>()?;
+        match operands.len() {
+            0..=2 => Ok(Instruction::with_operands(mnemonic, &operands)),
+            _ => Err(errors::IsaError(format!("Too many operands: {}", s))),
+        }
+    }
+
+    pub fn word_to_bytes(word: u16) -> Vec<u8> {
+        vec![((word & 0xFF00) >> 8) as u8, (word & 0xFF) as u8]
+    }
+}
+
+// Native syntax, i.e.: "ld a, (r0+)":
+impl<'a> fmt::Display for Instruction<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.operands() {
+            Some((mnemonic, operands)) if operands.is_empty() => write!(f, "{}", mnemonic),
+            Some((mnemonic, operands)) => write!(
+                f,
+                "{} {}",
+                mnemonic,
+                operands
+                    .iter()
+                    .map(|op| op.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Instruction<'static> {
+    type Err = errors::IsaError;
+
+    fn from_str(s: &str) -> Result<Instruction<'static>, errors::IsaError> {
+        let instruction = Instruction::parse(s)?;
+        let instruction = instruction.resolved().ok_or_else(|| {
+            errors::IsaError(format!("Unresolved label reference: {}", instruction))
+        })?;
+
+        match instruction.form() {
+            Some(_) => Ok(instruction),
+            None => Err(errors::IsaError(format!("Invalid instruction: {}", s))),
+        }
+    }
+}
+
+// Instructions are serialized in native syntax:
+#[cfg(feature = "serde")]
+impl<'a> serde::Serialize for Instruction<'a> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Instruction<'static> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let instruction = String::deserialize(deserializer)?;
+        instruction.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod opcode_tests {
+    use super::*;
+    use crate::registers;
+
+    fn check_inst(inst: &Instruction, expected_result: &Vec<u8>) {
+        let result = inst.build();
+        assert!(result.is_ok(), format!("Should compile: {:?}", inst));
+
+        let opcodes = result.unwrap();
+        assert_eq!(opcodes, *expected_result, "Wrong opcodes for {:?}", inst);
+    }
+
+    #[test]
+    fn check_ret() {
+        let inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Ret),
+            op1: None,
+            op2: None,
+        };
+        check_inst(&inst, &vec![0, 0x65]);
+    }
+
+    #[test]
+    fn check_opi() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Sub(
+                mnemonics::SspMnemonicModifier::Immediate,
+            )),
+            op1: Some(operators::SspOperator::Byte(0x50 as u8)),
+            op2: None,
+        };
+        check_inst(&inst, &vec![0x38, 0x50]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Cmp(
+            mnemonics::SspMnemonicModifier::Immediate,
+        ));
+        check_inst(&inst, &vec![0x78, 0x50]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Add(
+            mnemonics::SspMnemonicModifier::Immediate,
+        ));
+        check_inst(&inst, &vec![0x98, 0x50]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::And(
+            mnemonics::SspMnemonicModifier::Immediate,
+        ));
+        check_inst(&inst, &vec![0xB8, 0x50]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Or(
+            mnemonics::SspMnemonicModifier::Immediate,
+        ));
+        check_inst(&inst, &vec![0xD8, 0x50]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Eor(
+            mnemonics::SspMnemonicModifier::Immediate,
+        ));
+        check_inst(&inst, &vec![0xF8, 0x50]);
+    }
+
+    #[test]
+    fn check_op_imm() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Sub(
+                mnemonics::SspMnemonicModifier::Immediate,
+            )),
+            op1: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::A,
+            )),
+            op2: Some(operators::SspOperator::Word(0x5050)),
+        };
+        check_inst(&inst, &vec![0x28, 0x00, 0x50, 0x50]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Cmp(
+            mnemonics::SspMnemonicModifier::Immediate,
+        ));
+        check_inst(&inst, &vec![0x68, 0x00, 0x50, 0x50]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Add(
+            mnemonics::SspMnemonicModifier::Immediate,
+        ));
+        check_inst(&inst, &vec![0x88, 0x00, 0x50, 0x50]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::And(
+            mnemonics::SspMnemonicModifier::Immediate,
+        ));
+        check_inst(&inst, &vec![0xA8, 0x00, 0x50, 0x50]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Or(
+            mnemonics::SspMnemonicModifier::Immediate,
+        ));
+        check_inst(&inst, &vec![0xC8, 0x00, 0x50, 0x50]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Eor(
+            mnemonics::SspMnemonicModifier::Immediate,
+        ));
+        check_inst(&inst, &vec![0xE8, 0x00, 0x50, 0x50]);
+    }
+
+    #[test]
+    fn check_op_a_reg() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Sub(
+                mnemonics::SspMnemonicModifier::Reference,
+            )),
+            op1: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::A,
+            )),
+            op2: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::X,
+            )),
+        };
+        check_inst(&inst, &vec![0x20, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Cmp(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x60, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Add(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x80, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::And(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xA0, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Or(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xC0, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Eor(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xE0, 0x01]);
+    }
+
+    #[test]
+    fn check_op_a_ptr() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Sub(
+                mnemonics::SspMnemonicModifier::Reference,
+            )),
+            op1: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::A,
+            )),
+            op2: Some(operators::SspOperator::Ptr(
+                registers::SspPointerRegister::R1(registers::SspModifier::Zero),
+            )),
+        };
+        check_inst(&inst, &vec![0x32, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Cmp(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x72, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Add(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x92, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::And(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xB2, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Or(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xD2, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Eor(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xF2, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Sub(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        inst.op2 = Some(operators::SspOperator::Ptr(
+            registers::SspPointerRegister::R5(registers::SspModifier::Zero),
+        ));
+        check_inst(&inst, &vec![0x33, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Cmp(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x73, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Add(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x93, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::And(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xB3, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Or(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xD3, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Eor(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xF3, 0x01]);
+    }
+
+    #[test]
+    fn check_op_a_ptr_ref() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Sub(
+                mnemonics::SspMnemonicModifier::Reference,
+            )),
+            op1: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::A,
+            )),
+            op2: Some(operators::SspOperator::PtrRef(
+                registers::SspPointerRegister::R1(registers::SspModifier::Zero),
+            )),
+        };
+        check_inst(&inst, &vec![0x22, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Cmp(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x62, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Add(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x82, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::And(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xA2, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Or(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xC2, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Eor(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xE2, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Sub(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        inst.op2 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R1(registers::SspModifier::PostIncrement),
+        ));
+        check_inst(&inst, &vec![0x22, 0x0D]);
+
+        inst.op2 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R1(registers::SspModifier::PostIncrementModulo),
+        ));
+        check_inst(&inst, &vec![0x22, 0x05]);
+
+        inst.op2 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R1(registers::SspModifier::PostDecrementModulo),
+        ));
+        check_inst(&inst, &vec![0x22, 0x09]);
+
+        inst.op2 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R5(registers::SspModifier::PostIncrement),
+        ));
+        check_inst(&inst, &vec![0x23, 0x0D]);
+    }
+
+    #[test]
+    fn check_op_a_dbl_ptr_ref() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Sub(
+                mnemonics::SspMnemonicModifier::Reference,
+            )),
+            op1: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::A,
+            )),
+            op2: Some(operators::SspOperator::PtrDoubleRef(
+                registers::SspPointerRegister::R1(registers::SspModifier::Zero),
+            )),
+        };
+        check_inst(&inst, &vec![0x2A, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Cmp(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x6A, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Add(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x8A, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::And(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xAA, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Or(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xCA, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Eor(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xEA, 0x01]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Sub(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        inst.op2 = Some(operators::SspOperator::PtrDoubleRef(
+            registers::SspPointerRegister::R1(registers::SspModifier::PostIncrement),
+        ));
+        check_inst(&inst, &vec![0x2A, 0x0D]);
+
+        inst.op2 = Some(operators::SspOperator::PtrDoubleRef(
+            registers::SspPointerRegister::R1(registers::SspModifier::PostIncrementModulo),
+        ));
+        check_inst(&inst, &vec![0x2A, 0x05]);
+
+        inst.op2 = Some(operators::SspOperator::PtrDoubleRef(
+            registers::SspPointerRegister::R1(registers::SspModifier::PostDecrementModulo),
+        ));
+        check_inst(&inst, &vec![0x2A, 0x09]);
+
+        inst.op2 = Some(operators::SspOperator::PtrDoubleRef(
+            registers::SspPointerRegister::R5(registers::SspModifier::PostIncrement),
+        ));
+        check_inst(&inst, &vec![0x2B, 0x0D]);
+    }
+
+    #[test]
+    fn check_op_a_addr() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Sub(
+                mnemonics::SspMnemonicModifier::Reference,
+            )),
+            op1: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::A,
+            )),
+            op2: Some(operators::SspOperator::Word(0xFF)),
+        };
+        check_inst(&inst, &vec![0x26, 0xFF]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Cmp(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x66, 0xFF]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Add(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x86, 0xFF]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::And(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xA6, 0xFF]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Or(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xC6, 0xFF]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Eor(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0xE6, 0xFF]);
+
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Sub(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        inst.op2 = Some(operators::SspOperator::Word(0x1FF));
+        check_inst(&inst, &vec![0x27, 0xFF]);
+    }
+
+    #[test]
+    fn check_mod_f() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Mod),
+            op1: Some(operators::SspOperator::ConditionFlag(
+                operators::SspMnemonicConditionFlag::F,
+            )),
+            op2: Some(operators::SspOperator::FlagOperation(
+                operators::SspMnemonicFlagOperation::Resl,
+            )),
+        };
+        check_inst(&inst, &vec![0x94, 0x02]);
+
+        inst.op2 = Some(operators::SspOperator::FlagOperation(
+            operators::SspMnemonicFlagOperation::Setl,
+        ));
+        check_inst(&inst, &vec![0x94, 0x03]);
+
+        inst.op2 = Some(operators::SspOperator::FlagOperation(
+            operators::SspMnemonicFlagOperation::Resie,
+        ));
+        check_inst(&inst, &vec![0x94, 0x04]);
+
+        inst.op2 = Some(operators::SspOperator::FlagOperation(
+            operators::SspMnemonicFlagOperation::Setie,
+        ));
+        check_inst(&inst, &vec![0x94, 0x05]);
+
+        inst.op2 = Some(operators::SspOperator::FlagOperation(
+            operators::SspMnemonicFlagOperation::Resop,
+        ));
+        check_inst(&inst, &vec![0x94, 0x08]);
+
+        inst.op2 = Some(operators::SspOperator::FlagOperation(
+            operators::SspMnemonicFlagOperation::Setop,
+        ));
+        check_inst(&inst, &vec![0x94, 0x09]);
+
+        inst.op2 = Some(operators::SspOperator::FlagOperation(
+            operators::SspMnemonicFlagOperation::Res,
+        ));
+        check_inst(&inst, &vec![0x94, 0x0E]);
+
+        inst.op2 = Some(operators::SspOperator::FlagOperation(
+            operators::SspMnemonicFlagOperation::Set,
+        ));
+        check_inst(&inst, &vec![0x94, 0x0F]);
+    }
+
+    #[test]
+    fn check_ld_r_r() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Ld(
+                mnemonics::SspMnemonicModifier::Reference,
+            )),
+            op1: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::Dummy,
+            )),
+            op2: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::Dummy,
+            )),
+        };
+        check_inst(&inst, &vec![0x00, 0x00]);
+
+        inst.op1 = Some(operators::SspOperator::Reg(
+            registers::SspGeneralRegister::X,
+        ));
+        inst.op2 = Some(operators::SspOperator::Reg(
+            registers::SspGeneralRegister::A,
+        ));
+        check_inst(&inst, &vec![0x00, 0x13]);
+
+        inst.op1 = Some(operators::SspOperator::Reg(
+            registers::SspGeneralRegister::A,
+        ));
+        inst.op2 = Some(operators::SspOperator::Reg(
+            registers::SspGeneralRegister::Ext1,
+        ));
+        check_inst(&inst, &vec![0x00, 0x39]);
+    }
+
+    #[test]
+    fn check_ld_r_ri() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Ld(
+                mnemonics::SspMnemonicModifier::Reference,
+            )),
+            op1: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::A,
+            )),
+            op2: Some(operators::SspOperator::Ptr(
+                registers::SspPointerRegister::R5(registers::SspModifier::Zero),
+            )),
+        };
+        check_inst(&inst, &vec![0x13, 0x31]);
+
+        inst.op1 = Some(operators::SspOperator::Reg(
+            registers::SspGeneralRegister::Ext1,
+        ));
+        inst.op2 = Some(operators::SspOperator::Ptr(
+            registers::SspPointerRegister::R6(registers::SspModifier::Zero),
+        ));
+        check_inst(&inst, &vec![0x13, 0x92]);
+
+        inst.op1 = Some(operators::SspOperator::Reg(
+            registers::SspGeneralRegister::A,
+        ));
+        inst.op2 = Some(operators::SspOperator::Ptr(
+            registers::SspPointerRegister::R2(registers::SspModifier::Zero),
+        ));
+        check_inst(&inst, &vec![0x12, 0x32]);
+    }
+
+    #[test]
+    fn check_ld_ri_r() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Ld(
+                mnemonics::SspMnemonicModifier::Reference,
+            )),
+            op1: Some(operators::SspOperator::Ptr(
+                registers::SspPointerRegister::R2(registers::SspModifier::Zero),
+            )),
+            op2: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::A,
+            )),
+        };
+        check_inst(&inst, &vec![0x14, 0x32]);
+
+        inst.op1 = Some(operators::SspOperator::Ptr(
+            registers::SspPointerRegister::R6(registers::SspModifier::Zero),
+        ));
+        inst.op2 = Some(operators::SspOperator::Reg(
+            registers::SspGeneralRegister::Ext1,
+        ));
+        check_inst(&inst, &vec![0x15, 0x92]);
+
+        inst.op1 = Some(operators::SspOperator::Ptr(
+            registers::SspPointerRegister::R0(registers::SspModifier::Zero),
+        ));
+        inst.op2 = Some(operators::SspOperator::Reg(
+            registers::SspGeneralRegister::X,
+        ));
+        check_inst(&inst, &vec![0x14, 0x10]);
+    }
+
+    #[test]
+    fn check_ldi_r() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Ld(
+                mnemonics::SspMnemonicModifier::Immediate,
+            )),
+            op1: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::X,
+            )),
+            op2: Some(operators::SspOperator::Word(0x5050)),
+        };
+        check_inst(&inst, &vec![0x08, 0x10, 0x50, 0x50]);
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Ld(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x08, 0x10, 0x50, 0x50]);
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Ld(
+            mnemonics::SspMnemonicModifier::Immediate,
+        ));
+
+        inst.op1 = Some(operators::SspOperator::Reg(
+            registers::SspGeneralRegister::A,
+        ));
+        check_inst(&inst, &vec![0x08, 0x30, 0x50, 0x50]);
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Ld(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x08, 0x30, 0x50, 0x50]);
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Ld(
+            mnemonics::SspMnemonicModifier::Immediate,
+        ));
+
+        inst.op1 = Some(operators::SspOperator::Reg(
+            registers::SspGeneralRegister::Ext6,
+        ));
+        check_inst(&inst, &vec![0x08, 0xE0, 0x50, 0x50]);
+        inst.mnemonic = Some(mnemonics::SspMnemonic::Ld(
+            mnemonics::SspMnemonicModifier::Reference,
+        ));
+        check_inst(&inst, &vec![0x08, 0xE0, 0x50, 0x50]);
+    }
+
+    #[test]
+    fn check_ld_r_double_ref() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Ld(
+                mnemonics::SspMnemonicModifier::Reference,
+            )),
+            op1: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::A,
+            )),
+            op2: Some(operators::SspOperator::PtrDoubleRef(
+                registers::SspPointerRegister::R0(registers::SspModifier::Zero),
+            )),
+        };
+        check_inst(&inst, &vec![0x0A, 0x30]);
+
+        inst.op1 = Some(operators::SspOperator::Reg(
+            registers::SspGeneralRegister::X,
+        ));
+        inst.op2 = Some(operators::SspOperator::PtrDoubleRef(
+            registers::SspPointerRegister::R3(registers::SspModifier::Zero),
+        ));
+        check_inst(&inst, &vec![0x0A, 0x13]);
+
+        inst.op2 = Some(operators::SspOperator::PtrDoubleRef(
+            registers::SspPointerRegister::R7(registers::SspModifier::Zero),
+        ));
+        check_inst(&inst, &vec![0x0B, 0x13]);
+    }
+
+    #[test]
+    fn check_ldi_pointer_ref() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Ld(
+                mnemonics::SspMnemonicModifier::Immediate,
+            )),
+            op1: Some(operators::SspOperator::PtrRef(
+                registers::SspPointerRegister::R0(registers::SspModifier::Zero),
+            )),
+            op2: Some(operators::SspOperator::Word(0x5050)),
+        };
+        check_inst(&inst, &vec![0x0C, 0x00, 0x50, 0x50]);
+
+        inst.op1 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R4(registers::SspModifier::Zero),
+        ));
+        check_inst(&inst, &vec![0x0D, 0x00, 0x50, 0x50]);
+
+        inst.op1 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R4(registers::SspModifier::PostIncrement),
+        ));
+        check_inst(&inst, &vec![0x0D, 0x0C, 0x50, 0x50]);
+
+        inst.op1 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R4(registers::SspModifier::PostIncrementModulo),
+        ));
+        check_inst(&inst, &vec![0x0D, 0x04, 0x50, 0x50]);
+
+        inst.op1 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R4(registers::SspModifier::PostDecrementModulo),
+        ));
+        check_inst(&inst, &vec![0x0D, 0x08, 0x50, 0x50]);
+    }
+
+    #[test]
+    fn check_ld_ram_bank_addr_a() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Ld(
+                mnemonics::SspMnemonicModifier::Reference,
+            )),
+            op1: Some(operators::SspOperator::RamBankAddressA(0x50)),
+            op2: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::A,
+            )),
+        };
+        check_inst(&inst, &vec![0x0E, 0x50]);
+
+        inst.op1 = Some(operators::SspOperator::RamBankAddressB(0x50));
+        check_inst(&inst, &vec![0x0F, 0x50]);
+    }
+
+    #[test]
+    fn check_ld_a_ram_bank_addr() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Ld(
+                mnemonics::SspMnemonicModifier::Reference,
+            )),
+            op1: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::A,
+            )),
+            op2: Some(operators::SspOperator::RamBankAddressA(0x50)),
+        };
+        check_inst(&inst, &vec![0x06, 0x50]);
+
+        inst.op2 = Some(operators::SspOperator::RamBankAddressB(0x50));
+
+        check_inst(&inst, &vec![0x07, 0x50]);
+    }
+
+    #[test]
+    fn check_ld_r_ri_ref() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Ld(
+                mnemonics::SspMnemonicModifier::Reference,
+            )),
+            op1: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::Y,
+            )),
+            op2: Some(operators::SspOperator::PtrRef(
+                registers::SspPointerRegister::R0(registers::SspModifier::PostIncrementModulo),
+            )),
+        };
+        check_inst(&inst, &vec![0x02, 0x24]);
+
+        inst.op1 = Some(operators::SspOperator::Reg(
+            registers::SspGeneralRegister::A,
+        ));
+        inst.op2 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R3(registers::SspModifier::Bank3),
+        ));
+        check_inst(&inst, &vec![0x02, 0x3F]);
+
+        inst.op2 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R7(registers::SspModifier::Bank0),
+        ));
+        check_inst(&inst, &vec![0x03, 0x33]);
+    }
+
+    #[test]
+    fn check_ld_ri_ref_r() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Ld(
+                mnemonics::SspMnemonicModifier::Reference,
+            )),
+            op1: Some(operators::SspOperator::PtrRef(
+                registers::SspPointerRegister::R0(registers::SspModifier::PostIncrementModulo),
+            )),
+            op2: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::Y,
+            )),
+        };
+        check_inst(&inst, &vec![0x04, 0x24]);
+
+        inst.op1 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R3(registers::SspModifier::Bank3),
+        ));
+        inst.op2 = Some(operators::SspOperator::Reg(
+            registers::SspGeneralRegister::A,
+        ));
+        check_inst(&inst, &vec![0x04, 0x3F]);
+
+        inst.op1 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R7(registers::SspModifier::Bank0),
+        ));
+        check_inst(&inst, &vec![0x05, 0x33]);
+    }
+
+    #[test]
+    fn check_ldi_ri() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Ld(
+                mnemonics::SspMnemonicModifier::Immediate,
+            )),
+            op1: Some(operators::SspOperator::Ptr(
+                registers::SspPointerRegister::R0(registers::SspModifier::PostIncrementModulo),
+            )),
+            op2: Some(operators::SspOperator::Byte(0x50)),
+        };
+        check_inst(&inst, &vec![0x18, 0x50]);
+
+        inst.op1 = Some(operators::SspOperator::Ptr(
+            registers::SspPointerRegister::R6(registers::SspModifier::Zero),
+        ));
+        check_inst(&inst, &vec![0x1E, 0x50]);
+    }
+
+    #[test]
+    fn check_ld_r_ref_a() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Ld(
+                mnemonics::SspMnemonicModifier::Reference,
+            )),
+            op1: Some(operators::SspOperator::Reg(
+                registers::SspGeneralRegister::X,
+            )),
+            op2: Some(operators::SspOperator::PtrAccumulator),
+        };
+        check_inst(&inst, &vec![0x4A, 0x10]);
+
+        inst.op1 = Some(operators::SspOperator::Reg(
+            registers::SspGeneralRegister::Pc,
+        ));
+
+        check_inst(&inst, &vec![0x4A, 0x60]);
+    }
+
+    #[test]
+    fn check_call() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Call),
+            op1: Some(operators::SspOperator::Condition(
+                operators::SspMnemonicCondition::Always,
+            )),
+            op2: Some(operators::SspOperator::Word(0x5050)),
+        };
+        check_inst(&inst, &vec![0x48, 0x00, 0x50, 0x50]);
+
+        inst.op1 = Some(operators::SspOperator::Condition(
+            operators::SspMnemonicCondition::Z(true),
+        ));
+        check_inst(&inst, &vec![0x49, 0x50, 0x50, 0x50]);
+    }
+
+    #[test]
+    fn check_bra() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Bra),
+            op1: Some(operators::SspOperator::Condition(
+                operators::SspMnemonicCondition::Always,
+            )),
+            op2: Some(operators::SspOperator::Word(0x5050)),
+        };
+        check_inst(&inst, &vec![0x4C, 0x00, 0x50, 0x50]);
+
+        inst.op1 = Some(operators::SspOperator::Condition(
+            operators::SspMnemonicCondition::Z(true),
+        ));
+        check_inst(&inst, &vec![0x4D, 0x50, 0x50, 0x50]);
+    }
+
+    #[test]
+    fn check_mod_cond() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Mod),
+            op1: Some(operators::SspOperator::Condition(
+                operators::SspMnemonicCondition::Always,
+            )),
+            op2: Some(operators::SspOperator::AccOperation(
+                operators::SspMnemonicAccOperation::Shl,
+            )),
+        };
+        check_inst(&inst, &vec![0x90, 0x03]);
+
+        inst.op1 = Some(operators::SspOperator::Condition(
+            operators::SspMnemonicCondition::Z(false),
+        ));
+        inst.op2 = Some(operators::SspOperator::AccOperation(
+            operators::SspMnemonicAccOperation::Neg,
+        ));
+        check_inst(&inst, &vec![0x90, 0x56]);
+    }
+
+    #[test]
+    fn check_mld() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Mld),
+            op1: Some(operators::SspOperator::PtrRef(
+                registers::SspPointerRegister::R4(registers::SspModifier::PostIncrementModulo),
+            )),
+            op2: Some(operators::SspOperator::PtrRef(
+                registers::SspPointerRegister::R0(registers::SspModifier::PostIncrementModulo),
+            )),
+        };
+        check_inst(&inst, &vec![0xB7, 0x44]);
+
+        inst.op1 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R7(registers::SspModifier::Bank2),
+        ));
+        check_inst(&inst, &vec![0xB7, 0xB4]);
+    }
+
+    #[test]
+    fn check_mpya() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Mpya),
+            op1: Some(operators::SspOperator::PtrRef(
+                registers::SspPointerRegister::R4(registers::SspModifier::PostIncrementModulo),
+            )),
+            op2: Some(operators::SspOperator::PtrRef(
+                registers::SspPointerRegister::R0(registers::SspModifier::PostIncrementModulo),
+            )),
+        };
+        check_inst(&inst, &vec![0x97, 0x44]);
+
+        inst.op1 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R7(registers::SspModifier::Bank0),
+        ));
+        inst.op2 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R3(registers::SspModifier::Bank0),
+        ));
+        check_inst(&inst, &vec![0x97, 0x33]);
+    }
+
+    #[test]
+    fn check_mpys() {
+        let mut inst = Instruction {
+            mnemonic: Some(mnemonics::SspMnemonic::Mpys),
+            op1: Some(operators::SspOperator::PtrRef(
+                registers::SspPointerRegister::R4(registers::SspModifier::PostIncrementModulo),
+            )),
+            op2: Some(operators::SspOperator::PtrRef(
+                registers::SspPointerRegister::R0(registers::SspModifier::PostIncrementModulo),
+            )),
+        };
+        check_inst(&inst, &vec![0x37, 0x44]);
+
+        inst.op1 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R7(registers::SspModifier::Bank0),
+        ));
+        inst.op2 = Some(operators::SspOperator::PtrRef(
+            registers::SspPointerRegister::R3(registers::SspModifier::Bank0),
+        ));
+        check_inst(&inst, &vec![0x37, 0x33]);
+    }
+
+    #[test]
+    fn check_decode_encode_consistency() {
+        let mut decoded = vec![0; encoding::FORMS.len()];
+
+        for word in 0..=0xFFFF {
+            for (index, form) in encoding::FORMS.iter().enumerate() {
+                let operands = match form.decode(word, 0x1234) {
+                    Some(operands) => operands,
+                    None => continue,
+                };
+                let mut expected = vec![word];
+                if form.words == 2 {
+                    expected.push(0x1234);
+                }
+
+                assert!(form.matches(form.mnemonic, &operands), "{:?}", form);
+                assert_eq!(
+                    form.encode(&operands),
+                    Some(expected),
+                    "Wrong encoding for {:04X} as {:?}",
+                    word,
+                    operands
+                );
+                decoded[index] += 1;
+            }
+
+            if let Some(inst) = Instruction::decode(word, 0x1234) {
+                let opcodes = inst.build().unwrap();
+                assert_eq!(opcodes[..2], Instruction::word_to_bytes(word)[..]);
+                assert_eq!(opcodes.len() as u16, inst.size().unwrap() * 2);
+            }
+        }
+
+        for (form, count) in encoding::FORMS.iter().zip(decoded) {
+            assert!(count > 0, "Form never decoded: {:?}", form);
+        }
+    }
+
+    #[test]
+    fn check_decode() {
+        let inst = Instruction::decode(0x0065, 0).unwrap();
+        assert_eq!(inst.mnemonic, Some(mnemonics::SspMnemonic::Ret));
+
+        let inst = Instruction::decode(0x4D50, 0x0400).unwrap();
+        assert_eq!(
+            inst.operands(),
+            Some((
+                mnemonics::SspMnemonic::Bra,
+                vec![
+                    operators::SspOperator::Condition(operators::SspMnemonicCondition::Z(true)),
+                    operators::SspOperator::Word(0x0400)
+                ]
+            ))
+        );
+
+        // Condition 1 and flag operation 0 don't exist:
+        assert!(Instruction::decode(0x4C10, 0).is_none());
+        assert!(Instruction::decode(0x9400, 0).is_none());
+    }
+
+    #[test]
+    fn check_display_parse_round_trip() {
+        for word in 0..=0xFFFFu16 {
+            let instruction = match Instruction::decode(word, 0x1234) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+            let text = instruction.to_string();
+            let parsed = text.parse::<Instruction>();
+
+            assert_eq!(parsed.as_ref().ok(), Some(&instruction), "{}", text);
+            assert_eq!(parsed.unwrap().encode().unwrap()[0], word, "{}", text);
+        }
+    }
+
+    #[test]
+    fn check_parse() {
+        let inst = "ld a, (r0+)".parse::<Instruction>().unwrap();
+        assert_eq!(inst.encode().unwrap(), vec![0x023C]);
+        assert_eq!(inst.to_string(), "ld a, (r0+)");
+        assert_eq!("RET".parse::<Instruction>().unwrap().to_string(), "ret");
+        assert_eq!(
+            "ldi x 0x1234"
+                .parse::<Instruction>()
+                .unwrap()
+                .encode()
+                .unwrap(),
+            vec![0x0810, 0x1234]
+        );
+
+        assert!("".parse::<Instruction>().is_err());
+        assert!("lol a, x".parse::<Instruction>().is_err());
+        assert!("bra always, @loop".parse::<Instruction>().is_err());
+        assert!("ld a, x, y".parse::<Instruction>().is_err());
+        assert!("mpya a, x".parse::<Instruction>().is_err());
+    }
+
+    #[test]
+    fn check_resolved() {
+        let op = operators::SspOperator::new("@loop").unwrap();
+        let inst = Instruction::with_operands(
+            mnemonics::SspMnemonic::Call,
+            &[
+                operators::SspOperator::Condition(operators::SspMnemonicCondition::Always),
+                op,
+            ],
+        );
+        assert_eq!(inst.resolved(), None);
+        assert_eq!(inst.to_string(), "call always, @loop");
+
+        let inst = inst.new_with_op(&op);
+        assert_eq!(inst.operands().unwrap().1.len(), 2);
+    }
+
+    #[test]
+    fn check_resolve() {
+        let source = String::from("bra z=0, @loop");
+        let inst = Instruction::parse(&source).unwrap();
+        assert_eq!(inst.resolve(|_| None), None);
+
+        let owned = inst.resolve(|label| Some(if label == "loop" { 0x0402 } else { 0 }));
+        drop(source);
+        let owned = owned.unwrap();
+        assert_eq!(owned, "bra z=0, 0402".parse().unwrap());
+        assert_eq!(owned.encode().unwrap()[1], 0x0402);
+
+        assert!(Instruction::parse("ld a, lol").is_err());
+        assert!(Instruction::parse("ld a, x, y").is_err());
+        assert_eq!(
+            Instruction::parse("mpya a, x").unwrap().to_string(),
+            "mpya a, x"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn check_serde() {
+        let inst: Instruction = serde_json::from_str("\"mod f, setie\"").unwrap();
+        assert_eq!(inst.encode().unwrap(), vec![0x9405]);
+        assert_eq!(serde_json::to_string(&inst).unwrap(), "\"mod f, setie\"");
+        assert!(serde_json::from_str::<Instruction>("\"ld a\"").is_err());
+    }
+}
@@ -0,0 +1,9 @@
+// Instruction set model of the Samsung SSP16xx DSPs (i.e.: the SSP1601 inside the SVP): registers,
+// operands, mnemonics and the encoding table shared by the assembler, disassembler and emulator.
+pub mod encoding;
+pub mod errors;
+pub mod instructions;
+pub mod labels;
+pub mod mnemonics;
+pub mod operators;
+pub mod registers;
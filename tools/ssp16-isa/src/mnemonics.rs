@@ -1,10 +1,16 @@
+use crate::errors;
+use std::fmt;
+use std::str::FromStr;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SspMnemonicModifier {
     Immediate,
     Reference,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SspMnemonic {
     Sub(SspMnemonicModifier),
     Cmp(SspMnemonicModifier),
@@ -64,6 +70,39 @@ impl SspMnemonic {
     }
 }
 
+impl fmt::Display for SspMnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (name, modifier) = match *self {
+            SspMnemonic::Sub(modifier) => ("sub", Some(modifier)),
+            SspMnemonic::Cmp(modifier) => ("cmp", Some(modifier)),
+            SspMnemonic::Add(modifier) => ("add", Some(modifier)),
+            SspMnemonic::And(modifier) => ("and", Some(modifier)),
+            SspMnemonic::Or(modifier) => ("or", Some(modifier)),
+            SspMnemonic::Eor(modifier) => ("eor", Some(modifier)),
+            SspMnemonic::Ld(modifier) => ("ld", Some(modifier)),
+            SspMnemonic::Bra => ("bra", None),
+            SspMnemonic::Ret => ("ret", None),
+            SspMnemonic::Call => ("call", None),
+            SspMnemonic::Mld => ("mld", None),
+            SspMnemonic::Mpya => ("mpya", None),
+            SspMnemonic::Mpys => ("mpys", None),
+            SspMnemonic::Mod => ("mod", None),
+        };
+        match modifier {
+            Some(SspMnemonicModifier::Immediate) => write!(f, "{}i", name),
+            _ => write!(f, "{}", name),
+        }
+    }
+}
+
+impl FromStr for SspMnemonic {
+    type Err = errors::IsaError;
+
+    fn from_str(s: &str) -> Result<SspMnemonic, errors::IsaError> {
+        SspMnemonic::new(s).ok_or_else(|| errors::IsaError(format!("Invalid mnemonic: {}", s)))
+    }
+}
+
 #[cfg(test)]
 mod mnemonic_tests {
     use super::*;
@@ -140,4 +179,17 @@ mod mnemonic_tests {
         assert_eq!(SspMnemonic::new("lol"), None);
         assert_eq!(SspMnemonic::new(""), None);
     }
+
+    #[test]
+    fn check_mnemonics_round_trip() {
+        for name in &[
+            "ld", "ldi", "sub", "subi", "cmp", "cmpi", "add", "addi", "and", "andi", "or", "ori",
+            "eor", "eori", "bra", "ret", "call", "mld", "mpya", "mpys", "mod",
+        ] {
+            let mnemonic = name.parse::<SspMnemonic>().unwrap();
+            assert_eq!(mnemonic.to_string(), *name);
+        }
+        assert!("LD".parse::<SspMnemonic>().is_ok());
+        assert!("lol".parse::<SspMnemonic>().is_err());
+    }
 }
@@ -1,9 +1,12 @@
-use super::labels;
-use super::registers::SspGeneralRegister;
-use super::registers::SspPointerRegister;
+use crate::errors;
+use crate::labels;
+use crate::registers::SspGeneralRegister;
+use crate::registers::SspPointerRegister;
 use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SspMnemonicCondition {
     Always,
     Gpi0(bool),
@@ -90,9 +93,31 @@ impl SspMnemonicCondition {
             SspMnemonicCondition::Gpi3(true) => 1,
         }
     }
+
+    pub fn from_values(value: u8, flag: u8) -> Option<SspMnemonicCondition> {
+        let flag = match flag {
+            0 => false,
+            1 => true,
+            _ => return None,
+        };
+        match value {
+            0 if !flag => Some(SspMnemonicCondition::Always),
+            2 => Some(SspMnemonicCondition::Gpi0(flag)),
+            3 => Some(SspMnemonicCondition::Gpi1(flag)),
+            4 => Some(SspMnemonicCondition::L(flag)),
+            5 => Some(SspMnemonicCondition::Z(flag)),
+            6 => Some(SspMnemonicCondition::Ov(flag)),
+            7 => Some(SspMnemonicCondition::N(flag)),
+            8 => Some(SspMnemonicCondition::Diof(flag)),
+            9 => Some(SspMnemonicCondition::Gpi2(flag)),
+            10 => Some(SspMnemonicCondition::Gpi3(flag)),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SspMnemonicConditionFlag {
     F,
 }
@@ -107,6 +132,7 @@ impl SspMnemonicConditionFlag {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SspMnemonicAccOperation {
     Ror,
     Rol,
@@ -148,9 +174,24 @@ impl SspMnemonicAccOperation {
             SspMnemonicAccOperation::Abs => 7,
         }
     }
+
+    pub fn from_value(value: u8) -> Option<SspMnemonicAccOperation> {
+        match value {
+            0 => Some(SspMnemonicAccOperation::Ror),
+            1 => Some(SspMnemonicAccOperation::Rol),
+            2 => Some(SspMnemonicAccOperation::Shr),
+            3 => Some(SspMnemonicAccOperation::Shl),
+            4 => Some(SspMnemonicAccOperation::Inc),
+            5 => Some(SspMnemonicAccOperation::Dec),
+            6 => Some(SspMnemonicAccOperation::Neg),
+            7 => Some(SspMnemonicAccOperation::Abs),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SspMnemonicFlagOperation {
     Resl,
     Setl,
@@ -189,6 +230,20 @@ impl SspMnemonicFlagOperation {
             SspMnemonicFlagOperation::Set => 15,
         }
     }
+
+    pub fn from_value(value: u8) -> Option<SspMnemonicFlagOperation> {
+        match value {
+            2 => Some(SspMnemonicFlagOperation::Resl),
+            3 => Some(SspMnemonicFlagOperation::Setl),
+            4 => Some(SspMnemonicFlagOperation::Resie),
+            5 => Some(SspMnemonicFlagOperation::Setie),
+            8 => Some(SspMnemonicFlagOperation::Resop),
+            9 => Some(SspMnemonicFlagOperation::Setop),
+            14 => Some(SspMnemonicFlagOperation::Res),
+            15 => Some(SspMnemonicFlagOperation::Set),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -329,6 +384,184 @@ impl<'a> fmt::Debug for SspOperator<'a> {
     }
 }
 
+impl<'a> SspOperator<'a> {
+    // The same operator, as long as it isn't a label reference:
+    pub fn resolved(&self) -> Option<SspOperator<'static>> {
+        self.resolve(|_| None)
+    }
+
+    // The same operator, with label references replaced by the address `address` gives for them
+    // (`None` if it doesn't know the label):
+    pub fn resolve(&self, address: impl Fn(&str) -> Option<u16>) -> Option<SspOperator<'static>> {
+        match *self {
+            SspOperator::Reg(reg) => Some(SspOperator::Reg(reg)),
+            SspOperator::Ptr(preg) => Some(SspOperator::Ptr(preg)),
+            SspOperator::PtrRef(preg) => Some(SspOperator::PtrRef(preg)),
+            SspOperator::PtrDoubleRef(preg) => Some(SspOperator::PtrDoubleRef(preg)),
+            SspOperator::PtrAccumulator => Some(SspOperator::PtrAccumulator),
+            SspOperator::RamBankAddressA(addr) => Some(SspOperator::RamBankAddressA(addr)),
+            SspOperator::RamBankAddressB(addr) => Some(SspOperator::RamBankAddressB(addr)),
+            SspOperator::Condition(cond) => Some(SspOperator::Condition(cond)),
+            SspOperator::ConditionFlag(flag) => Some(SspOperator::ConditionFlag(flag)),
+            SspOperator::FlagOperation(op) => Some(SspOperator::FlagOperation(op)),
+            SspOperator::AccOperation(op) => Some(SspOperator::AccOperation(op)),
+            SspOperator::Word(value) => Some(SspOperator::Word(value)),
+            SspOperator::Byte(value) => Some(SspOperator::Byte(value)),
+            SspOperator::LabelRef(label) => address(label).map(SspOperator::Word),
+        }
+    }
+}
+
+impl fmt::Display for SspMnemonicCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SspMnemonicCondition::Always => return write!(f, "always"),
+            SspMnemonicCondition::Gpi0(_) => "gpi0",
+            SspMnemonicCondition::Gpi1(_) => "gpi1",
+            SspMnemonicCondition::L(_) => "l",
+            SspMnemonicCondition::Z(_) => "z",
+            SspMnemonicCondition::Ov(_) => "ov",
+            SspMnemonicCondition::N(_) => "n",
+            SspMnemonicCondition::Diof(_) => "diof",
+            SspMnemonicCondition::Gpi2(_) => "gpi2",
+            SspMnemonicCondition::Gpi3(_) => "gpi3",
+        };
+        write!(f, "{}={}", name, self.flag_value())
+    }
+}
+
+impl fmt::Display for SspMnemonicConditionFlag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SspMnemonicConditionFlag::F => write!(f, "f"),
+        }
+    }
+}
+
+impl fmt::Display for SspMnemonicAccOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SspMnemonicAccOperation::Ror => "ror",
+            SspMnemonicAccOperation::Rol => "rol",
+            SspMnemonicAccOperation::Shr => "shr",
+            SspMnemonicAccOperation::Shl => "shl",
+            SspMnemonicAccOperation::Inc => "inc",
+            SspMnemonicAccOperation::Dec => "dec",
+            SspMnemonicAccOperation::Neg => "neg",
+            SspMnemonicAccOperation::Abs => "abs",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for SspMnemonicFlagOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SspMnemonicFlagOperation::Resl => "resl",
+            SspMnemonicFlagOperation::Setl => "setl",
+            SspMnemonicFlagOperation::Resie => "resie",
+            SspMnemonicFlagOperation::Setie => "setie",
+            SspMnemonicFlagOperation::Resop => "resop",
+            SspMnemonicFlagOperation::Setop => "setop",
+            SspMnemonicFlagOperation::Res => "res",
+            SspMnemonicFlagOperation::Set => "set",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Native syntax, the one `SspOperator::new` parses:
+impl<'a> fmt::Display for SspOperator<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SspOperator::Reg(reg) => write!(f, "{}", reg),
+            SspOperator::Ptr(preg) => write!(f, "{}", preg),
+            SspOperator::PtrRef(preg) => write!(f, "({})", preg),
+            SspOperator::PtrDoubleRef(preg) => write!(f, "(({}))", preg),
+            SspOperator::PtrAccumulator => write!(f, "(a)"),
+            SspOperator::RamBankAddressA(addr) => write!(f, "A[{:02X}]", addr),
+            SspOperator::RamBankAddressB(addr) => write!(f, "B[{:02X}]", addr),
+            SspOperator::Condition(cond) => write!(f, "{}", cond),
+            SspOperator::ConditionFlag(flag) => write!(f, "{}", flag),
+            SspOperator::FlagOperation(op) => write!(f, "{}", op),
+            SspOperator::AccOperation(op) => write!(f, "{}", op),
+            SspOperator::Word(value) => write!(f, "{:04X}", value),
+            SspOperator::Byte(value) => write!(f, "{:02X}", value),
+            SspOperator::LabelRef(label) => write!(f, "@{}", label),
+        }
+    }
+}
+
+fn parse<T>(s: &str, kind: &str, new: fn(&str) -> Option<T>) -> Result<T, errors::IsaError> {
+    new(s).ok_or_else(|| errors::IsaError(format!("Invalid {}: {}", kind, s)))
+}
+
+impl FromStr for SspMnemonicCondition {
+    type Err = errors::IsaError;
+
+    fn from_str(s: &str) -> Result<SspMnemonicCondition, errors::IsaError> {
+        parse(s, "condition", SspMnemonicCondition::new)
+    }
+}
+
+impl FromStr for SspMnemonicConditionFlag {
+    type Err = errors::IsaError;
+
+    fn from_str(s: &str) -> Result<SspMnemonicConditionFlag, errors::IsaError> {
+        parse(s, "condition flag", SspMnemonicConditionFlag::new)
+    }
+}
+
+impl FromStr for SspMnemonicAccOperation {
+    type Err = errors::IsaError;
+
+    fn from_str(s: &str) -> Result<SspMnemonicAccOperation, errors::IsaError> {
+        parse(s, "accumulator operation", SspMnemonicAccOperation::new)
+    }
+}
+
+impl FromStr for SspMnemonicFlagOperation {
+    type Err = errors::IsaError;
+
+    fn from_str(s: &str) -> Result<SspMnemonicFlagOperation, errors::IsaError> {
+        parse(s, "flag operation", SspMnemonicFlagOperation::new)
+    }
+}
+
+// Label references borrow from the source they are found in, so they can't be parsed into owned
+// operators:
+impl FromStr for SspOperator<'static> {
+    type Err = errors::IsaError;
+
+    fn from_str(s: &str) -> Result<SspOperator<'static>, errors::IsaError> {
+        match SspOperator::new(s) {
+            Some(SspOperator::LabelRef(label)) => Err(errors::IsaError(format!(
+                "Unresolved label reference: @{}",
+                label
+            ))),
+            op => op
+                .and_then(|op| op.resolved())
+                .ok_or_else(|| errors::IsaError(format!("Invalid operator: {}", s))),
+        }
+    }
+}
+
+// Operators are serialized in native syntax:
+#[cfg(feature = "serde")]
+impl<'a> serde::Serialize for SspOperator<'a> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SspOperator<'static> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let op = String::deserialize(deserializer)?;
+        op.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod invalid_tests {
     use super::*;
@@ -411,8 +644,8 @@ mod general_registers_tests {
 
 #[cfg(test)]
 mod pointer_registers_tests {
-    use super::super::registers::SspModifier;
     use super::*;
+    use crate::registers::SspModifier;
 
     #[test]
     fn check_valid_pointer_registers() {
@@ -1302,3 +1535,66 @@ mod numeric_value_tests {
         assert_eq!(SspOperator::new("00000"), None);
     }
 }
+
+#[cfg(test)]
+mod display_tests {
+    use super::*;
+    use crate::registers::SspModifier;
+
+    #[test]
+    fn check_operators_round_trip() {
+        for op in &[
+            "-", "a", "ext7", "r2", "r5+!", "r3|01", "(r0+)", "((r6-))", "(r7|11)", "(a)", "A[1F]",
+            "B[00]", "always", "z=1", "gpi3=0", "f", "ror", "abs", "resl", "set", "0A", "FFAA",
+            "0012",
+        ] {
+            let operator = op.parse::<SspOperator>().unwrap();
+            assert_eq!(operator.to_string(), *op);
+        }
+    }
+
+    #[test]
+    fn check_operators_display() {
+        assert_eq!(
+            SspOperator::PtrDoubleRef(SspPointerRegister::R4(SspModifier::PostIncrement))
+                .to_string(),
+            "((r4+))"
+        );
+        assert_eq!(
+            SspOperator::Condition(SspMnemonicCondition::N(false)).to_string(),
+            "n=0"
+        );
+        assert_eq!(SspOperator::Word(0x12).to_string(), "0012");
+        assert_eq!(SspOperator::LabelRef("loop").to_string(), "@loop");
+        assert_eq!(SspOperator::new("z").unwrap().to_string(), "z=0");
+    }
+
+    #[test]
+    fn check_owned_operators() {
+        assert_eq!("0x1234".parse(), Ok(SspOperator::Word(0x1234)));
+        assert!("@loop".parse::<SspOperator>().is_err());
+        assert!("lol".parse::<SspOperator>().is_err());
+        assert_eq!(SspOperator::new("@loop").unwrap().resolved(), None);
+        assert_eq!(
+            SspOperator::new("@loop").unwrap().resolve(|_| Some(0x400)),
+            Some(SspOperator::Word(0x400))
+        );
+        assert_eq!(
+            "diof=1".parse::<SspMnemonicCondition>(),
+            Ok(SspMnemonicCondition::Diof(true))
+        );
+        assert!("ror".parse::<SspMnemonicFlagOperation>().is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn check_serde() {
+        let op: SspOperator = serde_json::from_str("\"(r1+!)\"").unwrap();
+        assert_eq!(serde_json::to_string(&op).unwrap(), "\"(r1+!)\"");
+        assert!(serde_json::from_str::<SspOperator>("\"@loop\"").is_err());
+        assert_eq!(
+            serde_json::to_string(&SspMnemonicAccOperation::Neg).unwrap(),
+            "\"Neg\""
+        );
+    }
+}
@@ -1,4 +1,9 @@
+use crate::errors;
+use std::fmt;
+use std::str::FromStr;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SspGeneralRegister {
     Dummy,
     X,
@@ -62,9 +67,32 @@ impl SspGeneralRegister {
             SspGeneralRegister::Ext7 => 0xF,
         }
     }
+
+    pub fn from_value(value: u8) -> Option<SspGeneralRegister> {
+        match value {
+            0 => Some(SspGeneralRegister::Dummy),
+            0x1 => Some(SspGeneralRegister::X),
+            0x2 => Some(SspGeneralRegister::Y),
+            0x3 => Some(SspGeneralRegister::A),
+            0x4 => Some(SspGeneralRegister::St),
+            0x5 => Some(SspGeneralRegister::Stack),
+            0x6 => Some(SspGeneralRegister::Pc),
+            0x7 => Some(SspGeneralRegister::P),
+            0x8 => Some(SspGeneralRegister::Ext0),
+            0x9 => Some(SspGeneralRegister::Ext1),
+            0xA => Some(SspGeneralRegister::Ext2),
+            0xB => Some(SspGeneralRegister::Ext3),
+            0xC => Some(SspGeneralRegister::Ext4),
+            0xD => Some(SspGeneralRegister::Ext5),
+            0xE => Some(SspGeneralRegister::Ext6),
+            0xF => Some(SspGeneralRegister::Ext7),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SspModifier {
     Zero,
     PostIncrement,
@@ -92,6 +120,7 @@ impl SspModifier {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SspPointerRegister {
     R0(SspModifier),
     R1(SspModifier),
@@ -185,6 +214,99 @@ impl SspPointerRegister {
             SspPointerRegister::R7(modifier) => modifier.value(),
         }
     }
+
+    // Register `number` (r0-r7) from its encoded fields. Modifiers of r3 and r7 select a RAM
+    // bank, the others iterate the pointer.
+    pub fn from_values(number: u8, modifier: u8) -> Option<SspPointerRegister> {
+        let modifier = match (number & 3, modifier) {
+            (_, 0) => SspModifier::Zero,
+            (3, 1) => SspModifier::Bank1,
+            (3, 2) => SspModifier::Bank2,
+            (3, 3) => SspModifier::Bank3,
+            (_, 1) => SspModifier::PostIncrementModulo,
+            (_, 2) => SspModifier::PostDecrementModulo,
+            (_, 3) => SspModifier::PostIncrement,
+            _ => return None,
+        };
+        match number {
+            0 => Some(SspPointerRegister::R0(modifier)),
+            1 => Some(SspPointerRegister::R1(modifier)),
+            2 => Some(SspPointerRegister::R2(modifier)),
+            3 => Some(SspPointerRegister::R3(modifier)),
+            4 => Some(SspPointerRegister::R4(modifier)),
+            5 => Some(SspPointerRegister::R5(modifier)),
+            6 => Some(SspPointerRegister::R6(modifier)),
+            7 => Some(SspPointerRegister::R7(modifier)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SspGeneralRegister {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SspGeneralRegister::Dummy => "-",
+            SspGeneralRegister::X => "x",
+            SspGeneralRegister::Y => "y",
+            SspGeneralRegister::A => "a",
+            SspGeneralRegister::St => "st",
+            SspGeneralRegister::Stack => "stack",
+            SspGeneralRegister::Pc => "pc",
+            SspGeneralRegister::P => "p",
+            SspGeneralRegister::Ext0 => "ext0",
+            SspGeneralRegister::Ext1 => "ext1",
+            SspGeneralRegister::Ext2 => "ext2",
+            SspGeneralRegister::Ext3 => "ext3",
+            SspGeneralRegister::Ext4 => "ext4",
+            SspGeneralRegister::Ext5 => "ext5",
+            SspGeneralRegister::Ext6 => "ext6",
+            SspGeneralRegister::Ext7 => "ext7",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for SspGeneralRegister {
+    type Err = errors::IsaError;
+
+    fn from_str(s: &str) -> Result<SspGeneralRegister, errors::IsaError> {
+        SspGeneralRegister::new(s)
+            .ok_or_else(|| errors::IsaError(format!("Invalid register: {}", s)))
+    }
+}
+
+impl fmt::Display for SspPointerRegister {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let modifier = match self {
+            SspPointerRegister::R0(modifier)
+            | SspPointerRegister::R1(modifier)
+            | SspPointerRegister::R2(modifier)
+            | SspPointerRegister::R3(modifier)
+            | SspPointerRegister::R4(modifier)
+            | SspPointerRegister::R5(modifier)
+            | SspPointerRegister::R6(modifier)
+            | SspPointerRegister::R7(modifier) => match modifier {
+                SspModifier::Zero => "",
+                SspModifier::PostIncrement => "+",
+                SspModifier::PostIncrementModulo => "+!",
+                SspModifier::PostDecrementModulo => "-",
+                SspModifier::Bank0 => "|00",
+                SspModifier::Bank1 => "|01",
+                SspModifier::Bank2 => "|10",
+                SspModifier::Bank3 => "|11",
+            },
+        };
+        write!(f, "r{}{}", self.ram_bank() * 4 + self.value(), modifier)
+    }
+}
+
+impl FromStr for SspPointerRegister {
+    type Err = errors::IsaError;
+
+    fn from_str(s: &str) -> Result<SspPointerRegister, errors::IsaError> {
+        SspPointerRegister::new(s)
+            .ok_or_else(|| errors::IsaError(format!("Invalid pointer register: {}", s)))
+    }
 }
 
 #[cfg(test)]
@@ -376,4 +498,27 @@ mod pointer_registers_tests {
             Some(SspPointerRegister::R7(SspModifier::Bank3))
         );
     }
+
+    #[test]
+    fn check_registers_round_trip() {
+        for value in 0..16 {
+            let reg = SspGeneralRegister::from_value(value).unwrap();
+            assert_eq!(reg.to_string().parse::<SspGeneralRegister>(), Ok(reg));
+        }
+        for number in 0..8 {
+            for modifier in 0..4 {
+                let preg = SspPointerRegister::from_values(number, modifier).unwrap();
+                assert_eq!(preg.to_string().parse::<SspPointerRegister>(), Ok(preg));
+            }
+        }
+        assert_eq!(
+            SspPointerRegister::R5(SspModifier::PostIncrementModulo).to_string(),
+            "r5+!"
+        );
+        assert_eq!(
+            SspPointerRegister::R7(SspModifier::Bank2).to_string(),
+            "r7|10"
+        );
+        assert!("r8".parse::<SspPointerRegister>().is_err());
+    }
 }
@@ -0,0 +1,128 @@
+use crate::cpu::Ssp1601;
+use crate::errors::SimError;
+use ssp16_isa::instructions::Instruction;
+use ssp16_isa::mnemonics::SspMnemonic;
+use ssp16_isa::operators::{SspMnemonicCondition, SspOperator};
+use std::collections::BTreeMap;
+
+// Times a conditional instruction found its condition true (the branch or call was taken, or the
+// accumulator operation done) and false:
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Branch {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+// Instructions run (by program word address), and the outcomes of the conditional ones. It adds up
+// every run it's used in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+    pub hits: BTreeMap<u16, u64>,
+    pub branches: BTreeMap<u16, Branch>,
+}
+
+impl Coverage {
+    // Runs an instruction, recording it (failed instructions aren't):
+    pub fn step(&mut self, ssp: &mut Ssp1601) -> Result<(), SimError> {
+        let pc = ssp.pc;
+        let taken = ssp
+            .instruction(pc)
+            .and_then(|instruction| condition(&instruction))
+            .map(|condition| ssp.condition(condition));
+        ssp.step()?;
+
+        *self.hits.entry(pc).or_default() += 1;
+        if let Some(taken) = taken {
+            let branch = self.branches.entry(pc).or_default();
+            if taken {
+                branch.taken += 1;
+            } else {
+                branch.not_taken += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Condition of a conditional `BRA`, `CALL` or `MOD` (`None` for anything else, or `always`):
+pub fn condition(instruction: &Instruction) -> Option<SspMnemonicCondition> {
+    match instruction.operands()? {
+        (SspMnemonic::Bra, operands)
+        | (SspMnemonic::Call, operands)
+        | (SspMnemonic::Mod, operands) => match operands.first() {
+            Some(SspOperator::Condition(SspMnemonicCondition::Always)) => None,
+            Some(SspOperator::Condition(condition)) => Some(*condition),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod coverage_tests {
+    use super::*;
+    use crate::memory::Memory;
+
+    // Simulator with `code` at 0x400:
+    fn simulator(code: &[&str]) -> Ssp1601 {
+        let mut rom = vec![0; 0x400];
+        for line in code {
+            rom.extend(line.parse::<Instruction>().unwrap().encode().unwrap());
+        }
+        Ssp1601::new(Memory::new(rom))
+    }
+
+    #[test]
+    fn check_coverage() {
+        // Counts A down from 2, negating it when it's negative (never)
+        let mut ssp = simulator(&[
+            "ld a, 0002",
+            "subi 01",
+            "mod n=1, neg",
+            "bra z=0, 0402",
+            "call z=0, 0400",
+            "ret",
+        ]);
+        let mut coverage = Coverage::default();
+
+        assert_eq!(ssp.call_with(0x400, 100, |ssp| coverage.step(ssp)), Ok(9));
+        assert_eq!(
+            coverage
+                .hits
+                .clone()
+                .into_iter()
+                .collect::<Vec<(u16, u64)>>(),
+            vec![
+                (0x400, 1),
+                (0x402, 2),
+                (0x403, 2),
+                (0x404, 2),
+                (0x406, 1),
+                (0x408, 1)
+            ]
+        );
+        let branches: Vec<(u16, u64, u64)> = coverage
+            .branches
+            .iter()
+            .map(|(address, branch)| (*address, branch.taken, branch.not_taken))
+            .collect();
+        assert_eq!(branches, vec![(0x403, 0, 2), (0x404, 1, 1), (0x406, 0, 1)]);
+    }
+
+    #[test]
+    fn check_conditions() {
+        let condition = |text: &str| super::condition(&text.parse::<Instruction>().unwrap());
+
+        assert_eq!(
+            condition("bra gpi0=1, 0400"),
+            Some(SspMnemonicCondition::Gpi0(true))
+        );
+        assert_eq!(
+            condition("mod z=1, abs"),
+            Some(SspMnemonicCondition::Z(true))
+        );
+        assert_eq!(condition("bra always, 0400"), None);
+        assert_eq!(condition("mod f, setie"), None);
+        assert_eq!(condition("ret"), None);
+    }
+}
@@ -0,0 +1,809 @@
+use crate::errors::SimError;
+use crate::memory::{Memory, PmAdapter};
+use ssp16_isa::instructions::Instruction;
+use ssp16_isa::mnemonics::{SspMnemonic, SspMnemonicModifier};
+use ssp16_isa::operators::{
+    SspMnemonicAccOperation, SspMnemonicCondition, SspMnemonicFlagOperation, SspOperator,
+};
+use ssp16_isa::registers::{SspGeneralRegister, SspModifier, SspPointerRegister};
+
+pub const STACK_SIZE: usize = 6;
+
+// ST bits:
+pub const ST_RPL: u16 = 0x0007;
+pub const ST_PM: u16 = 0x0060;
+pub const ST_IE: u16 = 0x0080;
+pub const ST_OP: u16 = 0x0100;
+pub const ST_L: u16 = 0x1000;
+pub const ST_Z: u16 = 0x2000;
+pub const ST_V: u16 = 0x4000;
+pub const ST_N: u16 = 0x8000;
+
+// XST state bits (ext0 when it isn't PM0), set when a side writes XST and cleared when the other
+// side reads the state:
+pub const XST_SSP_WRITTEN: u16 = 0x0001;
+pub const XST_68K_WRITTEN: u16 = 0x0002;
+
+// Memory spaces instructions access (besides fetching them): RAM banks A and B, program memory
+// (`((ri))` and `(a)`) and the external memory behind the PM registers (SVP word addresses).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Space {
+    Ram(usize),
+    Program,
+    External,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Access {
+    pub space: Space,
+    pub address: u32,
+    pub write: bool,
+    // Value of the word before the access (what undoing a write takes)
+    pub previous: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct Ssp1601 {
+    pub x: u16,
+    pub y: u16,
+    // 32 bits accumulator: `A` is the high word, `AL` (ext7) the low one
+    pub a: u32,
+    pub st: u16,
+    pub pc: u16,
+    pub stack: Vec<u16>,
+    pub r: [u8; 8],
+    pub ram: [[u16; 0x100]; 2],
+    // ext0-ext5 when they don't act as PM registers (i.e.: XST)
+    pub ext: [u16; 6],
+    pub pm: PmAdapter,
+    pub memory: Memory,
+    // Instructions executed so far
+    pub steps: u64,
+    // Memory accessed by the last instruction
+    pub accesses: Vec<Access>,
+}
+
+impl Ssp1601 {
+    pub fn new(memory: Memory) -> Ssp1601 {
+        Ssp1601 {
+            x: 0,
+            y: 0,
+            a: 0,
+            st: 0,
+            pc: 0x400,
+            stack: Vec::new(),
+            r: [0; 8],
+            ram: [[0; 0x100]; 2],
+            ext: [0; 6],
+            pm: PmAdapter::default(),
+            memory,
+            steps: 0,
+            accesses: Vec::new(),
+        }
+    }
+
+    // P always holds the product of X and Y (signed, shifted left once):
+    pub fn p(&self) -> u32 {
+        (self.x as i16 as i32 * self.y as i16 as i32 * 2) as u32
+    }
+
+    // The instruction at `address`:
+    pub fn instruction(&self, address: u16) -> Option<Instruction<'static>> {
+        Instruction::decode(
+            self.memory.program(address),
+            self.memory.program(address.wrapping_add(1)),
+        )
+    }
+
+    pub fn step(&mut self) -> Result<(), SimError> {
+        let address = self.pc;
+        let instruction = self.instruction(address).ok_or_else(|| {
+            SimError(format!(
+                "Invalid opcode {:04X} at {:04X}",
+                self.memory.program(address),
+                address
+            ))
+        })?;
+        let (mnemonic, operands) = instruction.operands().unwrap_or((SspMnemonic::Ret, vec![]));
+        self.pc = address.wrapping_add(instruction.size().unwrap_or(1));
+        self.steps += 1;
+        self.accesses.clear();
+
+        // PC is left at the instruction that failed
+        self.execute(mnemonic, &operands)
+            .map_err(|SimError(error)| {
+                self.pc = address;
+                SimError(format!("{} at {:04X}", error, address))
+            })
+    }
+
+    // Calls the routine at `address` and runs it until it returns, for `max_steps` instructions at
+    // most. Returns the number of instructions executed.
+    pub fn call(&mut self, address: u16, max_steps: u64) -> Result<u64, SimError> {
+        self.call_with(address, max_steps, Ssp1601::step)
+    }
+
+    // Like `call`, running each instruction through `step` (i.e.: to record coverage):
+    pub fn call_with(
+        &mut self,
+        address: u16,
+        max_steps: u64,
+        mut step: impl FnMut(&mut Ssp1601) -> Result<(), SimError>,
+    ) -> Result<u64, SimError> {
+        let depth = self.stack.len();
+        let start = self.steps;
+
+        self.push(self.pc)?;
+        self.pc = address;
+        while self.stack.len() > depth {
+            if self.steps - start >= max_steps {
+                return Err(SimError(format!(
+                    "No return after {} instructions (PC at {:04X})",
+                    max_steps, self.pc
+                )));
+            }
+            step(self)?;
+        }
+        Ok(self.steps - start)
+    }
+
+    fn execute(&mut self, mnemonic: SspMnemonic, operands: &[SspOperator]) -> Result<(), SimError> {
+        match (mnemonic, operands) {
+            (SspMnemonic::Ld(_), [destination, source]) => {
+                self.load(*destination, *source)?;
+            }
+
+            (SspMnemonic::Sub(_), _)
+            | (SspMnemonic::Cmp(_), _)
+            | (SspMnemonic::Add(_), _)
+            | (SspMnemonic::And(_), _)
+            | (SspMnemonic::Or(_), _)
+            | (SspMnemonic::Eor(_), _) => {
+                let value = match operands {
+                    [SspOperator::Byte(value)] => (*value as u32) << 16,
+                    [_, SspOperator::Word(value)] if SspMnemonic::is_immediate(mnemonic) => {
+                        (*value as u32) << 16
+                    }
+                    [_, source] => self.alu_operand(*source)?,
+                    _ => return Err(self.invalid(mnemonic, operands)),
+                };
+                self.alu(mnemonic, value);
+            }
+
+            (
+                SspMnemonic::Mod,
+                [SspOperator::Condition(condition), SspOperator::AccOperation(op)],
+            ) => {
+                if self.condition(*condition) {
+                    self.accumulator_operation(*op);
+                }
+            }
+            (SspMnemonic::Mod, [SspOperator::ConditionFlag(_), SspOperator::FlagOperation(op)]) => {
+                self.flag_operation(*op);
+            }
+
+            (SspMnemonic::Bra, [SspOperator::Condition(condition), SspOperator::Word(address)]) => {
+                if self.condition(*condition) {
+                    self.pc = *address;
+                }
+            }
+            (
+                SspMnemonic::Call,
+                [SspOperator::Condition(condition), SspOperator::Word(address)],
+            ) => {
+                if self.condition(*condition) {
+                    self.push(self.pc)?;
+                    self.pc = *address;
+                }
+            }
+
+            // Multiply-accumulate with the previous product, then load X and Y from RAM A/B
+            (SspMnemonic::Mld, [SspOperator::PtrRef(j), SspOperator::PtrRef(i)])
+            | (SspMnemonic::Mpya, [SspOperator::PtrRef(j), SspOperator::PtrRef(i)])
+            | (SspMnemonic::Mpys, [SspOperator::PtrRef(j), SspOperator::PtrRef(i)]) => {
+                match mnemonic {
+                    SspMnemonic::Mld => {
+                        self.a = 0;
+                        self.st &= !(ST_L | ST_Z | ST_V | ST_N);
+                    }
+                    SspMnemonic::Mpya => {
+                        self.alu(SspMnemonic::Add(SspMnemonicModifier::Reference), self.p())
+                    }
+                    _ => self.alu(SspMnemonic::Sub(SspMnemonicModifier::Reference), self.p()),
+                }
+                for preg in [*j, *i].iter() {
+                    let value = self.read_pointer(*preg);
+                    match preg.ram_bank() {
+                        0 => self.x = value,
+                        _ => self.y = value,
+                    }
+                }
+            }
+
+            (SspMnemonic::Ret, []) => self.pc = self.pop()?,
+
+            _ => return Err(self.invalid(mnemonic, operands)),
+        }
+        Ok(())
+    }
+
+    fn invalid(&self, mnemonic: SspMnemonic, operands: &[SspOperator]) -> SimError {
+        SimError(format!(
+            "Unsupported instruction {}",
+            Instruction::with_operands(mnemonic, operands)
+        ))
+    }
+
+    fn load(&mut self, destination: SspOperator, source: SspOperator) -> Result<(), SimError> {
+        // `ld a, p` copies the whole product
+        if destination == SspOperator::Reg(SspGeneralRegister::A)
+            && source == SspOperator::Reg(SspGeneralRegister::P)
+        {
+            self.a = self.p();
+            return Ok(());
+        }
+
+        let value = match source {
+            SspOperator::Reg(register) => self.read_register(register)?,
+            SspOperator::Ptr(preg) => self.r[number(preg)] as u16,
+            SspOperator::PtrRef(preg) => self.read_pointer(preg),
+            SspOperator::PtrDoubleRef(preg) => self.read_double_pointer(preg),
+            SspOperator::PtrAccumulator => self.read_program((self.a >> 16) as u16),
+            SspOperator::RamBankAddressA(address) => self.read_ram(0, address as usize),
+            SspOperator::RamBankAddressB(address) => self.read_ram(1, address as usize),
+            SspOperator::Word(value) => value,
+            SspOperator::Byte(value) => value as u16,
+            _ => return Err(SimError(format!("Invalid source {}", source))),
+        };
+
+        match destination {
+            SspOperator::Reg(register) => self.write_register(register, value)?,
+            SspOperator::Ptr(preg) => self.r[number(preg)] = value as u8,
+            SspOperator::PtrRef(preg) => self.write_pointer(preg, value),
+            SspOperator::RamBankAddressA(address) => self.write_ram(0, address as usize, value),
+            SspOperator::RamBankAddressB(address) => self.write_ram(1, address as usize, value),
+            _ => return Err(SimError(format!("Invalid destination {}", destination))),
+        }
+        Ok(())
+    }
+
+    // Operand of `OP A, s`, in the high word (P and A are used whole):
+    fn alu_operand(&mut self, source: SspOperator) -> Result<u32, SimError> {
+        let value = match source {
+            SspOperator::Reg(SspGeneralRegister::P) => return Ok(self.p()),
+            SspOperator::Reg(SspGeneralRegister::A) => return Ok(self.a),
+            SspOperator::Reg(register) => self.read_register(register)?,
+            SspOperator::Ptr(preg) => self.r[number(preg)] as u16,
+            SspOperator::PtrRef(preg) => self.read_pointer(preg),
+            SspOperator::PtrDoubleRef(preg) => self.read_double_pointer(preg),
+            // RAM address, bank B from 0x100
+            SspOperator::Byte(address) => self.read_ram(0, address as usize),
+            SspOperator::Word(address) => {
+                self.read_ram((address >> 8) as usize & 1, address as usize & 0xFF)
+            }
+            _ => return Err(SimError(format!("Invalid operand {}", source))),
+        };
+        Ok((value as u32) << 16)
+    }
+
+    // Flags as updated by emulators: arithmetic clears L and OV, logic operations leave them.
+    fn alu(&mut self, mnemonic: SspMnemonic, value: u32) {
+        let result = match mnemonic {
+            SspMnemonic::Sub(_) | SspMnemonic::Cmp(_) => self.a.wrapping_sub(value),
+            SspMnemonic::Add(_) => self.a.wrapping_add(value),
+            SspMnemonic::And(_) => self.a & value,
+            SspMnemonic::Or(_) => self.a | value,
+            _ => self.a ^ value,
+        };
+
+        if let SspMnemonic::Sub(_) | SspMnemonic::Cmp(_) | SspMnemonic::Add(_) = mnemonic {
+            self.st &= !(ST_L | ST_V);
+        }
+        self.update_zn(result);
+        if !matches!(mnemonic, SspMnemonic::Cmp(_)) {
+            self.a = result;
+        }
+    }
+
+    fn update_zn(&mut self, result: u32) {
+        self.st &= !(ST_Z | ST_N);
+        if result == 0 {
+            self.st |= ST_Z;
+        }
+        if result & 0x8000_0000 != 0 {
+            self.st |= ST_N;
+        }
+    }
+
+    fn accumulator_operation(&mut self, op: SspMnemonicAccOperation) {
+        self.a = match op {
+            SspMnemonicAccOperation::Ror => self.a.rotate_right(1),
+            SspMnemonicAccOperation::Rol => self.a.rotate_left(1),
+            SspMnemonicAccOperation::Shr => ((self.a as i32) >> 1) as u32,
+            SspMnemonicAccOperation::Shl => self.a << 1,
+            SspMnemonicAccOperation::Inc => self.a.wrapping_add(1),
+            SspMnemonicAccOperation::Dec => self.a.wrapping_sub(1),
+            SspMnemonicAccOperation::Neg => (self.a as i32).wrapping_neg() as u32,
+            SspMnemonicAccOperation::Abs => (self.a as i32).wrapping_abs() as u32,
+        };
+        self.update_zn(self.a);
+    }
+
+    // What `res` and `set` do isn't known, they are ignored:
+    fn flag_operation(&mut self, op: SspMnemonicFlagOperation) {
+        match op {
+            SspMnemonicFlagOperation::Resl => self.st &= !ST_L,
+            SspMnemonicFlagOperation::Setl => self.st |= ST_L,
+            SspMnemonicFlagOperation::Resie => self.st &= !ST_IE,
+            SspMnemonicFlagOperation::Setie => self.st |= ST_IE,
+            SspMnemonicFlagOperation::Resop => self.st &= !ST_OP,
+            SspMnemonicFlagOperation::Setop => self.st |= ST_OP,
+            SspMnemonicFlagOperation::Res | SspMnemonicFlagOperation::Set => (),
+        }
+    }
+
+    // GPI and DIOF inputs aren't connected, so they read as 0:
+    pub fn condition(&self, condition: SspMnemonicCondition) -> bool {
+        let flag = match condition {
+            SspMnemonicCondition::Always => return true,
+            SspMnemonicCondition::L(_) => self.st & ST_L != 0,
+            SspMnemonicCondition::Z(_) => self.st & ST_Z != 0,
+            SspMnemonicCondition::Ov(_) => self.st & ST_V != 0,
+            SspMnemonicCondition::N(_) => self.st & ST_N != 0,
+            _ => false,
+        };
+        flag as u8 == condition.flag_value()
+    }
+
+    pub fn push(&mut self, value: u16) -> Result<(), SimError> {
+        if self.stack.len() == STACK_SIZE {
+            return Err(SimError("Stack overflow".to_string()));
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Result<u16, SimError> {
+        self.stack
+            .pop()
+            .ok_or_else(|| SimError("Stack underflow".to_string()))
+    }
+
+    pub fn read_register(&mut self, register: SspGeneralRegister) -> Result<u16, SimError> {
+        Ok(match register {
+            SspGeneralRegister::Dummy => 0xFFFF,
+            SspGeneralRegister::X => self.x,
+            SspGeneralRegister::Y => self.y,
+            SspGeneralRegister::A => (self.a >> 16) as u16,
+            SspGeneralRegister::St => self.st,
+            SspGeneralRegister::Stack => self.pop()?,
+            SspGeneralRegister::Pc => self.pc,
+            SspGeneralRegister::P => (self.p() >> 16) as u16,
+            SspGeneralRegister::Ext6 => self.pm.read_pmc(),
+            SspGeneralRegister::Ext7 => self.a as u16,
+            ext => {
+                let index = ext.value() as usize - 8;
+                if self.is_pm(index) {
+                    self.record(Space::External, self.pm.address(index, false), false);
+                    self.pm.read_pm(index, &self.memory)
+                } else if index == 0 {
+                    let state = self.ext[0];
+                    self.ext[0] &= !XST_68K_WRITTEN;
+                    state
+                } else {
+                    self.ext[index]
+                }
+            }
+        })
+    }
+
+    pub fn write_register(
+        &mut self,
+        register: SspGeneralRegister,
+        value: u16,
+    ) -> Result<(), SimError> {
+        match register {
+            SspGeneralRegister::Dummy | SspGeneralRegister::P => (),
+            SspGeneralRegister::X => self.x = value,
+            SspGeneralRegister::Y => self.y = value,
+            SspGeneralRegister::A => self.a = (value as u32) << 16 | self.a & 0xFFFF,
+            SspGeneralRegister::St => self.st = value,
+            SspGeneralRegister::Stack => self.push(value)?,
+            SspGeneralRegister::Pc => self.pc = value,
+            SspGeneralRegister::Ext6 => self.pm.write_pmc(value),
+            SspGeneralRegister::Ext7 => self.a = self.a & 0xFFFF_0000 | value as u32,
+            ext => {
+                let index = ext.value() as usize - 8;
+                if self.is_pm(index) {
+                    self.record(Space::External, self.pm.address(index, true), true);
+                    self.pm.write_pm(index, value, &mut self.memory);
+                } else {
+                    self.ext[index] = value;
+                    if index == 3 {
+                        self.ext[0] |= XST_SSP_WRITTEN;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // PM4 is always available, PM0-PM3 only with ST bits 5-6 set:
+    fn is_pm(&self, index: usize) -> bool {
+        index == 4 || (index < 4 && self.st & ST_PM != 0)
+    }
+
+    // RAM bank and address a pointer register refers to, applying its modifier (`+!` is a plain
+    // increment, `-` and `+` wrap within the size in ST bits 0-2). r3 and r7 address the first four
+    // words of their bank directly.
+    fn pointer(&mut self, preg: SspPointerRegister) -> (usize, usize) {
+        let bank = preg.ram_bank() as usize;
+        if preg.value() == 3 {
+            return (bank, preg.modifier_value() as usize);
+        }
+
+        let index = number(preg);
+        let address = self.r[index];
+        let size = 1u8 << (self.st & ST_RPL);
+        let modulo = |next: u8| match size {
+            1 => next,
+            _ => address & !(size - 1) | next & (size - 1),
+        };
+        self.r[index] = match modifier(preg) {
+            SspModifier::PostIncrement => modulo(address.wrapping_add(1)),
+            SspModifier::PostIncrementModulo => address.wrapping_add(1),
+            SspModifier::PostDecrementModulo => modulo(address.wrapping_sub(1)),
+            _ => address,
+        };
+        (bank, address as usize)
+    }
+
+    pub fn read_pointer(&mut self, preg: SspPointerRegister) -> u16 {
+        let (bank, address) = self.pointer(preg);
+        self.read_ram(bank, address)
+    }
+
+    fn write_pointer(&mut self, preg: SspPointerRegister, value: u16) {
+        let (bank, address) = self.pointer(preg);
+        self.write_ram(bank, address, value);
+    }
+
+    // ((ri)): program memory at the address held in RAM, which is incremented afterwards
+    fn read_double_pointer(&mut self, preg: SspPointerRegister) -> u16 {
+        let (bank, address) = self.pointer(preg);
+        let program_address = self.read_ram(bank, address);
+        self.write_ram(bank, address, program_address.wrapping_add(1));
+        self.read_program(program_address)
+    }
+
+    fn read_ram(&mut self, bank: usize, address: usize) -> u16 {
+        self.record(Space::Ram(bank), Some(address as u32), false);
+        self.ram[bank][address]
+    }
+
+    fn write_ram(&mut self, bank: usize, address: usize, value: u16) {
+        self.record(Space::Ram(bank), Some(address as u32), true);
+        self.ram[bank][address] = value;
+    }
+
+    fn read_program(&mut self, address: u16) -> u16 {
+        self.record(Space::Program, Some(address as u32), false);
+        self.memory.program(address)
+    }
+
+    // Blind PM accesses (with no address) aren't recorded:
+    fn record(&mut self, space: Space, address: Option<u32>, write: bool) {
+        if let Some(address) = address {
+            let previous = match space {
+                Space::Ram(bank) => self.ram[bank][address as usize],
+                Space::Program => self.memory.program(address as u16),
+                Space::External => self.memory.read(address),
+            };
+            self.accesses.push(Access {
+                space,
+                address,
+                write,
+                previous,
+            });
+        }
+    }
+}
+
+// Number (0-7) of a pointer register:
+pub fn number(preg: SspPointerRegister) -> usize {
+    (preg.ram_bank() * 4 + preg.value()) as usize
+}
+
+fn modifier(preg: SspPointerRegister) -> SspModifier {
+    match preg {
+        SspPointerRegister::R0(modifier)
+        | SspPointerRegister::R1(modifier)
+        | SspPointerRegister::R2(modifier)
+        | SspPointerRegister::R3(modifier)
+        | SspPointerRegister::R4(modifier)
+        | SspPointerRegister::R5(modifier)
+        | SspPointerRegister::R6(modifier)
+        | SspPointerRegister::R7(modifier) => modifier,
+    }
+}
+
+#[cfg(test)]
+mod cpu_tests {
+    use super::*;
+
+    // Simulator with `code` assembled at 0x400:
+    fn simulator(code: &[&str]) -> Ssp1601 {
+        let mut rom = vec![0; 0x400];
+        for line in code {
+            rom.extend(line.parse::<Instruction>().unwrap().encode().unwrap());
+        }
+        Ssp1601::new(Memory::new(rom))
+    }
+
+    fn run(code: &[&str]) -> Ssp1601 {
+        let mut ssp = simulator(code);
+        let end = ssp.memory.rom.len() as u16;
+        while ssp.pc < end {
+            ssp.step().unwrap();
+        }
+        ssp
+    }
+
+    #[test]
+    fn check_loads() {
+        let ssp = run(&[
+            "ld x, 1234",
+            "ld y, x",
+            "ld a, FFFE",
+            "ld ext7, 0001",
+            "ld r0, 10",
+            "ld (r0+), y",
+            "ld (r0), a",
+            "ld (r7|01), x",
+            "ld B[01], a",
+        ]);
+
+        assert_eq!((ssp.x, ssp.y, ssp.a), (0x1234, 0x1234, 0xFFFE_0001));
+        assert_eq!(ssp.r[0], 0x11);
+        assert_eq!(&ssp.ram[0][0x10..0x12], &[0x1234, 0xFFFE]);
+        assert_eq!(ssp.ram[1][1], 0xFFFE);
+        assert_eq!(ssp.pc, 0x400 + 12);
+    }
+
+    #[test]
+    fn check_program_memory_reads() {
+        let mut ssp = simulator(&[
+            "ld r1, 00",
+            "ld x, ((r1))",
+            "ld y, ((r1))",
+            "ld a, 0400",
+            "ld st, (a)",
+        ]);
+        ssp.ram[0][0] = 0x0400;
+        for _ in 0..5 {
+            ssp.step().unwrap();
+        }
+
+        assert_eq!(ssp.x, ssp.memory.program(0x400));
+        assert_eq!(ssp.y, ssp.memory.program(0x401));
+        assert_eq!(ssp.ram[0][0], 0x0402);
+        assert_eq!(ssp.st, ssp.memory.program(0x400));
+    }
+
+    #[test]
+    fn check_alu() {
+        let ssp = run(&[
+            "ld a, 0010",
+            "addi 05",
+            "subi a, 0016",
+            "ld y, 0004",
+            "add a, y",
+        ]);
+        assert_eq!(ssp.a, 0x0003_0000);
+        assert_eq!(ssp.st & (ST_Z | ST_N), 0);
+
+        let ssp = run(&["ld a, 0001", "cmpi 01"]);
+        assert_eq!(ssp.a, 0x0001_0000);
+        assert_eq!(ssp.st & ST_Z, ST_Z);
+
+        let ssp = run(&["ld a, 00F0", "andi a, 003C", "ori 01", "eori a, 0001"]);
+        assert_eq!(ssp.a, 0x0030_0000);
+
+        // RAM operands
+        let mut ssp = simulator(&["add a, 02", "add a, 0102", "sub a, (r0)"]);
+        ssp.ram[0][0] = 0x0010;
+        ssp.ram[0][2] = 0x0010;
+        ssp.ram[1][2] = 0x0001;
+        for _ in 0..3 {
+            ssp.step().unwrap();
+        }
+        assert_eq!(ssp.a, 0x0001_0000);
+
+        let ssp = run(&["ld a, 0000", "subi 01"]);
+        assert_eq!(ssp.a, 0xFFFF_0000);
+        assert_eq!(ssp.st & ST_N, ST_N);
+    }
+
+    #[test]
+    fn check_multiplication() {
+        let mut ssp = simulator(&[
+            "mld (r4+), (r0+)",
+            "mpya (r4+), (r0+)",
+            "mpya (r4+), (r0+)",
+            "mpys (r4), (r0)",
+        ]);
+        ssp.ram[0][0..3].copy_from_slice(&[2, 3, 0xFFFF]);
+        ssp.ram[1][0..3].copy_from_slice(&[5, 7, 1]);
+        ssp.r[4] = 0;
+        for _ in 0..4 {
+            ssp.step().unwrap();
+        }
+
+        // 2*5*2 + 3*7*2, minus -1*1*2
+        assert_eq!(ssp.a, 64);
+
+        let ssp = run(&["ld x, 0003", "ld y, FFFE", "ld a, p"]);
+        assert_eq!(ssp.a, 0xFFFF_FFF4);
+    }
+
+    #[test]
+    fn check_accumulator_and_flag_operations() {
+        let ssp = run(&[
+            "ld a, 8000",
+            "mod always, shr",
+            "mod z=0, neg",
+            "mod f, setl",
+        ]);
+        assert_eq!(ssp.a, 0x4000_0000);
+        assert_eq!(ssp.st & ST_L, ST_L);
+
+        let ssp = run(&[
+            "ld a, FFFF",
+            "ld ext7, FFFF",
+            "cmpi 00",
+            "mod n=1, abs",
+            "mod always, inc",
+        ]);
+        assert_eq!(ssp.a, 2);
+    }
+
+    #[test]
+    fn check_branches_and_calls() {
+        let mut ssp = simulator(&[
+            "cmpi 00",
+            "bra z=1, 0405",
+            "ld x, 0001",
+            "call always, 0408",
+            "ret",
+            "ld y, 0002",
+            "ret",
+        ]);
+        ssp.call(0x400, 100).unwrap();
+
+        assert_eq!((ssp.x, ssp.y), (0, 2));
+        assert!(ssp.stack.is_empty());
+
+        let mut ssp = simulator(&["bra always, 0400"]);
+        assert!(ssp.call(0x400, 10).is_err());
+        assert_eq!(ssp.steps, 10);
+    }
+
+    #[test]
+    fn check_stack() {
+        let ssp = run(&["ld stack, 0001", "ld stack, 0002", "ld x, stack"]);
+        assert_eq!(ssp.x, 2);
+        assert_eq!(ssp.stack, vec![1]);
+
+        let mut ssp = simulator(&["ld a, stack"]);
+        assert_eq!(
+            ssp.step(),
+            Err(SimError("Stack underflow at 0400".to_string()))
+        );
+
+        let mut ssp = simulator(&["ld stack, -"; 7]);
+        assert!((0..7).map(|_| ssp.step()).any(|result| result.is_err()));
+    }
+
+    #[test]
+    fn check_pm_registers() {
+        let ssp = run(&[
+            "ld ext6, 0FFF",
+            "ld ext6, 0818",
+            "ld ext4, -",
+            "ld ext4, 1234",
+            "ld ext4, 5678",
+            "ld ext0, 0001",
+            "ld st, 0060",
+            "ld ext6, 1000",
+            "ld ext6, 0818",
+            "ld -, ext0",
+            "ld x, ext0",
+            "ld y, ext0",
+        ]);
+
+        assert_eq!(&ssp.memory.dram[0x1000..0x1002], &[0x1234, 0x5678]);
+        assert_eq!(ssp.ext[0], 1);
+        assert_eq!((ssp.x, ssp.y), (0x1234, 0x5678));
+    }
+
+    #[test]
+    fn check_xst_state() {
+        // The 68000 wrote XST, the SSP1601 reads the state and answers
+        let mut ssp = simulator(&["ld a, ext0", "ld x, ext3", "ld ext3, 1010", "ld y, ext0"]);
+        ssp.ext[0] = XST_68K_WRITTEN;
+        ssp.ext[3] = 0x0005;
+        for _ in 0..4 {
+            ssp.step().unwrap();
+        }
+
+        assert_eq!((ssp.a >> 16, ssp.x), (0x0002, 0x0005));
+        assert_eq!((ssp.y, ssp.ext[0], ssp.ext[3]), (0x0001, 0x0001, 0x1010));
+    }
+
+    #[test]
+    fn check_accesses() {
+        let mut ssp = simulator(&[
+            "ld (r0+), x",
+            "ld y, ((r4))",
+            "ld ext6, FFFF",
+            "ld ext6, 0817",
+            "ld ext4, -",
+            "ld ext4, a",
+        ]);
+        let access = |space, address, write, previous| Access {
+            space,
+            address,
+            write,
+            previous,
+        };
+        ssp.ram[0][0] = 0x0010;
+        ssp.memory.iram[0] = 0x1234;
+        ssp.memory.dram[0] = 0xABCD;
+
+        ssp.step().unwrap();
+        assert_eq!(ssp.accesses, vec![access(Space::Ram(0), 0, true, 0x0010)]);
+        ssp.step().unwrap();
+        assert_eq!(
+            ssp.accesses,
+            vec![
+                access(Space::Ram(1), 0, false, 0),
+                access(Space::Ram(1), 0, true, 0),
+                access(Space::Program, 0, false, 0x1234),
+            ]
+        );
+        for _ in 0..3 {
+            ssp.step().unwrap();
+        }
+        assert_eq!(ssp.accesses, vec![]);
+        ssp.step().unwrap();
+        assert_eq!(
+            ssp.accesses,
+            vec![access(Space::External, 0x180000, true, 0xABCD)]
+        );
+    }
+
+    #[test]
+    fn check_modulo_pointers() {
+        let ssp = run(&[
+            "ld st, 0002",
+            "ld r1, 07",
+            "ld x, (r1+!)",
+            "ld r2, 04",
+            "ld x, (r2-)",
+            "ld r5, FF",
+            "ld x, (r5+)",
+        ]);
+        assert_eq!(ssp.r[1], 0x08);
+        assert_eq!(ssp.r[2], 0x07);
+        assert_eq!(ssp.r[5], 0xFC);
+    }
+
+    #[test]
+    fn check_invalid_opcodes() {
+        let mut ssp = Ssp1601::new(Memory::new(vec![0xFFFF; 0x401]));
+        assert_eq!(
+            ssp.step(),
+            Err(SimError("Invalid opcode FFFF at 0400".to_string()))
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use ssp16_isa::instructions::Instruction;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// Lines are 1-based, like in editors:
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLine {
+    pub file: String,
+    pub line: usize,
+    pub text: String,
+}
+
+// What the assembler knows about a program: its labels and the source line of every instruction
+// (by program word address).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DebugInfo {
+    pub labels: BTreeMap<String, u16>,
+    pub lines: BTreeMap<u16, SourceLine>,
+}
+
+impl DebugInfo {
+    pub fn line(&self, address: u16) -> Option<&SourceLine> {
+        self.lines.get(&address)
+    }
+
+    // Address of the first instruction at `line` or after it (files can be given without their
+    // directory):
+    pub fn address(&self, file: Option<&str>, line: usize) -> Option<u16> {
+        let same_file = |source: &SourceLine| match file {
+            Some(file) => {
+                source.file == file || Path::new(&source.file).file_name() == Some(file.as_ref())
+            }
+            None => true,
+        };
+
+        self.lines
+            .iter()
+            .filter(|(_, source)| same_file(source) && source.line >= line)
+            .min_by_key(|(address, source)| (source.line, **address))
+            .map(|(address, _)| *address)
+    }
+
+    // Address of a label (`@` is optional), a line or `file:line`:
+    pub fn find(&self, location: &str) -> Option<u16> {
+        if let Some(address) = self.labels.get(location.trim_start_matches('@')) {
+            return Some(*address);
+        }
+        match location.rsplit_once(':') {
+            Some((file, line)) => self.address(Some(file), line.parse().ok()?),
+            None => self.address(None, location.parse().ok()?),
+        }
+    }
+
+    // An owned copy of `instruction` (i.e.: parsed from a source line), with its label references
+    // replaced by their addresses:
+    pub fn resolve(&self, instruction: &Instruction) -> Option<Instruction<'static>> {
+        instruction.resolve(|label| self.labels.get(label).copied())
+    }
+
+    // Closest label at or before `address`, and the offset from it:
+    pub fn label(&self, address: u16) -> Option<(&str, u16)> {
+        self.labels
+            .iter()
+            .filter(|(_, value)| **value <= address)
+            .max_by(|(a, a_value), (b, b_value)| a_value.cmp(b_value).then(b.cmp(a)))
+            .map(|(name, value)| (name.as_str(), address - value))
+    }
+
+    // `label+offset` (or just the address if there's no label before it):
+    pub fn describe(&self, address: u16) -> String {
+        match self.label(address) {
+            Some((label, 0)) => label.to_string(),
+            Some((label, offset)) => format!("{}+{:X}", label, offset),
+            None => format!("{:04X}", address),
+        }
+    }
+}
+
+#[cfg(test)]
+mod debug_tests {
+    use super::*;
+
+    fn debug_info() -> DebugInfo {
+        let source = |line, text: &str| SourceLine {
+            file: "src/main.svp".to_string(),
+            line,
+            text: text.to_string(),
+        };
+
+        DebugInfo {
+            labels: [("start", 0x400), ("loop", 0x402), ("again", 0x402)]
+                .iter()
+                .map(|(name, value)| (name.to_string(), *value))
+                .collect(),
+            lines: [
+                (0x400, source(3, "ld a, 0010")),
+                (0x402, source(5, "subi 01")),
+                (0x403, source(6, "bra z=0, @loop")),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+        }
+    }
+
+    #[test]
+    fn check_lines() {
+        let debug = debug_info();
+
+        assert_eq!(debug.line(0x402).map(|line| line.line), Some(5));
+        assert_eq!(debug.line(0x401), None);
+        assert_eq!(debug.address(Some("main.svp"), 4), Some(0x402));
+        assert_eq!(debug.address(Some("src/main.svp"), 6), Some(0x403));
+        assert_eq!(debug.address(None, 1), Some(0x400));
+        assert_eq!(debug.address(Some("other.svp"), 1), None);
+        assert_eq!(debug.address(None, 7), None);
+
+        assert_eq!(debug.find("@loop"), Some(0x402));
+        assert_eq!(debug.find("start"), Some(0x400));
+        assert_eq!(debug.find("main.svp:4"), Some(0x402));
+        assert_eq!(debug.find("6"), Some(0x403));
+        assert_eq!(debug.find("missing"), None);
+    }
+
+    #[test]
+    fn check_labels() {
+        let debug = debug_info();
+
+        assert_eq!(debug.label(0x403), Some(("again", 1)));
+        assert_eq!(debug.label(0x3FF), None);
+        assert_eq!(debug.describe(0x400), "start");
+        assert_eq!(debug.describe(0x404), "again+2");
+        assert_eq!(debug.describe(0x0010), "0010");
+    }
+
+    #[test]
+    fn check_resolve() {
+        let debug = debug_info();
+        let text = debug.line(0x403).unwrap().text.clone();
+
+        let instruction = debug.resolve(&Instruction::parse(&text).unwrap());
+        drop(text);
+        assert_eq!(instruction, Some("bra z=0, 0402".parse().unwrap()));
+        assert_eq!(
+            debug.resolve(&Instruction::parse("call always, @missing").unwrap()),
+            None
+        );
+    }
+}
@@ -0,0 +1,781 @@
+use crate::cpu::{Access, Space, Ssp1601};
+use crate::debug::DebugInfo;
+use crate::errors::SimError;
+use crate::memory::{DRAM_START, IRAM_START, ROM_END};
+use std::collections::BTreeSet;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+// Registers as GDB sees them (name and size in bits), in `g` packet order. `pc` is a byte address
+// like any other address GDB handles, `ext0`-`ext5` hold the values written to them when they
+// don't act as PM registers and `pmc` is `mode << 16 | address`.
+const REGISTERS: [(&str, usize); 21] = [
+    ("x", 16),
+    ("y", 16),
+    ("a", 32),
+    ("st", 16),
+    ("pc", 32),
+    ("p", 32),
+    ("r0", 16),
+    ("r1", 16),
+    ("r2", 16),
+    ("r3", 16),
+    ("r4", 16),
+    ("r5", 16),
+    ("r6", 16),
+    ("r7", 16),
+    ("ext0", 16),
+    ("ext1", 16),
+    ("ext2", 16),
+    ("ext3", 16),
+    ("ext4", 16),
+    ("ext5", 16),
+    ("pmc", 32),
+];
+
+// GDB byte addresses of each memory space (words are big endian). Program memory is seen from 0
+// like the SSP1601 sees it, so IRAM hides the first 0x800 bytes of the ROM. DRAM and IRAM are also
+// found at their 68000 addresses (SVP word addresses times 2), and RAM banks A and B after them.
+pub const ROM_BYTES_END: u32 = (ROM_END + 1) * 2;
+pub const IRAM_BYTES: u32 = 0x800;
+pub const DRAM_BYTES_START: u32 = DRAM_START * 2;
+pub const IRAM_BYTES_START: u32 = IRAM_START * 2;
+pub const RAM_A_START: u32 = 0x400000;
+pub const RAM_B_START: u32 = 0x400200;
+
+// Instructions run between checks for a GDB interrupt (Ctrl-C):
+const INTERRUPT_CHECK_STEPS: u64 = 0x1000;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum WatchKind {
+    Write,
+    Read,
+    Access,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Watchpoint {
+    pub kind: WatchKind,
+    pub address: u32,
+    pub length: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Stop {
+    Step,
+    Breakpoint,
+    Watchpoint(WatchKind, u32),
+    Interrupted,
+    Error(SimError),
+}
+
+impl Stop {
+    pub fn reply(&self) -> String {
+        match self {
+            Stop::Step | Stop::Breakpoint => "S05".to_string(),
+            Stop::Watchpoint(kind, address) => format!(
+                "T05{}:{:x};",
+                match kind {
+                    WatchKind::Write => "watch",
+                    WatchKind::Read => "rwatch",
+                    WatchKind::Access => "awatch",
+                },
+                address
+            ),
+            Stop::Interrupted => "S02".to_string(),
+            // SIGILL
+            Stop::Error(_) => "S04".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Response {
+    Reply(String),
+    Resume { step: bool },
+    Detach,
+    Kill,
+}
+
+pub struct GdbStub {
+    pub ssp: Ssp1601,
+    pub debug: DebugInfo,
+    // Program word addresses
+    pub breakpoints: BTreeSet<u16>,
+    pub watchpoints: Vec<Watchpoint>,
+    // State restored by `monitor reset`
+    initial: Ssp1601,
+}
+
+impl GdbStub {
+    pub fn new(ssp: Ssp1601, debug: DebugInfo) -> GdbStub {
+        GdbStub {
+            initial: ssp.clone(),
+            ssp,
+            debug,
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+        }
+    }
+
+    // Handles a packet (without its framing). Anything not supported gets an empty reply.
+    pub fn packet(&mut self, packet: &str) -> Response {
+        let reply = |reply: &str| Response::Reply(reply.to_string());
+        let command_length = packet.chars().next().map_or(0, char::len_utf8);
+        let (command, arguments) = packet.split_at(command_length);
+
+        match command {
+            "?" => reply("S05"),
+            "g" => Response::Reply(
+                (0..REGISTERS.len())
+                    .map(|index| self.register_hex(index))
+                    .collect(),
+            ),
+            "G" => match self.write_registers(arguments) {
+                Some(()) => reply("OK"),
+                None => reply("E01"),
+            },
+            "p" => match usize::from_str_radix(arguments, 16) {
+                Ok(index) if index < REGISTERS.len() => Response::Reply(self.register_hex(index)),
+                _ => reply("E01"),
+            },
+            "P" => match arguments.split_once('=').and_then(|(index, value)| {
+                let index = usize::from_str_radix(index, 16).ok()?;
+                self.set_register(index, u32::from_str_radix(value, 16).ok()?)
+            }) {
+                Some(()) => reply("OK"),
+                None => reply("E01"),
+            },
+            "m" => {
+                match range(arguments).and_then(|(address, length)| self.read(address, length)) {
+                    Some(bytes) => Response::Reply(hex(&bytes)),
+                    None => reply("E01"),
+                }
+            }
+            "M" => match arguments.split_once(':').and_then(|(range_, data)| {
+                let (address, length) = range(range_)?;
+                let bytes = unhex(data)?;
+                Some((address, bytes)).filter(|(_, bytes)| bytes.len() == length as usize)
+            }) {
+                Some((address, bytes)) if self.write(address, &bytes) => reply("OK"),
+                _ => reply("E01"),
+            },
+            "c" | "s" => {
+                if let Ok(address) = u32::from_str_radix(arguments, 16) {
+                    self.ssp.pc = (address / 2) as u16;
+                }
+                Response::Resume {
+                    step: command == "s",
+                }
+            }
+            "Z" | "z" => match self.set_point(command == "Z", arguments) {
+                Some(true) => reply("OK"),
+                Some(false) => reply(""),
+                None => reply("E01"),
+            },
+            "H" | "T" => reply("OK"),
+            "D" => Response::Detach,
+            "k" => Response::Kill,
+            "q" => self.query(arguments),
+            _ => reply(""),
+        }
+    }
+
+    fn query(&mut self, query: &str) -> Response {
+        let reply = |reply: &str| Response::Reply(reply.to_string());
+
+        if query.starts_with("Supported") {
+            return reply("PacketSize=1000;qXfer:features:read+");
+        }
+        if let Some(request) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            return match range(request) {
+                Some((offset, length)) => {
+                    let xml = target_xml();
+                    let start = (offset as usize).min(xml.len());
+                    let end = (start + length as usize).min(xml.len());
+                    let more = if end < xml.len() { "m" } else { "l" };
+                    Response::Reply(format!("{}{}", more, &xml[start..end]))
+                }
+                None => reply("E01"),
+            };
+        }
+        if let Some(command) = query.strip_prefix("Rcmd,") {
+            return match unhex(command).map(|command| String::from_utf8_lossy(&command).to_string())
+            {
+                Some(command) => Response::Reply(hex(self.monitor(&command).as_bytes())),
+                None => reply("E01"),
+            };
+        }
+
+        match query {
+            "C" => reply("QC1"),
+            "fThreadInfo" => reply("m1"),
+            "sThreadInfo" => reply("l"),
+            "Attached" => reply("1"),
+            _ if query.starts_with("Symbol") => reply("OK"),
+            _ => reply(""),
+        }
+    }
+
+    // `monitor` commands, giving access to the line information and labels of the program:
+    pub fn monitor(&mut self, command: &str) -> String {
+        let words: Vec<&str> = command.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["where"] => format!("{}\n", self.location(self.ssp.pc)),
+            ["line", location] => match self.debug.find(location) {
+                Some(address) => format!("{}\n", self.location(address)),
+                None => format!("No code found for {}\n", location),
+            },
+            ["break", location] => match self.debug.find(location) {
+                Some(address) => {
+                    self.breakpoints.insert(address);
+                    format!("Breakpoint at {}\n", self.location(address))
+                }
+                None => format!("No code found for {}\n", location),
+            },
+            ["stack"] if self.ssp.stack.is_empty() => "Stack is empty\n".to_string(),
+            ["stack"] => self
+                .ssp
+                .stack
+                .iter()
+                .rev()
+                .map(|address| format!("{}\n", self.location(*address)))
+                .collect(),
+            ["reset"] => {
+                self.ssp = self.initial.clone();
+                format!("Reset, PC at {}\n", self.location(self.ssp.pc))
+            }
+            _ => concat!(
+                "where              current location\n",
+                "line LOCATION      address of a label, LINE or FILE:LINE\n",
+                "break LOCATION     breakpoint at a label, LINE or FILE:LINE\n",
+                "stack              return addresses in the hardware stack\n",
+                "reset              restarts the program\n",
+            )
+            .to_string(),
+        }
+    }
+
+    // i.e.: `0x0804 (main+2) at main.svp:12: add a, y`
+    fn location(&self, address: u16) -> String {
+        let mut location = format!(
+            "{:#06x} ({})",
+            address as u32 * 2,
+            self.debug.describe(address)
+        );
+        if let Some(source) = self.debug.line(address) {
+            location.push_str(&format!(
+                " at {}:{}: {}",
+                source.file,
+                source.line,
+                source.text.trim()
+            ));
+        }
+        location
+    }
+
+    fn register(&self, index: usize) -> Option<u32> {
+        let ssp = &self.ssp;
+        Some(match index {
+            0 => ssp.x as u32,
+            1 => ssp.y as u32,
+            2 => ssp.a,
+            3 => ssp.st as u32,
+            4 => ssp.pc as u32 * 2,
+            5 => ssp.p(),
+            6..=13 => ssp.r[index - 6] as u32,
+            14..=19 => ssp.ext[index - 14] as u32,
+            20 => ssp.pm.pmc,
+            _ => return None,
+        })
+    }
+
+    // P can't be written, it's always X * Y * 2:
+    fn set_register(&mut self, index: usize, value: u32) -> Option<()> {
+        let ssp = &mut self.ssp;
+        match index {
+            0 => ssp.x = value as u16,
+            1 => ssp.y = value as u16,
+            2 => ssp.a = value,
+            3 => ssp.st = value as u16,
+            4 => ssp.pc = (value / 2) as u16,
+            5 => (),
+            6..=13 => ssp.r[index - 6] = value as u8,
+            14..=19 => ssp.ext[index - 14] = value as u16,
+            20 => ssp.pm.pmc = value,
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn register_hex(&self, index: usize) -> String {
+        let value = self.register(index).unwrap_or(0);
+        match REGISTERS[index].1 {
+            16 => format!("{:04x}", value),
+            _ => format!("{:08x}", value),
+        }
+    }
+
+    fn write_registers(&mut self, data: &str) -> Option<()> {
+        let mut start = 0;
+        for (index, (_, bits)) in REGISTERS.iter().enumerate() {
+            let end = start + bits / 4;
+            self.set_register(index, u32::from_str_radix(data.get(start..end)?, 16).ok()?)?;
+            start = end;
+        }
+        Some(())
+    }
+
+    // The word containing a byte address:
+    fn word(&mut self, address: u32) -> Option<&mut u16> {
+        let memory = &mut self.ssp.memory;
+        let offset = |start: u32| ((address - start) / 2) as usize;
+
+        match address {
+            _ if address < IRAM_BYTES => memory.iram.get_mut(offset(0)),
+            _ if address < ROM_BYTES_END => memory.rom.get_mut(offset(0)),
+            _ if (DRAM_BYTES_START..DRAM_BYTES_START + memory.dram.len() as u32 * 2)
+                .contains(&address) =>
+            {
+                memory.dram.get_mut(offset(DRAM_BYTES_START))
+            }
+            _ if (IRAM_BYTES_START..IRAM_BYTES_START + IRAM_BYTES).contains(&address) => {
+                memory.iram.get_mut(offset(IRAM_BYTES_START))
+            }
+            _ if (RAM_A_START..RAM_B_START).contains(&address) => {
+                self.ssp.ram[0].get_mut(offset(RAM_A_START))
+            }
+            _ if (RAM_B_START..RAM_B_START + 0x200).contains(&address) => {
+                self.ssp.ram[1].get_mut(offset(RAM_B_START))
+            }
+            _ => None,
+        }
+    }
+
+    // Bytes up to the first unmapped address (None if the first one already is):
+    pub fn read(&mut self, address: u32, length: u32) -> Option<Vec<u8>> {
+        let bytes: Vec<u8> = (address..address.saturating_add(length))
+            .map_while(|address| {
+                let shift = if address % 2 == 0 { 8 } else { 0 };
+                self.word(address).map(|word| (*word >> shift) as u8)
+            })
+            .collect();
+        Some(bytes).filter(|bytes| !bytes.is_empty() || length == 0)
+    }
+
+    pub fn write(&mut self, address: u32, bytes: &[u8]) -> bool {
+        for (address, byte) in (address..).zip(bytes) {
+            let shift = if address % 2 == 0 { 8 } else { 0 };
+            match self.word(address) {
+                Some(word) => *word = *word & !(0xFF << shift) | (*byte as u16) << shift,
+                None => return false,
+            }
+        }
+        true
+    }
+
+    // Z0/Z1 (breakpoints) and Z2/Z3/Z4 (write, read and access watchpoints). Returns whether the
+    // kind is supported, None for malformed packets.
+    fn set_point(&mut self, insert: bool, arguments: &str) -> Option<bool> {
+        let mut fields = arguments.split(',');
+        let kind = fields.next()?;
+        let address = u32::from_str_radix(fields.next()?, 16).ok()?;
+        let length = u32::from_str_radix(fields.next()?, 16).ok()?;
+
+        let kind = match kind {
+            "0" | "1" => {
+                let address = (address / 2) as u16;
+                if insert {
+                    self.breakpoints.insert(address);
+                } else {
+                    self.breakpoints.remove(&address);
+                }
+                return Some(true);
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::Access,
+            _ => return Some(false),
+        };
+        let watchpoint = Watchpoint {
+            kind,
+            address,
+            length,
+        };
+        if insert {
+            self.watchpoints.push(watchpoint);
+        } else {
+            self.watchpoints.retain(|other| *other != watchpoint);
+        }
+        Some(true)
+    }
+
+    // Runs until a breakpoint or watchpoint is hit, an instruction fails or `interrupted` says so
+    // (checked every few thousand instructions). Stepping runs a single instruction.
+    pub fn resume(&mut self, step: bool, mut interrupted: impl FnMut() -> bool) -> Stop {
+        let mut steps: u64 = 0;
+        loop {
+            if let Err(error) = self.ssp.step() {
+                return Stop::Error(error);
+            }
+            if let Some(stop) = self.watchpoint_hit() {
+                return stop;
+            }
+            if step {
+                return Stop::Step;
+            }
+            if self.breakpoints.contains(&self.ssp.pc) {
+                return Stop::Breakpoint;
+            }
+            steps += 1;
+            if steps.is_multiple_of(INTERRUPT_CHECK_STEPS) && interrupted() {
+                return Stop::Interrupted;
+            }
+        }
+    }
+
+    fn watchpoint_hit(&self) -> Option<Stop> {
+        self.ssp.accesses.iter().find_map(|access| {
+            let address = byte_address(access);
+            self.watchpoints
+                .iter()
+                .find(|watchpoint| {
+                    let kind = match watchpoint.kind {
+                        WatchKind::Write => access.write,
+                        WatchKind::Read => !access.write,
+                        WatchKind::Access => true,
+                    };
+                    kind && address < watchpoint.address.saturating_add(watchpoint.length)
+                        && watchpoint.address < address + 2
+                })
+                .map(|watchpoint| Stop::Watchpoint(watchpoint.kind, watchpoint.address))
+        })
+    }
+}
+
+fn byte_address(access: &Access) -> u32 {
+    match access.space {
+        Space::Ram(0) => RAM_A_START + access.address * 2,
+        Space::Ram(_) => RAM_B_START + access.address * 2,
+        Space::Program | Space::External => access.address * 2,
+    }
+}
+
+// Target description, so GDB knows the registers without knowing the SSP1601:
+fn target_xml() -> String {
+    let registers: String = REGISTERS
+        .iter()
+        .map(|(name, bits)| {
+            let kind = if *name == "pc" { "code_ptr" } else { "int" };
+            format!(
+                "<reg name=\"{}\" bitsize=\"{}\" type=\"{}\"/>",
+                name, bits, kind
+            )
+        })
+        .collect();
+
+    format!(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">\
+         <target version=\"1.0\"><feature name=\"org.ssp16.core\">{}</feature></target>",
+        registers
+    )
+}
+
+// `address,length` in hexadecimal:
+fn range(arguments: &str) -> Option<(u32, u32)> {
+    let (address, length) = arguments.split_once(',')?;
+    Some((
+        u32::from_str_radix(address, 16).ok()?,
+        u32::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(data: &str) -> Option<Vec<u8>> {
+    (0..data.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(data.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+// Next packet (without `$` and checksum), acknowledging it. Packets with a wrong checksum are
+// asked for again, and anything between packets (acks, interrupts) is skipped. None once the
+// connection is closed.
+pub fn read_packet<S: Read + Write>(stream: &mut S) -> io::Result<Option<String>> {
+    let mut byte = [0; 1];
+    loop {
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] == b'$' {
+                break;
+            }
+        }
+
+        let mut packet = Vec::new();
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            if byte[0] == b'#' {
+                break;
+            }
+            packet.push(byte[0]);
+        }
+        let mut checksum = [0; 2];
+        stream.read_exact(&mut checksum)?;
+
+        let expected = std::str::from_utf8(&checksum)
+            .ok()
+            .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+        if expected == Some(packet_checksum(&packet)) {
+            stream.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&packet).to_string()));
+        }
+        stream.write_all(b"-")?;
+    }
+}
+
+pub fn write_packet<W: Write>(stream: &mut W, packet: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "${}#{:02x}",
+        packet,
+        packet_checksum(packet.as_bytes())
+    )?;
+    stream.flush()
+}
+
+fn packet_checksum(packet: &[u8]) -> u8 {
+    packet
+        .iter()
+        .fold(0u8, |checksum, byte| checksum.wrapping_add(*byte))
+}
+
+// Whether GDB sent an interrupt (Ctrl-C) while the program runs:
+fn interrupted(stream: &mut TcpStream) -> bool {
+    let mut byte = [0; 1];
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let result = stream.read(&mut byte);
+    let _ = stream.set_nonblocking(false);
+    matches!(result, Ok(1) if byte[0] == 0x03)
+}
+
+// Runs a GDB session until GDB detaches, kills the program or closes the connection:
+pub fn serve(stub: &mut GdbStub, mut stream: TcpStream) -> io::Result<()> {
+    while let Some(packet) = read_packet(&mut stream)? {
+        let reply = match stub.packet(&packet) {
+            Response::Reply(reply) => reply,
+            Response::Resume { step } => {
+                let stop = stub.resume(step, || interrupted(&mut stream));
+                if let Stop::Error(error) = &stop {
+                    write_packet(
+                        &mut stream,
+                        &format!("O{}", hex(format!("{}\n", error).as_bytes())),
+                    )?;
+                }
+                stop.reply()
+            }
+            Response::Detach => {
+                write_packet(&mut stream, "OK")?;
+                return Ok(());
+            }
+            Response::Kill => return Ok(()),
+        };
+        write_packet(&mut stream, &reply)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod gdb_tests {
+    use super::*;
+    use crate::debug::SourceLine;
+    use crate::memory::Memory;
+    use ssp16_isa::instructions::Instruction;
+    use std::io::Cursor;
+
+    // Stub for `code` at 0x400, with each instruction in a line of `test.svp`:
+    fn stub(code: &[&str]) -> GdbStub {
+        let mut rom = vec![0; 0x400];
+        let mut debug = DebugInfo::default();
+        debug.labels.insert("start".to_string(), 0x400);
+        for (line, text) in code.iter().enumerate() {
+            debug.lines.insert(
+                rom.len() as u16,
+                SourceLine {
+                    file: "test.svp".to_string(),
+                    line: line + 1,
+                    text: text.to_string(),
+                },
+            );
+            let instruction = debug.resolve(&Instruction::parse(text).unwrap()).unwrap();
+            rom.extend(instruction.encode().unwrap());
+        }
+
+        GdbStub::new(Ssp1601::new(Memory::new(rom)), debug)
+    }
+
+    fn reply(stub: &mut GdbStub, packet: &str) -> String {
+        match stub.packet(packet) {
+            Response::Reply(reply) => reply,
+            response => panic!("Unexpected response: {:?}", response),
+        }
+    }
+
+    #[test]
+    fn check_packets() {
+        let mut stream = Cursor::new(b"+$m0,2#fb$g#00$g#67".to_vec());
+        assert_eq!(read_packet(&mut stream).unwrap(), Some("m0,2".to_string()));
+        assert_eq!(read_packet(&mut stream).unwrap(), Some("g".to_string()));
+        assert_eq!(read_packet(&mut stream).unwrap(), None);
+
+        let mut output = Vec::new();
+        write_packet(&mut output, "OK").unwrap();
+        assert_eq!(output, b"$OK#9a");
+
+        // Invalid UTF-8 turns into U+FFFD when read, which isn't a command:
+        let mut stub = stub(&["ret"]);
+        assert_eq!(reply(&mut stub, "\u{FFFD}m0,2"), "");
+        assert_eq!(reply(&mut stub, ""), "");
+    }
+
+    #[test]
+    fn check_registers() {
+        let mut stub = stub(&["ld x, 0003"]);
+        stub.ssp.y = 0xFFFE;
+        stub.ssp.a = 0x1234_5678;
+        stub.ssp.r[7] = 0x80;
+
+        let registers = reply(&mut stub, "g");
+        assert!(registers.starts_with("0000fffe123456780000" /* x, y, a, st */));
+        assert_eq!(&registers[20..28], "00000800");
+        assert_eq!(reply(&mut stub, "pd"), "0080");
+
+        assert_eq!(reply(&mut stub, "P0=0003"), "OK");
+        assert_eq!(reply(&mut stub, "p5"), "fffffff4");
+        assert_eq!(reply(&mut stub, "P4=00000810"), "OK");
+        assert_eq!(stub.ssp.pc, 0x408);
+        assert_eq!(reply(&mut stub, "p15"), "E01");
+
+        let registers = registers.replacen("0000", "0001", 1);
+        assert_eq!(reply(&mut stub, &format!("G{}", registers)), "OK");
+        assert_eq!(stub.ssp.x, 1);
+        assert_eq!(stub.ssp.pc, 0x400);
+    }
+
+    #[test]
+    fn check_memory() {
+        let mut stub = stub(&["ld x, 0003"]);
+        stub.ssp.memory.iram[1] = 0xABCD;
+        stub.ssp.ram[1][0xFF] = 0x1234;
+
+        assert_eq!(reply(&mut stub, "m800,3"), "081000");
+        assert_eq!(reply(&mut stub, "m2,2"), "abcd");
+        assert_eq!(reply(&mut stub, "m390002,2"), "abcd");
+        assert_eq!(reply(&mut stub, "m4003fe,4"), "1234");
+        assert_eq!(reply(&mut stub, "m500000,2"), "E01");
+
+        assert_eq!(reply(&mut stub, "M300001,3:12ab34"), "OK");
+        assert_eq!(&stub.ssp.memory.dram[0..3], &[0x0012, 0xAB34, 0]);
+        assert_eq!(reply(&mut stub, "M400000,2:1"), "E01");
+    }
+
+    #[test]
+    fn check_breakpoints_and_steps() {
+        let mut stub = stub(&["ld a, 0000", "addi 01", "bra always, 0402"]);
+
+        assert_eq!(reply(&mut stub, "Z0,806,2"), "OK");
+        assert_eq!(stub.packet("c"), Response::Resume { step: false });
+        assert_eq!(stub.resume(false, || false), Stop::Breakpoint);
+        assert_eq!(stub.ssp.pc, 0x403);
+
+        // Continuing from a breakpoint leaves it first
+        assert_eq!(stub.resume(false, || false), Stop::Breakpoint);
+        assert_eq!(stub.ssp.a, 0x0002_0000);
+        assert_eq!(stub.resume(true, || false), Stop::Step);
+        assert_eq!(stub.ssp.pc, 0x402);
+
+        assert_eq!(reply(&mut stub, "z0,806,2"), "OK");
+        assert_eq!(stub.resume(false, || true), Stop::Interrupted);
+        assert_eq!(Stop::Interrupted.reply(), "S02");
+
+        assert_eq!(stub.packet("s800"), Response::Resume { step: true });
+        assert_eq!(stub.ssp.pc, 0x400);
+    }
+
+    #[test]
+    fn check_watchpoints() {
+        let mut stub = stub(&[
+            "ld a, 0001",
+            "ld A[01], a",
+            "ld a, A[01]",
+            "ld a, A[02]",
+            "ret",
+        ]);
+
+        assert_eq!(reply(&mut stub, "Z3,400002,2"), "OK");
+        assert_eq!(reply(&mut stub, "Z2,400003,1"), "OK");
+        assert_eq!(
+            stub.resume(false, || false),
+            Stop::Watchpoint(WatchKind::Write, 0x400003)
+        );
+        assert_eq!(stub.ssp.pc, 0x403);
+        assert_eq!(
+            stub.resume(false, || false),
+            Stop::Watchpoint(WatchKind::Read, 0x400002)
+        );
+        assert_eq!(
+            Stop::Watchpoint(WatchKind::Read, 0x400002).reply(),
+            "T05rwatch:400002;"
+        );
+
+        // Nothing else to stop at: `ret` with an empty stack fails
+        match stub.resume(false, || false) {
+            Stop::Error(SimError(error)) => assert_eq!(error, "Stack underflow at 0405"),
+            stop => panic!("Unexpected stop: {:?}", stop),
+        }
+        assert_eq!(stub.ssp.pc, 0x405);
+    }
+
+    #[test]
+    fn check_queries() {
+        let mut stub = stub(&["ld x, 0001", "ld y, 0002", "call always, 0400"]);
+        let monitor = |stub: &mut GdbStub, command: &str| {
+            let output = unhex(&reply(stub, &format!("qRcmd,{}", hex(command.as_bytes()))));
+            String::from_utf8(output.unwrap()).unwrap()
+        };
+
+        assert!(reply(&mut stub, "qSupported:swbreak+").contains("qXfer:features:read+"));
+        let xml = reply(&mut stub, "qXfer:features:read:target.xml:0,1000");
+        assert!(xml.starts_with("l<?xml"));
+        assert!(xml.contains("<reg name=\"pc\" bitsize=\"32\" type=\"code_ptr\"/>"));
+        assert!(reply(&mut stub, "qXfer:features:read:target.xml:0,10").starts_with('m'));
+        assert_eq!(reply(&mut stub, "vMustReplyEmpty"), "");
+
+        assert_eq!(
+            monitor(&mut stub, "where"),
+            "0x0800 (start) at test.svp:1: ld x, 0001\n"
+        );
+        assert_eq!(
+            monitor(&mut stub, "break test.svp:2"),
+            "Breakpoint at 0x0804 (start+2) at test.svp:2: ld y, 0002\n"
+        );
+        assert_eq!(monitor(&mut stub, "line 9"), "No code found for 9\n");
+        assert_eq!(monitor(&mut stub, "stack"), "Stack is empty\n");
+
+        stub.resume(false, || false);
+        stub.resume(false, || false);
+        assert_eq!(monitor(&mut stub, "stack"), "0x080c (start+6)\n");
+        assert!(monitor(&mut stub, "reset").starts_with("Reset, PC at 0x0800"));
+        assert!(stub.ssp.stack.is_empty());
+        assert!(monitor(&mut stub, "help").contains("break LOCATION"));
+    }
+}
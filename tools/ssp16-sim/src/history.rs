@@ -0,0 +1,172 @@
+use crate::cpu::{Space, Ssp1601};
+use crate::errors::SimError;
+use crate::memory::PmAdapter;
+use std::collections::VecDeque;
+
+// Instructions remembered by default:
+pub const DEFAULT_LIMIT: usize = 100_000;
+
+// Registers before an instruction, and the words it wrote (in order) with their previous values:
+struct Snapshot {
+    x: u16,
+    y: u16,
+    a: u32,
+    st: u16,
+    pc: u16,
+    stack: Vec<u16>,
+    r: [u8; 8],
+    ext: [u16; 6],
+    pm: PmAdapter,
+    writes: Vec<(Space, u32, u16)>,
+}
+
+// Instructions run through it can be undone, up to the last `limit` ones:
+pub struct History {
+    snapshots: VecDeque<Snapshot>,
+    limit: usize,
+}
+
+impl History {
+    pub fn new(limit: usize) -> History {
+        History {
+            snapshots: VecDeque::new(),
+            limit,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    // Runs an instruction, remembering how to undo it (failed instructions change nothing):
+    pub fn step(&mut self, ssp: &mut Ssp1601) -> Result<(), SimError> {
+        let mut snapshot = Snapshot {
+            x: ssp.x,
+            y: ssp.y,
+            a: ssp.a,
+            st: ssp.st,
+            pc: ssp.pc,
+            stack: ssp.stack.clone(),
+            r: ssp.r,
+            ext: ssp.ext,
+            pm: ssp.pm.clone(),
+            writes: Vec::new(),
+        };
+        ssp.step()?;
+
+        snapshot.writes = ssp
+            .accesses
+            .iter()
+            .filter(|access| access.write)
+            .map(|access| (access.space, access.address, access.previous))
+            .collect();
+        if self.snapshots.len() == self.limit {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+        Ok(())
+    }
+
+    // Undoes the last instruction, returning false if there's none left to undo:
+    pub fn back(&mut self, ssp: &mut Ssp1601) -> bool {
+        let snapshot = match self.snapshots.pop_back() {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+
+        for (space, address, previous) in snapshot.writes.into_iter().rev() {
+            match space {
+                Space::Ram(bank) => ssp.ram[bank][address as usize] = previous,
+                Space::External => ssp.memory.write(address, previous, false),
+                Space::Program => (),
+            }
+        }
+        ssp.x = snapshot.x;
+        ssp.y = snapshot.y;
+        ssp.a = snapshot.a;
+        ssp.st = snapshot.st;
+        ssp.pc = snapshot.pc;
+        ssp.stack = snapshot.stack;
+        ssp.r = snapshot.r;
+        ssp.ext = snapshot.ext;
+        ssp.pm = snapshot.pm;
+        ssp.steps -= 1;
+        ssp.accesses.clear();
+        true
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use super::*;
+    use crate::memory::Memory;
+    use ssp16_isa::instructions::Instruction;
+
+    // Simulator with `code` at 0x400:
+    fn simulator(code: &[&str]) -> Ssp1601 {
+        let mut rom = vec![0; 0x400];
+        for line in code {
+            rom.extend(line.parse::<Instruction>().unwrap().encode().unwrap());
+        }
+        Ssp1601::new(Memory::new(rom))
+    }
+
+    #[test]
+    fn check_undo() {
+        let mut ssp = simulator(&[
+            "ld x, 0002",
+            "ld (r0+), x",
+            "call always, 0405",
+            "ld ext6, FFFF",
+            "ld ext6, 0817",
+            "ld ext4, -",
+            "ld ext4, x",
+            "ret",
+        ]);
+        ssp.ram[0][0] = 0x1111;
+        ssp.memory.dram[0] = 0x2222;
+        let mut history = History::new(DEFAULT_LIMIT);
+
+        for _ in 0..8 {
+            history.step(&mut ssp).unwrap();
+        }
+        assert_eq!((ssp.pc, ssp.steps, history.len()), (0x405, 8, 8));
+        assert_eq!((ssp.ram[0][0], ssp.memory.dram[0]), (0x0002, 0x0002));
+        assert!(history.step(&mut ssp).is_ok());
+        assert_eq!(history.len(), 9);
+
+        for _ in 0..9 {
+            assert!(history.back(&mut ssp));
+        }
+        assert!(!history.back(&mut ssp));
+        assert_eq!((ssp.pc, ssp.steps, ssp.x, ssp.r[0]), (0x400, 0, 0, 0));
+        assert_eq!((ssp.ram[0][0], ssp.memory.dram[0]), (0x1111, 0x2222));
+        assert!(ssp.stack.is_empty());
+        assert_eq!(ssp.pm.write[4], 0);
+    }
+
+    #[test]
+    fn check_limit() {
+        let mut ssp = simulator(&["addi 01", "bra always, 0400"]);
+        let mut history = History::new(3);
+
+        for _ in 0..10 {
+            history.step(&mut ssp).unwrap();
+        }
+        assert_eq!(history.len(), 3);
+        while history.back(&mut ssp) {}
+        assert_eq!((ssp.pc, ssp.steps, ssp.a), (0x401, 7, 0x0004_0000));
+
+        let mut ssp = simulator(&["ret"]);
+        assert!(history.step(&mut ssp).is_err());
+        assert_eq!((ssp.pc, history.len()), (0x400, 0));
+    }
+}
@@ -0,0 +1,10 @@
+// Simulator of the SSP1601 as found inside the SVP: the DSP core (registers, RAM banks, stack and
+// ALU) and the memory it sees (IRAM, ROM, DRAM and the PM registers giving access to them).
+pub mod coverage;
+pub mod cpu;
+pub mod debug;
+pub mod errors;
+pub mod gdb;
+pub mod history;
+pub mod megadrive;
+pub mod memory;
@@ -0,0 +1,154 @@
+use crate::cpu::{Ssp1601, XST_68K_WRITTEN, XST_SSP_WRITTEN};
+use crate::errors::SimError;
+
+// Cycles a wait gives up after, unless changed:
+pub const DEFAULT_TIMEOUT: u64 = 10_000_000;
+
+// Plays the 68000's side against a simulated SSP1601 through what the Mega Drive sees of the SVP:
+// XST, its state bits and DRAM. The SSP1601 runs in between, with time counted in its cycles as
+// `step` costs each instruction it runs.
+pub struct MegaDrive<S> {
+    pub ssp: Ssp1601,
+    pub cycles: u64,
+    pub timeout: u64,
+    step: S,
+}
+
+impl<S: FnMut(&mut Ssp1601) -> Result<u64, SimError>> MegaDrive<S> {
+    pub fn new(ssp: Ssp1601, step: S) -> MegaDrive<S> {
+        MegaDrive {
+            ssp,
+            cycles: 0,
+            timeout: DEFAULT_TIMEOUT,
+            step,
+        }
+    }
+
+    // A15000 (writing it tells the SSP1601 through the XST state):
+    pub fn write_xst(&mut self, value: u16) {
+        self.ssp.ext[3] = value;
+        self.ssp.ext[0] |= XST_68K_WRITTEN;
+    }
+
+    pub fn read_xst(&self) -> u16 {
+        self.ssp.ext[3]
+    }
+
+    // A15004 (reading it clears the bit telling the SSP1601 wrote XST):
+    pub fn read_state(&mut self) -> u16 {
+        let state = self.ssp.ext[0];
+        self.ssp.ext[0] &= !XST_SSP_WRITTEN;
+        state
+    }
+
+    // DRAM by word offset (0x300000 on, for the 68000):
+    pub fn read_dram(&self, offset: usize) -> u16 {
+        self.ssp.memory.dram[offset]
+    }
+
+    pub fn write_dram(&mut self, offset: usize, value: u16) {
+        self.ssp.memory.dram[offset] = value;
+    }
+
+    // Runs the SSP1601 until `cycle` (or a bit past it, as instructions aren't interrupted):
+    pub fn run_until(&mut self, cycle: u64) -> Result<(), SimError> {
+        while self.cycles < cycle {
+            self.cycles += (self.step)(&mut self.ssp)?;
+        }
+        Ok(())
+    }
+
+    pub fn run(&mut self, cycles: u64) -> Result<(), SimError> {
+        self.run_until(self.cycles + cycles)
+    }
+
+    // Polls the XST state (reading it, like the 68000 does) between instructions until its bits in
+    // `mask` are `value`, for `timeout` cycles at most. Returns the state found.
+    pub fn wait_state(&mut self, mask: u16, value: u16) -> Result<u16, SimError> {
+        let limit = self.cycles + self.timeout;
+        loop {
+            let state = self.read_state();
+            if state & mask == value {
+                return Ok(state);
+            }
+            if self.cycles >= limit {
+                return Err(SimError(format!(
+                    "XST state still {:04X} after {} cycles (PC at {:04X})",
+                    state, self.timeout, self.ssp.pc
+                )));
+            }
+            self.cycles += (self.step)(&mut self.ssp)?;
+        }
+    }
+}
+
+// Step function counting a cycle per instruction:
+pub fn one_cycle(ssp: &mut Ssp1601) -> Result<u64, SimError> {
+    ssp.step().map(|_| 1)
+}
+
+#[cfg(test)]
+mod megadrive_tests {
+    use super::*;
+    use crate::memory::Memory;
+    use ssp16_isa::instructions::Instruction;
+
+    // Waits for a command in XST, stores it at DRAM 0 and answers 1010 in XST:
+    fn megadrive() -> MegaDrive<impl FnMut(&mut Ssp1601) -> Result<u64, SimError>> {
+        let mut rom = vec![0; 0x400];
+        let code = [
+            "ld a, ext0",
+            "andi 02",
+            "bra z=1, 0400",
+            "ld x, ext3",
+            "ld ext6, FFFF",
+            "ld ext6, 0817",
+            "ld ext4, -",
+            "ld ext4, x",
+            "ld ext3, 1010",
+            "bra always, 0400",
+        ];
+        for line in code.iter() {
+            rom.extend(line.parse::<Instruction>().unwrap().encode().unwrap());
+        }
+        MegaDrive::new(Ssp1601::new(Memory::new(rom)), one_cycle)
+    }
+
+    #[test]
+    fn check_handshake() {
+        let mut megadrive = megadrive();
+
+        megadrive.run_until(10).unwrap();
+        assert_eq!((megadrive.cycles, megadrive.ssp.steps), (10, 10));
+        assert_eq!(megadrive.ssp.pc, 0x401);
+
+        megadrive.write_xst(0x0005);
+        assert_eq!(
+            megadrive.wait_state(XST_SSP_WRITTEN, XST_SSP_WRITTEN),
+            Ok(XST_SSP_WRITTEN)
+        );
+        assert_eq!(
+            (megadrive.read_xst(), megadrive.read_dram(0)),
+            (0x1010, 0x0005)
+        );
+        assert_eq!(megadrive.read_state(), 0);
+
+        megadrive.write_dram(1, 0x1234);
+        assert_eq!(megadrive.ssp.memory.dram[1], 0x1234);
+    }
+
+    #[test]
+    fn check_timeout() {
+        let mut megadrive = megadrive();
+        megadrive.timeout = 50;
+
+        assert_eq!(
+            megadrive.wait_state(XST_SSP_WRITTEN, XST_SSP_WRITTEN),
+            Err(SimError(
+                "XST state still 0000 after 50 cycles (PC at 0402)".to_string()
+            ))
+        );
+        assert_eq!(megadrive.cycles, 50);
+        assert_eq!(megadrive.wait_state(XST_68K_WRITTEN, 0), Ok(0));
+    }
+}
@@ -0,0 +1,319 @@
+// Sizes in words:
+pub const IRAM_SIZE: usize = 0x400;
+pub const DRAM_SIZE: usize = 0x10000;
+
+// Regions reachable through the PM registers, in SVP word addresses (68000 byte address / 2):
+pub const ROM_END: u32 = 0x0FFFFF;
+pub const DRAM_START: u32 = 0x180000;
+pub const IRAM_START: u32 = 0x1C8000;
+
+// PM mode word flags (the low bits hold the high bits of the address):
+const OVERWRITE: u16 = 0x0400;
+const CELL: u16 = 0x4000;
+const DECREMENT: u16 = 0x8000;
+const ADDRESS_MASK: u32 = 0x7FFFFF;
+
+// Memory seen by the SSP1601 inside the SVP. Program memory is IRAM below 0x400 and ROM (by word
+// address) above it.
+#[derive(Debug, Clone)]
+pub struct Memory {
+    pub rom: Vec<u16>,
+    pub iram: Vec<u16>,
+    pub dram: Vec<u16>,
+}
+
+impl Memory {
+    pub fn new(rom: Vec<u16>) -> Memory {
+        Memory {
+            rom,
+            iram: vec![0; IRAM_SIZE],
+            dram: vec![0; DRAM_SIZE],
+        }
+    }
+
+    // ROM image as found in the cartridge (big endian words):
+    pub fn from_bytes(rom: &[u8]) -> Memory {
+        Memory::new(
+            rom.chunks(2)
+                .map(|word| (word[0] as u16) << 8 | *word.get(1).unwrap_or(&0) as u16)
+                .collect(),
+        )
+    }
+
+    // DRAM as the 68000 reads it from 300000 on (big endian words):
+    pub fn dram_bytes(&self) -> Vec<u8> {
+        self.dram
+            .iter()
+            .flat_map(|word| word.to_be_bytes())
+            .collect()
+    }
+
+    pub fn program(&self, address: u16) -> u16 {
+        if (address as usize) < IRAM_SIZE {
+            self.iram[address as usize]
+        } else {
+            self.rom.get(address as usize).copied().unwrap_or(0)
+        }
+    }
+
+    // Word at an SVP word address. Anything outside ROM, DRAM and IRAM reads as 0.
+    pub fn read(&self, address: u32) -> u16 {
+        if address <= ROM_END {
+            self.rom.get(address as usize).copied().unwrap_or(0)
+        } else if let Some(offset) = offset(address, DRAM_START, DRAM_SIZE) {
+            self.dram[offset]
+        } else if let Some(offset) = offset(address, IRAM_START, IRAM_SIZE) {
+            self.iram[offset]
+        } else {
+            0
+        }
+    }
+
+    // Writes to DRAM or IRAM (anything else is ignored). With `overwrite` only the non-zero
+    // nibbles of `value` are written.
+    pub fn write(&mut self, address: u32, value: u16, overwrite: bool) {
+        let word = if let Some(offset) = offset(address, DRAM_START, DRAM_SIZE) {
+            &mut self.dram[offset]
+        } else if let Some(offset) = offset(address, IRAM_START, IRAM_SIZE) {
+            &mut self.iram[offset]
+        } else {
+            return;
+        };
+
+        if overwrite {
+            for shift in (0..16).step_by(4) {
+                if value & (0xF << shift) != 0 {
+                    *word = *word & !(0xF << shift) | value & (0xF << shift);
+                }
+            }
+        } else {
+            *word = value;
+        }
+    }
+}
+
+fn offset(address: u32, start: u32, size: usize) -> Option<usize> {
+    address
+        .checked_sub(start)
+        .map(|offset| offset as usize)
+        .filter(|offset| *offset < size)
+}
+
+// Memory adapter behind the PM registers. PMC takes an address and then a mode word, and the next
+// access to a PM register (a "blind" one, its value is ignored) selects them for reading or
+// writing through it. Programmed values are kept as `mode << 16 | address`.
+#[derive(Debug, Clone, Default)]
+pub struct PmAdapter {
+    pub pmc: u32,
+    has_address: bool,
+    is_set: bool,
+    pub read: [u32; 5],
+    pub write: [u32; 5],
+}
+
+impl PmAdapter {
+    pub fn read_pmc(&mut self) -> u16 {
+        self.advance_pmc();
+        if self.is_set {
+            (self.pmc >> 16) as u16
+        } else {
+            self.pmc as u16
+        }
+    }
+
+    pub fn write_pmc(&mut self, value: u16) {
+        if self.has_address {
+            self.pmc = (value as u32) << 16 | self.pmc & 0xFFFF;
+        } else {
+            self.pmc = self.pmc & 0xFFFF_0000 | value as u32;
+        }
+        self.advance_pmc();
+    }
+
+    fn advance_pmc(&mut self) {
+        self.is_set = self.has_address;
+        self.has_address = !self.has_address;
+    }
+
+    // Address the next access to a PM register goes to (None if it's a blind one):
+    pub fn address(&self, register: usize, write: bool) -> Option<u32> {
+        match (self.is_set, write) {
+            (true, _) => None,
+            (false, false) => Some(self.read[register] & ADDRESS_MASK),
+            (false, true) => Some(self.write[register] & ADDRESS_MASK),
+        }
+    }
+
+    pub fn read_pm(&mut self, register: usize, memory: &Memory) -> u16 {
+        self.has_address = false;
+        if self.is_set {
+            self.is_set = false;
+            self.read[register] = self.pmc;
+            return 0;
+        }
+
+        let value = memory.read(self.read[register] & ADDRESS_MASK);
+        self.read[register] = advance(self.read[register]);
+        self.pmc = self.read[register];
+        value
+    }
+
+    // Blind writes already advance the address (as seen in emulators, see `PMSET`):
+    pub fn write_pm(&mut self, register: usize, value: u16, memory: &mut Memory) {
+        self.has_address = false;
+        if self.is_set {
+            self.is_set = false;
+            self.write[register] = advance(self.pmc);
+            return;
+        }
+
+        let mode = (self.write[register] >> 16) as u16;
+        memory.write(
+            self.write[register] & ADDRESS_MASK,
+            value,
+            mode & OVERWRITE != 0,
+        );
+        self.write[register] = advance(self.write[register]);
+        self.pmc = self.write[register];
+    }
+}
+
+// Programmed value with its mode as `PMSET` options, i.e.: `180000 inc=1 overwrite`:
+pub fn describe_programmed(programmed: u32) -> String {
+    let mode = (programmed >> 16) as u16;
+    let increment = match (mode >> 11) & 7 {
+        0 => 0,
+        7 => 0x80,
+        code => 1 << (code - 1),
+    };
+    let mut description = format!(
+        "{:06X} inc={}{}",
+        programmed & ADDRESS_MASK,
+        if mode & DECREMENT != 0 && increment != 0 {
+            "-"
+        } else {
+            ""
+        },
+        increment
+    );
+    if mode & OVERWRITE != 0 {
+        description.push_str(" overwrite");
+    }
+    if mode & CELL != 0 {
+        description.push_str(" cell");
+    }
+    description
+}
+
+// Next address of a programmed PM register: the increment is in bits 11-13 of the mode (0, 1, 2,
+// 4, 8, 0x10, 0x20 or 0x80 words), cell mode moves through 8x8 tile cells.
+fn advance(programmed: u32) -> u32 {
+    let mode = (programmed >> 16) as u16;
+    let address = programmed & ADDRESS_MASK;
+    let increment = match (mode >> 11) & 7 {
+        _ if mode & CELL != 0 && address & 1 != 0 => 0x1F,
+        _ if mode & CELL != 0 => 1,
+        0 => 0,
+        7 => 0x80,
+        code => 1 << (code - 1),
+    };
+    let address = if mode & DECREMENT != 0 {
+        address.wrapping_sub(increment)
+    } else {
+        address.wrapping_add(increment)
+    };
+    programmed & !ADDRESS_MASK | address & ADDRESS_MASK
+}
+
+#[cfg(test)]
+mod memory_tests {
+    use super::*;
+
+    fn program(pm: &mut PmAdapter, address: u32, mode: u16) {
+        pm.write_pmc(address as u16);
+        pm.write_pmc(mode | (address >> 16) as u16);
+    }
+
+    #[test]
+    fn check_program_memory() {
+        let mut memory = Memory::from_bytes(
+            &[0; 0x800]
+                .iter()
+                .chain(&[0x12, 0x34])
+                .copied()
+                .collect::<Vec<u8>>(),
+        );
+        memory.iram[0x3FF] = 0xABCD;
+
+        assert_eq!(memory.program(0x400), 0x1234);
+        assert_eq!(memory.program(0x3FF), 0xABCD);
+        assert_eq!(memory.program(0x401), 0);
+    }
+
+    #[test]
+    fn check_pm_writes() {
+        let mut memory = Memory::new(vec![]);
+        let mut pm = PmAdapter::default();
+
+        // pmset pm4, write, 181000, inc=1
+        program(&mut pm, 0x180FFF, 0x0800);
+        pm.write_pm(4, 0xFFFF, &mut memory);
+        pm.write_pm(4, 0x1234, &mut memory);
+        pm.write_pm(4, 0x5678, &mut memory);
+        assert_eq!(&memory.dram[0x1000..0x1002], &[0x1234, 0x5678]);
+
+        // Overwrite only replaces the non-zero nibbles
+        program(&mut pm, 0x181000, 0x0400);
+        pm.write_pm(4, 0xFFFF, &mut memory);
+        pm.write_pm(4, 0x0A0B, &mut memory);
+        assert_eq!(memory.dram[0x1000], 0x1A3B);
+        assert_eq!(
+            &memory.dram_bytes()[0x2000..0x2004],
+            &[0x1A, 0x3B, 0x56, 0x78]
+        );
+
+        // Cell mode
+        program(&mut pm, 0x180000, 0x4000);
+        pm.write_pm(4, 0xFFFF, &mut memory);
+        for value in 1..=4 {
+            pm.write_pm(4, value, &mut memory);
+        }
+        assert_eq!(memory.dram[0x01], 1);
+        assert_eq!(&memory.dram[0x20..0x22], &[2, 3]);
+        assert_eq!(memory.dram[0x40], 4);
+
+        // IRAM, decrementing
+        program(&mut pm, 0x1C8011, 0x8800);
+        pm.write_pm(4, 0xFFFF, &mut memory);
+        pm.write_pm(4, 0x0001, &mut memory);
+        pm.write_pm(4, 0x0002, &mut memory);
+        assert_eq!(&memory.iram[0x0F..0x11], &[0x0002, 0x0001]);
+    }
+
+    #[test]
+    fn check_pm_reads() {
+        let mut memory = Memory::new((0..0x10).collect());
+        let mut pm = PmAdapter::default();
+        memory.dram[0x0100] = 0xAAAA;
+        memory.dram[0x0102] = 0xBBBB;
+
+        program(&mut pm, 0x000004, 0x0800);
+        assert_eq!(pm.read_pm(1, &memory), 0);
+        assert_eq!(pm.read_pm(1, &memory), 4);
+        assert_eq!(pm.read_pm(1, &memory), 5);
+
+        program(&mut pm, 0x180100, 0x1000);
+        pm.read_pm(2, &memory);
+        assert_eq!(pm.read_pm(2, &memory), 0xAAAA);
+        assert_eq!(pm.read_pm(2, &memory), 0xBBBB);
+        assert_eq!(pm.read_pm(1, &memory), 6);
+    }
+
+    #[test]
+    fn check_programmed_description() {
+        assert_eq!(describe_programmed(0), "000000 inc=0");
+        assert_eq!(describe_programmed(0x0818_0000), "180000 inc=1");
+        assert_eq!(describe_programmed(0x8C1C_8011), "1C8011 inc=-1 overwrite");
+        assert_eq!(describe_programmed(0x7818_0010), "180010 inc=128 cell");
+    }
+}
@@ -4,6 +4,9 @@ pub enum SspMacro {
     Org,
     Equ,
     Equb,
+    Export,
+    Align,
+    Limit,
 }
 
 impl SspMacro {
@@ -13,6 +16,9 @@ impl SspMacro {
             "org" => Some(SspMacro::Org),
             "equ" => Some(SspMacro::Equ),
             "equb" => Some(SspMacro::Equb),
+            "export" => Some(SspMacro::Export),
+            "align" => Some(SspMacro::Align),
+            "limit" => Some(SspMacro::Limit),
             _ => None,
         }
     }
@@ -28,6 +34,9 @@ mod macros_tests {
         assert_eq!(SspMacro::new("org"), Some(SspMacro::Org));
         assert_eq!(SspMacro::new("equ"), Some(SspMacro::Equ));
         assert_eq!(SspMacro::new("equb"), Some(SspMacro::Equb));
+        assert_eq!(SspMacro::new("export"), Some(SspMacro::Export));
+        assert_eq!(SspMacro::new("ALIGN"), Some(SspMacro::Align));
+        assert_eq!(SspMacro::new("limit"), Some(SspMacro::Limit));
     }
 
     #[test]
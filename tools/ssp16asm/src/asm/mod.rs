@@ -1,5 +1,4 @@
-pub mod labels;
 pub mod macros;
-pub mod mnemonics;
-pub mod operators;
-pub mod registers;
+pub mod targets;
+
+pub use ssp16_isa::{labels, mnemonics, operators, registers};
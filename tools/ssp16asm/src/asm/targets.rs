@@ -0,0 +1,121 @@
+use crate::tokenization::tokens;
+use serde::Deserialize;
+
+// Hardware the code runs on. The SSP1601 only knows `ext0`-`ext7`, a target can give them the
+// names its documentation uses (and provide constants for it).
+#[derive(Debug, Copy, Clone, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    #[default]
+    Generic,
+    Svp,
+}
+
+// Names of the SVP external registers. `ext0` is also read as the XST state (bits 0-1: XST
+// written by the SSP/68000), and `ext3` is XST or PM3 depending on ST5/ST6.
+const SVP_REGISTERS: [(&str, &str); 9] = [
+    ("pm0", "ext0"),
+    ("xst_state", "ext0"),
+    ("pm1", "ext1"),
+    ("pm2", "ext2"),
+    ("pm3", "ext3"),
+    ("xst", "ext3"),
+    ("pm4", "ext4"),
+    ("pmc", "ext6"),
+    ("al", "ext7"),
+];
+
+// ST bits, usable like any other EQU constant:
+const SVP_CONSTANTS: [(&str, u16); 8] = [
+    ("ST_RPL", 0x0007),
+    ("ST5", 0x0020),
+    ("ST6", 0x0040),
+    ("ST_PM", 0x0060),
+    ("ST_L", 0x1000),
+    ("ST_Z", 0x2000),
+    ("ST_V", 0x4000),
+    ("ST_N", 0x8000),
+];
+
+impl Target {
+    pub fn new(name: &str) -> Option<Target> {
+        match name.to_lowercase().as_str() {
+            "generic" => Some(Target::Generic),
+            "svp" => Some(Target::Svp),
+            _ => None,
+        }
+    }
+
+    pub fn registers(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Target::Generic => &[],
+            Target::Svp => &SVP_REGISTERS,
+        }
+    }
+
+    pub fn constants(&self) -> &'static [(&'static str, u16)] {
+        match self {
+            Target::Generic => &[],
+            Target::Svp => &SVP_CONSTANTS,
+        }
+    }
+
+    // The `extN` name for a register alias, or the word itself:
+    pub fn resolve<'a>(&self, word: &'a str) -> &'a str {
+        self.registers()
+            .iter()
+            .find(|(alias, _)| word.eq_ignore_ascii_case(alias))
+            .map(|(_, register)| *register)
+            .unwrap_or(word)
+    }
+
+    // Replaces register aliases in code (comments are left alone). Lines are kept, columns after
+    // an alias may change.
+    pub fn resolve_source(&self, contents: &str) -> String {
+        if self.registers().is_empty() {
+            return contents.to_string();
+        }
+
+        let mut source = contents
+            .lines()
+            .map(|line| {
+                let mut resolved = line.to_string();
+                for (column, word) in tokens::split_line_with_columns(line).into_iter().rev() {
+                    let register = self.resolve(word);
+                    if register != word {
+                        resolved.replace_range(column..column + word.len(), register);
+                    }
+                }
+                resolved
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        if contents.ends_with('\n') {
+            source.push('\n');
+        }
+        source
+    }
+}
+
+#[cfg(test)]
+mod targets_tests {
+    use super::*;
+
+    #[test]
+    fn check_svp_registers() {
+        assert_eq!(Target::Svp.resolve("PM4"), "ext4");
+        assert_eq!(Target::Svp.resolve("xst"), "ext3");
+        assert_eq!(Target::Svp.resolve("al"), "ext7");
+        assert_eq!(Target::Svp.resolve("a"), "a");
+        assert_eq!(Target::Generic.resolve("pm4"), "pm4");
+    }
+
+    #[test]
+    fn check_resolve_source() {
+        assert_eq!(
+            Target::Svp.resolve_source("loop: ld a, XST_State # pm0\n\tld pmc, al\n"),
+            "loop: ld a, ext0 # pm0\n\tld ext6, ext7\n"
+        );
+        assert_eq!(Target::Generic.resolve_source("ld a, xst\n"), "ld a, xst\n");
+    }
+}
@@ -4,7 +4,6 @@ use super::operations;
 use crate::asm::macros;
 use crate::asm::mnemonics;
 use crate::asm::operators;
-use crate::asm::registers;
 use crate::tokenization::tokens;
 use std::collections::HashMap;
 use std::error::Error;
@@ -28,10 +27,12 @@ pub fn extract_tables<'a>(
     let mut current_dw: bool = false;
     let mut current_equ: bool = false;
     let mut current_equb: bool = false;
+    let mut current_align: bool = false;
+    let mut current_limit: bool = false;
     let mut current_equ_label: &str = "";
-    let mut current_mnemonic: Option<mnemonics::SspMnemonic> = None;
+    let mut current_instruction: Option<instructions::Instruction> = None;
 
-    let (symbols, equs, equbs, _) = tokens.iter().fold(
+    let (mut symbols, equs, equbs, last_token) = tokens.iter().fold(
         (
             HashMap::<&'a str, u16>::new(),
             HashMap::<&'a str, u16>::new(),
@@ -51,14 +52,13 @@ pub fn extract_tables<'a>(
                 (tokens::Token::Mnemonic(mnemonic), Some(tokens::Token::Label(label))) => {
                     current_dw = false;
                     current_org = false;
-                    current_mnemonic = Some(*mnemonic);
                     symbols.insert(label, current_address);
-                    1
+                    start_instruction(&mut current_instruction, *mnemonic)
                 }
                 (tokens::Token::Invalid(_), Some(tokens::Token::Label(label))) => {
                     current_dw = false;
                     current_org = false;
-                    current_mnemonic = None;
+                    current_instruction = None;
                     symbols.insert(label, current_address);
                     0
                 }
@@ -68,7 +68,21 @@ pub fn extract_tables<'a>(
                     current_dw = false;
                     current_equ = false;
                     current_equb = false;
-                    current_mnemonic = None;
+                    current_instruction = None;
+                    0
+                }
+                (tokens::Token::Macro(macros::SspMacro::Align), _) => {
+                    current_org = false;
+                    current_dw = false;
+                    current_align = true;
+                    current_instruction = None;
+                    0
+                }
+                (tokens::Token::Macro(macros::SspMacro::Limit), _) => {
+                    current_org = false;
+                    current_dw = false;
+                    current_limit = true;
+                    current_instruction = None;
                     0
                 }
                 (tokens::Token::Macro(macros::SspMacro::Dw), Some(tokens::Token::Label(label))) => {
@@ -76,7 +90,7 @@ pub fn extract_tables<'a>(
                     current_dw = true;
                     current_equ = false;
                     current_equb = false;
-                    current_mnemonic = None;
+                    current_instruction = None;
                     symbols.insert(label, current_address);
                     0
                 }
@@ -85,7 +99,7 @@ pub fn extract_tables<'a>(
                     current_dw = true;
                     current_equ = false;
                     current_equb = false;
-                    current_mnemonic = None;
+                    current_instruction = None;
                     0
                 }
                 (
@@ -96,7 +110,7 @@ pub fn extract_tables<'a>(
                     current_dw = false;
                     current_equ = true;
                     current_equb = false;
-                    current_mnemonic = None;
+                    current_instruction = None;
                     current_equ_label = label;
                     0
                 }
@@ -109,144 +123,37 @@ pub fn extract_tables<'a>(
                     current_dw = false;
                     current_equ = false;
                     current_equb = true;
-                    current_mnemonic = None;
+                    current_instruction = None;
                     current_equ_label = label;
                     0
                 }
 
-                (
-                    tokens::Token::Operator(operators::SspOperator::RamBankAddressA(_)),
-                    Some(tokens::Token::Mnemonic(mnemonics::SspMnemonic::Ld(
-                        mnemonics::SspMnemonicModifier::Reference,
-                    ))),
-                ) => {
-                    // LD A[addr], A should be just size 1 instruction
-                    current_mnemonic = None;
-                    0
-                }
-
-                (
-                    tokens::Token::Operator(operators::SspOperator::RamBankAddressB(_)),
-                    Some(tokens::Token::Mnemonic(mnemonics::SspMnemonic::Ld(
-                        mnemonics::SspMnemonicModifier::Reference,
-                    ))),
-                ) => {
-                    // LD B[addr], A should be just size 1 instruction
-                    current_mnemonic = None;
-                    0
-                }
-
-                (
-                    tokens::Token::Mnemonic(mnemonics::SspMnemonic::Ld(
-                        mnemonics::SspMnemonicModifier::Reference,
-                    )),
-                    Some(tokens::Token::Operator(operators::SspOperator::RamBankAddressA(_))),
-                ) => {
-                    // LD A, A[addr] should be just size 1 instruction
-                    current_mnemonic = None;
-                    0
-                }
-
-                (
-                    tokens::Token::Mnemonic(mnemonics::SspMnemonic::Ld(
-                        mnemonics::SspMnemonicModifier::Reference,
-                    )),
-                    Some(tokens::Token::Operator(operators::SspOperator::RamBankAddressB(_))),
-                ) => {
-                    // LD A, B[addr] should be just size 1 instruction
-                    current_mnemonic = None;
-                    0
-                }
-
-                // OP A, addr needs to treat address words as size 1 instruction:
-                (
-                    tokens::Token::Operator(operators::SspOperator::Word(_)),
-                    Some(tokens::Token::Operator(operators::SspOperator::Reg(
-                        registers::SspGeneralRegister::A,
-                    ))),
-                ) if (current_mnemonic
-                    == Some(mnemonics::SspMnemonic::Sub(
-                        mnemonics::SspMnemonicModifier::Reference,
-                    ))) =>
+                // Operands add the words the instruction takes besides the first one. Labels not
+                // known yet are sized as words.
+                (tokens::Token::Operator(operator), _)
+                    if current_instruction.map(|i| !i.is_complete()) == Some(true) =>
                 {
-                    // Special case for OP A, addr
-                    current_mnemonic = None;
-                    0
-                }
-
-                (
-                    tokens::Token::Operator(operators::SspOperator::Word(_)),
-                    Some(tokens::Token::Operator(operators::SspOperator::Reg(
-                        registers::SspGeneralRegister::A,
-                    ))),
-                ) if (current_mnemonic
-                    == Some(mnemonics::SspMnemonic::Cmp(
-                        mnemonics::SspMnemonicModifier::Reference,
-                    ))) =>
-                {
-                    // Special case for OP A, addr
-                    current_mnemonic = None;
-                    0
-                }
-
-                (
-                    tokens::Token::Operator(operators::SspOperator::Word(_)),
-                    Some(tokens::Token::Operator(operators::SspOperator::Reg(
-                        registers::SspGeneralRegister::A,
-                    ))),
-                ) if (current_mnemonic
-                    == Some(mnemonics::SspMnemonic::Add(
-                        mnemonics::SspMnemonicModifier::Reference,
-                    ))) =>
-                {
-                    // Special case for OP A, addr
-                    current_mnemonic = None;
-                    0
-                }
-
-                (
-                    tokens::Token::Operator(operators::SspOperator::Word(_)),
-                    Some(tokens::Token::Operator(operators::SspOperator::Reg(
-                        registers::SspGeneralRegister::A,
-                    ))),
-                ) if (current_mnemonic
-                    == Some(mnemonics::SspMnemonic::And(
-                        mnemonics::SspMnemonicModifier::Reference,
-                    ))) =>
-                {
-                    // Special case for OP A, addr
-                    current_mnemonic = None;
-                    0
-                }
-
-                (
-                    tokens::Token::Operator(operators::SspOperator::Word(_)),
-                    Some(tokens::Token::Operator(operators::SspOperator::Reg(
-                        registers::SspGeneralRegister::A,
-                    ))),
-                ) if (current_mnemonic
-                    == Some(mnemonics::SspMnemonic::Or(
-                        mnemonics::SspMnemonicModifier::Reference,
-                    ))) =>
-                {
-                    // Special case for OP A, addr
-                    current_mnemonic = None;
-                    0
-                }
-
-                (
-                    tokens::Token::Operator(operators::SspOperator::Word(_)),
-                    Some(tokens::Token::Operator(operators::SspOperator::Reg(
-                        registers::SspGeneralRegister::A,
-                    ))),
-                ) if (current_mnemonic
-                    == Some(mnemonics::SspMnemonic::Eor(
-                        mnemonics::SspMnemonicModifier::Reference,
-                    ))) =>
-                {
-                    // Special case for OP A, addr
-                    current_mnemonic = None;
-                    0
+                    let operator = match operator {
+                        operators::SspOperator::LabelRef(label) => match equbs.get(label) {
+                            Some(value) => operators::SspOperator::Byte(*value),
+                            None => operators::SspOperator::Word(
+                                equs.get(label)
+                                    .or_else(|| symbols.get(label))
+                                    .copied()
+                                    .unwrap_or(0),
+                            ),
+                        },
+                        _ => *operator,
+                    };
+                    let instruction = current_instruction.unwrap().new_with_op(&operator);
+
+                    if instruction.is_complete() {
+                        current_instruction = None;
+                        instruction.size().unwrap_or(1) - 1
+                    } else {
+                        current_instruction = Some(instruction);
+                        0
+                    }
                 }
 
                 (tokens::Token::Operator(operators::SspOperator::Word(value)), _) => {
@@ -254,6 +161,12 @@ pub fn extract_tables<'a>(
                         current_address = *value;
                         current_org = false;
                         0
+                    } else if current_align {
+                        current_align = false;
+                        alignment_padding(current_address, *value)
+                    } else if current_limit {
+                        current_limit = false;
+                        0
                     } else if current_dw {
                         1
                     } else if current_equ {
@@ -269,17 +182,32 @@ pub fn extract_tables<'a>(
                     if current_equb {
                         equbs.insert(current_equ_label, *value);
                         current_equb = false;
+                        0
+                    } else if current_align {
+                        current_align = false;
+                        alignment_padding(current_address, *value as u16)
+                    } else if current_limit {
+                        current_limit = false;
+                        0
+                    } else if current_dw {
+                        // Written as a word too
+                        1
+                    } else {
+                        0
                     }
-                    0
                 }
 
+                (
+                    tokens::Token::Operator(operators::SspOperator::LabelRef(_)),
+                    Some(tokens::Token::Macro(macros::SspMacro::Export)),
+                ) => 0,
+
                 (tokens::Token::Operator(operators::SspOperator::LabelRef(_)), _) => 1,
 
                 (tokens::Token::Mnemonic(mnemonic), _) => {
                     current_dw = false;
                     current_org = false;
-                    current_mnemonic = Some(*mnemonic);
-                    1
+                    start_instruction(&mut current_instruction, *mnemonic)
                 }
 
                 (_, _) => {
@@ -294,9 +222,63 @@ pub fn extract_tables<'a>(
             (symbols, equs, equbs, Some(*token))
         },
     );
+    // A label at the end of the source points right after the last word
+    if let Some(tokens::Token::Label(label)) = last_token {
+        symbols.insert(label, current_address);
+    }
     (symbols, equs, equbs)
 }
 
+// Size of the first word of an instruction, or of the whole instruction if it takes no operands:
+fn start_instruction<'a>(
+    current_instruction: &mut Option<instructions::Instruction<'a>>,
+    mnemonic: mnemonics::SspMnemonic,
+) -> u16 {
+    let instruction = instructions::Instruction::new(mnemonic);
+
+    if instruction.is_complete() {
+        *current_instruction = None;
+        instruction.size().unwrap_or(1)
+    } else {
+        *current_instruction = Some(instruction);
+        1
+    }
+}
+
+// Words to skip so the next one starts at a multiple of `alignment`:
+fn alignment_padding(address: u16, alignment: u16) -> u16 {
+    match alignment {
+        0 => 0,
+        _ => ((alignment as u32 - address as u32 % alignment as u32) % alignment as u32) as u16,
+    }
+}
+
+// Result of assembling a list of tokens. Along with the binary, the program memory span
+// (start/end byte addresses) written by each token is kept, and errors point to the index of the
+// token that caused them:
+pub struct Assembly {
+    pub binary: Vec<u8>,
+    pub spans: Vec<Option<(u64, u64)>>,
+    pub errors: Vec<(usize, errors::AssemblyError)>,
+}
+
+impl Assembly {
+    // The assembly itself, or all its errors if there's any:
+    pub fn checked(self) -> Result<Assembly, Box<dyn Error>> {
+        if self.errors.is_empty() {
+            Ok(self)
+        } else {
+            let errors: Vec<errors::AssemblyError> =
+                self.errors.into_iter().map(|(_, error)| error).collect();
+
+            Err(Box::new(errors::AssemblyError(format!(
+                "{}",
+                error_list(&errors)
+            ))))
+        }
+    }
+}
+
 pub fn generate_opcodes<'a>(
     tokens: &Vec<tokens::Token<'a>>,
     symbols: &'a HashMap<&'a str, u16>,
@@ -307,6 +289,33 @@ pub fn generate_opcodes<'a>(
     should_fill: bool,
     max_binary_size_in_megs: u8,
 ) -> Result<Vec<u8>, Box<dyn Error>> {
+    let assembly = assemble(
+        tokens,
+        symbols,
+        equs,
+        equbs,
+        show_debug,
+        input_base_rom,
+        should_fill,
+        max_binary_size_in_megs,
+    )?;
+
+    Ok(assembly.checked()?.binary)
+}
+
+// Same as `generate_opcodes`, but errors in single instructions don't stop the assembly process.
+// Only errors that prevent creating a binary at all are returned as `Err`:
+#[allow(clippy::too_many_arguments)]
+pub fn assemble<'a>(
+    tokens: &Vec<tokens::Token<'a>>,
+    symbols: &'a HashMap<&'a str, u16>,
+    equs: &'a HashMap<&'a str, u16>,
+    equbs: &'a HashMap<&'a str, u8>,
+    show_debug: bool,
+    input_base_rom: Option<String>,
+    should_fill: bool,
+    max_binary_size_in_megs: u8,
+) -> Result<Assembly, Box<dyn Error>> {
     let mut binary: Vec<u8> = if let Some(ref base_file_path) = input_base_rom {
         let mut file = File::open(&base_file_path).expect("input base file not found");
         let metadata = fs::metadata(&base_file_path).expect("unable to read base file metadata");
@@ -329,16 +338,21 @@ pub fn generate_opcodes<'a>(
 
     let mut current_address: u64 = 0;
     let mut current_operation: Option<operations::Operation> = None;
-    let mut errors: Vec<errors::AssemblyError> = Vec::new();
+    let mut errors: Vec<(usize, errors::AssemblyError)> = Vec::new();
     let mut max_address: u64 = 0;
+    let mut spans: Vec<Option<(u64, u64)>> = Vec::new();
+    // Last word address code can be placed at in the current section, and whether it was exceeded
+    let mut current_limit: Option<(u16, bool)> = None;
 
-    for token in tokens.iter() {
+    for (index, token) in tokens.iter().enumerate() {
         if max_address > max_binary_size_in_megs as u64 * 0x100000 {
             return Err(Box::new(errors::AssemblyError(
                 "Exceeded max binary size.".to_string(),
             )));
         }
         let mut proper_token: tokens::Token = *token;
+        let start_address = current_address;
+        let mut has_written = false;
 
         proper_token = match token {
             tokens::Token::Operator(operators::SspOperator::LabelRef(label))
@@ -389,6 +403,49 @@ pub fn generate_opcodes<'a>(
             ) => {
                 current_operation = None;
                 current_address = *value as u64 * 2;
+                current_limit = None;
+            }
+
+            // Align macro (skips words without writing them, like ORG)
+            (
+                Some(operations::Operation::Macro(macros::SspMacro::Align)),
+                tokens::Token::Operator(operators::SspOperator::Word(_)),
+            )
+            | (
+                Some(operations::Operation::Macro(macros::SspMacro::Align)),
+                tokens::Token::Operator(operators::SspOperator::Byte(_)),
+            ) => {
+                let alignment = match proper_token {
+                    tokens::Token::Operator(operators::SspOperator::Byte(value)) => value as u16,
+                    tokens::Token::Operator(operators::SspOperator::Word(value)) => value,
+                    _ => 0,
+                };
+                if alignment == 0 {
+                    errors.push((
+                        index,
+                        errors::AssemblyError("ALIGN needs a non-zero number of words".to_string()),
+                    ));
+                } else {
+                    current_address +=
+                        alignment_padding((current_address / 2) as u16, alignment) as u64 * 2;
+                }
+                current_operation = None;
+            }
+
+            // Limit macro
+            (
+                Some(operations::Operation::Macro(macros::SspMacro::Limit)),
+                tokens::Token::Operator(operators::SspOperator::Word(value)),
+            ) => {
+                current_limit = Some((*value, false));
+                current_operation = None;
+            }
+            (
+                Some(operations::Operation::Macro(macros::SspMacro::Limit)),
+                tokens::Token::Operator(operators::SspOperator::Byte(value)),
+            ) => {
+                current_limit = Some((*value as u16, false));
+                current_operation = None;
             }
 
             // Equ macro
@@ -407,6 +464,18 @@ pub fn generate_opcodes<'a>(
                 current_operation = None;
             }
 
+            // Export macro (exported symbols are collected before generating opcodes)
+            (
+                Some(operations::Operation::Macro(macros::SspMacro::Export)),
+                tokens::Token::Operator(operators::SspOperator::Word(_)),
+            )
+            | (
+                Some(operations::Operation::Macro(macros::SspMacro::Export)),
+                tokens::Token::Operator(operators::SspOperator::Byte(_)),
+            ) => {
+                current_operation = None;
+            }
+
             // Dw macro
             (
                 Some(operations::Operation::Macro(macros::SspMacro::Dw)),
@@ -417,8 +486,7 @@ pub fn generate_opcodes<'a>(
                 binary[current_address as usize] = bytes[0];
                 binary[(current_address + 1) as usize] = bytes[1];
                 current_address += 2;
-
-                current_operation = None;
+                has_written = true;
             }
 
             // Dw byte will write a word either way (introduced for compatibility reasons)
@@ -429,21 +497,30 @@ pub fn generate_opcodes<'a>(
                 binary[current_address as usize] = 0;
                 binary[(current_address + 1) as usize] = *value;
                 current_address += 2;
+                has_written = true;
             }
 
             // **** Instructions ****
-            (None, tokens::Token::Mnemonic(m)) => {
+            (None, tokens::Token::Mnemonic(m))
+            | (
+                Some(operations::Operation::Macro(macros::SspMacro::Dw)),
+                tokens::Token::Mnemonic(m),
+            ) => {
                 let instruction = instructions::Instruction::new(*m);
                 if instruction.is_complete() {
                     if show_debug {
                         println!("Complete instruction: {:?}", instruction);
                     }
 
-                    let opcodes = instruction.build()?;
-
-                    for opcode in opcodes.iter() {
-                        binary[current_address as usize] = *opcode;
-                        current_address += 1;
+                    match instruction.build() {
+                        Ok(opcodes) => {
+                            for opcode in opcodes.iter() {
+                                binary[current_address as usize] = *opcode;
+                                current_address += 1;
+                            }
+                            has_written = true;
+                        }
+                        Err(e) => errors.push((index, errors::AssemblyError(e.to_string()))),
                     }
                     current_operation = None;
                 } else {
@@ -451,21 +528,25 @@ pub fn generate_opcodes<'a>(
                 }
             }
 
-            (Some(operations::Operation::Instruction(i)), proper_token)
-                if i.validate_op(proper_token) =>
+            (Some(operations::Operation::Instruction(i)), tokens::Token::Operator(operator))
+                if !i.is_complete() =>
             {
-                let instruction = i.new_with_op(proper_token);
+                let instruction = i.new_with_op(operator);
 
                 if instruction.is_complete() {
                     if show_debug {
                         println!("Complete instruction: {:?}", instruction);
                     }
 
-                    let opcodes = instruction.build()?;
-
-                    for opcode in opcodes.iter() {
-                        binary[current_address as usize] = *opcode;
-                        current_address += 1;
+                    match instruction.build() {
+                        Ok(opcodes) => {
+                            for opcode in opcodes.iter() {
+                                binary[current_address as usize] = *opcode;
+                                current_address += 1;
+                            }
+                            has_written = true;
+                        }
+                        Err(e) => errors.push((index, errors::AssemblyError(e.to_string()))),
                     }
                     current_operation = None;
                 } else {
@@ -473,15 +554,37 @@ pub fn generate_opcodes<'a>(
                 }
             }
 
-            _ => errors.push(errors::AssemblyError(format!(
-                "Invalid token: {:?} for instruction {:?}",
-                proper_token, current_operation
-            ))),
+            _ => errors.push((
+                index,
+                errors::AssemblyError(format!(
+                    "Invalid token: {:?} for instruction {:?}",
+                    proper_token, current_operation
+                )),
+            )),
+        }
+
+        if let Some((limit, false)) = current_limit {
+            if has_written && current_address > (limit as u64 + 1) * 2 {
+                errors.push((
+                    index,
+                    errors::AssemblyError(format!(
+                        "Code placed past the LIMIT of {:04X} (ends at {:04X})",
+                        limit,
+                        current_address / 2 - 1
+                    )),
+                ));
+                current_limit = Some((limit, true));
+            }
         }
 
         if max_address < current_address {
             max_address = current_address;
         }
+        spans.push(if has_written {
+            Some((start_address, current_address))
+        } else {
+            None
+        });
     }
 
     if !should_fill {
@@ -502,14 +605,11 @@ pub fn generate_opcodes<'a>(
         }
     }
 
-    if errors.is_empty() {
-        Ok(binary)
-    } else {
-        Err(Box::new(errors::AssemblyError(format!(
-            "{}",
-            error_list(&errors)
-        ))))
-    }
+    Ok(Assembly {
+        binary,
+        spans,
+        errors,
+    })
 }
 
 pub fn error_list(errors: &Vec<errors::AssemblyError>) -> String {
@@ -519,3 +619,68 @@ pub fn error_list(errors: &Vec<errors::AssemblyError>) -> String {
         acc
     })
 }
+
+#[cfg(test)]
+mod assembly_tests {
+    use super::*;
+
+    fn assemble_source(contents: &str) -> Vec<u8> {
+        let (tokens, _) = tokens::tokenize_with_lines(contents).unwrap();
+        let (symbols, equs, equbs) = extract_tables(&tokens);
+
+        generate_opcodes(&tokens, &symbols, &equs, &equbs, false, None, false, 4).unwrap()
+    }
+
+    #[test]
+    fn check_multiple_dw_words() {
+        let binary = assemble_source("org 0000\ntable: dw 1234 @after 12\nafter: ret\n");
+
+        assert_eq!(binary, vec![0x12, 0x34, 0x00, 0x03, 0x00, 0x12, 0x00, 0x65]);
+    }
+
+    #[test]
+    fn check_align_and_trailing_label() {
+        let (tokens, _) =
+            tokens::tokenize_with_lines("org 0401\nret\nalign 4\nfirst: ret\nalign 0004\nend:\n")
+                .unwrap();
+        let (symbols, _, _) = extract_tables(&tokens);
+
+        assert_eq!(symbols.get("first"), Some(&0x404));
+        assert_eq!(symbols.get("end"), Some(&0x408));
+    }
+
+    #[test]
+    fn check_instruction_sizes() {
+        let (tokens, _) = tokens::tokenize_with_lines(
+            "var: equ 0012\nsmall: equb 12\norg 0400\nadd a, @var\nfirst: ld a, @var\nsecond: addi @small\nld A[12], a\nthird: bra always, @third\nend:\n",
+        )
+        .unwrap();
+        let (symbols, _, _) = extract_tables(&tokens);
+
+        assert_eq!(symbols.get("first"), Some(&0x401));
+        assert_eq!(symbols.get("second"), Some(&0x403));
+        assert_eq!(symbols.get("third"), Some(&0x405));
+        assert_eq!(symbols.get("end"), Some(&0x407));
+    }
+
+    #[test]
+    fn check_limit() {
+        let source = "org 03FE\nlimit 03FF\nld a, 1234\nret\nret\norg 0400\nret\n";
+        let (tokens, _) = tokens::tokenize_with_lines(source).unwrap();
+        let (symbols, equs, equbs) = extract_tables(&tokens);
+        let assembly = assemble(&tokens, &symbols, &equs, &equbs, false, None, false, 4).unwrap();
+
+        let errors: Vec<(usize, String)> = assembly
+            .errors
+            .iter()
+            .map(|(index, error)| (*index, error.to_string()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![(
+                7,
+                "Code placed past the LIMIT of 03FF (ends at 0400)".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use super::conditionals;
+use super::data;
+use super::errors;
+use std::collections::HashMap;
+use std::error::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assertion {
+    pub line: usize,
+    pub expression: String,
+    pub message: Option<String>,
+}
+
+pub fn assertion_line(line: &str) -> Option<data::RawLine<'_>> {
+    data::raw_line(line).filter(|raw| raw.keyword.eq_ignore_ascii_case("assert"))
+}
+
+// Takes `ASSERT expression, "message"` lines out of the source (keeping their labels), so they can
+// be checked once the value of every symbol is known. Lines are 0-based.
+pub fn extract_assertions(contents: &str) -> (String, Vec<Assertion>, Vec<(usize, String)>) {
+    let mut assertions = Vec::new();
+    let mut errors = Vec::new();
+    let mut output: Vec<&str> = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let raw = match assertion_line(line) {
+            Some(raw) => raw,
+            None => {
+                output.push(line);
+                continue;
+            }
+        };
+        output.push(line[..raw.column].trim_end());
+
+        match data::parse_arguments(raw.arguments).as_deref() {
+            Ok([data::Argument::Value(expression)]) => assertions.push(Assertion {
+                line: index,
+                expression: expression.split_whitespace().collect(),
+                message: None,
+            }),
+            Ok([data::Argument::Value(expression), data::Argument::Text(message)]) => assertions
+                .push(Assertion {
+                    line: index,
+                    expression: expression.split_whitespace().collect(),
+                    message: Some(message.clone()),
+                }),
+            Ok(_) => errors.push((index, "Expected ASSERT expression, \"message\"".to_string())),
+            Err(error) => errors.push((index, error.clone())),
+        }
+    }
+
+    let mut source = output.join("\n");
+    if contents.ends_with('\n') {
+        source.push('\n');
+    }
+    (source, assertions, errors)
+}
+
+// Same as `extract_assertions`, failing with all the errors found:
+pub fn remove_assertions(contents: &str) -> Result<(String, Vec<Assertion>), Box<dyn Error>> {
+    let (source, assertions, errors) = extract_assertions(contents);
+
+    if errors.is_empty() {
+        Ok((source, assertions))
+    } else {
+        Err(Box::new(errors::AssemblyError::from_lines(&errors)))
+    }
+}
+
+// Every label and constant, as used in expressions:
+pub fn symbol_values(
+    symbols: &HashMap<&str, u16>,
+    equs: &HashMap<&str, u16>,
+    equbs: &HashMap<&str, u8>,
+) -> HashMap<String, u16> {
+    symbols
+        .iter()
+        .chain(equs.iter())
+        .map(|(name, value)| (name.to_string(), *value))
+        .chain(
+            equbs
+                .iter()
+                .map(|(name, value)| (name.to_string(), *value as u16)),
+        )
+        .collect()
+}
+
+pub fn failed_assertions(
+    assertions: &[Assertion],
+    symbols: &HashMap<&str, u16>,
+    equs: &HashMap<&str, u16>,
+    equbs: &HashMap<&str, u8>,
+) -> Vec<(usize, String)> {
+    let values = symbol_values(symbols, equs, equbs);
+
+    assertions
+        .iter()
+        .filter_map(
+            |assertion| match conditionals::evaluate(&assertion.expression, &values) {
+                Ok(true) => None,
+                Ok(false) => Some((
+                    assertion.line,
+                    format!(
+                        "Assertion failed: {}",
+                        assertion.message.as_ref().unwrap_or(&assertion.expression)
+                    ),
+                )),
+                Err(error) => Some((assertion.line, format!("Invalid assertion: {}", error))),
+            },
+        )
+        .collect()
+}
+
+// Same as `failed_assertions`, failing with all the assertions that don't hold:
+pub fn check(
+    assertions: &[Assertion],
+    symbols: &HashMap<&str, u16>,
+    equs: &HashMap<&str, u16>,
+    equbs: &HashMap<&str, u8>,
+) -> Result<(), Box<dyn Error>> {
+    let failed = failed_assertions(assertions, symbols, equs, equbs);
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(Box::new(errors::AssemblyError::from_lines(&failed)))
+    }
+}
+
+#[cfg(test)]
+mod assertions_tests {
+    use super::*;
+
+    #[test]
+    fn check_extract_assertions() {
+        let (source, assertions, errors) = extract_assertions(
+            "org 0400\nend: ASSERT @end <= 0800, \"Too big, #1\" # comment\nassert SIZE\nassert\n",
+        );
+
+        assert_eq!(source, "org 0400\nend:\n\n\n");
+        assert_eq!(
+            assertions,
+            vec![
+                Assertion {
+                    line: 1,
+                    expression: "@end<=0800".to_string(),
+                    message: Some("Too big, #1".to_string()),
+                },
+                Assertion {
+                    line: 2,
+                    expression: "SIZE".to_string(),
+                    message: None,
+                },
+            ]
+        );
+        assert_eq!(
+            errors,
+            vec![(3, "Expected ASSERT expression, \"message\"".to_string())]
+        );
+    }
+
+    #[test]
+    fn check_failed_assertions() {
+        let (_, assertions, _) = extract_assertions(
+            "assert @end - @start <= 0010, \"Routine too big\"\nassert @end < 0500\nassert @size == 20\nassert @missing\n",
+        );
+        let symbols: HashMap<&str, u16> =
+            vec![("start", 0x400), ("end", 0x420)].into_iter().collect();
+        let equs = HashMap::new();
+        let equbs: HashMap<&str, u8> = vec![("size", 0x20)].into_iter().collect();
+
+        assert_eq!(
+            failed_assertions(&assertions, &symbols, &equs, &equbs),
+            vec![
+                (0, "Assertion failed: Routine too big".to_string()),
+                (
+                    3,
+                    "Invalid assertion: Undefined symbol in condition: missing".to_string()
+                ),
+            ]
+        );
+    }
+}
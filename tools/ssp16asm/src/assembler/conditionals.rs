@@ -0,0 +1,414 @@
+use super::errors;
+use crate::asm::macros;
+use crate::asm::operators;
+use crate::tokenization::tokens;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Directive {
+    If,
+    Elseif,
+    Else,
+    Endif,
+    Ifdef,
+    Ifndef,
+}
+
+impl Directive {
+    pub fn new(directive: &str) -> Option<Directive> {
+        match directive.to_lowercase().as_str() {
+            "if" => Some(Directive::If),
+            "elseif" => Some(Directive::Elseif),
+            "else" => Some(Directive::Else),
+            "endif" => Some(Directive::Endif),
+            "ifdef" => Some(Directive::Ifdef),
+            "ifndef" => Some(Directive::Ifndef),
+            _ => None,
+        }
+    }
+}
+
+// Conditional directive starting a line (if any), along with its arguments:
+pub fn directive(line: &str) -> Option<(Directive, Vec<&str>)> {
+    let words: Vec<&str> = tokens::split_line_with_columns(line)
+        .into_iter()
+        .map(|(_, word)| word)
+        .collect();
+
+    words
+        .first()
+        .and_then(|word| Directive::new(word))
+        .map(|directive| (directive, words[1..].to_vec()))
+}
+
+// A `-D NAME=value` definition (`value` being a hexadecimal number like in sources, `0001` if
+// missing):
+pub fn parse_define(define: &str) -> Result<(String, u16), Box<dyn Error>> {
+    let (name, value) = match define.split_once('=') {
+        Some((name, value)) => (name.trim(), Some(value.trim())),
+        None => (define.trim(), None),
+    };
+    let value = match value.map(operators::SspOperator::new) {
+        None => Some(1),
+        Some(Some(operators::SspOperator::Word(value))) => Some(value),
+        Some(Some(operators::SspOperator::Byte(value))) => Some(value as u16),
+        Some(_) => None,
+    };
+
+    match value {
+        Some(value) if is_name(name) => Ok((name.to_string(), value)),
+        _ => Err(Box::new(errors::AssemblyError(format!(
+            "Invalid definition: {} (expected NAME or NAME=value)",
+            define
+        )))),
+    }
+}
+
+struct Block {
+    // Lines of the enclosing block are assembled
+    parent_active: bool,
+    // A branch of this block was already assembled
+    taken: bool,
+    active: bool,
+    has_else: bool,
+    line: usize,
+}
+
+// Resolves conditional directives, returning the source with directive lines and skipped lines
+// blanked out (so line numbers don't change), along with errors and the (0-based) line each one
+// was found in. Conditions can use `defines` and the EQU/EQUB constants defined above them in
+// assembled lines, and `ifdef`/`ifndef` labels defined above them too.
+pub fn active_source(
+    contents: &str,
+    defines: &HashMap<String, u16>,
+) -> (String, Vec<(usize, String)>) {
+    let mut values: HashMap<String, u16> = defines.clone();
+    let mut names: HashSet<String> = defines.keys().cloned().collect();
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut errors: Vec<(usize, String)> = Vec::new();
+    let mut output: Vec<&str> = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let active = blocks.last().map(|b| b.active).unwrap_or(true);
+
+        let (directive, arguments) = match directive(line) {
+            Some(directive) => directive,
+            None => {
+                if active {
+                    if let Err(error) = define_symbols(line, defines, &mut values, &mut names) {
+                        errors.push((index, error));
+                    }
+                    output.push(line);
+                } else {
+                    output.push("");
+                }
+                continue;
+            }
+        };
+        output.push("");
+
+        let mut condition = |directive: Directive| -> bool {
+            if !active && directive != Directive::Elseif {
+                return false;
+            }
+            let result = match directive {
+                Directive::Ifdef | Directive::Ifndef => match arguments.as_slice() {
+                    [name] => Ok(names.contains(name.trim_start_matches('@'))
+                        == (directive == Directive::Ifdef)),
+                    _ => Err("Expected a single symbol name".to_string()),
+                },
+                _ => evaluate(&arguments.concat(), &values),
+            };
+
+            result.unwrap_or_else(|error| {
+                errors.push((index, error));
+                false
+            })
+        };
+
+        match directive {
+            Directive::If | Directive::Ifdef | Directive::Ifndef => {
+                let result = condition(directive);
+                blocks.push(Block {
+                    parent_active: active,
+                    taken: result,
+                    active: result,
+                    has_else: false,
+                    line: index,
+                });
+            }
+            Directive::Elseif => match blocks.last() {
+                Some(block) if !block.has_else => {
+                    let enabled = block.parent_active && !block.taken;
+                    let result = enabled && condition(directive);
+                    let block = blocks.last_mut().unwrap();
+                    block.active = result;
+                    block.taken = block.taken || result;
+                }
+                Some(_) => errors.push((index, "`elseif` after `else`".to_string())),
+                None => errors.push((index, "`elseif` without `if`".to_string())),
+            },
+            Directive::Else => match blocks.last_mut() {
+                Some(block) if !block.has_else => {
+                    block.active = block.parent_active && !block.taken;
+                    block.taken = true;
+                    block.has_else = true;
+                }
+                Some(_) => errors.push((index, "Duplicated `else`".to_string())),
+                None => errors.push((index, "`else` without `if`".to_string())),
+            },
+            Directive::Endif => {
+                if blocks.pop().is_none() {
+                    errors.push((index, "`endif` without `if`".to_string()));
+                }
+            }
+        }
+        if directive != Directive::If
+            && directive != Directive::Ifdef
+            && directive != Directive::Ifndef
+            && directive != Directive::Elseif
+            && !arguments.is_empty()
+        {
+            errors.push((index, format!("Unexpected arguments: {:?}", arguments)));
+        }
+    }
+
+    for block in blocks {
+        errors.push((block.line, "`if` without `endif`".to_string()));
+    }
+
+    let mut source = output.join("\n");
+    if contents.ends_with('\n') {
+        source.push('\n');
+    }
+    (source, errors)
+}
+
+// Same as `active_source`, failing with all the errors found:
+pub fn preprocess(
+    contents: &str,
+    defines: &HashMap<String, u16>,
+) -> Result<String, Box<dyn Error>> {
+    let (source, errors) = active_source(contents, defines);
+
+    if errors.is_empty() {
+        Ok(source)
+    } else {
+        Err(Box::new(errors::AssemblyError::from_lines(&errors)))
+    }
+}
+
+// Keeps track of the symbols defined in an assembled line:
+fn define_symbols(
+    line: &str,
+    defines: &HashMap<String, u16>,
+    values: &mut HashMap<String, u16>,
+    names: &mut HashSet<String>,
+) -> Result<(), String> {
+    let words: Vec<&str> = tokens::split_line_with_columns(line)
+        .into_iter()
+        .map(|(_, word)| word)
+        .collect();
+    let label = match words.first().filter(|word| word.ends_with(':')) {
+        Some(label) => label.trim_end_matches(':'),
+        None => return Ok(()),
+    };
+    if defines.contains_key(label) {
+        return Err(format!("{} is already defined with -D", label));
+    }
+
+    let value = match (
+        words.get(1).and_then(|word| macros::SspMacro::new(word)),
+        words
+            .get(2)
+            .and_then(|word| operators::SspOperator::new(word)),
+    ) {
+        (Some(macros::SspMacro::Equ), Some(operators::SspOperator::Word(value))) => Some(value),
+        (Some(macros::SspMacro::Equb), Some(operators::SspOperator::Byte(value))) => {
+            Some(value as u16)
+        }
+        _ => None,
+    };
+
+    names.insert(label.to_string());
+    if let Some(value) = value {
+        values.insert(label.to_string(), value);
+    }
+    Ok(())
+}
+
+// Conditions are a single value (true if not 0) or two values compared with `==`, `!=`, `<`,
+// `<=`, `>` or `>=`. Values are symbols (with or without `@`) or hexadecimal numbers, and can be
+// added or subtracted (i.e.: `@end-@start<=0100`).
+pub fn evaluate(expression: &str, values: &HashMap<String, u16>) -> Result<bool, String> {
+    let value = |operand: &str| value(operand, values);
+
+    if expression.is_empty() {
+        return Err("Missing condition".to_string());
+    }
+    for operator in ["==", "!=", "<=", ">=", "<", ">"].iter() {
+        if let Some((left, right)) = expression.split_once(operator) {
+            let (left, right) = (value(left)?, value(right)?);
+
+            return Ok(match *operator {
+                "==" => left == right,
+                "!=" => left != right,
+                "<=" => left <= right,
+                ">=" => left >= right,
+                "<" => left < right,
+                _ => left > right,
+            });
+        }
+    }
+
+    value(expression).map(|value| value != 0)
+}
+
+// A value as found in conditions (i.e.: `@table+0010`):
+pub fn value(operand: &str, values: &HashMap<String, u16>) -> Result<i64, String> {
+    let term = |operand: &str| -> Result<i64, String> {
+        let name = operand.trim_start_matches('@');
+        match values.get(name) {
+            Some(value) => Ok(*value as i64),
+            None => match operators::SspOperator::new(operand) {
+                Some(operators::SspOperator::Word(value)) => Ok(value as i64),
+                Some(operators::SspOperator::Byte(value)) => Ok(value as i64),
+                _ if is_name(name) => Err(format!("Undefined symbol in condition: {}", name)),
+                _ => Err(format!("Invalid value in condition: {}", operand)),
+            },
+        }
+    };
+
+    let mut total = 0;
+    let mut sign = 1;
+    let mut start = 0;
+    for (index, c) in operand.char_indices().chain(Some((operand.len(), '+'))) {
+        if c == '+' || c == '-' {
+            total += sign * term(&operand[start..index])?;
+            sign = if c == '-' { -1 } else { 1 };
+            start = index + 1;
+        }
+    }
+    Ok(total)
+}
+
+fn is_name(name: &str) -> bool {
+    name.chars()
+        .next()
+        .map(|c| c.is_ascii_alphabetic() || c == '_')
+        .unwrap_or(false)
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod conditionals_tests {
+    use super::*;
+
+    fn defines(list: &[(&str, u16)]) -> HashMap<String, u16> {
+        list.iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect()
+    }
+
+    #[test]
+    fn check_parse_define() {
+        assert_eq!(parse_define("DEBUG").unwrap(), ("DEBUG".to_string(), 1));
+        assert_eq!(
+            parse_define("PLATFORM=0002").unwrap(),
+            ("PLATFORM".to_string(), 2)
+        );
+        assert_eq!(
+            parse_define("COUNT=10").unwrap(),
+            ("COUNT".to_string(), 0x10)
+        );
+        assert!(parse_define("=0001").is_err());
+        assert!(parse_define("DEBUG=yes").is_err());
+    }
+
+    #[test]
+    fn check_if_else() {
+        let source = "if @EMULATOR\nld a, x\nelse\nld a, y\nendif\nret\n";
+
+        assert_eq!(
+            preprocess(source, &defines(&[("EMULATOR", 1)])).unwrap(),
+            "\nld a, x\n\n\n\nret\n"
+        );
+        assert_eq!(
+            preprocess(source, &defines(&[("EMULATOR", 0)])).unwrap(),
+            "\n\n\nld a, y\n\nret\n"
+        );
+    }
+
+    #[test]
+    fn check_elseif_and_nesting() {
+        let source = "\
+PLATFORM: equ 0002
+if PLATFORM == 0001
+ld a, x
+elseif PLATFORM==0002
+ifdef DEBUG
+ld a, y
+else
+ld a, 0001
+endif
+else
+ld a, 0000
+endif
+";
+
+        assert_eq!(
+            preprocess(source, &HashMap::new()).unwrap(),
+            "PLATFORM: equ 0002\n\n\n\n\n\n\nld a, 0001\n\n\n\n\n"
+        );
+        assert_eq!(
+            preprocess(source, &defines(&[("DEBUG", 1)])).unwrap(),
+            "PLATFORM: equ 0002\n\n\n\n\nld a, y\n\n\n\n\n\n\n"
+        );
+    }
+
+    #[test]
+    fn check_skipped_definitions() {
+        let source = "ifndef SIZE\nSIZE: equ 0010\nendif\nif SIZE > 000F\nld a, x\nendif\n";
+
+        assert_eq!(
+            preprocess(source, &HashMap::new()).unwrap(),
+            "\nSIZE: equ 0010\n\n\nld a, x\n\n"
+        );
+        assert_eq!(
+            preprocess(source, &defines(&[("SIZE", 1)])).unwrap(),
+            "\n\n\n\n\n\n"
+        );
+    }
+
+    #[test]
+    fn check_expressions() {
+        let values = defines(&[("start", 0x400), ("end", 0x480)]);
+
+        assert_eq!(evaluate("@end-@start==0080", &values), Ok(true));
+        assert_eq!(evaluate("end+10-start>0090", &values), Ok(false));
+        assert_eq!(evaluate("0000", &values), Ok(false));
+        assert!(evaluate("@end-", &values).is_err());
+    }
+
+    #[test]
+    fn check_errors() {
+        let (_, errors) = active_source(
+            "else\nif UNDEFINED\nendif\nendif\nDEBUG: equ 0001\nifdef\nif 0001\n",
+            &defines(&[("DEBUG", 1)]),
+        );
+
+        assert_eq!(
+            errors,
+            vec![
+                (0, "`else` without `if`".to_string()),
+                (1, "Undefined symbol in condition: UNDEFINED".to_string()),
+                (3, "`endif` without `if`".to_string()),
+                (4, "DEBUG is already defined with -D".to_string()),
+                (5, "Expected a single symbol name".to_string()),
+                (5, "`if` without `endif`".to_string()),
+                (6, "`if` without `endif`".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,458 @@
+use super::errors;
+use crate::tokenization::tokens;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const ADDRESS_SPACE_WORDS: usize = 0x10000;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Endianness {
+    Big,
+    Little,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DataDirective {
+    Db(Endianness),
+    Ds,
+    Fill,
+    Charmap,
+    Ascii,
+    Asciiw,
+    Incbin,
+}
+
+impl DataDirective {
+    pub fn new(directive: &str) -> Option<DataDirective> {
+        match directive.to_lowercase().as_str() {
+            "db" | "db.be" => Some(DataDirective::Db(Endianness::Big)),
+            "db.le" => Some(DataDirective::Db(Endianness::Little)),
+            "ds" => Some(DataDirective::Ds),
+            "fill" => Some(DataDirective::Fill),
+            "charmap" => Some(DataDirective::Charmap),
+            "ascii" => Some(DataDirective::Ascii),
+            "asciiw" => Some(DataDirective::Asciiw),
+            "incbin" => Some(DataDirective::Incbin),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Argument {
+    Text(String),
+    Value(String),
+}
+
+// A directive line split in its parts. Arguments can contain strings, so they're kept as
+// written (without the comment):
+pub struct RawLine<'a> {
+    pub column: usize,
+    pub keyword: &'a str,
+    pub arguments: &'a str,
+    pub comment: Option<&'a str>,
+}
+
+pub fn raw_line(line: &str) -> Option<RawLine<'_>> {
+    let (column, keyword) = tokens::split_line_with_columns(line)
+        .into_iter()
+        .find(|(_, word)| !matches!(tokens::Token::new(word), tokens::Token::Label(_)))?;
+
+    let rest = &line[column + keyword.len()..];
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut comment_start = None;
+    for (index, c) in rest.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => {
+                comment_start = Some(index);
+                break;
+            }
+            _ => (),
+        }
+    }
+
+    Some(RawLine {
+        column,
+        keyword,
+        arguments: rest[..comment_start.unwrap_or(rest.len())].trim(),
+        comment: comment_start.map(|start| rest[start..].trim_end()),
+    })
+}
+
+pub fn data_line(line: &str) -> Option<(DataDirective, RawLine<'_>)> {
+    let raw = raw_line(line)?;
+    DataDirective::new(raw.keyword).map(|directive| (directive, raw))
+}
+
+// Replaces data directives with the `DW` words they stand for, on the same line (labels are kept,
+// so the assembler sizes and assembles them like any other `DW`). `INCBIN` files are relative to
+// `directory`. Errors are returned along with the (0-based) line each one was found in.
+pub fn expanded_source(contents: &str, directory: &Path) -> (String, Vec<(usize, String)>) {
+    let mut charmap: HashMap<char, u8> = HashMap::new();
+    let mut errors: Vec<(usize, String)> = Vec::new();
+    let mut output: Vec<String> = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let (directive, data) = match data_line(line) {
+            Some(data) => data,
+            None => {
+                output.push(line.to_string());
+                continue;
+            }
+        };
+
+        let words = parse_arguments(data.arguments)
+            .and_then(|arguments| words(directive, &arguments, &mut charmap, directory));
+        let prefix = line[..data.column].trim_end();
+
+        match words {
+            Ok(words) if words.is_empty() => output.push(prefix.to_string()),
+            Ok(words) => output.push(format!("{} dw {}", prefix, words.join(" "))),
+            Err(error) => {
+                errors.push((index, error));
+                output.push(prefix.to_string());
+            }
+        }
+    }
+
+    let mut source = output.join("\n");
+    if contents.ends_with('\n') {
+        source.push('\n');
+    }
+    (source, errors)
+}
+
+// Same as `expanded_source`, failing with all the errors found:
+pub fn expand(contents: &str, directory: &Path) -> Result<String, Box<dyn Error>> {
+    let (source, errors) = expanded_source(contents, directory);
+
+    if errors.is_empty() {
+        Ok(source)
+    } else {
+        Err(Box::new(errors::AssemblyError::from_lines(&errors)))
+    }
+}
+
+// Files included with `INCBIN`, relative to `directory` as `expanded_source` reads them:
+pub fn included_files(contents: &str, directory: &Path) -> Vec<PathBuf> {
+    contents
+        .lines()
+        .filter_map(data_line)
+        .filter(|(directive, _)| *directive == DataDirective::Incbin)
+        .filter_map(
+            |(_, data)| match parse_arguments(data.arguments).ok()?.first() {
+                Some(Argument::Text(filename)) => Some(directory.join(filename)),
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+fn words(
+    directive: DataDirective,
+    arguments: &[Argument],
+    charmap: &mut HashMap<char, u8>,
+    directory: &Path,
+) -> Result<Vec<String>, String> {
+    match (directive, arguments) {
+        (DataDirective::Db(endianness), _) if !arguments.is_empty() => {
+            let bytes = arguments
+                .iter()
+                .map(byte)
+                .collect::<Result<Vec<u8>, String>>()?;
+            Ok(pack(&bytes, endianness))
+        }
+
+        (DataDirective::Ds, [Argument::Value(count)]) => {
+            Ok(vec!["0000".to_string(); word_count(count)?])
+        }
+
+        (DataDirective::Fill, [Argument::Value(count), Argument::Value(value)]) => {
+            Ok(vec![value.clone(); word_count(count)?])
+        }
+
+        (DataDirective::Charmap, []) => {
+            charmap.clear();
+            Ok(Vec::new())
+        }
+        (DataDirective::Charmap, [Argument::Text(characters), first]) => {
+            let first = byte(first)?;
+            if first as usize + characters.chars().count() > 0x100 {
+                return Err(format!("Character codes past FF for {:?}", characters));
+            }
+            for (offset, c) in characters.chars().enumerate() {
+                charmap.insert(c, first + offset as u8);
+            }
+            Ok(Vec::new())
+        }
+
+        (DataDirective::Ascii, _) | (DataDirective::Asciiw, _) if !arguments.is_empty() => {
+            let mut bytes = Vec::new();
+            for argument in arguments.iter() {
+                match argument {
+                    Argument::Text(text) => {
+                        for c in text.chars() {
+                            bytes.push(character(c, charmap)?);
+                        }
+                    }
+                    value => bytes.push(byte(value)?),
+                }
+            }
+
+            if directive == DataDirective::Ascii {
+                Ok(pack(&bytes, Endianness::Big))
+            } else {
+                Ok(bytes.iter().map(|b| format!("{:04X}", b)).collect())
+            }
+        }
+
+        (DataDirective::Incbin, [Argument::Text(filename), rest @ ..]) if rest.len() <= 2 => {
+            let data = fs::read(directory.join(filename))
+                .map_err(|e| format!("Unable to read {}: {}", filename, e))?;
+            let offset = match rest.first() {
+                Some(Argument::Value(offset)) => number(offset)? as usize,
+                Some(_) => return Err("Invalid INCBIN offset".to_string()),
+                None => 0,
+            };
+            let length = match rest.get(1) {
+                Some(Argument::Value(length)) => number(length)? as usize,
+                Some(_) => return Err("Invalid INCBIN length".to_string()),
+                None => data.len().saturating_sub(offset),
+            };
+
+            match data.get(offset..offset + length) {
+                Some(bytes) => Ok(pack(bytes, Endianness::Big)),
+                None => Err(format!(
+                    "{} is {:X} bytes long, can't include {:X} bytes from offset {:X}",
+                    filename,
+                    data.len(),
+                    length,
+                    offset
+                )),
+            }
+        }
+
+        (DataDirective::Db(_), _) => Err("Expected DB byte, byte...".to_string()),
+        (DataDirective::Ds, _) => Err("Expected DS count".to_string()),
+        (DataDirective::Fill, _) => Err("Expected FILL count, value".to_string()),
+        (DataDirective::Charmap, _) => {
+            Err("Expected CHARMAP \"characters\", first_code".to_string())
+        }
+        (DataDirective::Ascii, _) | (DataDirective::Asciiw, _) => {
+            Err("Expected a string and/or bytes".to_string())
+        }
+        (DataDirective::Incbin, _) => Err("Expected INCBIN \"file\", offset, length".to_string()),
+    }
+}
+
+// Words reserved by `DS`/`FILL`, no more than the whole 16-bit address space:
+fn word_count(value: &str) -> Result<usize, String> {
+    match number(value)? as usize {
+        count if count > ADDRESS_SPACE_WORDS => Err(format!(
+            "Count {} is larger than the address space ({:X} words)",
+            value, ADDRESS_SPACE_WORDS
+        )),
+        count => Ok(count),
+    }
+}
+
+// Two bytes per word, the first one in the high byte for big endian (the order they have in
+// the ROM). An odd number of bytes is padded with 00:
+fn pack(bytes: &[u8], endianness: Endianness) -> Vec<String> {
+    bytes
+        .chunks(2)
+        .map(|pair| {
+            let (first, second) = (pair[0] as u16, pair.get(1).copied().unwrap_or(0) as u16);
+            let word = match endianness {
+                Endianness::Big => first << 8 | second,
+                Endianness::Little => second << 8 | first,
+            };
+            format!("{:04X}", word)
+        })
+        .collect()
+}
+
+fn character(c: char, charmap: &HashMap<char, u8>) -> Result<u8, String> {
+    match charmap.get(&c) {
+        Some(code) => Ok(*code),
+        None if charmap.is_empty() && c.is_ascii() => Ok(c as u8),
+        None if charmap.is_empty() => Err(format!("Non ASCII character: {:?}", c)),
+        None => Err(format!("Character not in CHARMAP: {:?}", c)),
+    }
+}
+
+fn byte(argument: &Argument) -> Result<u8, String> {
+    match argument {
+        Argument::Value(value) => match number(value)? {
+            number if number <= 0xFF => Ok(number as u8),
+            _ => Err(format!("Value doesn't fit in a byte: {}", value)),
+        },
+        Argument::Text(text) => Err(format!("Expected a byte, found {:?}", text)),
+    }
+}
+
+// Hexadecimal numbers, like everywhere else in sources:
+pub fn number(value: &str) -> Result<u32, String> {
+    u32::from_str_radix(value.trim_start_matches("0x").trim_end_matches('h'), 16)
+        .map_err(|_| format!("Invalid hexadecimal number: {}", value))
+}
+
+// Comma separated arguments, which can be strings:
+pub fn parse_arguments(raw: &str) -> Result<Vec<Argument>, String> {
+    let mut arguments = Vec::new();
+    let mut chars = raw.chars().peekable();
+
+    loop {
+        while chars.peek().map(|c| c.is_whitespace()) == Some(true) {
+            chars.next();
+        }
+        match chars.peek() {
+            None if arguments.is_empty() => break,
+            None => return Err("Missing argument after ','".to_string()),
+            Some('"') => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => text.push(match chars.next() {
+                            Some('n') => '\n',
+                            Some('0') => '\0',
+                            Some(c @ '"') | Some(c @ '\\') => c,
+                            other => return Err(format!("Invalid escape sequence: \\{:?}", other)),
+                        }),
+                        Some(c) => text.push(c),
+                        None => return Err("Unterminated string".to_string()),
+                    }
+                }
+                arguments.push(Argument::Text(text));
+            }
+            Some(_) => {
+                let mut value = String::new();
+                while let Some(c) = chars.peek().filter(|c| **c != ',') {
+                    value.push(*c);
+                    chars.next();
+                }
+                let value = value.trim();
+                if value.is_empty() {
+                    return Err("Missing argument before ','".to_string());
+                }
+                arguments.push(Argument::Value(value.to_string()));
+            }
+        }
+
+        while chars.peek().map(|c| c.is_whitespace()) == Some(true) {
+            chars.next();
+        }
+        match chars.next() {
+            None => break,
+            Some(',') => (),
+            Some(c) => return Err(format!("Unexpected {:?} after argument", c)),
+        }
+    }
+
+    Ok(arguments)
+}
+
+#[cfg(test)]
+mod data_tests {
+    use super::*;
+    use std::env;
+
+    fn expand_ok(contents: &str) -> String {
+        expand(contents, Path::new("")).unwrap()
+    }
+
+    #[test]
+    fn check_bytes() {
+        assert_eq!(
+            expand_ok("table: db 01, 02, 0x3, FFh # comment\ndb.le 01, 02, 03\nret\n"),
+            "table: dw 0102 03FF\n dw 0201 0003\nret\n"
+        );
+    }
+
+    #[test]
+    fn check_fills() {
+        assert_eq!(
+            expand_ok("buffer: ds 3\nfill 2, @buffer\nfill 0, 1234\n"),
+            "buffer: dw 0000 0000 0000\n dw @buffer @buffer\n\n"
+        );
+
+        let (_, errors) = expanded_source("ds 10000\nfill FFFFFFF, 1\n", Path::new(""));
+        assert_eq!(
+            errors,
+            vec![(
+                1,
+                "Count FFFFFFF is larger than the address space (10000 words)".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn check_strings() {
+        assert_eq!(
+            expand_ok("text: ascii \"Hi, \\\"#1\\\"\", 00\nasciiw \"ok\"\n"),
+            "text: dw 4869 2C20 2223 3122 0000\n dw 006F 006B\n"
+        );
+        assert_eq!(
+            expand_ok(
+                "charmap \"ABC\", 01\ncharmap \" \", 00\nascii \"CAB A\"\ncharmap\nascii \"A\"\n"
+            ),
+            "\n\n dw 0301 0200 0100\n\n dw 4100\n"
+        );
+    }
+
+    #[test]
+    fn check_incbin() {
+        let directory = env::temp_dir();
+        fs::write(directory.join("ssp16asm_incbin.bin"), [1, 2, 3, 4, 5]).unwrap();
+
+        assert_eq!(
+            expand("incbin \"ssp16asm_incbin.bin\"\n", &directory).unwrap(),
+            " dw 0102 0304 0500\n"
+        );
+        assert_eq!(
+            expand("data: incbin \"ssp16asm_incbin.bin\", 1, 2\n", &directory).unwrap(),
+            "data: dw 0203\n"
+        );
+        assert!(expand("incbin \"ssp16asm_incbin.bin\", 4, 2\n", &directory).is_err());
+        assert!(expand("incbin \"ssp16asm_missing.bin\"\n", &directory).is_err());
+        assert_eq!(
+            included_files(
+                "incbin \"a.bin\"\nret\ndata: INCBIN \"b.bin\", 2 # tiles\nincbin 1\n",
+                Path::new("src")
+            ),
+            vec![
+                Path::new("src").join("a.bin"),
+                Path::new("src").join("b.bin")
+            ]
+        );
+    }
+
+    #[test]
+    fn check_errors() {
+        let (source, errors) = expanded_source(
+            "label: db 100\nds\nascii \"open\nfill 2,\ncharmap \"ABC\"\ncharmap \"A\", 01\nascii \"B\"\n",
+            Path::new(""),
+        );
+
+        assert_eq!(source, "label:\n\n\n\n\n\n\n");
+        assert_eq!(
+            errors,
+            vec![
+                (0, "Value doesn't fit in a byte: 100".to_string()),
+                (1, "Expected DS count".to_string()),
+                (2, "Unterminated string".to_string()),
+                (3, "Missing argument after ','".to_string()),
+                (4, "Expected CHARMAP \"characters\", first_code".to_string()),
+                (6, "Character not in CHARMAP: 'B'".to_string()),
+            ]
+        );
+    }
+}
@@ -4,6 +4,18 @@ use std::fmt;
 #[derive(Debug)]
 pub struct AssemblyError(pub String);
 
+impl AssemblyError {
+    // One error per line, for errors found in (0-based) source lines:
+    pub fn from_lines(errors: &[(usize, String)]) -> AssemblyError {
+        AssemblyError(
+            errors
+                .iter()
+                .map(|(line, error)| format!("Line {}: {}\n", line + 1, error))
+                .collect(),
+        )
+    }
+}
+
 impl fmt::Display for AssemblyError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.0)
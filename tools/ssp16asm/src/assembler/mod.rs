@@ -1,4 +1,10 @@
 pub mod assembly;
+pub mod assertions;
+pub mod conditionals;
+pub mod data;
 pub mod errors;
-pub mod instructions;
 pub mod operations;
+pub mod pmset;
+pub mod symbols;
+
+pub use ssp16_isa::instructions;
@@ -0,0 +1,361 @@
+use super::data;
+use super::errors;
+use crate::import::m68k::ImportedSymbol;
+use std::error::Error;
+
+// Regions the memory adapter can access, in SVP word addresses (68000 byte address / 2):
+const ROM: (u32, u32) = (0x000000, 0x0FFFFF);
+const DRAM: (u32, u32) = (0x180000, 0x18FFFF);
+const IRAM: (u32, u32) = (0x1C8000, 0x1C83FF);
+
+// Mode word flags (the low 7 bits hold the high bits of the address):
+const OVERWRITE: u16 = 0x0400;
+const CELL: u16 = 0x4000;
+const DECREMENT: u16 = 0x8000;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PmSetting {
+    pub register: u8,
+    pub write: bool,
+    pub address: u32,
+    pub increment: i32,
+    pub overwrite: bool,
+    pub cell: bool,
+    pub exact: bool,
+}
+
+impl PmSetting {
+    // `PMSET pmN, read|write, address, options`, options being `inc=N` (0, 1, 2, 4, 8, 16, 32,
+    // 128, negative to decrement), `overwrite`, `cell` and `exact`. The address can be the name of
+    // a symbol imported from the M68000 side (i.e.: a DRAM buffer).
+    pub fn parse(
+        arguments: &[data::Argument],
+        imported: &[ImportedSymbol],
+    ) -> Result<PmSetting, String> {
+        let values = arguments
+            .iter()
+            .map(|argument| match argument {
+                data::Argument::Value(value) => Ok(value.to_lowercase()),
+                data::Argument::Text(text) => Err(format!("Unexpected string {:?}", text)),
+            })
+            .collect::<Result<Vec<String>, String>>()?;
+
+        let (register, direction, address, options) = match values.as_slice() {
+            [register, direction, address, options @ ..] => (register, direction, address, options),
+            _ => return Err("Expected PMSET pmN, read|write, address, options".to_string()),
+        };
+
+        let mut setting = PmSetting {
+            register: match register.as_str() {
+                "pm0" => 0,
+                "pm1" => 1,
+                "pm2" => 2,
+                "pm3" => 3,
+                "pm4" => 4,
+                _ => return Err(format!("Expected pm0 to pm4, found {}", register)),
+            },
+            write: match direction.as_str() {
+                "read" => false,
+                "write" => true,
+                _ => return Err(format!("Expected read or write, found {}", direction)),
+            },
+            address: address_of(address, imported)?,
+            increment: 0,
+            overwrite: false,
+            cell: false,
+            exact: false,
+        };
+
+        for option in options {
+            match option.split_once('=') {
+                Some(("inc", value)) => {
+                    setting.increment = match value.strip_prefix('-') {
+                        Some(value) => -(data::number(value)? as i32),
+                        None => data::number(value)? as i32,
+                    }
+                }
+                None if option == "overwrite" => setting.overwrite = true,
+                None if option == "cell" => setting.cell = true,
+                None if option == "exact" => setting.exact = true,
+                _ => return Err(format!("Unknown PMSET option: {}", option)),
+            }
+        }
+        Ok(setting)
+    }
+
+    // Address programmed into the adapter. Blind writes already advance it once (as seen in
+    // emulators), so by default the increment is taken off for the first real write to land on
+    // the given address. `exact` programs the address as is.
+    pub fn programmed_address(&self) -> u32 {
+        if self.write && !self.exact {
+            (self.address as i64 - self.increment as i64) as u32
+        } else {
+            self.address
+        }
+    }
+
+    // The two words written to ext6: the low 16 bits of the address, and then the mode.
+    pub fn words(&self) -> Result<(u16, u16), String> {
+        let code = match self.increment.abs() {
+            0 => 0,
+            1 => 1,
+            2 => 2,
+            4 => 3,
+            8 => 4,
+            0x10 => 5,
+            0x20 => 6,
+            0x80 => 7,
+            _ => {
+                return Err(format!(
+                    "Invalid increment: {}{:X}",
+                    if self.increment < 0 { "-" } else { "" },
+                    self.increment.abs()
+                ))
+            }
+        };
+
+        let (region, name) = if within(self.address, ROM) {
+            (ROM, "ROM")
+        } else if within(self.address, DRAM) {
+            (DRAM, "DRAM")
+        } else if within(self.address, IRAM) {
+            (IRAM, "IRAM")
+        } else {
+            return Err(format!(
+                "Address {:06X} is outside ROM ({:06X}-{:06X}), DRAM ({:06X}-{:06X}) and IRAM ({:06X}-{:06X})",
+                self.address, ROM.0, ROM.1, DRAM.0, DRAM.1, IRAM.0, IRAM.1
+            ));
+        };
+
+        match (name, self.write) {
+            ("ROM", true) => return Err("ROM can't be written".to_string()),
+            ("ROM", false) if self.increment != 1 => {
+                return Err("ROM can only be read with inc=1".to_string())
+            }
+            ("IRAM", false) => return Err("IRAM can't be read through PM registers".to_string()),
+            _ => (),
+        }
+        if (self.overwrite || self.cell) && !(name == "DRAM" && self.write) {
+            return Err("overwrite and cell only apply to DRAM writes".to_string());
+        }
+        if self.cell && self.increment != 0 {
+            return Err("cell already sets the increment".to_string());
+        }
+
+        let address = self.programmed_address();
+        if !within(address, region) {
+            return Err(format!(
+                "Address {:06X} minus the increment is outside {}, use exact",
+                self.address, name
+            ));
+        }
+
+        let mut mode = (address >> 16) as u16 & 0x7F | code << 11;
+        if self.increment < 0 {
+            mode |= DECREMENT;
+        }
+        if self.overwrite {
+            mode |= OVERWRITE;
+        }
+        if self.cell {
+            mode |= CELL;
+        }
+        Ok((address as u16, mode))
+    }
+
+    // Programs the adapter and then selects the register with a blind access:
+    pub fn instructions(&self) -> Result<String, String> {
+        let (address, mode) = self.words()?;
+        let access = if self.write {
+            format!("ld ext{}, -", self.register)
+        } else {
+            format!("ld -, ext{}", self.register)
+        };
+        Ok(format!(
+            "ld ext6, {:04X} ld ext6, {:04X} {}",
+            address, mode, access
+        ))
+    }
+}
+
+// Hexadecimal numbers, falling back to imported symbols (always for `@name`):
+fn address_of(value: &str, imported: &[ImportedSymbol]) -> Result<u32, String> {
+    let name = value.trim_start_matches('@');
+    let symbol = imported
+        .iter()
+        .find(|symbol| symbol.name.eq_ignore_ascii_case(name));
+
+    match symbol {
+        Some(symbol) if value.starts_with('@') || data::number(value).is_err() => Ok(symbol.value),
+        None if value.starts_with('@') => Err(format!("Undefined imported symbol: {}", name)),
+        _ => data::number(value),
+    }
+}
+
+fn within(address: u32, (start, end): (u32, u32)) -> bool {
+    (start..=end).contains(&address)
+}
+
+pub fn pmset_line(line: &str) -> Option<data::RawLine<'_>> {
+    data::raw_line(line).filter(|raw| raw.keyword.eq_ignore_ascii_case("pmset"))
+}
+
+// Replaces `PMSET` directives with the instructions that program the PM register, on the same
+// line. Errors are returned along with the (0-based) line each one was found in.
+pub fn expanded_source(
+    contents: &str,
+    imported: &[ImportedSymbol],
+) -> (String, Vec<(usize, String)>) {
+    let mut errors = Vec::new();
+    let mut output: Vec<String> = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let raw = match pmset_line(line) {
+            Some(raw) => raw,
+            None => {
+                output.push(line.to_string());
+                continue;
+            }
+        };
+        let prefix = line[..raw.column].trim_end();
+
+        match data::parse_arguments(raw.arguments)
+            .and_then(|arguments| PmSetting::parse(&arguments, imported))
+            .and_then(|setting| setting.instructions())
+        {
+            Ok(instructions) if prefix.is_empty() => output.push(instructions),
+            Ok(instructions) => output.push(format!("{} {}", prefix, instructions)),
+            Err(error) => {
+                errors.push((index, error));
+                output.push(prefix.to_string());
+            }
+        }
+    }
+
+    let mut source = output.join("\n");
+    if contents.ends_with('\n') {
+        source.push('\n');
+    }
+    (source, errors)
+}
+
+// Same as `expanded_source`, failing with all the errors found:
+pub fn expand(contents: &str, imported: &[ImportedSymbol]) -> Result<String, Box<dyn Error>> {
+    let (source, errors) = expanded_source(contents, imported);
+
+    if errors.is_empty() {
+        Ok(source)
+    } else {
+        Err(Box::new(errors::AssemblyError::from_lines(&errors)))
+    }
+}
+
+#[cfg(test)]
+mod pmset_tests {
+    use super::*;
+    use crate::import::m68k::ImportedKind;
+
+    fn setting(arguments: &str) -> Result<PmSetting, String> {
+        data::parse_arguments(arguments).and_then(|arguments| PmSetting::parse(&arguments, &[]))
+    }
+
+    #[test]
+    fn check_sample_sequence() {
+        let (source, errors) = expanded_source(
+            "start: PMSET pm4, write, 0x181000, inc=1 # DRAM\nret\n",
+            &[],
+        );
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            source,
+            "start: ld ext6, 0FFF ld ext6, 0818 ld ext4, -\nret\n"
+        );
+    }
+
+    #[test]
+    fn check_words() {
+        let words = |arguments| setting(arguments).and_then(|setting| setting.words());
+
+        assert_eq!(words("pm0, read, 012345, inc=1"), Ok((0x2345, 0x0801)));
+        assert_eq!(words("pm1, read, 180000"), Ok((0x0000, 0x0018)));
+        assert_eq!(words("pm2, write, 180100, inc=-20"), Ok((0x0120, 0xB018)));
+        assert_eq!(
+            words("pm4, write, 180100, inc=80, exact"),
+            Ok((0x0100, 0x3818))
+        );
+        assert_eq!(words("pm4, write, 180100, overwrite"), Ok((0x0100, 0x0418)));
+        assert_eq!(words("pm4, write, 180100, cell"), Ok((0x0100, 0x4018)));
+        assert_eq!(words("pm4, write, 1C8000"), Ok((0x8000, 0x001C)));
+    }
+
+    #[test]
+    fn check_invalid_settings() {
+        let words = |arguments| setting(arguments).and_then(|setting| setting.words());
+
+        assert_eq!(
+            words("pm5, write, 180000"),
+            Err("Expected pm0 to pm4, found pm5".to_string())
+        );
+        assert_eq!(
+            words("pm4, write, 100000"),
+            Err("Address 100000 is outside ROM (000000-0FFFFF), DRAM (180000-18FFFF) and IRAM (1C8000-1C83FF)".to_string())
+        );
+        assert_eq!(
+            words("pm4, write, 001000, inc=1"),
+            Err("ROM can't be written".to_string())
+        );
+        assert_eq!(
+            words("pm4, read, 001000, inc=2"),
+            Err("ROM can only be read with inc=1".to_string())
+        );
+        assert_eq!(
+            words("pm4, write, 180000, inc=1"),
+            Err("Address 180000 minus the increment is outside DRAM, use exact".to_string())
+        );
+        assert_eq!(
+            words("pm4, write, 180000, inc=3"),
+            Err("Invalid increment: 3".to_string())
+        );
+        assert_eq!(
+            words("pm4, read, 180000, cell"),
+            Err("overwrite and cell only apply to DRAM writes".to_string())
+        );
+        assert_eq!(
+            words("pm4, write, 180000, step=1"),
+            Err("Unknown PMSET option: step=1".to_string())
+        );
+    }
+
+    #[test]
+    fn check_imported_addresses() {
+        let imported = [ImportedSymbol {
+            name: "dramTilesOrigin".to_string(),
+            value: 0x181000,
+            kind: ImportedKind::Label,
+        }];
+        let words = |arguments| {
+            data::parse_arguments(arguments)
+                .and_then(|arguments| PmSetting::parse(&arguments, &imported))
+                .and_then(|setting| setting.words())
+        };
+
+        assert_eq!(
+            words("pm4, write, dramtilesorigin, inc=1"),
+            Ok((0x0FFF, 0x0818))
+        );
+        assert_eq!(words("pm4, read, @dramTilesOrigin"), Ok((0x1000, 0x0018)));
+        assert_eq!(
+            words("pm4, read, @missing"),
+            Err("Undefined imported symbol: missing".to_string())
+        );
+    }
+
+    #[test]
+    fn check_read_sequence() {
+        assert_eq!(
+            setting("pm2, read, 180010, inc=2").and_then(|setting| setting.instructions()),
+            Ok("ld ext6, 0010 ld ext6, 1018 ld -, ext2".to_string())
+        );
+    }
+}
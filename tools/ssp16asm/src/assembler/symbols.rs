@@ -0,0 +1,147 @@
+use crate::asm::macros;
+use crate::asm::operators;
+use crate::tokenization::tokens;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Label,
+    Equ,
+    Equb,
+}
+
+// Everything we know about a symbol once tables have been extracted: where it was defined
+// and which section (the address set by the last `ORG` before it) it belongs to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Symbol {
+    pub name: String,
+    pub value: u16,
+    pub kind: SymbolKind,
+    pub file: String,
+    pub line: usize,
+    pub section: Option<u16>,
+}
+
+pub fn collect_symbols<'a>(
+    tokens: &[tokens::Token<'a>],
+    lines: &[usize],
+    filename: &str,
+    symbols: &HashMap<&'a str, u16>,
+    equs: &HashMap<&'a str, u16>,
+    equbs: &HashMap<&'a str, u8>,
+) -> Vec<Symbol> {
+    let mut section: Option<u16> = None;
+    let mut result: Vec<Symbol> = Vec::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        match (token, tokens.get(index + 1)) {
+            (
+                tokens::Token::Macro(macros::SspMacro::Org),
+                Some(tokens::Token::Operator(operators::SspOperator::Word(value))),
+            ) => section = Some(*value),
+
+            (tokens::Token::Label(label), _) => {
+                let definition = if let Some(value) = equs.get(label) {
+                    Some((*value, SymbolKind::Equ))
+                } else if let Some(value) = equbs.get(label) {
+                    Some((*value as u16, SymbolKind::Equb))
+                } else {
+                    symbols.get(label).map(|value| (*value, SymbolKind::Label))
+                };
+
+                if let Some((value, kind)) = definition {
+                    result.push(Symbol {
+                        name: label.to_string(),
+                        value,
+                        kind,
+                        file: filename.to_string(),
+                        line: lines.get(index).copied().unwrap_or(0),
+                        section,
+                    });
+                }
+            }
+
+            _ => (),
+        }
+    }
+
+    result.sort_by(|a, b| a.value.cmp(&b.value).then_with(|| a.name.cmp(&b.name)));
+    result
+}
+
+// Names of the symbols selected with the `EXPORT` directive (i.e.: `export @label`), in source order:
+pub fn collect_exports<'a>(tokens: &[tokens::Token<'a>]) -> Vec<&'a str> {
+    tokens
+        .windows(2)
+        .filter_map(|pair| match (pair[0], pair[1]) {
+            (
+                tokens::Token::Macro(macros::SspMacro::Export),
+                tokens::Token::Operator(operators::SspOperator::LabelRef(label)),
+            ) => Some(label),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod symbols_tests {
+    use super::*;
+    use crate::assembler::assembly;
+
+    #[test]
+    fn check_collected_symbols() {
+        let source = "const: EQU 0x1234\nbyte: EQUB 12\norg 400\nstart: ld a, x\n\nend: ret\n";
+        let (tokens, lines) = tokens::tokenize_with_lines(source).unwrap();
+        let (symbols, equs, equbs) = assembly::extract_tables(&tokens);
+
+        let collected = collect_symbols(&tokens, &lines, "test.svp", &symbols, &equs, &equbs);
+
+        assert_eq!(
+            collected,
+            vec![
+                Symbol {
+                    name: "byte".to_string(),
+                    value: 0x12,
+                    kind: SymbolKind::Equb,
+                    file: "test.svp".to_string(),
+                    line: 2,
+                    section: None,
+                },
+                Symbol {
+                    name: "start".to_string(),
+                    value: 0x400,
+                    kind: SymbolKind::Label,
+                    file: "test.svp".to_string(),
+                    line: 4,
+                    section: Some(0x400),
+                },
+                Symbol {
+                    name: "end".to_string(),
+                    value: 0x401,
+                    kind: SymbolKind::Label,
+                    file: "test.svp".to_string(),
+                    line: 6,
+                    section: Some(0x400),
+                },
+                Symbol {
+                    name: "const".to_string(),
+                    value: 0x1234,
+                    kind: SymbolKind::Equ,
+                    file: "test.svp".to_string(),
+                    line: 1,
+                    section: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn check_collected_exports() {
+        let source = "export @start\nvalue: EQU 0x1234\nexport @value\norg 400\nstart: ld a, x\n";
+        let (tokens, _) = tokens::tokenize_with_lines(source).unwrap();
+
+        assert_eq!(collect_exports(&tokens), vec!["start", "value"]);
+    }
+}
@@ -0,0 +1,22 @@
+use clap::{load_yaml, App};
+use ssp16asm::{DebuggerConfig, ProgramConfig};
+use std::process;
+
+fn main() {
+    let yaml = load_yaml!("ssp16dbg.yml");
+    let matches = App::from_yaml(yaml).get_matches();
+
+    let config = DebuggerConfig {
+        program: ProgramConfig::new_from_matches(&matches),
+        entry: matches.value_of("entry").map(|e| e.to_string()),
+        history: matches
+            .value_of("history")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(ssp16_sim::history::DEFAULT_LIMIT),
+    };
+
+    if let Err(e) = ssp16asm::debugger(config) {
+        eprintln!("Application error(s): \n\n{}", e);
+        process::exit(1);
+    }
+}
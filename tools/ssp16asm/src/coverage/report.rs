@@ -0,0 +1,272 @@
+use ssp16_isa::instructions::Instruction;
+use ssp16_sim::coverage::{self, Branch, Coverage};
+use ssp16_sim::debug::DebugInfo;
+use ssp16_sim::memory::Memory;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+// Times a source line ran (its most run instruction, if there are several) and the outcomes of its
+// conditional instructions:
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LineCoverage {
+    pub hits: u64,
+    pub branches: Vec<Branch>,
+}
+
+// Coverage of the lines with instructions in each source file (lines are 1-based):
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceCoverage {
+    pub files: BTreeMap<String, BTreeMap<usize, LineCoverage>>,
+}
+
+impl SourceCoverage {
+    // Maps the addresses run to the lines they were assembled from. Code run from other addresses
+    // (i.e.: copied to IRAM) isn't counted.
+    pub fn new(coverage: &Coverage, debug: &DebugInfo, memory: &Memory) -> SourceCoverage {
+        let mut files: BTreeMap<String, BTreeMap<usize, LineCoverage>> = BTreeMap::new();
+
+        for (address, source) in debug.lines.iter() {
+            let line = files
+                .entry(source.file.clone())
+                .or_default()
+                .entry(source.line)
+                .or_default();
+            line.hits = line
+                .hits
+                .max(coverage.hits.get(address).copied().unwrap_or(0));
+
+            let instruction = Instruction::decode(
+                memory.program(*address),
+                memory.program(address.wrapping_add(1)),
+            );
+            if instruction.and_then(|i| coverage::condition(&i)).is_some() {
+                line.branches
+                    .push(coverage.branches.get(address).copied().unwrap_or_default());
+            }
+        }
+
+        SourceCoverage { files }
+    }
+
+    pub fn counts(&self) -> Counts {
+        Counts::of(self.files.values().flat_map(|lines| lines.values()))
+    }
+
+    pub fn summary(&self) -> String {
+        let counts = self.counts();
+        format!(
+            "{}/{} lines ({}), {}/{} branches ({})",
+            counts.lines_hit,
+            counts.lines,
+            percentage(counts.lines_hit, counts.lines),
+            counts.branches_hit,
+            counts.branches,
+            percentage(counts.branches_hit, counts.branches)
+        )
+    }
+
+    // Tracefile for lcov tools (i.e.: genhtml), with branch 0 of each block being the condition
+    // found true and branch 1 false:
+    pub fn lcov(&self) -> String {
+        let mut lcov = String::new();
+
+        for (file, lines) in self.files.iter() {
+            writeln!(lcov, "TN:\nSF:{}", file).unwrap();
+            for (number, line) in lines.iter() {
+                for (block, branch) in line.branches.iter().enumerate() {
+                    for (index, count) in [branch.taken, branch.not_taken].iter().enumerate() {
+                        if line.hits == 0 {
+                            writeln!(lcov, "BRDA:{},{},{},-", number, block, index).unwrap();
+                        } else {
+                            writeln!(lcov, "BRDA:{},{},{},{}", number, block, index, count)
+                                .unwrap();
+                        }
+                    }
+                }
+            }
+            let counts = Counts::of(lines.values());
+            writeln!(lcov, "BRF:{}\nBRH:{}", counts.branches, counts.branches_hit).unwrap();
+            for (number, line) in lines.iter() {
+                writeln!(lcov, "DA:{},{}", number, line.hits).unwrap();
+            }
+            writeln!(
+                lcov,
+                "LF:{}\nLH:{}\nend_of_record",
+                counts.lines, counts.lines_hit
+            )
+            .unwrap();
+        }
+
+        lcov
+    }
+
+    // `source` (the contents of `file`) with the times each line ran before it, like gcov does:
+    // `-` for lines with no instructions and `#####` for those never run. Conditional instructions
+    // are followed by the times they found their condition true and false, marked with `!` unless
+    // both happened.
+    pub fn annotated(&self, file: &str, source: &str) -> String {
+        let empty = BTreeMap::new();
+        let lines = self.files.get(file).unwrap_or(&empty);
+        let mut report = String::new();
+
+        for (index, text) in source.lines().enumerate() {
+            let line = lines.get(&(index + 1));
+            let count = match line {
+                None => "-".to_string(),
+                Some(line) if line.hits == 0 => "#####".to_string(),
+                Some(line) => line.hits.to_string(),
+            };
+            writeln!(report, "{:>9}:{:>5}:{}", count, index + 1, text).unwrap();
+
+            for branch in line.iter().flat_map(|line| line.branches.iter()) {
+                let mark = if branch.taken > 0 && branch.not_taken > 0 {
+                    " "
+                } else {
+                    "!"
+                };
+                writeln!(
+                    report,
+                    "{:>9}:{:>5}: taken {}, not taken {}",
+                    mark, "", branch.taken, branch.not_taken
+                )
+                .unwrap();
+            }
+        }
+
+        report
+    }
+}
+
+// Lines run and lines with instructions, and branch outcomes seen and possible (two per
+// conditional instruction):
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Counts {
+    pub lines_hit: usize,
+    pub lines: usize,
+    pub branches_hit: usize,
+    pub branches: usize,
+}
+
+impl Counts {
+    fn of<'a>(lines: impl Iterator<Item = &'a LineCoverage>) -> Counts {
+        let mut counts = Counts::default();
+        for line in lines {
+            counts.lines_hit += (line.hits > 0) as usize;
+            counts.lines += 1;
+            for branch in line.branches.iter() {
+                counts.branches_hit +=
+                    (branch.taken > 0) as usize + (branch.not_taken > 0) as usize;
+                counts.branches += 2;
+            }
+        }
+        counts
+    }
+}
+
+fn percentage(count: usize, total: usize) -> String {
+    match total {
+        0 => "-".to_string(),
+        total => format!("{:.2}%", count as f64 * 100.0 / total as f64),
+    }
+}
+
+#[cfg(test)]
+mod report_tests {
+    use super::*;
+    use ssp16_sim::cpu::Ssp1601;
+    use ssp16_sim::debug::SourceLine;
+
+    const SOURCE: &str = "org 400\nstart:\n\tld a, 0001\n\tbra z=1, @never\n\tmod n=1, neg\n\
+                          \tret\nnever:\n\tld x, 0001\n\tret\n";
+
+    // Coverage of SOURCE after calling start:
+    fn source_coverage() -> SourceCoverage {
+        let mut rom = vec![0; 0x400];
+        let mut debug = DebugInfo::default();
+        let code = [
+            (3, "ld a, 0001"),
+            (4, "bra z=1, 0406"),
+            (5, "mod n=1, neg"),
+            (6, "ret"),
+            (8, "ld x, 0001"),
+            (9, "ret"),
+        ];
+        for (line, text) in code.iter() {
+            debug.lines.insert(
+                rom.len() as u16,
+                SourceLine {
+                    file: "main.svp".to_string(),
+                    line: *line,
+                    text: text.to_string(),
+                },
+            );
+            rom.extend(text.parse::<Instruction>().unwrap().encode().unwrap());
+        }
+        let memory = Memory::new(rom);
+        let mut ssp = Ssp1601::new(memory.clone());
+        let mut coverage = Coverage::default();
+        ssp.call_with(0x400, 100, |ssp| coverage.step(ssp)).unwrap();
+
+        SourceCoverage::new(&coverage, &debug, &memory)
+    }
+
+    #[test]
+    fn check_counts() {
+        let coverage = source_coverage();
+
+        assert_eq!(
+            coverage.counts(),
+            Counts {
+                lines_hit: 4,
+                lines: 6,
+                branches_hit: 2,
+                branches: 4
+            }
+        );
+        assert_eq!(
+            coverage.summary(),
+            "4/6 lines (66.67%), 2/4 branches (50.00%)"
+        );
+        assert_eq!(
+            SourceCoverage::default().summary(),
+            "0/0 lines (-), 0/0 branches (-)"
+        );
+    }
+
+    #[test]
+    fn check_lcov() {
+        assert_eq!(
+            source_coverage().lcov(),
+            "TN:\nSF:main.svp\nBRDA:4,0,0,0\nBRDA:4,0,1,1\nBRDA:5,0,0,0\nBRDA:5,0,1,1\n\
+             BRF:4\nBRH:2\nDA:3,1\nDA:4,1\nDA:5,1\nDA:6,1\nDA:8,0\nDA:9,0\nLF:6\nLH:4\n\
+             end_of_record\n"
+        );
+    }
+
+    #[test]
+    fn check_annotated() {
+        let report = source_coverage().annotated("main.svp", SOURCE);
+        let lines: Vec<&str> = report.lines().collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                "        -:    1:org 400",
+                "        -:    2:start:",
+                "        1:    3:\tld a, 0001",
+                "        1:    4:\tbra z=1, @never",
+                "        !:     : taken 0, not taken 1",
+                "        1:    5:\tmod n=1, neg",
+                "        !:     : taken 0, not taken 1",
+                "        1:    6:\tret",
+                "        -:    7:never:",
+                "    #####:    8:\tld x, 0001",
+                "    #####:    9:\tret",
+            ]
+        );
+        assert_eq!(
+            source_coverage().annotated("other.svp", "ret\n"),
+            "        -:    1:ret\n"
+        );
+    }
+}
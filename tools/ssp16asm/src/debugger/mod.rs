@@ -0,0 +1,3 @@
+pub mod session;
+pub mod tui;
+pub mod views;
@@ -0,0 +1,235 @@
+use crate::asm::mnemonics::SspMnemonic;
+use ssp16_sim::cpu::Ssp1601;
+use ssp16_sim::debug::DebugInfo;
+use ssp16_sim::history::History;
+use std::collections::BTreeSet;
+
+// Instructions a run goes through before giving control back (i.e.: code waiting for the 68000),
+// unless changed:
+pub const RUN_LIMIT: u64 = 10_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stop {
+    Stepped,
+    Breakpoint,
+    Limit,
+    Error(String),
+    // Nothing left to reverse
+    HistoryStart,
+}
+
+impl Stop {
+    pub fn message(&self, session: &Session) -> String {
+        let location = session.debug.describe(session.ssp.pc);
+        match self {
+            Stop::Stepped => format!("Stopped at {}", location),
+            Stop::Breakpoint => format!("Breakpoint at {}", location),
+            Stop::Limit => format!(
+                "Stopped at {} after {} instructions",
+                location, session.run_limit
+            ),
+            Stop::Error(error) => error.clone(),
+            Stop::HistoryStart => "No more history to go back in".to_string(),
+        }
+    }
+}
+
+// A program running on the simulator under the debugger. Every instruction goes through the
+// history, so they can be undone.
+pub struct Session {
+    pub ssp: Ssp1601,
+    pub debug: DebugInfo,
+    pub breakpoints: BTreeSet<u16>,
+    pub history: History,
+    pub run_limit: u64,
+    // State `reset` goes back to
+    initial: Ssp1601,
+}
+
+impl Session {
+    pub fn new(ssp: Ssp1601, debug: DebugInfo, history_limit: usize) -> Session {
+        Session {
+            initial: ssp.clone(),
+            ssp,
+            debug,
+            breakpoints: BTreeSet::new(),
+            history: History::new(history_limit),
+            run_limit: RUN_LIMIT,
+        }
+    }
+
+    pub fn step(&mut self) -> Stop {
+        match self.history.step(&mut self.ssp) {
+            Ok(()) => Stop::Stepped,
+            Err(error) => Stop::Error(error.0),
+        }
+    }
+
+    // Runs a whole routine when stopped at a `CALL` (taken or not), a single instruction otherwise:
+    pub fn step_over(&mut self) -> Stop {
+        let pc = self.ssp.pc;
+        let next = match self.ssp.instruction(pc) {
+            Some(instruction) => match instruction.operands() {
+                Some((SspMnemonic::Call, _)) => pc.wrapping_add(instruction.size().unwrap_or(2)),
+                _ => return self.step(),
+            },
+            None => return self.step(),
+        };
+        let depth = self.ssp.stack.len();
+
+        self.run_until(|ssp| ssp.pc == next && ssp.stack.len() <= depth)
+    }
+
+    // Runs until a breakpoint:
+    pub fn run(&mut self) -> Stop {
+        self.run_until(|_| false)
+    }
+
+    // Runs until reaching `address` (or a breakpoint before that):
+    pub fn run_to(&mut self, address: u16) -> Stop {
+        self.run_until(|ssp| ssp.pc == address)
+    }
+
+    pub fn reverse_step(&mut self) -> Stop {
+        if self.history.back(&mut self.ssp) {
+            Stop::Stepped
+        } else {
+            Stop::HistoryStart
+        }
+    }
+
+    // Sets or clears a breakpoint at a label (or line), returning its address and whether it's set:
+    pub fn toggle_breakpoint(&mut self, location: &str) -> Result<(u16, bool), String> {
+        let address = self
+            .debug
+            .find(location)
+            .ok_or_else(|| format!("No code found for {}", location))?;
+
+        if self.breakpoints.remove(&address) {
+            Ok((address, false))
+        } else {
+            self.breakpoints.insert(address);
+            Ok((address, true))
+        }
+    }
+
+    // Back to the state the program started in (breakpoints are kept):
+    pub fn reset(&mut self) {
+        self.ssp = self.initial.clone();
+        self.history.clear();
+    }
+
+    // Runs at least one instruction, until `done`, a breakpoint, an error or the run limit:
+    fn run_until(&mut self, done: impl Fn(&Ssp1601) -> bool) -> Stop {
+        for _ in 0..self.run_limit {
+            if let Err(error) = self.history.step(&mut self.ssp) {
+                return Stop::Error(error.0);
+            }
+            if done(&self.ssp) {
+                return Stop::Stepped;
+            }
+            if self.breakpoints.contains(&self.ssp.pc) {
+                return Stop::Breakpoint;
+            }
+        }
+        Stop::Limit
+    }
+}
+
+#[cfg(test)]
+mod session_tests {
+    use super::*;
+    use ssp16_isa::instructions::Instruction;
+    use ssp16_sim::memory::Memory;
+
+    // Session with `code` at 0x400 and labels in it:
+    fn session(code: &[&str], labels: &[(&str, u16)]) -> Session {
+        let mut rom = vec![0; 0x400];
+        for line in code {
+            rom.extend(line.parse::<Instruction>().unwrap().encode().unwrap());
+        }
+        let mut debug = DebugInfo::default();
+        for (name, address) in labels {
+            debug.labels.insert(name.to_string(), *address);
+        }
+
+        Session::new(Ssp1601::new(Memory::new(rom)), debug, 100)
+    }
+
+    #[test]
+    fn check_steps() {
+        // main: x = 3, calls double twice. double: a = x * 2
+        let mut session = session(
+            &[
+                "ld x, 0003",
+                "call always, 0407",
+                "call always, 0407",
+                "ret",
+                "ld a, x",
+                "add a, x",
+                "ret",
+            ],
+            &[("main", 0x400), ("double", 0x407)],
+        );
+
+        assert_eq!(session.step(), Stop::Stepped);
+        assert_eq!(session.step_over(), Stop::Stepped);
+        assert_eq!((session.ssp.pc, session.ssp.a), (0x404, 0x0006_0000));
+        assert_eq!(session.step(), Stop::Stepped);
+        assert_eq!(session.ssp.pc, 0x407);
+        assert_eq!(session.step_over(), Stop::Stepped);
+        assert_eq!(session.ssp.pc, 0x408);
+
+        for _ in 0..6 {
+            assert_eq!(session.reverse_step(), Stop::Stepped);
+        }
+        assert_eq!((session.ssp.pc, session.ssp.a), (0x402, 0));
+        assert!(session.ssp.stack.is_empty());
+
+        assert_eq!(session.toggle_breakpoint("@double"), Ok((0x407, true)));
+        assert_eq!(session.run(), Stop::Breakpoint);
+        assert_eq!(session.ssp.pc, 0x407);
+        // Breakpoints inside the routine stop stepping over it
+        assert_eq!(session.reverse_step(), Stop::Stepped);
+        assert_eq!(session.step_over(), Stop::Breakpoint);
+        assert_eq!(session.toggle_breakpoint("double"), Ok((0x407, false)));
+
+        assert_eq!(
+            session.toggle_breakpoint("missing"),
+            Err("No code found for missing".to_string())
+        );
+        assert_eq!(
+            session.run(),
+            Stop::Error("Stack underflow at 0406".to_string())
+        );
+        assert_eq!(
+            Stop::Error("Stack underflow at 0406".to_string()).message(&session),
+            "Stack underflow at 0406"
+        );
+
+        session.reset();
+        assert_eq!(session.run_to(0x408), Stop::Stepped);
+        assert_eq!((session.ssp.pc, session.ssp.steps), (0x408, 3));
+        assert_eq!(session.history.len(), 3);
+        assert_eq!(Stop::Breakpoint.message(&session), "Breakpoint at double+1");
+    }
+
+    #[test]
+    fn check_limits() {
+        let mut session = session(&["bra always, 0400"], &[]);
+        session.run_limit = 1000;
+
+        assert_eq!(session.run(), Stop::Limit);
+        assert_eq!(session.ssp.steps, 1000);
+        assert_eq!(
+            Stop::Limit.message(&session),
+            "Stopped at 0400 after 1000 instructions"
+        );
+        assert_eq!(session.history.len(), 100);
+
+        for _ in 0..100 {
+            assert_eq!(session.reverse_step(), Stop::Stepped);
+        }
+        assert_eq!(session.reverse_step(), Stop::HistoryStart);
+    }
+}
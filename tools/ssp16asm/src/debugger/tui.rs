@@ -0,0 +1,265 @@
+use super::session::Session;
+use super::views;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::Print;
+use crossterm::{cursor, execute, queue, terminal};
+use std::io::{self, Write};
+
+const HELP: &str =
+    "s step  n next  c continue  r back  g run to  b break  m bank  PgUp/PgDn RAM  R reset  q quit";
+// Columns of the registers and memory panel (the source takes the rest):
+const PANEL_WIDTH: u16 = 48;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Prompt {
+    Breakpoint,
+    RunTo,
+}
+
+pub struct Debugger {
+    session: Session,
+    file: String,
+    source: Vec<String>,
+    bank: usize,
+    ram_offset: usize,
+    status: String,
+    prompt: Option<(Prompt, String)>,
+}
+
+impl Debugger {
+    // `source` holds the lines of `file`, the file the program was assembled from:
+    pub fn new(session: Session, file: &str, source: Vec<String>) -> Debugger {
+        let status = format!("Stopped at {}", session.debug.describe(session.ssp.pc));
+        Debugger {
+            session,
+            file: file.to_string(),
+            source,
+            bank: 0,
+            ram_offset: 0,
+            status,
+            prompt: None,
+        }
+    }
+
+    // Handles a key, returning false once the user quits:
+    fn key(&mut self, code: KeyCode) -> bool {
+        if let Some((prompt, mut input)) = self.prompt.take() {
+            match code {
+                KeyCode::Enter => self.answer(prompt, input.trim()),
+                KeyCode::Esc => self.status = String::new(),
+                KeyCode::Backspace => {
+                    input.pop();
+                    self.prompt = Some((prompt, input));
+                }
+                KeyCode::Char(c) => {
+                    input.push(c);
+                    self.prompt = Some((prompt, input));
+                }
+                _ => self.prompt = Some((prompt, input)),
+            }
+            return true;
+        }
+
+        let stop = match code {
+            KeyCode::Char('s') => self.session.step(),
+            KeyCode::Char('n') => self.session.step_over(),
+            KeyCode::Char('c') => self.session.run(),
+            KeyCode::Char('r') => self.session.reverse_step(),
+            KeyCode::Char('b') => {
+                self.prompt = Some((Prompt::Breakpoint, String::new()));
+                return true;
+            }
+            KeyCode::Char('g') => {
+                self.prompt = Some((Prompt::RunTo, String::new()));
+                return true;
+            }
+            KeyCode::Char('m') => {
+                self.bank = 1 - self.bank;
+                return true;
+            }
+            KeyCode::PageDown => {
+                self.ram_offset = (self.ram_offset + 0x40).min(0xC0);
+                return true;
+            }
+            KeyCode::PageUp => {
+                self.ram_offset = self.ram_offset.saturating_sub(0x40);
+                return true;
+            }
+            KeyCode::Char('R') => {
+                self.session.reset();
+                self.status = format!(
+                    "Reset, stopped at {}",
+                    self.session.debug.describe(self.session.ssp.pc)
+                );
+                return true;
+            }
+            KeyCode::Char('q') => return false,
+            _ => return true,
+        };
+        self.status = stop.message(&self.session);
+        true
+    }
+
+    fn answer(&mut self, prompt: Prompt, input: &str) {
+        self.status = match prompt {
+            Prompt::Breakpoint => match self.session.toggle_breakpoint(input) {
+                Ok((address, true)) => format!("Breakpoint set at {:04X} ({})", address, input),
+                Ok((address, false)) => {
+                    format!("Breakpoint cleared at {:04X} ({})", address, input)
+                }
+                Err(error) => error,
+            },
+            Prompt::RunTo => match self.session.debug.find(input) {
+                Some(address) => self.session.run_to(address).message(&self.session),
+                None => format!("No code found for {}", input),
+            },
+        };
+    }
+
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let panel = PANEL_WIDTH.min(width / 2);
+        let main = width.saturating_sub(panel + 1) as usize;
+        let rows = height.saturating_sub(2) as usize;
+
+        let mut side = views::registers(&self.session);
+        side.push(String::new());
+        side.extend(views::stack(&self.session));
+        side.push(String::new());
+        side.extend(views::pm_registers(&self.session.ssp));
+        side.push(String::new());
+        let ram_rows = rows.saturating_sub(side.len());
+        side.extend(views::ram(
+            &self.session.ssp,
+            self.bank,
+            self.ram_offset,
+            ram_rows,
+        ));
+
+        queue!(out, terminal::Clear(terminal::ClearType::All))?;
+        let source = views::source(&self.session, &self.file, &self.source, rows);
+        for (row, line) in source.iter().enumerate() {
+            queue!(out, cursor::MoveTo(0, row as u16), Print(fit(line, main)))?;
+        }
+        for (row, line) in side.iter().take(rows).enumerate() {
+            queue!(
+                out,
+                cursor::MoveTo(main as u16 + 1, row as u16),
+                Print(fit(line, panel as usize))
+            )?;
+        }
+
+        let status = match self.prompt {
+            Some((Prompt::Breakpoint, ref input)) => {
+                format!("Breakpoint at (label or line): {}", input)
+            }
+            Some((Prompt::RunTo, ref input)) => format!("Run to (label or line): {}", input),
+            None => self.status.clone(),
+        };
+        queue!(
+            out,
+            cursor::MoveTo(0, rows as u16),
+            Print(fit(&status, width as usize)),
+            cursor::MoveTo(0, rows as u16 + 1),
+            Print(fit(HELP, width as usize))
+        )?;
+        out.flush()
+    }
+}
+
+fn fit(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+// Raw mode and the alternate screen, left even when returning with an error:
+struct Screen;
+
+impl Screen {
+    fn new() -> io::Result<Screen> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+pub fn run(mut debugger: Debugger) -> io::Result<()> {
+    let _screen = Screen::new()?;
+    let mut out = io::stdout();
+
+    loop {
+        debugger.draw(&mut out)?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !debugger.key(key.code) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tui_tests {
+    use super::*;
+    use ssp16_isa::instructions::Instruction;
+    use ssp16_sim::cpu::Ssp1601;
+    use ssp16_sim::debug::DebugInfo;
+    use ssp16_sim::memory::Memory;
+
+    fn debugger() -> Debugger {
+        let mut rom = vec![0; 0x400];
+        for line in &["ld x, 0001", "ld y, 0002", "ret"] {
+            rom.extend(line.parse::<Instruction>().unwrap().encode().unwrap());
+        }
+        let mut debug = DebugInfo::default();
+        debug.labels.insert("start".to_string(), 0x400);
+        debug.labels.insert("second".to_string(), 0x402);
+
+        Debugger::new(
+            Session::new(Ssp1601::new(Memory::new(rom)), debug, 10),
+            "main.svp",
+            vec![],
+        )
+    }
+
+    fn keys(debugger: &mut Debugger, keys: &str) {
+        for key in keys.chars() {
+            debugger.key(KeyCode::Char(key));
+        }
+    }
+
+    #[test]
+    fn check_keys() {
+        let mut debugger = debugger();
+        assert_eq!(debugger.status, "Stopped at start");
+
+        keys(&mut debugger, "bsecondd");
+        debugger.key(KeyCode::Backspace);
+        debugger.key(KeyCode::Enter);
+        assert_eq!(debugger.status, "Breakpoint set at 0402 (second)");
+        assert!(debugger.session.breakpoints.contains(&0x402));
+
+        keys(&mut debugger, "c");
+        assert_eq!(debugger.status, "Breakpoint at second");
+        keys(&mut debugger, "r");
+        assert_eq!(debugger.session.ssp.pc, 0x400);
+
+        keys(&mut debugger, "gmissing");
+        debugger.key(KeyCode::Enter);
+        assert_eq!(debugger.status, "No code found for missing");
+
+        keys(&mut debugger, "mm");
+        assert_eq!(debugger.bank, 0);
+        debugger.key(KeyCode::PageDown);
+        assert_eq!(debugger.ram_offset, 0x40);
+
+        keys(&mut debugger, "sR");
+        assert_eq!(debugger.session.ssp.steps, 0);
+        assert!(!debugger.key(KeyCode::Char('q')));
+    }
+}
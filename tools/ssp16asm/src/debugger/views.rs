@@ -0,0 +1,262 @@
+use super::session::Session;
+use ssp16_sim::cpu::{self, Ssp1601};
+use ssp16_sim::memory;
+
+// Words per line in RAM dumps:
+pub const RAM_WORDS_PER_LINE: usize = 8;
+
+// `rows` lines of `source` (the lines of `file`) around the current one, marked with `=>`
+// (breakpoints with `*`). Code with no source line (i.e.: copied to IRAM) is disassembled instead.
+pub fn source(session: &Session, file: &str, source: &[String], rows: usize) -> Vec<String> {
+    let pc = session.ssp.pc;
+    let current = match session.debug.line(pc) {
+        Some(line) if line.file == file => line.line,
+        _ => return disassembly(session, rows),
+    };
+    let breakpoint_lines: Vec<usize> = session
+        .breakpoints
+        .iter()
+        .filter_map(|address| session.debug.line(*address))
+        .filter(|line| line.file == file)
+        .map(|line| line.line)
+        .collect();
+
+    let first = current.saturating_sub(rows / 2).max(1);
+    (first..first + rows)
+        .take_while(|line| *line <= source.len())
+        .map(|line| {
+            format!(
+                "{}{}{:5} {}",
+                if line == current { "=>" } else { "  " },
+                if breakpoint_lines.contains(&line) {
+                    "*"
+                } else {
+                    " "
+                },
+                line,
+                source[line - 1].replace('\t', "    ")
+            )
+        })
+        .collect()
+}
+
+// Instructions from the current one on, as decoded from program memory:
+pub fn disassembly(session: &Session, rows: usize) -> Vec<String> {
+    let mut address = session.ssp.pc;
+    let mut lines = Vec::new();
+
+    while lines.len() < rows {
+        let instruction = session.ssp.instruction(address);
+        lines.push(format!(
+            "{}{}{:04X}  {}",
+            if address == session.ssp.pc {
+                "=>"
+            } else {
+                "  "
+            },
+            if session.breakpoints.contains(&address) {
+                "*"
+            } else {
+                " "
+            },
+            address,
+            match instruction {
+                Some(ref instruction) => instruction.to_string(),
+                None => format!("dw {:04X}", session.ssp.memory.program(address)),
+            }
+        ));
+        address = address.wrapping_add(instruction.and_then(|i| i.size()).unwrap_or(1));
+    }
+
+    lines
+}
+
+pub fn registers(session: &Session) -> Vec<String> {
+    let ssp = &session.ssp;
+    let flag = |mask: u16| (ssp.st & mask != 0) as u8;
+
+    vec![
+        format!("PC {:04X} {}", ssp.pc, session.debug.describe(ssp.pc)),
+        format!("X  {:04X}      Y  {:04X}", ssp.x, ssp.y),
+        format!("A  {:08X}  P  {:08X}", ssp.a, ssp.p()),
+        format!(
+            "ST {:04X}  N{} V{} Z{} L{} OP{} IE{} PM{} RPL{}",
+            ssp.st,
+            flag(cpu::ST_N),
+            flag(cpu::ST_V),
+            flag(cpu::ST_Z),
+            flag(cpu::ST_L),
+            flag(cpu::ST_OP),
+            flag(cpu::ST_IE),
+            (ssp.st & cpu::ST_PM) >> 5,
+            ssp.st & cpu::ST_RPL
+        ),
+        pointers(ssp, 0..4),
+        pointers(ssp, 4..8),
+        format!(
+            "XST {:04X}  steps {}  history {}",
+            ssp.ext[3],
+            ssp.steps,
+            session.history.len()
+        ),
+    ]
+}
+
+fn pointers(ssp: &Ssp1601, range: std::ops::Range<usize>) -> String {
+    range
+        .map(|index| format!("R{} {:02X}", index, ssp.r[index]))
+        .collect::<Vec<String>>()
+        .join("  ")
+}
+
+// Return addresses, the most recent first:
+pub fn stack(session: &Session) -> Vec<String> {
+    let mut lines = vec![format!(
+        "Stack ({}/{})",
+        session.ssp.stack.len(),
+        cpu::STACK_SIZE
+    )];
+    lines.extend(
+        session
+            .ssp
+            .stack
+            .iter()
+            .rev()
+            .map(|address| format!("  {:04X} {}", address, session.debug.describe(*address))),
+    );
+    lines
+}
+
+// Addresses and modes programmed in PM0-PM4 for reading and writing:
+pub fn pm_registers(ssp: &Ssp1601) -> Vec<String> {
+    let mut lines = vec![format!("PMC {:08X}", ssp.pm.pmc)];
+    for index in 0..5 {
+        lines.push(format!(
+            "PM{} R {}",
+            index,
+            memory::describe_programmed(ssp.pm.read[index])
+        ));
+        lines.push(format!(
+            "    W {}",
+            memory::describe_programmed(ssp.pm.write[index])
+        ));
+    }
+    lines
+}
+
+// `rows` lines of a RAM bank from `offset` (a word offset):
+pub fn ram(ssp: &Ssp1601, bank: usize, offset: usize, rows: usize) -> Vec<String> {
+    let name = if bank == 0 { "A" } else { "B" };
+
+    (offset..ssp.ram[bank].len())
+        .step_by(RAM_WORDS_PER_LINE)
+        .take(rows)
+        .map(|start| {
+            let words: Vec<String> = ssp.ram[bank][start..start + RAM_WORDS_PER_LINE]
+                .iter()
+                .map(|word| format!("{:04X}", word))
+                .collect();
+            format!("{}[{:02X}] {}", name, start, words.join(" "))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod views_tests {
+    use super::*;
+    use ssp16_isa::instructions::Instruction;
+    use ssp16_sim::debug::{DebugInfo, SourceLine};
+    use ssp16_sim::memory::Memory;
+
+    // Session with each line of `code` in `main.svp`, from line 2 on (line 1 is a label):
+    fn session(code: &[&str]) -> (Session, Vec<String>) {
+        let mut rom = vec![0; 0x400];
+        let mut debug = DebugInfo::default();
+        let mut source = vec!["main:".to_string()];
+        for (line, text) in code.iter().enumerate() {
+            debug.lines.insert(
+                rom.len() as u16,
+                SourceLine {
+                    file: "main.svp".to_string(),
+                    line: line + 2,
+                    text: text.to_string(),
+                },
+            );
+            rom.extend(text.parse::<Instruction>().unwrap().encode().unwrap());
+            source.push(format!("\t{}", text));
+        }
+        debug.labels.insert("main".to_string(), 0x400);
+
+        (
+            Session::new(Ssp1601::new(Memory::new(rom)), debug, 10),
+            source,
+        )
+    }
+
+    #[test]
+    fn check_source() {
+        let (mut session, source) = session(&["ld x, 0001", "call always, 0400", "ret"]);
+        session.toggle_breakpoint("main.svp:4").unwrap();
+        session.step();
+
+        assert_eq!(
+            super::source(&session, "main.svp", &source, 3),
+            vec![
+                "       2     ld x, 0001",
+                "=>     3     call always, 0400",
+                "  *    4     ret",
+            ]
+        );
+        assert_eq!(super::source(&session, "main.svp", &source, 10).len(), 4);
+
+        assert_eq!(
+            super::source(&session, "other.svp", &source, 2),
+            vec!["=> 0402  call always, 0400", "  *0404  ret"]
+        );
+    }
+
+    #[test]
+    fn check_registers() {
+        let (mut session, _) = session(&["ld x, 0003", "ld y, FFFE", "call always, 0400"]);
+        session.ssp.st = cpu::ST_Z | cpu::ST_N | 0x0062;
+        session.ssp.r[5] = 0x80;
+        for _ in 0..3 {
+            session.step();
+        }
+
+        assert_eq!(
+            registers(&session),
+            vec![
+                "PC 0400 main",
+                "X  0003      Y  FFFE",
+                "A  00000000  P  FFFFFFF4",
+                "ST A062  N1 V0 Z1 L0 OP0 IE0 PM3 RPL2",
+                "R0 00  R1 00  R2 00  R3 00",
+                "R4 00  R5 80  R6 00  R7 00",
+                "XST 0000  steps 3  history 3",
+            ]
+        );
+        assert_eq!(stack(&session), vec!["Stack (1/6)", "  0406 main+6"]);
+    }
+
+    #[test]
+    fn check_memory() {
+        let (mut session, _) = session(&["ret"]);
+        session.ssp.pm.read[2] = 0x0818_0010;
+        session.ssp.ram[1][0x0A] = 0x1234;
+
+        let pm = pm_registers(&session.ssp);
+        assert_eq!(pm.len(), 11);
+        assert_eq!(pm[5], "PM2 R 180010 inc=1");
+        assert_eq!(pm[6], "    W 000000 inc=0");
+
+        assert_eq!(
+            ram(&session.ssp, 1, 0x08, 2),
+            vec![
+                "B[08] 0000 0000 1234 0000 0000 0000 0000 0000",
+                "B[10] 0000 0000 0000 0000 0000 0000 0000 0000",
+            ]
+        );
+        assert_eq!(ram(&session.ssp, 0, 0xF8, 4).len(), 1);
+    }
+}
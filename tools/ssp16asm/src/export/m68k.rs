@@ -0,0 +1,216 @@
+use crate::assembler::errors;
+use crate::assembler::symbols;
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ExportFormat {
+    Asm68k,
+    Vasm,
+    CHeader,
+}
+
+// A symbol as seen from the Mega Drive/Genesis side. Labels point to SVP program memory,
+// so their word addresses are converted to M68000 byte addresses. Constants are kept as they are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportedSymbol {
+    pub name: String,
+    pub value: u32,
+    pub kind: symbols::SymbolKind,
+}
+
+impl ExportedSymbol {
+    pub fn new(symbol: &symbols::Symbol) -> ExportedSymbol {
+        ExportedSymbol {
+            name: symbol.name.clone(),
+            value: match symbol.kind {
+                symbols::SymbolKind::Label => symbol.value as u32 * 2,
+                _ => symbol.value as u32,
+            },
+            kind: symbol.kind,
+        }
+    }
+
+    fn comment(&self) -> &str {
+        match self.kind {
+            symbols::SymbolKind::Label => "SVP program address (M68000 view)",
+            symbols::SymbolKind::Equ => "SVP word constant",
+            symbols::SymbolKind::Equb => "SVP byte constant",
+        }
+    }
+
+    fn hex_digits(&self) -> usize {
+        match self.kind {
+            symbols::SymbolKind::Label => 8,
+            symbols::SymbolKind::Equ => 4,
+            symbols::SymbolKind::Equb => 2,
+        }
+    }
+}
+
+pub fn exported_symbols(
+    exports: &[&str],
+    all_symbols: &[symbols::Symbol],
+) -> Result<Vec<ExportedSymbol>, Box<dyn Error>> {
+    let (exported, missing) = exports.iter().fold(
+        (Vec::new(), Vec::new()),
+        |(mut exported, mut missing), name| {
+            match all_symbols.iter().find(|symbol| symbol.name == *name) {
+                Some(symbol) => exported.push(ExportedSymbol::new(symbol)),
+                None => missing.push(*name),
+            }
+            (exported, missing)
+        },
+    );
+
+    if missing.is_empty() {
+        Ok(exported)
+    } else {
+        Err(Box::new(errors::AssemblyError(format!(
+            "Exported symbols not defined: {:?}",
+            missing
+        ))))
+    }
+}
+
+pub fn render(format: ExportFormat, symbols: &[ExportedSymbol], guard: &str) -> String {
+    let header = "Generated by ssp16asm from SVP sources, do not edit";
+    let mut result = String::new();
+
+    match format {
+        ExportFormat::Asm68k => {
+            result.push_str(&format!("; {}\n\n", header));
+            for symbol in symbols {
+                result.push_str(&format!(
+                    "{}\t\tequ ${:0width$X}\t; {}\n",
+                    symbol.name,
+                    symbol.value,
+                    symbol.comment(),
+                    width = symbol.hex_digits()
+                ));
+            }
+        }
+        ExportFormat::Vasm => {
+            result.push_str(&format!("; {}\n\n", header));
+            for symbol in symbols {
+                result.push_str(&format!(
+                    "{}\t\t= ${:0width$X}\t; {}\n",
+                    symbol.name,
+                    symbol.value,
+                    symbol.comment(),
+                    width = symbol.hex_digits()
+                ));
+            }
+        }
+        ExportFormat::CHeader => {
+            result.push_str(&format!("/* {} */\n\n", header));
+            result.push_str(&format!("#ifndef {}\n#define {}\n\n", guard, guard));
+            for symbol in symbols {
+                result.push_str(&format!(
+                    "#define {} 0x{:0width$X} /* {} */\n",
+                    symbol.name,
+                    symbol.value,
+                    symbol.comment(),
+                    width = symbol.hex_digits()
+                ));
+            }
+            result.push_str(&format!("\n#endif /* {} */\n", guard));
+        }
+    }
+
+    result
+}
+
+pub fn write_exports(
+    format: ExportFormat,
+    symbols: &[ExportedSymbol],
+    filename: &str,
+) -> Result<(), Box<dyn Error>> {
+    let guard = Path::new(filename)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    let mut file = File::create(filename)?;
+    file.write_all(render(format, symbols, &guard).as_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod m68k_tests {
+    use super::*;
+
+    fn symbol(name: &str, value: u16, kind: symbols::SymbolKind) -> symbols::Symbol {
+        symbols::Symbol {
+            name: name.to_string(),
+            value,
+            kind,
+            file: "test.svp".to_string(),
+            line: 1,
+            section: None,
+        }
+    }
+
+    fn test_symbols() -> Vec<symbols::Symbol> {
+        vec![
+            symbol("entry", 0x400, symbols::SymbolKind::Label),
+            symbol("CommandId", 0x100, symbols::SymbolKind::Equ),
+            symbol("Offset", 0x10, symbols::SymbolKind::Equb),
+        ]
+    }
+
+    #[test]
+    fn check_exported_symbols() {
+        let exported = exported_symbols(&["entry", "CommandId"], &test_symbols()).unwrap();
+
+        assert_eq!(
+            exported,
+            vec![
+                ExportedSymbol {
+                    name: "entry".to_string(),
+                    value: 0x800,
+                    kind: symbols::SymbolKind::Label,
+                },
+                ExportedSymbol {
+                    name: "CommandId".to_string(),
+                    value: 0x100,
+                    kind: symbols::SymbolKind::Equ,
+                },
+            ]
+        );
+
+        assert!(exported_symbols(&["missing"], &test_symbols()).is_err());
+    }
+
+    #[test]
+    fn check_render_formats() {
+        let exported =
+            exported_symbols(&["entry", "CommandId", "Offset"], &test_symbols()).unwrap();
+
+        let asm68k = render(ExportFormat::Asm68k, &exported, "SVP_INC");
+        assert!(asm68k.contains("entry\t\tequ $00000800\t;"));
+        assert!(asm68k.contains("CommandId\t\tequ $0100\t;"));
+        assert!(asm68k.contains("Offset\t\tequ $10\t;"));
+
+        let vasm = render(ExportFormat::Vasm, &exported, "SVP_INC");
+        assert!(vasm.contains("entry\t\t= $00000800\t;"));
+
+        let header = render(ExportFormat::CHeader, &exported, "SVP_H");
+        assert!(header.contains("#ifndef SVP_H\n#define SVP_H\n"));
+        assert!(header.contains("#define entry 0x00000800 /*"));
+        assert!(header.contains("#define CommandId 0x0100 /*"));
+        assert!(header.ends_with("#endif /* SVP_H */\n"));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::assembler::symbols;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+
+#[derive(Debug, Serialize)]
+pub struct BaseRom {
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    pub input_files: Vec<String>,
+    pub output_file: String,
+    pub base_rom: Option<BaseRom>,
+    pub output_size: usize,
+    pub fill: bool,
+    pub max_binary_size: u64,
+}
+
+// Machine-readable view of an assembly run, so other toolchains (i.e.: the M68000 side of a
+// project) can consume SVP entry points and constants instead of copying them by hand:
+#[derive(Debug, Serialize)]
+pub struct SymbolReport {
+    pub build: BuildInfo,
+    pub symbols: Vec<symbols::Symbol>,
+    pub equs: Vec<symbols::Symbol>,
+    pub equbs: Vec<symbols::Symbol>,
+}
+
+impl BaseRom {
+    pub fn from_file(path: &str) -> Result<BaseRom, Box<dyn Error>> {
+        let contents = fs::read(path)?;
+
+        Ok(BaseRom {
+            path: path.to_string(),
+            size: contents.len() as u64,
+            sha256: Sha256::digest(&contents)
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        })
+    }
+}
+
+impl SymbolReport {
+    pub fn new(build: BuildInfo, all_symbols: Vec<symbols::Symbol>) -> SymbolReport {
+        let of_kind = |kind: symbols::SymbolKind| {
+            all_symbols
+                .iter()
+                .filter(|symbol| symbol.kind == kind)
+                .cloned()
+                .collect::<Vec<symbols::Symbol>>()
+        };
+
+        SymbolReport {
+            build,
+            symbols: of_kind(symbols::SymbolKind::Label),
+            equs: of_kind(symbols::SymbolKind::Equ),
+            equbs: of_kind(symbols::SymbolKind::Equb),
+        }
+    }
+
+    pub fn write_json(&self, filename: &str) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer_pretty(File::create(filename)?, self)?;
+        Ok(())
+    }
+
+    pub fn write_yaml(&self, filename: &str) -> Result<(), Box<dyn Error>> {
+        serde_yaml::to_writer(File::create(filename)?, self)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod report_tests {
+    use super::*;
+
+    fn symbol(name: &str, value: u16, kind: symbols::SymbolKind) -> symbols::Symbol {
+        symbols::Symbol {
+            name: name.to_string(),
+            value,
+            kind,
+            file: "test.svp".to_string(),
+            line: 1,
+            section: Some(0x400),
+        }
+    }
+
+    #[test]
+    fn check_report_splits_tables() {
+        let report = SymbolReport::new(
+            BuildInfo {
+                input_files: vec!["test.svp".to_string()],
+                output_file: "test.bin".to_string(),
+                base_rom: None,
+                output_size: 4,
+                fill: false,
+                max_binary_size: 0x400000,
+            },
+            vec![
+                symbol("start", 0x400, symbols::SymbolKind::Label),
+                symbol("value", 0x1234, symbols::SymbolKind::Equ),
+                symbol("byte", 0x12, symbols::SymbolKind::Equb),
+            ],
+        );
+
+        assert_eq!(
+            report.symbols,
+            vec![symbol("start", 0x400, symbols::SymbolKind::Label)]
+        );
+        assert_eq!(
+            report.equs,
+            vec![symbol("value", 0x1234, symbols::SymbolKind::Equ)]
+        );
+        assert_eq!(
+            report.equbs,
+            vec![symbol("byte", 0x12, symbols::SymbolKind::Equb)]
+        );
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["symbols"][0]["kind"], "label");
+        assert_eq!(json["symbols"][0]["section"], 0x400);
+        assert_eq!(json["build"]["base_rom"], serde_json::Value::Null);
+    }
+}
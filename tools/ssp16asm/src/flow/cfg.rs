@@ -0,0 +1,886 @@
+use crate::asm::macros::SspMacro;
+use crate::asm::mnemonics::SspMnemonic;
+use crate::asm::operators::{SspMnemonicCondition, SspOperator};
+use crate::asm::registers::SspGeneralRegister;
+use crate::assembler::assembly;
+use crate::assembler::instructions;
+use crate::tokenization::tokens;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::error::Error;
+
+// SVP code always starts at word address 0x400
+pub const ENTRY_POINT: u16 = 0x400;
+pub const HARDWARE_STACK_LEVELS: usize = 6;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub address: u16,
+    pub size: u16,
+    pub line: usize,
+    pub text: String,
+    pub operands: Vec<String>,
+    pub condition: Option<String>,
+    pub jump: Option<u16>,
+    pub call: Option<u16>,
+    pub returns: bool,
+    pub computed: bool,
+    // Reads PM0-PM4 (XST and its status included) or branches on the GPI inputs, so loops doing
+    // it wait for the 68000 side
+    pub polls: bool,
+}
+
+impl Instruction {
+    fn is_conditional(&self) -> bool {
+        self.condition.is_some()
+    }
+
+    fn falls_through(&self) -> bool {
+        !(self.returns || self.computed || (self.jump.is_some() && !self.is_conditional()))
+    }
+
+    fn ends_block(&self) -> bool {
+        self.jump.is_some() || self.call.is_some() || self.returns || self.computed
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EdgeKind {
+    Fallthrough,
+    Branch,
+    Call,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub start: u16,
+    pub instructions: Vec<Instruction>,
+    pub edges: Vec<(EdgeKind, u16)>,
+    pub reachable: bool,
+}
+
+impl Block {
+    pub fn end(&self) -> u16 {
+        self.instructions
+            .last()
+            .map(|i| i.address + i.size)
+            .unwrap_or(self.start)
+    }
+
+    pub fn last(&self) -> &Instruction {
+        self.instructions.last().unwrap()
+    }
+
+    fn lines(&self) -> (usize, usize) {
+        (
+            self.instructions.first().unwrap().line,
+            self.instructions.last().unwrap().line,
+        )
+    }
+
+    // Edges within the same routine (calls come back to the next instruction):
+    fn local_successors(&self) -> impl Iterator<Item = u16> + '_ {
+        self.edges
+            .iter()
+            .filter(|(kind, _)| *kind != EdgeKind::Call)
+            .map(|(_, to)| *to)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+// Basic blocks of the assembled program (by word address), along with the labels pointing to
+// them. Targets outside the assembled code (i.e.: into a base ROM) are kept as external.
+// Labels used as values (i.e.: `ld (r6), @return_here` or `dw @handler`) are assumed to be
+// possible targets of computed jumps (`ld pc, ...`).
+pub struct ControlFlowGraph {
+    pub blocks: BTreeMap<u16, Block>,
+    pub labels: HashMap<u16, Vec<String>>,
+    pub external: BTreeSet<u16>,
+    pub address_taken: BTreeSet<u16>,
+}
+
+impl ControlFlowGraph {
+    // From the preprocessed source, with the symbol tables it was assembled with (target
+    // constants, defines and imported symbols included):
+    pub fn new(
+        contents: &str,
+        symbol_table: &HashMap<&str, u16>,
+        equ_table: &HashMap<&str, u16>,
+        equb_table: &HashMap<&str, u8>,
+    ) -> Result<ControlFlowGraph, Box<dyn Error>> {
+        let (tokens, lines) = tokens::tokenize_with_lines(contents)?;
+
+        let assembly = assembly::assemble(
+            &tokens,
+            symbol_table,
+            equ_table,
+            equb_table,
+            false,
+            None,
+            false,
+            4,
+        )?;
+        if let Some((_, error)) = assembly.errors.into_iter().next() {
+            return Err(Box::new(error));
+        }
+
+        let source_lines: Vec<&str> = contents.lines().collect();
+        let binary = &assembly.binary;
+        let mut instructions: BTreeMap<u16, Instruction> = BTreeMap::new();
+        for (index, span) in assembly.spans.iter().enumerate() {
+            let (start, end) = match span {
+                Some(span) => *span,
+                None => continue,
+            };
+            // Spans belong to the last token of each instruction (or DW value)
+            let head = (0..=index).rev().find(|i| {
+                matches!(
+                    tokens[*i],
+                    tokens::Token::Mnemonic(_) | tokens::Token::Macro(_)
+                )
+            });
+            let head = match head.map(|head| (head, tokens[head])) {
+                Some((head, tokens::Token::Mnemonic(_))) => head,
+                _ => continue,
+            };
+            let word = |address: u64| {
+                (*binary.get(address as usize).unwrap_or(&0) as u16) << 8
+                    | *binary.get(address as usize + 1).unwrap_or(&0) as u16
+            };
+            let (mnemonic, operands) =
+                match instructions::Instruction::decode(word(start), word(start + 2))
+                    .and_then(|i| i.operands())
+                {
+                    Some(decoded) => decoded,
+                    None => continue,
+                };
+            let line = lines[head];
+            let words: Vec<&str> = tokens::split_line_with_columns(source_lines[line - 1])
+                .into_iter()
+                .map(|(_, word)| word)
+                .skip_while(|word| matches!(tokens::Token::new(word), tokens::Token::Label(_)))
+                .collect();
+
+            instructions.insert(
+                (start / 2) as u16,
+                new_instruction(
+                    (start / 2) as u16,
+                    ((end - start) / 2) as u16,
+                    line,
+                    mnemonic,
+                    &operands,
+                    &words,
+                ),
+            );
+        }
+
+        let address_taken = tokens
+            .iter()
+            .enumerate()
+            .filter_map(|(index, token)| match token {
+                tokens::Token::Operator(SspOperator::LabelRef(label)) => {
+                    let head = tokens[..index].iter().rev().find(|token| {
+                        matches!(token, tokens::Token::Mnemonic(_) | tokens::Token::Macro(_))
+                    });
+                    match head {
+                        Some(tokens::Token::Mnemonic(SspMnemonic::Bra))
+                        | Some(tokens::Token::Mnemonic(SspMnemonic::Call))
+                        | Some(tokens::Token::Macro(SspMacro::Export)) => None,
+                        _ => symbol_table.get(label).copied(),
+                    }
+                }
+                _ => None,
+            })
+            .collect();
+
+        let mut labels: HashMap<u16, Vec<String>> = HashMap::new();
+        let mut sorted_symbols: Vec<(&&str, &u16)> = symbol_table.iter().collect();
+        sorted_symbols.sort();
+        for (name, address) in sorted_symbols {
+            labels.entry(*address).or_default().push(name.to_string());
+        }
+
+        let mut graph = ControlFlowGraph {
+            blocks: split_blocks(&instructions),
+            labels,
+            external: BTreeSet::new(),
+            address_taken,
+        };
+        graph.external = graph
+            .blocks
+            .values()
+            .flat_map(|block| block.edges.iter().map(|(_, to)| *to))
+            .filter(|to| !instructions.contains_key(to))
+            .collect();
+        graph.mark_reachable();
+
+        Ok(graph)
+    }
+
+    // All assembled instructions, by address:
+    pub fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.blocks
+            .values()
+            .flat_map(|block| block.instructions.iter())
+    }
+
+    pub fn name(&self, address: u16) -> String {
+        self.labels
+            .get(&address)
+            .and_then(|names| names.first().cloned())
+            .unwrap_or_else(|| format!("{:04X}", address))
+    }
+
+    fn mark_reachable(&mut self) {
+        let mut pending = VecDeque::from(vec![ENTRY_POINT]);
+        let mut reached = HashSet::new();
+        let mut computed = false;
+
+        while let Some(address) = pending.pop_front() {
+            if let Some(block) = self.blocks.get(&address) {
+                if reached.insert(address) {
+                    pending.extend(block.edges.iter().map(|(_, to)| *to));
+
+                    if block.last().computed && !computed {
+                        computed = true;
+                        pending.extend(self.address_taken.iter());
+                    }
+                }
+            }
+        }
+
+        for (address, block) in self.blocks.iter_mut() {
+            block.reachable = reached.contains(address);
+        }
+    }
+
+    pub fn findings(&self) -> Vec<Finding> {
+        if !self.blocks.contains_key(&ENTRY_POINT) {
+            return vec![Finding {
+                line: None,
+                message: format!("No code at the entry point ({:04X})", ENTRY_POINT),
+            }];
+        }
+
+        let mut findings = self.unreachable_code();
+        findings.extend(self.endless_loops());
+        findings.extend(self.stack_usage());
+        findings.extend(
+            self.blocks
+                .values()
+                .filter(|block| block.reachable && block.last().falls_through())
+                .filter(|block| !self.blocks.contains_key(&block.end()))
+                .map(|block| Finding {
+                    line: Some(block.last().line),
+                    message: format!(
+                        "Execution runs past the last instruction of the program ({:04X})",
+                        block.last().address
+                    ),
+                }),
+        );
+
+        findings.sort_by_key(|finding| finding.line);
+        findings
+    }
+
+    fn unreachable_code(&self) -> Vec<Finding> {
+        let mut ranges: Vec<(&Block, &Block)> = Vec::new();
+        for block in self.blocks.values().filter(|block| !block.reachable) {
+            match ranges.last_mut() {
+                Some((_, last)) if last.end() == block.start => *last = block,
+                _ => ranges.push((block, block)),
+            }
+        }
+
+        ranges
+            .into_iter()
+            .map(|(first, last)| Finding {
+                line: Some(first.lines().0),
+                message: format!(
+                    "Unreachable code: {:04X}-{:04X} (lines {}-{})",
+                    first.start,
+                    last.end() - 1,
+                    first.lines().0,
+                    last.lines().1
+                ),
+            })
+            .collect()
+    }
+
+    // Loops (strongly connected components) without any way out, other than single
+    // instruction spins (`bra always, @self`), which are always intended:
+    fn endless_loops(&self) -> Vec<Finding> {
+        strongly_connected(self)
+            .into_iter()
+            .filter(|component| {
+                let block = &self.blocks[&component[0]];
+                component.len() > 1 || block.local_successors().any(|to| to == block.start)
+            })
+            .filter(|component| {
+                !component.iter().any(|address| {
+                    let block = &self.blocks[address];
+                    block.last().returns
+                        || block.last().computed
+                        || block.local_successors().any(|to| !component.contains(&to))
+                })
+            })
+            .filter(|component| {
+                let block = &self.blocks[&component[0]];
+                !(component.len() == 1 && block.instructions.len() == 1)
+            })
+            // Loops waiting for the 68000 (i.e.: a main loop polling XST for commands) are meant
+            // to run forever:
+            .filter(|component| {
+                !component.iter().any(|address| {
+                    self.blocks[address]
+                        .instructions
+                        .iter()
+                        .any(|instruction| instruction.polls)
+                })
+            })
+            .map(|component| {
+                let first = component.iter().min().unwrap();
+                let lines = component.iter().map(|address| self.blocks[address].lines());
+
+                Finding {
+                    line: Some(self.blocks[first].lines().0),
+                    message: format!(
+                        "Endless loop with no exit at {} (lines {}-{})",
+                        self.name(*first),
+                        lines.clone().map(|(first, _)| first).min().unwrap(),
+                        lines.map(|(_, last)| last).max().unwrap()
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    fn stack_usage(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let mut routines: BTreeMap<u16, Routine> = BTreeMap::new();
+        let mut pending = vec![ENTRY_POINT];
+
+        while let Some(start) = pending.pop() {
+            if routines.contains_key(&start) || !self.blocks.contains_key(&start) {
+                continue;
+            }
+            let routine = self.routine(start);
+            pending.extend(routine.calls.iter().map(|(target, _)| *target));
+            routines.insert(start, routine);
+        }
+
+        for (start, routine) in routines.iter() {
+            if *start == ENTRY_POINT {
+                if let Some(line) = routine.return_line {
+                    findings.push(Finding {
+                        line: Some(line),
+                        message: "RET reached from the entry point without a matching CALL (the hardware stack is empty)".to_string(),
+                    });
+                }
+            } else if routine.return_line.is_none() && !routine.computed {
+                findings.push(Finding {
+                    line: Some(self.blocks[start].lines().0),
+                    message: format!(
+                        "Routine {} never returns, every CALL to it leaves an entry in the hardware stack",
+                        self.name(*start)
+                    ),
+                });
+            }
+        }
+
+        let mut chain = vec![(ENTRY_POINT, 0)];
+        let mut deepest = HashMap::new();
+        let mut reported = (false, HashSet::new());
+        self.visit_calls(
+            &routines,
+            &mut chain,
+            &mut deepest,
+            &mut reported,
+            &mut findings,
+        );
+
+        findings
+    }
+
+    fn visit_calls(
+        &self,
+        routines: &BTreeMap<u16, Routine>,
+        chain: &mut Vec<(u16, usize)>,
+        deepest: &mut HashMap<u16, usize>,
+        reported: &mut (bool, HashSet<u16>),
+        findings: &mut Vec<Finding>,
+    ) {
+        let (current, _) = *chain.last().unwrap();
+        let depth = chain.len() - 1;
+        if deepest.get(&current).map(|d| *d >= depth) == Some(true) {
+            return;
+        }
+        deepest.insert(current, depth);
+
+        let describe = |chain: &[(u16, usize)], target: u16| {
+            chain
+                .iter()
+                .map(|(address, _)| self.name(*address))
+                .chain(std::iter::once(self.name(target)))
+                .collect::<Vec<String>>()
+                .join(" -> ")
+        };
+
+        for (target, line) in routines[&current].calls.iter() {
+            if !routines.contains_key(target) {
+                continue;
+            }
+
+            if chain.iter().any(|(address, _)| address == target) {
+                if reported.1.insert(*target) {
+                    findings.push(Finding {
+                        line: Some(*line),
+                        message: format!(
+                            "Recursive calls use an unbounded amount of the hardware stack: {}",
+                            describe(chain, *target)
+                        ),
+                    });
+                }
+            } else if depth + 1 > HARDWARE_STACK_LEVELS {
+                if !reported.0 {
+                    reported.0 = true;
+                    findings.push(Finding {
+                        line: Some(*line),
+                        message: format!(
+                            "Nested calls reach {} levels, over the {} levels of the hardware stack: {}",
+                            depth + 1,
+                            HARDWARE_STACK_LEVELS,
+                            describe(chain, *target)
+                        ),
+                    });
+                }
+            } else {
+                chain.push((*target, *line));
+                self.visit_calls(routines, chain, deepest, reported, findings);
+                chain.pop();
+            }
+        }
+    }
+
+    // Blocks reached from the start of a routine without following calls:
+    fn routine(&self, start: u16) -> Routine {
+        let mut routine = Routine {
+            calls: Vec::new(),
+            return_line: None,
+            computed: false,
+        };
+        let mut pending = vec![start];
+        let mut visited = HashSet::new();
+
+        while let Some(address) = pending.pop() {
+            let block = match self.blocks.get(&address) {
+                Some(block) if visited.insert(address) => block,
+                _ => continue,
+            };
+            let last = block.last();
+
+            if let Some(target) = last.call {
+                routine.calls.push((target, last.line));
+            }
+            if last.returns && routine.return_line.map(|l| l > last.line) != Some(false) {
+                routine.return_line = Some(last.line);
+            }
+            routine.computed |= last.computed;
+            pending.extend(block.local_successors());
+        }
+
+        routine.calls.sort();
+        routine
+    }
+}
+
+struct Routine {
+    calls: Vec<(u16, usize)>,
+    return_line: Option<usize>,
+    computed: bool,
+}
+
+fn new_instruction(
+    address: u16,
+    size: u16,
+    line: usize,
+    mnemonic: SspMnemonic,
+    operands: &[SspOperator],
+    words: &[&str],
+) -> Instruction {
+    let target = match operands.get(1) {
+        Some(SspOperator::Word(value)) => Some(*value),
+        Some(SspOperator::Byte(value)) => Some(*value as u16),
+        _ => None,
+    };
+    let condition = match operands.first() {
+        Some(SspOperator::Condition(SspMnemonicCondition::Always)) | None => None,
+        Some(_) => words.get(1).map(|word| word.to_string()),
+    };
+    let mut instruction = Instruction {
+        address,
+        size,
+        line,
+        text: match words.split_first() {
+            Some((head, rest)) if !rest.is_empty() => format!("{} {}", head, rest.join(", ")),
+            Some((head, _)) => head.to_string(),
+            None => String::new(),
+        },
+        operands: words.iter().skip(1).map(|word| word.to_string()).collect(),
+        condition: None,
+        jump: None,
+        call: None,
+        returns: false,
+        computed: false,
+        polls: polls(operands),
+    };
+
+    match mnemonic {
+        SspMnemonic::Bra => {
+            instruction.jump = target;
+            instruction.condition = condition;
+        }
+        SspMnemonic::Call => {
+            instruction.call = target;
+            instruction.condition = condition;
+        }
+        SspMnemonic::Ret => instruction.returns = true,
+        SspMnemonic::Ld(_)
+            if operands.first() == Some(&SspOperator::Reg(SspGeneralRegister::Pc)) =>
+        {
+            match target {
+                Some(target) => instruction.jump = Some(target),
+                None => instruction.computed = true,
+            }
+        }
+        _ => (),
+    }
+
+    instruction
+}
+
+fn polls(operands: &[SspOperator]) -> bool {
+    let reads_pm = operands.iter().skip(1).any(|operand| {
+        matches!(
+            operand,
+            SspOperator::Reg(SspGeneralRegister::Ext0)
+                | SspOperator::Reg(SspGeneralRegister::Ext1)
+                | SspOperator::Reg(SspGeneralRegister::Ext2)
+                | SspOperator::Reg(SspGeneralRegister::Ext3)
+                | SspOperator::Reg(SspGeneralRegister::Ext4)
+        )
+    });
+    let branches_on_gpi = matches!(
+        operands.first(),
+        Some(SspOperator::Condition(SspMnemonicCondition::Gpi0(_)))
+            | Some(SspOperator::Condition(SspMnemonicCondition::Gpi1(_)))
+            | Some(SspOperator::Condition(SspMnemonicCondition::Gpi2(_)))
+            | Some(SspOperator::Condition(SspMnemonicCondition::Gpi3(_)))
+    );
+
+    reads_pm || branches_on_gpi
+}
+
+fn split_blocks(instructions: &BTreeMap<u16, Instruction>) -> BTreeMap<u16, Block> {
+    let mut leaders: BTreeSet<u16> = instructions
+        .values()
+        .flat_map(|i| i.jump.into_iter().chain(i.call))
+        .collect();
+    leaders.insert(ENTRY_POINT);
+    let mut previous: Option<&Instruction> = None;
+    for instruction in instructions.values() {
+        match previous {
+            Some(previous)
+                if previous.address + previous.size == instruction.address
+                    && !previous.ends_block() => {}
+            _ => {
+                leaders.insert(instruction.address);
+            }
+        }
+        previous = Some(instruction);
+    }
+
+    let mut blocks: BTreeMap<u16, Block> = BTreeMap::new();
+    let mut current: Option<Block> = None;
+    for instruction in instructions.values() {
+        if leaders.contains(&instruction.address) {
+            if let Some(block) = current.take() {
+                blocks.insert(block.start, block);
+            }
+        }
+        current
+            .get_or_insert_with(|| Block {
+                start: instruction.address,
+                instructions: Vec::new(),
+                edges: Vec::new(),
+                reachable: false,
+            })
+            .instructions
+            .push(instruction.clone());
+    }
+    if let Some(block) = current {
+        blocks.insert(block.start, block);
+    }
+
+    for block in blocks.values_mut() {
+        let last = block.last().clone();
+        if let Some(target) = last.jump {
+            block.edges.push((EdgeKind::Branch, target));
+        }
+        if let Some(target) = last.call {
+            block.edges.push((EdgeKind::Call, target));
+        }
+        if last.falls_through() && instructions.contains_key(&block.end()) {
+            block.edges.push((EdgeKind::Fallthrough, block.end()));
+        }
+    }
+
+    blocks
+}
+
+// Tarjan's algorithm over the reachable blocks, following edges within routines:
+fn strongly_connected(graph: &ControlFlowGraph) -> Vec<Vec<u16>> {
+    struct State {
+        index: usize,
+        indexes: HashMap<u16, usize>,
+        low_links: HashMap<u16, usize>,
+        stack: Vec<u16>,
+        on_stack: HashSet<u16>,
+        components: Vec<Vec<u16>>,
+    }
+
+    fn connect(graph: &ControlFlowGraph, state: &mut State, address: u16) {
+        state.indexes.insert(address, state.index);
+        state.low_links.insert(address, state.index);
+        state.index += 1;
+        state.stack.push(address);
+        state.on_stack.insert(address);
+
+        for to in graph.blocks[&address].local_successors() {
+            if !graph.blocks.contains_key(&to) {
+                continue;
+            }
+            if !state.indexes.contains_key(&to) {
+                connect(graph, state, to);
+                let low_link = state.low_links[&address].min(state.low_links[&to]);
+                state.low_links.insert(address, low_link);
+            } else if state.on_stack.contains(&to) {
+                let low_link = state.low_links[&address].min(state.indexes[&to]);
+                state.low_links.insert(address, low_link);
+            }
+        }
+
+        if state.low_links[&address] == state.indexes[&address] {
+            let mut component = Vec::new();
+            while let Some(member) = state.stack.pop() {
+                state.on_stack.remove(&member);
+                component.push(member);
+                if member == address {
+                    break;
+                }
+            }
+            component.sort();
+            state.components.push(component);
+        }
+    }
+
+    let mut state = State {
+        index: 0,
+        indexes: HashMap::new(),
+        low_links: HashMap::new(),
+        stack: Vec::new(),
+        on_stack: HashSet::new(),
+        components: Vec::new(),
+    };
+    for (address, block) in graph.blocks.iter() {
+        if block.reachable && !state.indexes.contains_key(address) {
+            connect(graph, &mut state, *address);
+        }
+    }
+
+    state.components
+}
+
+#[cfg(test)]
+mod cfg_tests {
+    use super::*;
+
+    // Graph of a source using only its own symbols:
+    fn graph(contents: &str) -> ControlFlowGraph {
+        let (tokens, _) = tokens::tokenize_with_lines(contents).unwrap();
+        let (symbol_table, equ_table, equb_table) = assembly::extract_tables(&tokens);
+        ControlFlowGraph::new(contents, &symbol_table, &equ_table, &equb_table).unwrap()
+    }
+
+    fn messages(contents: &str) -> Vec<(Option<usize>, String)> {
+        graph(contents)
+            .findings()
+            .into_iter()
+            .map(|finding| (finding.line, finding.message))
+            .collect()
+    }
+
+    #[test]
+    fn check_blocks() {
+        let graph = graph(
+            "org 400\nstart: ld a, 1234\nbra z=1, @start\ncall always, @routine\nld pc, a\nroutine: ret\n",
+        );
+        let blocks: Vec<(u16, Vec<(EdgeKind, u16)>)> = graph
+            .blocks
+            .values()
+            .map(|block| (block.start, block.edges.clone()))
+            .collect();
+
+        assert_eq!(
+            blocks,
+            vec![
+                (
+                    0x400,
+                    vec![(EdgeKind::Branch, 0x400), (EdgeKind::Fallthrough, 0x404)]
+                ),
+                (
+                    0x404,
+                    vec![(EdgeKind::Call, 0x407), (EdgeKind::Fallthrough, 0x406)]
+                ),
+                (0x406, vec![]),
+                (0x407, vec![]),
+            ]
+        );
+        assert_eq!(
+            graph.blocks[&0x400].last().condition,
+            Some("z=1".to_string())
+        );
+        assert!(graph.blocks[&0x406].last().computed);
+        assert!(graph.blocks.values().all(|block| block.reachable));
+    }
+
+    #[test]
+    fn check_unreachable_code_and_loops() {
+        let source = "\
+org 400
+main: ld a, ext0
+andi 2
+bra z=1, @main
+loop: ld a, 0001
+bra always, @loop
+dead: ld x, a
+ld y, a
+spin: bra always, @spin
+";
+        let waiting = "\
+org 400
+commands: ld a, ext0
+andi 2
+bra z=1, @commands
+ld x, ext3
+bra always, @commands
+";
+
+        assert_eq!(
+            messages(source),
+            vec![
+                (
+                    Some(5),
+                    "Endless loop with no exit at loop (lines 5-6)".to_string()
+                ),
+                (
+                    Some(7),
+                    "Unreachable code: 0408-040B (lines 7-9)".to_string()
+                ),
+            ]
+        );
+        assert_eq!(messages("org 400\nspin: bra always, @spin\n"), vec![]);
+        assert_eq!(messages(waiting), vec![]);
+        assert_eq!(
+            messages("org 400\nwait: ld a, 0001\nbra gpi0=0, @wait\nbra always, @wait\n"),
+            vec![]
+        );
+        assert_eq!(
+            messages("org 400\nfill: ld ext4, a\nbra always, @fill\n").len(),
+            1
+        );
+    }
+
+    #[test]
+    fn check_computed_jumps() {
+        let source = "\
+org 400
+main: ld (r6), @back
+bra always, @routine
+back: bra always, @main
+routine: ld a, 0001
+ld pc, (r6)
+unused: ld pc, (r6)
+";
+
+        assert_eq!(
+            messages(source),
+            vec![(
+                Some(7),
+                "Unreachable code: 0409-0409 (lines 7-7)".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn check_stack_usage() {
+        let mut source = "org 400\nmain: call always, @r1\nspin: bra always, @spin\n".to_string();
+        for level in 1..=7 {
+            source.push_str(&format!("r{}: call always, @r{}\nret\n", level, level + 1));
+        }
+        source.push_str("r8: ret\n");
+
+        let findings = messages(&source);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].0, Some(14));
+        assert!(findings[0]
+            .1
+            .starts_with("Nested calls reach 7 levels, over the 6 levels of the hardware stack: main -> r1 -> r2"));
+
+        assert_eq!(
+            messages("org 400\nmain: call always, @r\nspin: bra always, @spin\nr: call always, @r\nret\n"),
+            vec![(
+                Some(4),
+                "Recursive calls use an unbounded amount of the hardware stack: main -> r -> r"
+                    .to_string()
+            )]
+        );
+        assert_eq!(
+            messages(
+                "org 400\nmain: call always, @r\nspin: bra always, @spin\nr: bra always, @spin\n"
+            ),
+            vec![(
+                Some(4),
+                "Routine r never returns, every CALL to it leaves an entry in the hardware stack"
+                    .to_string()
+            )]
+        );
+        assert_eq!(
+            messages("org 400\nld x, a\nret\n"),
+            vec![(
+                Some(3),
+                "RET reached from the entry point without a matching CALL (the hardware stack is empty)"
+                    .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn check_program_end() {
+        assert_eq!(
+            messages("org 400\nld x, a\n"),
+            vec![(
+                Some(2),
+                "Execution runs past the last instruction of the program (0400)".to_string()
+            )]
+        );
+        assert_eq!(
+            messages("org 500\nret\n"),
+            vec![(None, "No code at the entry point (0400)".to_string())]
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use super::cfg;
+
+// Graphviz DOT for a control flow graph: one box per basic block (unreachable ones greyed out)
+// and one ellipse per address outside the assembled code. Branches show their condition and
+// calls are dashed.
+pub fn to_dot(graph: &cfg::ControlFlowGraph) -> String {
+    let mut dot = String::from("digraph svp {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+    for block in graph.blocks.values() {
+        let mut label = graph
+            .labels
+            .get(&block.start)
+            .map(|names| format!("{}:\\l", names.join(":\\l")))
+            .unwrap_or_default();
+        for instruction in block.instructions.iter() {
+            label.push_str(&format!(
+                "{:04X}  {}\\l",
+                instruction.address,
+                escape(&instruction.text)
+            ));
+        }
+
+        dot.push_str(&format!(
+            "    b{:04X} [label=\"{}\"{}];\n",
+            block.start,
+            label,
+            if block.reachable {
+                ""
+            } else {
+                ", style=filled, fillcolor=lightgrey"
+            }
+        ));
+    }
+    for address in graph.external.iter() {
+        dot.push_str(&format!(
+            "    b{:04X} [label=\"{}\", shape=ellipse];\n",
+            address,
+            escape(&graph.name(*address))
+        ));
+    }
+
+    for block in graph.blocks.values() {
+        for (kind, to) in block.edges.iter() {
+            let attributes = match (kind, &block.last().condition) {
+                (cfg::EdgeKind::Fallthrough, _) => String::new(),
+                (cfg::EdgeKind::Branch, Some(condition)) => {
+                    format!(" [label=\"{}\"]", escape(condition))
+                }
+                (cfg::EdgeKind::Branch, None) => String::new(),
+                (cfg::EdgeKind::Call, Some(condition)) => {
+                    format!(" [label=\"call {}\", style=dashed]", escape(condition))
+                }
+                (cfg::EdgeKind::Call, None) => " [label=\"call\", style=dashed]".to_string(),
+            };
+
+            dot.push_str(&format!(
+                "    b{:04X} -> b{:04X}{};\n",
+                block.start, to, attributes
+            ));
+        }
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod dot_tests {
+    use super::*;
+    use crate::assembler::assembly;
+    use crate::tokenization::tokens;
+
+    #[test]
+    fn check_dot_output() {
+        let contents = "org 400\nstart: call z=1, @sub\nbra always, 0200\nsub: ret\n";
+        let (tokens, _) = tokens::tokenize_with_lines(contents).unwrap();
+        let (symbol_table, equ_table, equb_table) = assembly::extract_tables(&tokens);
+        let graph =
+            cfg::ControlFlowGraph::new(contents, &symbol_table, &equ_table, &equb_table).unwrap();
+
+        assert_eq!(
+            to_dot(&graph),
+            "digraph svp {
+    node [shape=box, fontname=\"monospace\"];
+    b0400 [label=\"start:\\l0400  call z=1, @sub\\l\"];
+    b0402 [label=\"0402  bra always, 0200\\l\"];
+    b0404 [label=\"sub:\\l0404  ret\\l\"];
+    b0200 [label=\"0200\", shape=ellipse];
+    b0400 -> b0404 [label=\"call z=1\", style=dashed];
+    b0400 -> b0402;
+    b0402 -> b0200;
+}
+"
+        );
+    }
+}
@@ -0,0 +1,2 @@
+pub mod cfg;
+pub mod dot;
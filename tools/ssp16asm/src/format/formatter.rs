@@ -0,0 +1,365 @@
+use crate::asm::operators;
+use crate::asm::targets::Target;
+use crate::assembler::assertions;
+use crate::assembler::conditionals;
+use crate::assembler::data;
+use crate::assembler::errors;
+use crate::assembler::pmset;
+use crate::testing::cases;
+use crate::tokenization::tokens;
+use std::error::Error;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Case {
+    Lower,
+    Upper,
+}
+
+// Columns where each part of a line starts. An operand column of 0 means operands go right
+// after the mnemonic. If a part doesn't fit before the next column, a single space is used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatOptions {
+    pub label_column: usize,
+    pub mnemonic_column: usize,
+    pub operand_column: usize,
+    pub comment_column: usize,
+    pub case: Case,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions {
+            label_column: 0,
+            mnemonic_column: 24,
+            operand_column: 0,
+            comment_column: 56,
+            case: Case::Lower,
+        }
+    }
+}
+
+// Re-emits a source file line by line from its tokens. Tokens never move between lines, so the
+// token stream (and therefore the assembled code) is the same before and after formatting,
+// which is checked before returning the result. Register aliases of every target are accepted.
+pub fn format_source(contents: &str, options: &FormatOptions) -> Result<String, Box<dyn Error>> {
+    let original = without_directives(contents);
+    let original = Target::Svp.resolve_source(&original);
+    let (original_tokens, _) = tokens::tokenize_with_lines(&original)?;
+
+    let test_lines = cases::test_block_lines(contents);
+    let mut lines: Vec<String> = contents
+        .lines()
+        .zip(test_lines)
+        .map(
+            |(line, is_test)| match data::raw_line(line).filter(|_| is_test) {
+                Some(raw) => format_raw_line(line, &raw, options),
+                None => format_line(line, options),
+            },
+        )
+        .collect();
+    while lines.last().map(|line| line.is_empty()) == Some(true) {
+        lines.pop();
+    }
+    let formatted = lines
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect::<String>();
+
+    let formatted_code = Target::Svp.resolve_source(&without_directives(&formatted));
+    let (formatted_tokens, _) = tokens::tokenize_with_lines(&formatted_code)?;
+    if format!("{:?}", original_tokens) != format!("{:?}", formatted_tokens) {
+        return Err(Box::new(errors::AssemblyError(
+            "Formatting would change the assembled code, source left untouched".to_string(),
+        )));
+    }
+
+    Ok(formatted)
+}
+
+// Conditional assembly, data, PMSET and assertion directives (and TEST blocks) aren't tokens, so
+// they're left out when comparing (their arguments are kept as written):
+fn without_directives(contents: &str) -> String {
+    let (contents, _, _) = cases::extract_tests(contents, Target::Svp);
+    contents
+        .lines()
+        .filter(|line| {
+            conditionals::directive(line).is_none()
+                && data::data_line(line).is_none()
+                && pmset::pmset_line(line).is_none()
+                && assertions::assertion_line(line).is_none()
+        })
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+fn format_line(line: &str, options: &FormatOptions) -> String {
+    if let Some(raw) = data::data_line(line)
+        .map(|(_, raw)| raw)
+        .or_else(|| pmset::pmset_line(line))
+        .or_else(|| assertions::assertion_line(line))
+    {
+        return format_raw_line(line, &raw, options);
+    }
+
+    let comment = tokens::split_line(line)
+        .into_iter()
+        .find(|word| word.starts_with('#'))
+        .map(|word| line[word.as_ptr() as usize - line.as_ptr() as usize..].trim_end());
+    let words: Vec<&str> = tokens::split_line_with_columns(line)
+        .into_iter()
+        .map(|(_, word)| word)
+        .collect();
+
+    let label_count = words
+        .iter()
+        .take_while(|word| matches!(tokens::Token::new(word), tokens::Token::Label(_)))
+        .count();
+    let (labels, code) = words.split_at(label_count);
+
+    let mut result = String::new();
+    if !labels.is_empty() {
+        pad_to(&mut result, options.label_column);
+        result.push_str(&labels.join(" "));
+    }
+    if let Some((head, operands)) = code.split_first() {
+        let is_directive = conditionals::Directive::new(head).is_some();
+        pad_to(&mut result, options.mnemonic_column);
+        result.push_str(&if is_directive {
+            directive_case(head, options.case)
+        } else {
+            normalize_case(head, options.case)
+        });
+
+        if is_directive && !operands.is_empty() {
+            // Conditions are expressions rather than operands
+            pad_to(&mut result, options.operand_column);
+            result.push_str(&operands.join(" "));
+        } else if !operands.is_empty() {
+            pad_to(&mut result, options.operand_column);
+            result.push_str(
+                &operands
+                    .iter()
+                    .map(|operand| normalize_case(operand, options.case))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+            );
+        }
+    }
+    if let Some(comment) = comment {
+        let column = if !result.is_empty() {
+            options.comment_column
+        } else if line.starts_with(char::is_whitespace) {
+            options.mnemonic_column
+        } else {
+            0
+        };
+        pad_to(&mut result, column);
+        result.push_str(comment);
+    }
+
+    result
+}
+
+fn format_raw_line(line: &str, data: &data::RawLine, options: &FormatOptions) -> String {
+    let labels = tokens::split_line(&line[..data.column]);
+
+    let mut result = String::new();
+    if !labels.is_empty() {
+        pad_to(&mut result, options.label_column);
+        result.push_str(&labels.join(" "));
+    }
+    pad_to(&mut result, options.mnemonic_column);
+    result.push_str(&directive_case(data.keyword, options.case));
+    if !data.arguments.is_empty() {
+        pad_to(&mut result, options.operand_column);
+        result.push_str(data.arguments);
+    }
+    if let Some(comment) = data.comment {
+        pad_to(&mut result, options.comment_column);
+        result.push_str(comment);
+    }
+
+    result
+}
+
+fn directive_case(word: &str, case: Case) -> String {
+    match case {
+        Case::Lower => word.to_lowercase(),
+        Case::Upper => word.to_uppercase(),
+    }
+}
+
+fn pad_to(line: &mut String, column: usize) {
+    if line.len() < column {
+        line.push_str(&" ".repeat(column - line.len()));
+    } else if !line.is_empty() {
+        line.push(' ');
+    }
+}
+
+// Mnemonics, directives, registers, conditions and operations are case insensitive. Anything
+// else (labels, numbers, RAM bank addresses...) is kept as written, and so is any token whose
+// meaning would change with a different case (i.e.: `(a)`):
+fn normalize_case(word: &str, case: Case) -> String {
+    let token = |word| tokens::Token::new(Target::Svp.resolve(word));
+    let is_keyword = match token(word) {
+        tokens::Token::Mnemonic(_) | tokens::Token::Macro(_) => true,
+        tokens::Token::Operator(operator) => !matches!(
+            operator,
+            operators::SspOperator::Word(_)
+                | operators::SspOperator::Byte(_)
+                | operators::SspOperator::RamBankAddressA(_)
+                | operators::SspOperator::RamBankAddressB(_)
+                | operators::SspOperator::LabelRef(_)
+        ),
+        _ => false,
+    };
+    let candidate = match case {
+        Case::Lower => word.to_lowercase(),
+        Case::Upper => word.to_uppercase(),
+    };
+
+    if is_keyword && format!("{:?}", token(word)) == format!("{:?}", token(&candidate)) {
+        candidate
+    } else {
+        word.to_string()
+    }
+}
+
+#[cfg(test)]
+mod formatter_tests {
+    use super::*;
+    use crate::assembler::assembly;
+    use std::fs;
+    use std::path::Path;
+
+    const SOURCE: &str = "\
+# Header comment
+org 400\t\t# start
+
+init:\t# Clear registers
+\tEOR A,A
+  LD X, (a)
+value: EQU 0x1234
+loop:   ld A[0x0A], a
+\t\t  # indented comment
+        bra Z=1, @loop   \t
+\n\n";
+
+    const FORMATTED: &str = "\
+# Header comment
+                        org 400                         # start
+
+init:                                                   # Clear registers
+                        eor a, a
+                        ld x, (a)
+value:                  equ 0x1234
+loop:                   ld A[0x0A], a
+                        # indented comment
+                        bra z=1, @loop
+";
+
+    fn assemble(contents: &str) -> Vec<u8> {
+        let (tokens, _) = tokens::tokenize_with_lines(contents).unwrap();
+        let (symbols, equs, equbs) = assembly::extract_tables(&tokens);
+
+        assembly::generate_opcodes(&tokens, &symbols, &equs, &equbs, false, None, false, 4).unwrap()
+    }
+
+    #[test]
+    fn check_default_format() {
+        let formatted = format_source(SOURCE, &FormatOptions::default()).unwrap();
+
+        assert_eq!(formatted, FORMATTED);
+        assert_eq!(
+            format_source(&formatted, &FormatOptions::default()).unwrap(),
+            formatted
+        );
+        assert_eq!(assemble(&formatted), assemble(SOURCE));
+    }
+
+    #[test]
+    fn check_custom_columns_and_case() {
+        let options = FormatOptions {
+            label_column: 0,
+            mnemonic_column: 8,
+            operand_column: 14,
+            comment_column: 30,
+            case: Case::Upper,
+        };
+
+        assert_eq!(
+            format_source("loop: ld x, (a) # copy\nbra z=1, @loop\n", &options).unwrap(),
+            "loop:   LD    X, (a)          # copy\n        BRA   Z=1, @loop\n"
+        );
+    }
+
+    #[test]
+    fn check_conditional_directives() {
+        assert_eq!(
+            format_source(
+                "IFDEF DEBUG # debug only\nld a, x\n  if PLATFORM==0001\nELSE\nEndif\n",
+                &FormatOptions::default()
+            )
+            .unwrap(),
+            "                        ifdef DEBUG                     # debug only
+                        ld a, x
+                        if PLATFORM==0001
+                        else
+                        endif
+"
+        );
+    }
+
+    #[test]
+    fn check_data_directives() {
+        assert_eq!(
+            format_source(
+                "text:  ASCII  \"a, # b\",00#comment\n\tDB.LE 01,02\nAssert @text<0800\n PMSET  pm4,write, 181000\nLD PM4,AL\n",
+                &FormatOptions::default()
+            )
+            .unwrap(),
+            "text:                   ascii \"a, # b\",00               #comment
+                        db.le 01,02
+                        assert @text<0800
+                        pmset pm4,write, 181000
+                        ld pm4, al
+"
+        );
+    }
+
+    #[test]
+    fn check_invalid_sources_are_not_formatted() {
+        assert!(format_source("ld a, lol\n", &FormatOptions::default()).is_err());
+    }
+
+    #[test]
+    fn check_samples_assemble_to_identical_bytes() {
+        let samples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../samples");
+
+        for sample in fs::read_dir(samples).unwrap() {
+            let sample = sample.unwrap().path();
+            if !sample.is_dir() {
+                continue;
+            }
+
+            for file in fs::read_dir(sample).unwrap() {
+                let path = file.unwrap().path();
+                if path.extension().map(|e| e == "svp") != Some(true) {
+                    continue;
+                }
+
+                let contents = fs::read_to_string(&path).unwrap();
+                let formatted = format_source(&contents, &FormatOptions::default()).unwrap();
+
+                assert_eq!(assemble(&formatted), assemble(&contents), "{:?}", path);
+                assert_eq!(
+                    format_source(&formatted, &FormatOptions::default()).unwrap(),
+                    formatted,
+                    "{:?}",
+                    path
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use crate::assembler::errors;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+
+// Column where source code starts in SNASM68K/asm68k listing files:
+const LISTING_SOURCE_COLUMN: usize = 36;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ImportedKind {
+    Label,
+    Constant,
+}
+
+// Mega Drive windows into memory the SVP addresses too (DRAM and IRAM), by byte address:
+const SVP_WINDOWS: [(u32, u32); 2] = [(0x300000, 0x31FFFF), (0x390000, 0x3907FF)];
+
+// Names at the start of a listing line that define something other than a label:
+const DEFINITIONS: [&str; 12] = [
+    "macro", "macros", "equ", "equs", "equr", "set", "reg", "rs", "rsset", "rsreset", "substr",
+    "section",
+];
+
+// A symbol defined by the M68000 side of a project. Labels are stored as SVP word addresses
+// (Mega Drive byte address / 2), and so are constants pointing to DRAM or IRAM (i.e.: buffers the
+// SVP shares with the 68000). Other constants are kept as they were defined.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedSymbol {
+    pub name: String,
+    pub value: u32,
+    pub kind: ImportedKind,
+}
+
+impl ImportedSymbol {
+    fn from_m68k(name: &str, value: u32, kind: ImportedKind) -> Option<ImportedSymbol> {
+        match kind {
+            // Labels at odd addresses (i.e.: strings) can't be addressed as words by the SVP:
+            ImportedKind::Label if value % 2 == 1 => None,
+            ImportedKind::Label => Some(ImportedSymbol {
+                name: name.to_string(),
+                value: value / 2,
+                kind,
+            }),
+            ImportedKind::Constant
+                if SVP_WINDOWS
+                    .iter()
+                    .any(|(start, end)| (*start..=*end).contains(&value)) =>
+            {
+                ImportedSymbol::from_m68k(name, value, ImportedKind::Label)
+            }
+            ImportedKind::Constant => Some(ImportedSymbol {
+                name: name.to_string(),
+                value,
+                kind,
+            }),
+        }
+    }
+}
+
+// Reads symbols from a M68000 build, the format is detected from the file contents:
+//
+// - SNASM68K symbol maps (`.map`, binary files starting with `MND`).
+// - SNASM68K/asm68k listings (`.lst`).
+// - vasm listings (including the `Symbols by name:` table).
+// - Plain `equ` include files (SNASM68K/asm68k/vasm syntax).
+pub fn import_file(filename: &str) -> Result<Vec<ImportedSymbol>, Box<dyn Error>> {
+    let data = fs::read(filename)?;
+
+    if data.starts_with(b"MND") {
+        return parse_snasm_map(&data);
+    }
+
+    let text = String::from_utf8_lossy(&data);
+    if text.contains("Symbols by name:") {
+        Ok(parse_vasm_listing(&text))
+    } else if text.lines().any(is_listing_line) {
+        Ok(parse_listing(&text))
+    } else {
+        Ok(parse_equates(&text))
+    }
+}
+
+pub fn parse_snasm_map(data: &[u8]) -> Result<Vec<ImportedSymbol>, Box<dyn Error>> {
+    let mut result = Vec::new();
+    let mut index = 8;
+
+    while index < data.len() {
+        if index + 6 > data.len() {
+            return Err(map_error());
+        }
+
+        let value = u32::from_le_bytes([
+            data[index],
+            data[index + 1],
+            data[index + 2],
+            data[index + 3],
+        ]);
+        let name_length = data[index + 5] as usize;
+        let name = data
+            .get(index + 6..index + 6 + name_length)
+            .ok_or_else(map_error)?;
+
+        result.extend(ImportedSymbol::from_m68k(
+            &String::from_utf8_lossy(name),
+            value,
+            ImportedKind::Label,
+        ));
+        index += 6 + name_length;
+    }
+
+    Ok(result)
+}
+
+fn map_error() -> Box<dyn Error> {
+    Box::new(errors::AssemblyError(
+        "Truncated SNASM68K symbol map".to_string(),
+    ))
+}
+
+fn is_listing_line(line: &str) -> bool {
+    line.len() > 9
+        && line.is_char_boundary(8)
+        && line[..8].chars().all(|c| c.is_ascii_hexdigit())
+        && line[8..].starts_with(' ')
+}
+
+pub fn parse_listing(text: &str) -> Vec<ImportedSymbol> {
+    text.lines()
+        .filter(|line| is_listing_line(line))
+        .filter_map(|line| {
+            let address = u32::from_str_radix(&line[..8], 16).ok()?;
+            let source = line.get(LISTING_SOURCE_COLUMN..)?;
+            let name = leading_identifier(source)?;
+
+            match line[9..].strip_prefix('=') {
+                // Equates show their value instead of the generated bytes:
+                Some(value) => ImportedSymbol::from_m68k(
+                    name,
+                    u32::from_str_radix(value.get(..8)?, 16).ok()?,
+                    ImportedKind::Constant,
+                ),
+                None if is_definition(&source[name.len()..]) => None,
+                None => ImportedSymbol::from_m68k(name, address, ImportedKind::Label),
+            }
+        })
+        .collect()
+}
+
+pub fn parse_vasm_listing(text: &str) -> Vec<ImportedSymbol> {
+    text.lines()
+        .skip_while(|line| !line.starts_with("Symbols by name:"))
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next()?;
+            let (section, value) = fields.next()?.split_once(':')?;
+            let value = u32::from_str_radix(value, 16).ok()?;
+
+            // Absolute symbols are equates, everything else belongs to a section:
+            let kind = if section == "A" {
+                ImportedKind::Constant
+            } else {
+                ImportedKind::Label
+            };
+
+            ImportedSymbol::from_m68k(name, value, kind)
+        })
+        .collect()
+}
+
+pub fn parse_equates(text: &str) -> Vec<ImportedSymbol> {
+    text.lines()
+        .filter_map(|line| {
+            let code = line.split(';').next()?;
+            let name = leading_identifier(code)?;
+            let mut fields = code[name.len()..]
+                .trim_start_matches(':')
+                .split_whitespace();
+
+            match fields.next()?.to_lowercase().as_str() {
+                "equ" | "=" => ImportedSymbol::from_m68k(
+                    name,
+                    parse_m68k_number(fields.next()?)?,
+                    ImportedKind::Constant,
+                ),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// Whether what follows a name makes it a macro, equate, register list... (`Name: macro`):
+fn is_definition(rest: &str) -> bool {
+    let word = rest
+        .trim_start_matches(':')
+        .split_whitespace()
+        .next()
+        .unwrap_or("");
+    let directive = word.split('.').next().unwrap_or("").to_lowercase();
+
+    word.starts_with('=') || DEFINITIONS.contains(&directive.as_str())
+}
+
+// Only labels/equates defined at the start of a line are considered (local labels are skipped):
+fn leading_identifier(source: &str) -> Option<&str> {
+    let end = source
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(source.len());
+    let name = &source[..end];
+
+    match name.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => Some(name),
+        _ => None,
+    }
+}
+
+fn parse_m68k_number(raw: &str) -> Option<u32> {
+    if let Some(hex) = raw.strip_prefix('$') {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(hex) = raw.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = raw.strip_prefix('%') {
+        u32::from_str_radix(binary, 2).ok()
+    } else {
+        raw.parse().ok()
+    }
+}
+
+// Adds imported symbols to the word constants table, as long as they fit in 16 bits and the
+// SVP sources don't already define a symbol with the same name (the first file importing a name
+// wins over the others). Returns the names skipped because the SVP sources define them and the
+// ones skipped because they're wider than 16 bits.
+pub fn merge_into_tables<'a>(
+    imported: &'a [ImportedSymbol],
+    symbols: &HashMap<&'a str, u16>,
+    equs: &mut HashMap<&'a str, u16>,
+    equbs: &HashMap<&'a str, u8>,
+) -> (Vec<&'a str>, Vec<&'a str>) {
+    let mut merged: HashSet<&str> = HashSet::new();
+    let mut conflicts = Vec::new();
+    let mut wide = Vec::new();
+
+    for symbol in imported {
+        let name = symbol.name.as_str();
+        let same_value = equs.get(name).map(|value| *value as u32) == Some(symbol.value);
+        if merged.contains(name) || same_value {
+            // Already imported from another file (i.e.: the same symbol in a `.map` and a `.lst`)
+            // or defined with the same value
+        } else if symbols.contains_key(name) || equs.contains_key(name) || equbs.contains_key(name)
+        {
+            conflicts.push(name);
+        } else if symbol.value > 0xFFFF {
+            wide.push(name);
+        } else {
+            equs.insert(name, symbol.value as u16);
+            merged.insert(name);
+        }
+    }
+
+    (conflicts, wide)
+}
+
+#[cfg(test)]
+mod m68k_import_tests {
+    use super::*;
+
+    fn label(name: &str, value: u32) -> ImportedSymbol {
+        ImportedSymbol {
+            name: name.to_string(),
+            value,
+            kind: ImportedKind::Label,
+        }
+    }
+
+    fn constant(name: &str, value: u32) -> ImportedSymbol {
+        ImportedSymbol {
+            name: name.to_string(),
+            value,
+            kind: ImportedKind::Constant,
+        }
+    }
+
+    #[test]
+    fn check_snasm_map() {
+        let mut data = b"MND\x01\x00\x00\x00\x00".to_vec();
+        data.extend_from_slice(&[0xF0, 0x09, 0x00, 0x00, 0x02, 0x04]);
+        data.extend_from_slice(b"pads");
+        data.extend_from_slice(&[0x0B, 0x01, 0x02, 0x00, 0x02, 0x03]);
+        data.extend_from_slice(b"odd");
+
+        assert_eq!(parse_snasm_map(&data).unwrap(), vec![label("pads", 0x4F8)]);
+        assert!(parse_snasm_map(&data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn check_listing() {
+        let listing = "\
+000009F0                            SVP_PaddingStart:
+000009F0                            \tDCB.b 0x20000-SVP_PaddingStart,0x00
+000208FC =00302000                  dramTilesOrigin         equ 0x00302000
+00020904                            @loop_y:
+00020904 6000 FFE8                      bra @loop_y
+00000200                            SetCRAMWrite: macro addr
+00000200                            regs\tequr d0-d2
+00000200                            count\tset 2
+00020904                            start: move.w d0, d1
+";
+
+        assert_eq!(
+            parse_listing(listing),
+            vec![
+                label("SVP_PaddingStart", 0x4F8),
+                label("dramTilesOrigin", 0x181000),
+                label("start", 0x10482)
+            ]
+        );
+    }
+
+    #[test]
+    fn check_vasm_listing() {
+        let listing = "\
+Symbols by name:
+buffer                           A:00302000
+start                            00:00000200
+
+Symbols by value:
+00000200 start
+";
+
+        assert_eq!(
+            parse_vasm_listing(listing),
+            vec![label("buffer", 0x181000), label("start", 0x100)]
+        );
+    }
+
+    #[test]
+    fn check_equates() {
+        let equates = "\
+regXST          equ 0x00A15000
+size_word\t\tequ 2 ; comment
+tiles: equ $1000
+mask = %1010
+size_palette_w\t\tequ size_palette_b/size_word
+svp_iram = $390010
+ram_buffer equ $FF0000
+\tmove.w d0, d1
+";
+
+        assert_eq!(
+            parse_equates(equates),
+            vec![
+                constant("regXST", 0xA15000),
+                constant("size_word", 2),
+                constant("tiles", 0x1000),
+                constant("mask", 0xA),
+                label("svp_iram", 0x1C8008),
+                constant("ram_buffer", 0xFF0000),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_merge_into_tables() {
+        let imported = vec![
+            label("svp_padding", 0x4F8),
+            constant("dram", 0x302000),
+            constant("local", 1),
+            label("svp_padding", 0x4F8),
+            constant("svp_padding", 0x4FA),
+        ];
+        let symbols: HashMap<&str, u16> = HashMap::new();
+        let mut equs: HashMap<&str, u16> = HashMap::new();
+        let equbs: HashMap<&str, u8> = HashMap::new();
+        equs.insert("local", 2);
+
+        let skipped = merge_into_tables(&imported, &symbols, &mut equs, &equbs);
+
+        assert_eq!(skipped, (vec!["local"], vec!["dram"]));
+        assert_eq!(equs.get("svp_padding"), Some(&0x4F8));
+        assert_eq!(equs.get("local"), Some(&2));
+    }
+}
@@ -0,0 +1 @@
+pub mod m68k;
@@ -1,20 +1,51 @@
 mod asm;
 mod assembler;
+mod coverage;
+mod debugger;
+mod export;
+mod flow;
+mod format;
+mod import;
+mod lint;
+mod lsp;
+mod profiling;
+mod project;
+mod simulation;
+mod stimulus;
+mod syntax;
+mod testing;
+mod tiles;
+mod timing;
 mod tokenization;
 
 #[macro_use]
 extern crate clap;
-use clap::App;
+use clap::{App, ArgMatches};
 
+use asm::targets;
 use assembler::assembly;
+use assembler::assertions;
+use assembler::conditionals;
+use assembler::data;
+use assembler::errors;
+use assembler::pmset;
+use assembler::symbols;
+use export::m68k;
+use export::report;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use std::fs;
 use std::fs::File;
 use std::io::Write;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use tokenization::tokens;
 
+const DEFAULT_GDB_PORT: u16 = 3333;
+
+#[derive(Clone)]
 pub struct Config {
     pub input_filename: String,
     pub output_filename: String,
@@ -23,19 +54,348 @@ pub struct Config {
     pub input_base_rom: Option<String>,
     pub should_fill: bool,
     pub max_binary_size_in_megs: u8,
+    pub symbols_json: Option<String>,
+    pub symbols_yaml: Option<String>,
+    pub exports: Vec<(m68k::ExportFormat, String)>,
+    pub imports: Vec<String>,
+    pub defines: Vec<String>,
+    pub target: targets::Target,
+    pub syntax: syntax::Syntax,
+    pub write_output: bool,
+    pub flow_report: bool,
+    pub flow_dot: Option<String>,
+    pub timing_report: bool,
+    pub cost_model: Option<String>,
+    pub verbose: bool,
 }
 
-impl Config {
-    pub fn new_from_args() -> Result<Config, ()> {
+pub struct BuildConfig {
+    pub manifest_filename: String,
+    pub force: bool,
+}
+
+pub struct FormatConfig {
+    pub files: Vec<String>,
+    pub check: bool,
+    pub label_column: usize,
+    pub mnemonic_column: usize,
+    pub operand_column: usize,
+    pub comment_column: usize,
+    pub uppercase: bool,
+}
+
+pub struct ConvertConfig {
+    pub input_filename: String,
+    pub output_filename: String,
+    pub to: syntax::Syntax,
+}
+
+pub struct LintConfig {
+    pub files: Vec<String>,
+    pub target: targets::Target,
+}
+
+// What's needed to assemble a program for the simulator:
+pub struct ProgramConfig {
+    pub input_filename: String,
+    pub input_base_rom: Option<String>,
+    pub imports: Vec<String>,
+    pub defines: Vec<String>,
+    pub target: targets::Target,
+    pub syntax: syntax::Syntax,
+}
+
+pub struct TestConfig {
+    pub program: ProgramConfig,
+    pub test_files: Vec<String>,
+    pub max_steps: u64,
+    pub lcov: Option<String>,
+    pub coverage: Option<String>,
+}
+
+pub struct GdbConfig {
+    pub program: ProgramConfig,
+    pub port: u16,
+    pub entry: Option<String>,
+}
+
+pub struct ProfileConfig {
+    pub program: ProgramConfig,
+    pub entry: Option<String>,
+    pub max_steps: u64,
+    pub cost_model: Option<String>,
+    pub folded: Option<String>,
+}
+
+pub struct StimulusConfig {
+    pub program: ProgramConfig,
+    pub script_filename: String,
+    pub entry: Option<String>,
+    pub cost_model: Option<String>,
+    pub timeout: u64,
+    pub dump_dram: Option<String>,
+}
+
+pub struct TilesConfig {
+    pub dump_filename: String,
+    pub output_filename: String,
+    pub offset: usize,
+    pub layout: tiles::render::Layout,
+    pub palette: Option<String>,
+    pub palette_line: usize,
+    pub reference: Option<String>,
+}
+
+pub struct DebuggerConfig {
+    pub program: ProgramConfig,
+    pub entry: Option<String>,
+    pub history: usize,
+}
+
+pub enum Command {
+    Assemble(Config),
+    Watch(Config),
+    Build(BuildConfig),
+    Lsp,
+    Format(FormatConfig),
+    Convert(ConvertConfig),
+    Lint(LintConfig),
+    Test(TestConfig),
+    Gdb(GdbConfig),
+    Profile(ProfileConfig),
+    Stimulus(StimulusConfig),
+    Tiles(TilesConfig),
+}
+
+impl Command {
+    // Invalid option values exit with the usual clap error, while the help shown when there's
+    // nothing to do just returns `Err`:
+    pub fn new_from_args() -> Result<Command, ()> {
+        Command::new_from(std::env::args_os()).map_err(|error| match error.kind {
+            clap::ErrorKind::HelpDisplayed => (),
+            _ => error.exit(),
+        })
+    }
+
+    // Parses a command line, starting with the program name:
+    fn new_from<I, T>(args: I) -> Result<Command, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
         let yaml = load_yaml!("cli.yml");
-        let matches = App::from_yaml(yaml).get_matches();
+        let matches = App::from_yaml(yaml).get_matches_from(args);
 
+        if let Some(build) = matches.subcommand_matches("build") {
+            return Ok(Command::Build(BuildConfig {
+                manifest_filename: build.value_of("MANIFEST").unwrap_or("svp.toml").to_string(),
+                force: build.occurrences_of("force") > 0,
+            }));
+        }
+
+        if let Some(fmt) = matches.subcommand_matches("fmt") {
+            let defaults = format::formatter::FormatOptions::default();
+            let column = |arg: &str, default: usize| number_value(fmt, arg, default);
+
+            return Ok(Command::Format(FormatConfig {
+                files: fmt
+                    .values_of("FILES")
+                    .map(|files| files.map(|f| f.to_string()).collect())
+                    .unwrap_or_default(),
+                check: fmt.occurrences_of("check") > 0,
+                label_column: column("label_column", defaults.label_column)?,
+                mnemonic_column: column("mnemonic_column", defaults.mnemonic_column)?,
+                operand_column: column("operand_column", defaults.operand_column)?,
+                comment_column: column("comment_column", defaults.comment_column)?,
+                uppercase: fmt.occurrences_of("uppercase") > 0,
+            }));
+        }
+
+        if let Some(convert) = matches.subcommand_matches("convert") {
+            return Ok(Command::Convert(ConvertConfig {
+                input_filename: convert.value_of("INPUT").unwrap_or_default().to_string(),
+                output_filename: convert.value_of("OUTPUT").unwrap_or_default().to_string(),
+                to: convert
+                    .value_of("to")
+                    .and_then(syntax::Syntax::new)
+                    .unwrap_or_default(),
+            }));
+        }
+
+        if let Some(lint) = matches.subcommand_matches("lint") {
+            return Ok(Command::Lint(LintConfig {
+                files: lint
+                    .values_of("FILES")
+                    .map(|files| files.map(|f| f.to_string()).collect())
+                    .unwrap_or_default(),
+                target: lint
+                    .value_of("target")
+                    .and_then(targets::Target::new)
+                    .unwrap_or_default(),
+            }));
+        }
+
+        if let Some(test) = matches.subcommand_matches("test") {
+            return Ok(Command::Test(TestConfig {
+                program: ProgramConfig::new_from_matches(test),
+                test_files: test
+                    .values_of("TESTS")
+                    .map(|files| files.map(|f| f.to_string()).collect())
+                    .unwrap_or_default(),
+                max_steps: number_value(test, "max_steps", testing::runner::DEFAULT_MAX_STEPS)?,
+                lcov: test.value_of("lcov").map(|l| l.to_string()),
+                coverage: test.value_of("coverage").map(|c| c.to_string()),
+            }));
+        }
+
+        if let Some(gdb) = matches.subcommand_matches("gdb") {
+            return Ok(Command::Gdb(GdbConfig {
+                program: ProgramConfig::new_from_matches(gdb),
+                // Port 0 would pick any free port, and GDB wouldn't know which one:
+                port: option_value(gdb, "port", DEFAULT_GDB_PORT, |v| {
+                    v.parse().ok().filter(|port| *port != 0)
+                })?,
+                entry: gdb.value_of("entry").map(|e| e.to_string()),
+            }));
+        }
+
+        if let Some(profile) = matches.subcommand_matches("profile") {
+            return Ok(Command::Profile(ProfileConfig {
+                program: ProgramConfig::new_from_matches(profile),
+                entry: profile.value_of("entry").map(|e| e.to_string()),
+                max_steps: number_value(profile, "max_steps", testing::runner::DEFAULT_MAX_STEPS)?,
+                cost_model: profile.value_of("cost_model").map(|c| c.to_string()),
+                folded: profile.value_of("folded").map(|f| f.to_string()),
+            }));
+        }
+
+        if let Some(stimulus) = matches.subcommand_matches("stimulus") {
+            return Ok(Command::Stimulus(StimulusConfig {
+                program: ProgramConfig::new_from_matches(stimulus),
+                script_filename: stimulus.value_of("SCRIPT").unwrap().to_string(),
+                entry: stimulus.value_of("entry").map(|e| e.to_string()),
+                cost_model: stimulus.value_of("cost_model").map(|c| c.to_string()),
+                timeout: number_value(stimulus, "timeout", ssp16_sim::megadrive::DEFAULT_TIMEOUT)?,
+                dump_dram: stimulus.value_of("dump_dram").map(|d| d.to_string()),
+            }));
+        }
+
+        if let Some(tiles) = matches.subcommand_matches("tiles") {
+            return Ok(Command::Tiles(TilesConfig {
+                dump_filename: tiles.value_of("INPUT").unwrap().to_string(),
+                output_filename: tiles.value_of("OUTPUT").unwrap().to_string(),
+                offset: option_value(tiles, "offset", 0, |v| usize::from_str_radix(v, 16).ok())?,
+                layout: tiles::render::Layout {
+                    tiles: number_value(tiles, "tiles", 64)?,
+                    columns: number_value(tiles, "columns", 8)?,
+                    order: tiles
+                        .value_of("order")
+                        .and_then(tiles::render::Order::new)
+                        .unwrap_or_default(),
+                },
+                palette: tiles.value_of("palette").map(|p| p.to_string()),
+                palette_line: number_value(tiles, "palette_line", 0)?,
+                reference: tiles.value_of("compare").map(|c| c.to_string()),
+            }));
+        }
+
+        if matches.subcommand_matches("lsp").is_some() {
+            return Ok(Command::Lsp);
+        }
+
+        match Config::new_from_matches(&matches) {
+            Some(config) if matches.occurrences_of("watch") > 0 => Ok(Command::Watch(config)),
+            Some(config) => Ok(Command::Assemble(config)),
+            None => match App::from_yaml(yaml).print_long_help() {
+                _ => Err(clap::Error {
+                    message: String::new(),
+                    kind: clap::ErrorKind::HelpDisplayed,
+                    info: None,
+                }),
+            },
+        }
+    }
+}
+
+// Value of an option read with `parse`, `default` if it isn't given:
+fn option_value<T>(
+    matches: &ArgMatches,
+    arg: &str,
+    default: T,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<T, clap::Error> {
+    match matches.value_of(arg) {
+        Some(value) => parse(value).ok_or_else(|| {
+            clap::Error::with_description(
+                &format!("Invalid value for '--{}': {}", arg.replace('_', "-"), value),
+                clap::ErrorKind::InvalidValue,
+            )
+        }),
+        None => Ok(default),
+    }
+}
+
+fn number_value<T: FromStr>(matches: &ArgMatches, arg: &str, default: T) -> Result<T, clap::Error> {
+    option_value(matches, arg, default, |value| value.parse().ok())
+}
+
+impl ProgramConfig {
+    // From the arguments the subcommands running programs share:
+    pub fn new_from_matches(matches: &ArgMatches) -> ProgramConfig {
+        let values = |arg: &str| {
+            matches
+                .values_of(arg)
+                .map(|values| values.map(|v| v.to_string()).collect())
+                .unwrap_or_default()
+        };
+
+        ProgramConfig {
+            input_filename: matches.value_of("INPUT").unwrap_or_default().to_string(),
+            input_base_rom: matches.value_of("base").map(|b| b.to_string()),
+            imports: values("import"),
+            defines: values("define"),
+            target: matches
+                .value_of("target")
+                .and_then(targets::Target::new)
+                .unwrap_or_default(),
+            syntax: matches
+                .value_of("syntax")
+                .and_then(syntax::Syntax::new)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn new_from_matches(matches: &ArgMatches) -> Option<Config> {
         match (matches.value_of("INPUT"), matches.value_of("OUTPUT")) {
             (Some(input), Some(output)) => {
                 let is_debug = matches.occurrences_of("debug") > 0;
                 let is_hex = matches.occurrences_of("hex") > 0;
                 let should_fill = matches.occurrences_of("fill") > 0;
                 let input_base_rom = matches.value_of("base").map(|b| b.to_string());
+                let symbols_json = matches.value_of("symbols_json").map(|f| f.to_string());
+                let symbols_yaml = matches.value_of("symbols_yaml").map(|f| f.to_string());
+                let exports = [
+                    ("export_asm68k", m68k::ExportFormat::Asm68k),
+                    ("export_vasm", m68k::ExportFormat::Vasm),
+                    ("export_c", m68k::ExportFormat::CHeader),
+                ]
+                .iter()
+                .filter_map(|(arg, format)| {
+                    matches
+                        .value_of(arg)
+                        .map(|filename| (*format, filename.to_string()))
+                })
+                .collect();
+                let imports = matches
+                    .values_of("import")
+                    .map(|files| files.map(|f| f.to_string()).collect())
+                    .unwrap_or_default();
+                let defines = matches
+                    .values_of("define")
+                    .map(|defines| defines.map(|d| d.to_string()).collect())
+                    .unwrap_or_default();
 
                 let max_binary_size_in_megs = if matches.occurrences_of("1M") > 0 {
                     1
@@ -45,7 +405,7 @@ impl Config {
                     4
                 };
 
-                Ok(Config {
+                Some(Config {
                     input_filename: input.to_string(),
                     output_filename: output.to_string(),
                     is_debug,
@@ -53,36 +413,138 @@ impl Config {
                     input_base_rom: input_base_rom,
                     should_fill: should_fill,
                     max_binary_size_in_megs: max_binary_size_in_megs,
+                    symbols_json,
+                    symbols_yaml,
+                    exports,
+                    imports,
+                    defines,
+                    target: matches
+                        .value_of("target")
+                        .and_then(targets::Target::new)
+                        .unwrap_or_default(),
+                    syntax: matches
+                        .value_of("syntax")
+                        .and_then(syntax::Syntax::new)
+                        .unwrap_or_default(),
+                    write_output: matches.occurrences_of("no_output") == 0,
+                    flow_report: matches.occurrences_of("flow") > 0,
+                    flow_dot: matches.value_of("flow_dot").map(|f| f.to_string()),
+                    timing_report: matches.occurrences_of("timing") > 0,
+                    cost_model: matches.value_of("cost_model").map(|f| f.to_string()),
+                    verbose: matches.occurrences_of("verbose") > 0,
                 })
             }
-            _ => match App::from_yaml(yaml).print_long_help() {
-                _ => Err(()),
-            },
+            _ => None,
         }
     }
 }
 
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.input_filename)?;
-    let tokens = tokens::tokenize(contents.as_str())?;
+    let imported_symbols = import_symbols(&config.imports)?;
+    let defines = parse_defines(&config.defines)?;
+
+    let (contents, _) = read_source(&config.input_filename, config.syntax, config.target)?;
+    let (contents, assertions) = preprocess(
+        &contents,
+        &config.input_filename,
+        config.target,
+        &defines,
+        &imported_symbols,
+    )?;
+    let (tokens, lines) = tokens::tokenize_with_lines(contents.as_str())?;
 
-    let (symbol_table, equ_table, equb_table) = assembly::extract_tables(&tokens);
+    let (symbol_table, equ_table, equb_table) = symbol_tables(
+        &tokens,
+        config.target,
+        &defines,
+        &imported_symbols,
+        config.verbose,
+    );
     let opcodes = assembly::generate_opcodes(
         &tokens,
         &symbol_table,
         &equ_table,
         &equb_table,
         config.is_debug,
-        config.input_base_rom,
+        config.input_base_rom.clone(),
         config.should_fill,
         config.max_binary_size_in_megs,
     )?;
+    assertions::check(&assertions, &symbol_table, &equ_table, &equb_table)?;
 
-    let mut file = File::create(config.output_filename.clone())?;
-    file.write_all(&opcodes)?;
+    let all_symbols = symbols::collect_symbols(
+        &tokens,
+        &lines,
+        &config.input_filename,
+        &symbol_table,
+        &equ_table,
+        &equb_table,
+    );
 
-    if config.is_hex {
-        write_hex_file(format!("{}.{}", config.output_filename, "hex"), &opcodes)?;
+    if !config.exports.is_empty() {
+        let exported = m68k::exported_symbols(&symbols::collect_exports(&tokens), &all_symbols)?;
+        for (format, filename) in config.exports.iter().filter(|_| config.write_output) {
+            m68k::write_exports(*format, &exported, filename)?;
+        }
+    }
+
+    if config.write_output && (config.symbols_json.is_some() || config.symbols_yaml.is_some()) {
+        let symbol_report = report::SymbolReport::new(
+            report::BuildInfo {
+                input_files: vec![config.input_filename.clone()],
+                output_file: config.output_filename.clone(),
+                base_rom: match config.input_base_rom {
+                    Some(ref base_rom) => Some(report::BaseRom::from_file(base_rom)?),
+                    None => None,
+                },
+                output_size: opcodes.len(),
+                fill: config.should_fill,
+                max_binary_size: config.max_binary_size_in_megs as u64 * 0x100000,
+            },
+            all_symbols,
+        );
+
+        if let Some(ref filename) = config.symbols_json {
+            symbol_report.write_json(filename)?;
+        }
+        if let Some(ref filename) = config.symbols_yaml {
+            symbol_report.write_yaml(filename)?;
+        }
+    }
+
+    if config.write_output {
+        let mut file = File::create(config.output_filename.clone())?;
+        file.write_all(&opcodes)?;
+
+        if config.is_hex {
+            write_hex_file(format!("{}.{}", config.output_filename, "hex"), &opcodes)?;
+        }
+    }
+
+    if config.flow_report
+        || config.timing_report
+        || (config.write_output && config.flow_dot.is_some())
+    {
+        let graph =
+            flow::cfg::ControlFlowGraph::new(&contents, &symbol_table, &equ_table, &equb_table)?;
+
+        if config.flow_report {
+            print_flow_report(&graph);
+        }
+        if config.timing_report {
+            let model = match config.cost_model {
+                Some(ref filename) => timing::model::CostModel::from_file(filename)?,
+                None => timing::model::CostModel::default(),
+            };
+            let timing = timing::estimator::estimate(&contents, &graph, &model);
+
+            println!();
+            println!("Timing estimate:");
+            print!("{}", timing.report(&model));
+        }
+        if let Some(ref filename) = config.flow_dot.as_ref().filter(|_| config.write_output) {
+            fs::write(filename, flow::dot::to_dot(&graph))?;
+        }
     }
 
     if config.is_debug {
@@ -98,6 +560,520 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+fn import_symbols(
+    filenames: &[String],
+) -> Result<Vec<import::m68k::ImportedSymbol>, Box<dyn Error>> {
+    Ok(filenames
+        .iter()
+        .map(|filename| import::m68k::import_file(filename))
+        .collect::<Result<Vec<_>, _>>()?
+        .concat())
+}
+
+fn parse_defines(defines: &[String]) -> Result<HashMap<String, u16>, Box<dyn Error>> {
+    defines
+        .iter()
+        .map(|define| conditionals::parse_define(define))
+        .collect()
+}
+
+// Reads a source file in native syntax, taking its TEST blocks out:
+fn read_source(
+    filename: &str,
+    syntax: syntax::Syntax,
+    target: targets::Target,
+) -> Result<(String, Vec<testing::cases::TestCase>), Box<dyn Error>> {
+    let (contents, tests, errors) =
+        testing::cases::extract_tests(&fs::read_to_string(filename)?, target);
+
+    if errors.is_empty() {
+        Ok((syntax.to_native(&contents), tests))
+    } else {
+        Err(Box::new(errors::AssemblyError::from_lines(&errors)))
+    }
+}
+
+// Binary files the input file includes with `INCBIN` outside skipped conditional blocks (none if
+// it can't be read or preprocessed, the assembly reports why):
+fn included_files(config: &Config) -> Vec<PathBuf> {
+    let directory = Path::new(&config.input_filename)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    let contents = parse_defines(&config.defines).and_then(|defines| {
+        let (contents, _) = read_source(&config.input_filename, config.syntax, config.target)?;
+        conditionals::preprocess(&contents, &defines)
+    });
+
+    contents
+        .map(|contents| data::included_files(&contents, directory))
+        .unwrap_or_default()
+}
+
+// Source as the tokenizer takes it (conditional, data and PMSET directives expanded, assertions
+// taken out and register aliases resolved):
+fn preprocess(
+    contents: &str,
+    filename: &str,
+    target: targets::Target,
+    defines: &HashMap<String, u16>,
+    imported_symbols: &[import::m68k::ImportedSymbol],
+) -> Result<(String, Vec<assertions::Assertion>), Box<dyn Error>> {
+    let contents = conditionals::preprocess(contents, defines)?;
+    let contents = data::expand(
+        &contents,
+        Path::new(filename)
+            .parent()
+            .unwrap_or_else(|| Path::new("")),
+    )?;
+    let contents = pmset::expand(&contents, imported_symbols)?;
+    let (contents, assertions) = assertions::remove_assertions(&contents)?;
+
+    Ok((target.resolve_source(&contents), assertions))
+}
+
+// Labels, word constants and byte constants:
+type SymbolTables<'a> = (
+    HashMap<&'a str, u16>,
+    HashMap<&'a str, u16>,
+    HashMap<&'a str, u8>,
+);
+
+// Labels and constants, along with the target constants, defines and imported symbols:
+fn symbol_tables<'a>(
+    tokens: &Vec<tokens::Token<'a>>,
+    target: targets::Target,
+    defines: &'a HashMap<String, u16>,
+    imported_symbols: &'a [import::m68k::ImportedSymbol],
+    verbose: bool,
+) -> SymbolTables<'a> {
+    let (symbol_table, mut equ_table, equb_table) = assembly::extract_tables(tokens);
+    for (name, value) in target.constants() {
+        equ_table.entry(name).or_insert(*value);
+    }
+    for (name, value) in defines.iter() {
+        equ_table.insert(name.as_str(), *value);
+    }
+    let (conflicts, wide) = import::m68k::merge_into_tables(
+        imported_symbols,
+        &symbol_table,
+        &mut equ_table,
+        &equb_table,
+    );
+    if !conflicts.is_empty() {
+        println!(
+            "Imported symbols not added (already defined in the SVP sources): {:?}",
+            conflicts
+        );
+    }
+    // M68000 builds have plenty of these (i.e.: 68000 RAM and ROM addresses):
+    if verbose && !wide.is_empty() {
+        println!(
+            "Imported symbols not added (wider than 16 bits): {:?}",
+            wide
+        );
+    }
+
+    (symbol_table, equ_table, equb_table)
+}
+
+fn print_flow_report(graph: &flow::cfg::ControlFlowGraph) {
+    let findings = graph.findings();
+
+    println!();
+    println!("Control flow analysis:");
+    if findings.is_empty() {
+        println!("\tNo issues found");
+    }
+    for finding in findings {
+        match finding.line {
+            Some(line) => println!("\tLine {}: {}", line, finding.message),
+            None => println!("\t{}", finding.message),
+        }
+    }
+}
+
+pub fn watch(config: Config) -> Result<(), Box<dyn Error>> {
+    project::watch::watch(config)
+}
+
+pub fn lsp() -> Result<(), Box<dyn Error>> {
+    lsp::server::run()
+}
+
+pub fn format(config: FormatConfig) -> Result<(), Box<dyn Error>> {
+    let options = format::formatter::FormatOptions {
+        label_column: config.label_column,
+        mnemonic_column: config.mnemonic_column,
+        operand_column: config.operand_column,
+        comment_column: config.comment_column,
+        case: if config.uppercase {
+            format::formatter::Case::Upper
+        } else {
+            format::formatter::Case::Lower
+        },
+    };
+    let mut unformatted = Vec::new();
+
+    for filename in config.files.iter() {
+        let contents = fs::read_to_string(filename)?;
+        let formatted = format::formatter::format_source(&contents, &options)
+            .map_err(|e| errors::AssemblyError(format!("{}: {}", filename, e)))?;
+
+        if formatted != contents {
+            if config.check {
+                unformatted.push(filename.as_str());
+            } else {
+                fs::write(filename, formatted)?;
+                println!("Formatted {}", filename);
+            }
+        }
+    }
+
+    if unformatted.is_empty() {
+        Ok(())
+    } else {
+        Err(Box::new(errors::AssemblyError(format!(
+            "Files not formatted: {:?}",
+            unformatted
+        ))))
+    }
+}
+
+pub fn convert(config: ConvertConfig) -> Result<(), Box<dyn Error>> {
+    let from = match config.to {
+        syntax::Syntax::Native => syntax::Syntax::Disasm,
+        syntax::Syntax::Disasm => syntax::Syntax::Native,
+    };
+    let contents = fs::read_to_string(&config.input_filename)?;
+
+    fs::write(
+        &config.output_filename,
+        syntax::convert(&contents, from, config.to),
+    )?;
+    Ok(())
+}
+
+pub fn lint(config: LintConfig) -> Result<(), Box<dyn Error>> {
+    let mut count = 0;
+
+    for filename in config.files.iter() {
+        let contents = fs::read_to_string(filename)?;
+
+        for warning in lint::linter::lint(&contents, config.target) {
+            println!(
+                "{}:{}:{}: {} [{}]",
+                filename,
+                warning.line + 1,
+                warning.start + 1,
+                warning.message,
+                warning.rule.name()
+            );
+            count += 1;
+        }
+    }
+
+    if count == 0 {
+        Ok(())
+    } else {
+        Err(Box::new(errors::AssemblyError(format!(
+            "{} lint warning(s) found",
+            count
+        ))))
+    }
+}
+
+// Assembles a program for the simulator, returning it along with the TEST blocks in its source:
+fn simulated_program(
+    config: &ProgramConfig,
+) -> Result<(simulation::program::Program, Vec<testing::cases::TestCase>), Box<dyn Error>> {
+    let imported_symbols = import_symbols(&config.imports)?;
+    let defines = parse_defines(&config.defines)?;
+
+    let source = fs::read_to_string(&config.input_filename)?;
+    let (contents, tests) = read_source(&config.input_filename, config.syntax, config.target)?;
+    let (contents, assertions) = preprocess(
+        &contents,
+        &config.input_filename,
+        config.target,
+        &defines,
+        &imported_symbols,
+    )?;
+    let (tokens, lines) = tokens::tokenize_with_lines(contents.as_str())?;
+    let (symbol_table, equ_table, equb_table) =
+        symbol_tables(&tokens, config.target, &defines, &imported_symbols, false);
+    let assembly = assembly::assemble(
+        &tokens,
+        &symbol_table,
+        &equ_table,
+        &equb_table,
+        false,
+        config.input_base_rom.clone(),
+        false,
+        4,
+    )?
+    .checked()?;
+    assertions::check(&assertions, &symbol_table, &equ_table, &equb_table)?;
+
+    let program = simulation::program::Program::new(
+        &config.input_filename,
+        &source,
+        &tokens,
+        &lines,
+        &assembly,
+        &symbol_table,
+        assertions::symbol_values(&symbol_table, &equ_table, &equb_table),
+    );
+    Ok((program, tests))
+}
+
+// Assembles the input file and runs its tests, and those in the test files, on the simulator:
+pub fn test(config: TestConfig) -> Result<(), Box<dyn Error>> {
+    let (program, tests) = simulated_program(&config.program)?;
+    let target = config.program.target;
+
+    let mut test_files = vec![(config.program.input_filename.clone(), tests)];
+    for filename in config.test_files.iter() {
+        let (rest, tests, mut errors) =
+            testing::cases::extract_tests(&fs::read_to_string(filename)?, target);
+        errors.extend(
+            rest.lines()
+                .enumerate()
+                .filter(|(_, line)| !tokens::remove_comments(tokens::split_line(line)).is_empty())
+                .map(|(line, _)| (line, "Only TEST blocks can be in test files".to_string())),
+        );
+        errors.sort_by_key(|(line, _)| *line);
+        if !errors.is_empty() {
+            return Err(Box::new(errors::AssemblyError(format!(
+                "{}:\n{}",
+                filename,
+                errors::AssemblyError::from_lines(&errors)
+            ))));
+        }
+        test_files.push((filename.clone(), tests));
+    }
+
+    let mut failed = 0;
+    let mut count = 0;
+    let mut coverage = ssp16_sim::coverage::Coverage::default();
+
+    for (filename, tests) in test_files.iter() {
+        for test in tests.iter() {
+            let result = testing::runner::run_test(
+                test,
+                &program.memory,
+                &program.values,
+                config.max_steps,
+                &mut coverage,
+            );
+
+            if result.passed() {
+                println!("test {} ... ok ({} steps)", test.name, result.steps);
+            } else {
+                println!("test {} ... FAILED", test.name);
+                for (line, failure) in result.failures.iter() {
+                    println!("\t{}:{}: {}", filename, line + 1, failure);
+                }
+                failed += 1;
+            }
+            count += 1;
+        }
+    }
+
+    println!();
+    println!(
+        "{} test(s), {} passed, {} failed",
+        count,
+        count - failed,
+        failed
+    );
+    if config.lcov.is_some() || config.coverage.is_some() {
+        let coverage =
+            coverage::report::SourceCoverage::new(&coverage, &program.debug, &program.memory);
+        println!("Coverage: {}", coverage.summary());
+        if let Some(ref filename) = config.lcov {
+            fs::write(filename, coverage.lcov())?;
+        }
+        if let Some(ref filename) = config.coverage {
+            let mut report = String::new();
+            for file in coverage.files.keys() {
+                report.push_str(&coverage.annotated(file, &fs::read_to_string(file)?));
+            }
+            fs::write(filename, report)?;
+        }
+    }
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(Box::new(errors::AssemblyError(format!(
+            "{} test(s) failed",
+            failed
+        ))))
+    }
+}
+
+// Simulator with the program loaded and its PC at `entry` (a label or value), along with the
+// program's line information:
+fn simulator(
+    config: &ProgramConfig,
+    entry: &Option<String>,
+) -> Result<(ssp16_sim::cpu::Ssp1601, ssp16_sim::debug::DebugInfo), Box<dyn Error>> {
+    let (program, _) = simulated_program(config)?;
+    let mut ssp = ssp16_sim::cpu::Ssp1601::new(program.memory);
+    if let Some(ref entry) = entry {
+        ssp.pc = entry_point(entry, &program.values)?;
+    }
+
+    Ok((ssp, program.debug))
+}
+
+fn entry_point(entry: &str, values: &HashMap<String, u16>) -> Result<u16, Box<dyn Error>> {
+    let address = conditionals::value(entry, values).map_err(|error| {
+        errors::AssemblyError(format!("Invalid entry point {}: {}", entry, error))
+    })?;
+    Ok(address as u16)
+}
+
+// Assembles the input file and lets GDB debug it on the simulator, serving a single session:
+pub fn gdb(config: GdbConfig) -> Result<(), Box<dyn Error>> {
+    let (ssp, debug) = simulator(&config.program, &config.entry)?;
+
+    let listener = TcpListener::bind(("127.0.0.1", config.port))?;
+    println!(
+        "Waiting for GDB on port {} (target remote :{})...",
+        config.port, config.port
+    );
+    let (stream, address) = listener.accept()?;
+    println!("GDB connected from {}", address);
+
+    let mut stub = ssp16_sim::gdb::GdbStub::new(ssp, debug);
+    ssp16_sim::gdb::serve(&mut stub, stream)?;
+    println!("GDB session ended");
+
+    Ok(())
+}
+
+// Assembles the input file and runs it on the simulator, reporting where cycles are spent:
+pub fn profile(config: ProfileConfig) -> Result<(), Box<dyn Error>> {
+    let (mut ssp, debug) = simulator(&config.program, &config.entry)?;
+    let model = match config.cost_model {
+        Some(ref filename) => timing::model::CostModel::from_file(filename)?,
+        None => timing::model::CostModel::default(),
+    };
+
+    let profile = profiling::profiler::profile(&mut ssp, &debug, &model, config.max_steps);
+    print!("{}", profile.report(&model));
+    if let Some(ref filename) = config.folded {
+        fs::write(filename, profile.folded())?;
+    }
+
+    Ok(())
+}
+
+// Assembles the input file and runs it on the simulator against a script playing the 68000 side:
+pub fn stimulus(config: StimulusConfig) -> Result<(), Box<dyn Error>> {
+    let (program, _) = simulated_program(&config.program)?;
+    let (commands, errors) = stimulus::script::parse(&fs::read_to_string(&config.script_filename)?);
+    if !errors.is_empty() {
+        return Err(Box::new(errors::AssemblyError(format!(
+            "{}:\n{}",
+            config.script_filename,
+            errors::AssemblyError::from_lines(&errors)
+        ))));
+    }
+    let model = match config.cost_model {
+        Some(ref filename) => timing::model::CostModel::from_file(filename)?,
+        None => timing::model::CostModel::default(),
+    };
+
+    let mut ssp = ssp16_sim::cpu::Ssp1601::new(program.memory);
+    if let Some(ref entry) = config.entry {
+        ssp.pc = entry_point(entry, &program.values)?;
+    }
+    let mut megadrive = ssp16_sim::megadrive::MegaDrive::new(ssp, |ssp| {
+        profiling::profiler::step(ssp, &model).map(|counters| counters.cycles)
+    });
+    megadrive.timeout = config.timeout;
+
+    let outcome = stimulus::player::play(&commands, &mut megadrive, &program.values);
+    for (line, message) in outcome.log.iter() {
+        println!("{}:{}: {}", config.script_filename, line + 1, message);
+    }
+    println!(
+        "\nStopped at cycle {} ({} instructions), {} check(s), {} failed",
+        megadrive.cycles, megadrive.ssp.steps, outcome.checks, outcome.failures
+    );
+    if let Some(ref filename) = config.dump_dram {
+        fs::write(filename, megadrive.ssp.memory.dram_bytes())?;
+    }
+
+    if outcome.passed() {
+        Ok(())
+    } else {
+        Err(Box::new(errors::AssemblyError(format!(
+            "{} failure(s) in {}",
+            outcome.failures, config.script_filename
+        ))))
+    }
+}
+
+// Renders tiles from a DRAM dump to a PNG image, checking it against a reference one if given:
+pub fn tiles(config: TilesConfig) -> Result<(), Box<dyn Error>> {
+    let error = |filename: &str, message: String| {
+        Box::new(errors::AssemblyError(format!("{}: {}", filename, message)))
+    };
+    config.layout.check().map_err(errors::AssemblyError)?;
+    let words = ssp16_sim::memory::Memory::from_bytes(&fs::read(&config.dump_filename)?).rom;
+    let palette = match config.palette {
+        Some(ref filename) => {
+            tiles::render::Palette::from_cram(&fs::read(filename)?, config.palette_line)
+                .map_err(|message| error(filename, message))?
+        }
+        None => tiles::render::Palette::default(),
+    };
+
+    let image = tiles::render::render(&words, config.offset, &config.layout, &palette)
+        .map_err(|message| error(&config.dump_filename, message))?;
+    let png = image
+        .to_png()
+        .map_err(|message| error(&config.output_filename, message))?;
+    fs::write(&config.output_filename, png)?;
+    println!(
+        "Rendered {} tile(s) ({}x{} pixels) to {}",
+        config.layout.tiles, image.width, image.height, config.output_filename
+    );
+
+    if let Some(ref filename) = config.reference {
+        let reference =
+            tiles::image::Image::from_png(&fs::read(filename)?).map_err(|m| error(filename, m))?;
+        tiles::render::compare(&image, &reference, config.offset, &config.layout)
+            .map_err(|message| error(filename, message))?;
+        println!("The image matches {}", filename);
+    }
+
+    Ok(())
+}
+
+// Assembles the input file and debugs it on the simulator in the terminal:
+pub fn debugger(config: DebuggerConfig) -> Result<(), Box<dyn Error>> {
+    let (ssp, debug) = simulator(&config.program, &config.entry)?;
+    let filename = &config.program.input_filename;
+    let source = fs::read_to_string(filename)?
+        .lines()
+        .map(|line| line.to_string())
+        .collect();
+
+    let session = debugger::session::Session::new(ssp, debug, config.history);
+    debugger::tui::run(debugger::tui::Debugger::new(session, filename, source))?;
+
+    Ok(())
+}
+
+pub fn build(config: BuildConfig) -> Result<(), Box<dyn Error>> {
+    project::build::build(&config.manifest_filename, config.force)?;
+
+    Ok(())
+}
+
 pub fn write_hex_file(filename: String, opcodes: &Vec<u8>) -> Result<(), Box<dyn Error>> {
     let mut hex_file = File::create(filename)?;
 
@@ -125,8 +1101,11 @@ pub fn write_hex_file(filename: String, opcodes: &Vec<u8>) -> Result<(), Box<dyn
 
 pub fn print_table<'a, T: fmt::UpperHex>(table: &HashMap<&'a str, T>, title: &str) {
     if table.len() > 0 {
+        let mut entries: Vec<(&&'a str, &T)> = table.iter().collect();
+        entries.sort_by_key(|(k, _)| **k);
+
         println!("**** {} ****", title);
-        table
+        entries
             .iter()
             .for_each(|(k, v)| println!("{} -> {:04X}", k, v))
     } else {
@@ -147,3 +1126,138 @@ pub fn print_debug_info<'a>(tokens: &Vec<tokens::Token>, opcodes: &Vec<u8>) {
     println!("\nGenerated opcodes:");
     println!("{:02X?}", opcodes);
 }
+
+#[cfg(test)]
+mod cli_tests {
+    use super::*;
+
+    #[test]
+    fn check_inputs_named_like_subcommands() {
+        let args = ["ssp16asm", "-b", "output.bin", "tests.svp", "rom_svp.bin"];
+        let mut config = match Command::new_from(args.iter()) {
+            Ok(Command::Assemble(config)) => config,
+            _ => panic!("tests.svp wasn't taken as the input file"),
+        };
+        assert_eq!(
+            (
+                config.input_filename.as_str(),
+                config.output_filename.as_str()
+            ),
+            ("tests.svp", "rom_svp.bin")
+        );
+
+        let directory = std::env::temp_dir().join(format!("ssp16asm_cli_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = |name: &str| directory.join(name).to_str().unwrap().to_string();
+        fs::write(path("output.bin"), [0xFF; 10]).unwrap();
+        fs::write(path("tests.svp"), "org 0002\nld a, 1234\nret\n").unwrap();
+        config.input_filename = path("tests.svp");
+        config.output_filename = path("rom_svp.bin");
+        config.input_base_rom = Some(path("output.bin"));
+
+        run(config).unwrap();
+        assert_eq!(
+            fs::read(path("rom_svp.bin")).unwrap(),
+            vec![0xFF, 0xFF, 0xFF, 0xFF, 0x08, 0x30, 0x12, 0x34, 0x00, 0x65]
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    // Error message for a command line, if it's rejected:
+    fn error(args: &[&str]) -> Option<String> {
+        Command::new_from(std::iter::once(&"ssp16asm").chain(args))
+            .err()
+            .map(|error| error.message)
+    }
+
+    #[test]
+    fn check_invalid_tile_options() {
+        let tiles = |options: &[&str]| error(&[&["tiles", "d.bin", "o.png"], options].concat());
+
+        assert_eq!(
+            tiles(&["--offset", "0100", "--tiles", "2", "--columns", "1"]),
+            None
+        );
+        assert!(tiles(&["--offset", "zz"])
+            .unwrap()
+            .ends_with("Invalid value for '--offset': zz"));
+        assert!(tiles(&["--tiles", "abc"])
+            .unwrap()
+            .ends_with("Invalid value for '--tiles': abc"));
+        assert!(tiles(&["--columns", "1.5"]).is_some());
+    }
+
+    #[test]
+    fn check_invalid_numbers() {
+        assert_eq!(error(&["test", "a.svp", "--max-steps", "1000"]), None);
+        assert!(error(&["test", "a.svp", "--max-steps", "10k"])
+            .unwrap()
+            .ends_with("Invalid value for '--max-steps': 10k"));
+        assert!(error(&["profile", "a.svp", "--max-steps", "10k"]).is_some());
+
+        assert_eq!(error(&["gdb", "a.svp", "--port", "2345"]), None);
+        for port in ["99999", "abc", "0"].iter() {
+            assert!(error(&["gdb", "a.svp", "--port", port])
+                .unwrap()
+                .ends_with(&format!("Invalid value for '--port': {}", port)));
+        }
+
+        assert_eq!(error(&["fmt", "a.svp", "--comment-column", "40"]), None);
+        assert!(error(&["fmt", "a.svp", "--label-column", "x"])
+            .unwrap()
+            .ends_with("Invalid value for '--label-column': x"));
+
+        let stimulus = ["stimulus", "a.svp", "s.txt", "--timeout"];
+        assert_eq!(error(&[&stimulus[..], &["500"]].concat()), None);
+        assert!(error(&[&stimulus[..], &["1e6"]].concat())
+            .unwrap()
+            .ends_with("Invalid value for '--timeout': 1e6"));
+    }
+
+    #[test]
+    fn check_flow_with_target_constants() {
+        let filename =
+            std::env::temp_dir().join(format!("ssp16asm_target_{}.svp", std::process::id()));
+        fs::write(&filename, "org 0400\nld st, @ST5\nret\n").unwrap();
+        let input = filename.to_str().unwrap();
+        let args = [
+            "ssp16asm",
+            "--target",
+            "svp",
+            "--flow",
+            "--timing",
+            "--no-output",
+        ];
+
+        match Command::new_from(args.iter().chain(&[input, "rom.bin"])) {
+            Ok(Command::Assemble(config)) => run(config).unwrap(),
+            _ => panic!("Expected an assembly run"),
+        }
+
+        fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn check_flow_with_defines() {
+        let filename =
+            std::env::temp_dir().join(format!("ssp16asm_define_{}.svp", std::process::id()));
+        fs::write(&filename, "org 0400\nld a, @LEVEL\nret\n").unwrap();
+        let input = filename.to_str().unwrap();
+        let args = [
+            "ssp16asm",
+            "-D",
+            "LEVEL=5",
+            "--flow",
+            "--timing",
+            "--no-output",
+        ];
+
+        match Command::new_from(args.iter().chain(&[input, "rom.bin"])) {
+            Ok(Command::Assemble(config)) => run(config).unwrap(),
+            _ => panic!("Expected an assembly run"),
+        }
+
+        fs::remove_file(&filename).unwrap();
+    }
+}
@@ -0,0 +1,438 @@
+use crate::asm::mnemonics::SspMnemonic;
+use crate::asm::operators::SspOperator;
+use crate::asm::registers::{SspGeneralRegister, SspModifier, SspPointerRegister};
+use crate::asm::targets::Target;
+use crate::assembler::assembly;
+use crate::testing::cases;
+use crate::tokenization::tokens;
+use std::collections::HashMap;
+
+// ST bits 0-2 (RPL): modulo size used by the `-` and `+` pointer modifiers
+const MODULO_SIZE_MASK: u16 = 0x0007;
+// ST bits 5-6 (ST5/ST6): make EXT3 (XST) and the other PM registers act as programmable memory
+const EXT3_MODE_MASK: u16 = 0x0060;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Rule {
+    ModuloWithoutSize,
+    BankRegisterModifier,
+    ProductDelay,
+    UntraceableControlFlow,
+    Ext3ModeNotRestored,
+    XstInPmMode,
+}
+
+impl Rule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::ModuloWithoutSize => "modulo-without-size",
+            Rule::BankRegisterModifier => "bank-register-modifier",
+            Rule::ProductDelay => "product-delay",
+            Rule::UntraceableControlFlow => "untraceable-control-flow",
+            Rule::Ext3ModeNotRestored => "ext3-mode-not-restored",
+            Rule::XstInPmMode => "xst-in-pm-mode",
+        }
+    }
+}
+
+// Lines are 0-based and columns are byte offsets within the line, same as the LSP analysis:
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+    pub rule: Rule,
+    pub message: String,
+}
+
+struct Operand<'a> {
+    start: usize,
+    text: &'a str,
+    operator: Option<SspOperator<'a>>,
+}
+
+struct Statement<'a> {
+    line: usize,
+    start: usize,
+    end: usize,
+    labelled: bool,
+    mnemonic: Option<SspMnemonic>,
+    operands: Vec<Operand<'a>>,
+}
+
+impl<'a> Statement<'a> {
+    fn operator(&self, index: usize) -> Option<SspOperator<'a>> {
+        self.operands
+            .get(index)
+            .and_then(|operand| operand.operator)
+    }
+
+    fn writes_register(&self, register: SspGeneralRegister) -> bool {
+        matches!(self.mnemonic, Some(SspMnemonic::Ld(_)))
+            && self.operator(0) == Some(SspOperator::Reg(register))
+    }
+
+    // Second operand of a load or an ALU operation (`MOD` and the DSP instructions don't read
+    // general registers):
+    fn reads_register(&self, register: SspGeneralRegister) -> bool {
+        match self.mnemonic {
+            Some(SspMnemonic::Ld(_))
+            | Some(SspMnemonic::Sub(_))
+            | Some(SspMnemonic::Cmp(_))
+            | Some(SspMnemonic::Add(_))
+            | Some(SspMnemonic::And(_))
+            | Some(SspMnemonic::Or(_))
+            | Some(SspMnemonic::Eor(_)) => self.operator(1) == Some(SspOperator::Reg(register)),
+            _ => false,
+        }
+    }
+
+    fn warning(&self, rule: Rule, message: String) -> Warning {
+        Warning {
+            line: self.line,
+            start: self.start,
+            end: self.end,
+            rule,
+            message,
+        }
+    }
+}
+
+// Looks for code that assembles fine but most likely won't do what's intended on hardware.
+// Rules are checked in source order, without following jumps. Register aliases and constants
+// of `target` are understood.
+pub fn lint(contents: &str, target: Target) -> Vec<Warning> {
+    // TEST blocks aren't code
+    let (contents, _, _) = cases::extract_tests(contents, target);
+    let contents = contents.as_str();
+    let statements = parse_statements(contents, target);
+    let valid_tokens: Vec<tokens::Token> = contents
+        .lines()
+        .flat_map(|line| tokens::split_line_with_columns(line).into_iter())
+        .map(|(_, raw)| tokens::Token::new(target.resolve(raw)))
+        .filter(|token| token.invalid_token().is_none())
+        .collect();
+    let (_, mut equs, equbs) = assembly::extract_tables(&valid_tokens);
+    for (name, value) in target.constants() {
+        equs.entry(name).or_insert(*value);
+    }
+
+    let mut warnings = bank_register_modifiers(contents);
+    // None while ST hasn't been written yet, Some(None) if written with an unknown value
+    let mut st_value: Option<Option<u16>> = None;
+    let mut ext3_mode_write: Option<&Statement> = None;
+    let mut previous: Option<&Statement> = None;
+
+    for statement in statements.iter() {
+        let mnemonic = match statement.mnemonic {
+            Some(mnemonic) => mnemonic,
+            None => {
+                previous = None;
+                continue;
+            }
+        };
+
+        // Modulo modifiers
+        let modulo_set = matches!(st_value, Some(Some(value)) if value & MODULO_SIZE_MASK != 0)
+            || st_value == Some(None);
+        for operand in statement.operands.iter() {
+            if !modulo_set && uses_modulo(operand.operator) {
+                warnings.push(Warning {
+                    line: statement.line,
+                    start: operand.start,
+                    end: operand.start + operand.text.len(),
+                    rule: Rule::ModuloWithoutSize,
+                    message: format!(
+                        "`{}` updates the pointer modulo the size set in `ST` (bits 0-2), but it hasn't been set",
+                        operand.text
+                    ),
+                });
+            }
+        }
+
+        // XST while ST5/ST6 make EXT3 act as PM3
+        let pm_mode = matches!(st_value, Some(Some(value)) if value & EXT3_MODE_MASK != 0);
+        for operand in statement.operands.iter() {
+            if pm_mode && operand.text.eq_ignore_ascii_case("xst") && operand.operator.is_some() {
+                warnings.push(Warning {
+                    line: statement.line,
+                    start: operand.start,
+                    end: operand.start + operand.text.len(),
+                    rule: Rule::XstInPmMode,
+                    message:
+                        "`XST` is used while `ST` bits 5-6 are set, so `EXT3` acts as `PM3` here"
+                            .to_string(),
+                });
+            }
+        }
+
+        // P right after a multiplication
+        if let Some(previous) = previous {
+            let multiplies = matches!(
+                previous.mnemonic,
+                Some(SspMnemonic::Mld) | Some(SspMnemonic::Mpya) | Some(SspMnemonic::Mpys)
+            ) || previous.writes_register(SspGeneralRegister::X)
+                || previous.writes_register(SspGeneralRegister::Y);
+
+            if multiplies && !statement.labelled && statement.reads_register(SspGeneralRegister::P)
+            {
+                warnings.push(statement.warning(
+                    Rule::ProductDelay,
+                    format!(
+                        "`P` is read right after `X`/`Y` changed in line {}, the new product isn't available until one instruction later",
+                        previous.line + 1
+                    ),
+                ));
+            }
+        }
+
+        // Control flow
+        if statement.writes_register(SspGeneralRegister::Pc) && immediate(statement).is_none() {
+            warnings.push(statement.warning(
+                Rule::UntraceableControlFlow,
+                "`PC` is loaded from a register or memory, the destination of this jump can't be followed".to_string(),
+            ));
+        }
+        if statement.writes_register(SspGeneralRegister::Stack) {
+            warnings.push(statement.warning(
+                Rule::UntraceableControlFlow,
+                "Writing to `STACK` pushes a return address by hand, a later `RET` will jump to it".to_string(),
+            ));
+        }
+
+        // ST writes
+        if statement.writes_register(SspGeneralRegister::St) {
+            let value = immediate(statement).and_then(|op| resolve(op, &equs, &equbs));
+
+            st_value = Some(value);
+            ext3_mode_write = match value {
+                Some(value) if value & EXT3_MODE_MASK != 0 => ext3_mode_write.or(Some(statement)),
+                _ => None,
+            };
+        }
+        if mnemonic == SspMnemonic::Ret {
+            warnings.extend(ext3_mode_write.take().map(ext3_mode_warning));
+        }
+
+        previous = Some(statement);
+    }
+    warnings.extend(ext3_mode_write.take().map(ext3_mode_warning));
+
+    warnings.sort_by_key(|w| (w.line, w.start));
+    warnings
+}
+
+fn ext3_mode_warning(statement: &Statement) -> Warning {
+    statement.warning(
+        Rule::Ext3ModeNotRestored,
+        "This `ST` write changes how `EXT3` behaves (bits 5-6), but they aren't cleared again before returning".to_string(),
+    )
+}
+
+fn parse_statements(contents: &str, target: Target) -> Vec<Statement<'_>> {
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(line, text)| {
+            let words = tokens::split_line_with_columns(text);
+            let labels = words
+                .iter()
+                .take_while(|(_, word)| matches!(tokens::Token::new(word), tokens::Token::Label(_)))
+                .count();
+            let (head_column, head) = *words.get(labels)?;
+            let (last_column, last) = *words.last()?;
+
+            Some(Statement {
+                line,
+                start: head_column,
+                end: last_column + last.len(),
+                labelled: labels > 0,
+                mnemonic: SspMnemonic::new(head),
+                operands: words[labels + 1..]
+                    .iter()
+                    .map(|(start, text)| Operand {
+                        start: *start,
+                        text,
+                        operator: SspOperator::new(target.resolve(text)),
+                    })
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+// `R3` and `R7` can't be incremented/decremented: those operands are invalid tokens for the
+// assembler, so they are looked for in the raw source to explain why.
+fn bank_register_modifiers(contents: &str) -> Vec<Warning> {
+    contents
+        .lines()
+        .enumerate()
+        .flat_map(|(line, text)| {
+            tokens::split_line_with_columns(text)
+                .into_iter()
+                .filter_map(move |(start, word)| {
+                    let register = word
+                        .trim_start_matches('(')
+                        .trim_end_matches(')')
+                        .to_lowercase();
+                    let modifier = register
+                        .strip_prefix("r3")
+                        .or_else(|| register.strip_prefix("r7"))?;
+
+                    if ["+", "-", "+!"].contains(&modifier) {
+                        Some(Warning {
+                            line,
+                            start,
+                            end: start + word.len(),
+                            rule: Rule::BankRegisterModifier,
+                            message: format!(
+                                "`{}`: `R3` and `R7` only support the `|00`, `|01`, `|10` and `|11` modifiers",
+                                word
+                            ),
+                        })
+                    } else {
+                        None
+                    }
+                })
+        })
+        .collect()
+}
+
+fn uses_modulo(operator: Option<SspOperator>) -> bool {
+    let register = match operator {
+        Some(SspOperator::Ptr(register))
+        | Some(SspOperator::PtrRef(register))
+        | Some(SspOperator::PtrDoubleRef(register)) => register,
+        _ => return false,
+    };
+
+    match register {
+        SspPointerRegister::R0(modifier)
+        | SspPointerRegister::R1(modifier)
+        | SspPointerRegister::R2(modifier)
+        | SspPointerRegister::R3(modifier)
+        | SspPointerRegister::R4(modifier)
+        | SspPointerRegister::R5(modifier)
+        | SspPointerRegister::R6(modifier)
+        | SspPointerRegister::R7(modifier) => matches!(
+            modifier,
+            SspModifier::PostIncrement | SspModifier::PostDecrementModulo
+        ),
+    }
+}
+
+fn immediate<'a>(statement: &Statement<'a>) -> Option<SspOperator<'a>> {
+    match statement.operator(1) {
+        Some(op @ SspOperator::Word(_))
+        | Some(op @ SspOperator::Byte(_))
+        | Some(op @ SspOperator::LabelRef(_)) => Some(op),
+        _ => None,
+    }
+}
+
+fn resolve(
+    operator: SspOperator,
+    equs: &HashMap<&str, u16>,
+    equbs: &HashMap<&str, u8>,
+) -> Option<u16> {
+    match operator {
+        SspOperator::Word(value) => Some(value),
+        SspOperator::Byte(value) => Some(value as u16),
+        SspOperator::LabelRef(label) => equs
+            .get(label)
+            .copied()
+            .or_else(|| equbs.get(label).map(|value| *value as u16)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod linter_tests {
+    use super::*;
+
+    fn rules(contents: &str) -> Vec<(usize, Rule)> {
+        lint(contents, Target::Generic)
+            .iter()
+            .map(|warning| (warning.line, warning.rule))
+            .collect()
+    }
+
+    #[test]
+    fn check_modulo_without_size() {
+        assert_eq!(
+            rules("ld a, (r0+!)\nld st, 0003\nld x, (r1-)\nld st, 0\nld y, (r4+)\n"),
+            vec![(4, Rule::ModuloWithoutSize)]
+        );
+        assert_eq!(
+            rules("ld st, 0\nld x, (r0-)\nld st, a\nld x, (r0-)\n"),
+            vec![(1, Rule::ModuloWithoutSize)]
+        );
+    }
+
+    #[test]
+    fn check_bank_register_modifiers() {
+        let warnings = lint(
+            "ld a, (r3+)\nld x, ((R7+!))\nld y, (r3|01)\n",
+            Target::Generic,
+        );
+
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].rule, Rule::BankRegisterModifier);
+        assert_eq!(
+            (warnings[1].line, warnings[1].start, warnings[1].end),
+            (1, 6, 14)
+        );
+    }
+
+    #[test]
+    fn check_product_delay() {
+        assert_eq!(
+            rules("mld (r0), (r4)\nld a, p\nld x, 12\nadd a, p\nld y, 1\nld -, -\nadd a, p\n"),
+            vec![(1, Rule::ProductDelay), (3, Rule::ProductDelay)]
+        );
+        assert_eq!(rules("ld x, 12\nlabel: ld a, p\n"), vec![]);
+    }
+
+    #[test]
+    fn check_untraceable_control_flow() {
+        assert_eq!(
+            rules("ld pc, 0400\nld pc, @start\nld pc, a\nld stack, x\nld x, stack\nstart: ret\n"),
+            vec![
+                (2, Rule::UntraceableControlFlow),
+                (3, Rule::UntraceableControlFlow)
+            ]
+        );
+    }
+
+    #[test]
+    fn check_ext3_mode_restore() {
+        assert_eq!(
+            rules("ld st, 0030\nld ext3, 1010\nld st, 0000\nret\n"),
+            vec![]
+        );
+        assert_eq!(
+            rules("mode: equ 0060\nld st, @mode\nld ext3, a\nret\nld st, 0020\n"),
+            vec![
+                (1, Rule::Ext3ModeNotRestored),
+                (4, Rule::Ext3ModeNotRestored)
+            ]
+        );
+    }
+
+    #[test]
+    fn check_xst_in_pm_mode() {
+        let warnings = lint(
+            "ld a, xst\nld st, @ST_PM\nld pm3, a\nld a, XST\nld st, 0\nld xst, a\n",
+            Target::Svp,
+        );
+
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|w| (w.line, w.start, w.rule))
+                .collect::<Vec<_>>(),
+            vec![(3, 6, Rule::XstInPmMode)]
+        );
+        assert_eq!(rules("ld st, 0060\nld a, xst\nld st, 0\n"), vec![]);
+    }
+}
@@ -0,0 +1,587 @@
+use crate::asm::operators;
+use crate::asm::targets::Target;
+use crate::assembler::assembly;
+use crate::assembler::assertions;
+use crate::assembler::conditionals;
+use crate::assembler::data;
+use crate::assembler::pmset;
+use crate::assembler::symbols;
+use crate::import::m68k as m68k_import;
+use crate::lint::linter;
+use crate::testing::cases;
+use crate::tokenization::tokens;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// Lines are 0-based and columns are byte offsets within the line (converting them to the
+// editor's view is up to the protocol side):
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub location: Location,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+struct SourceToken {
+    location: Location,
+    text: String,
+}
+
+// Everything known about a source file after assembling it, used to answer editor requests
+// without assembling again:
+pub struct Analysis {
+    source_tokens: Vec<SourceToken>,
+    spans: Vec<Option<(u64, u64)>>,
+    binary: Vec<u8>,
+    symbols: Vec<symbols::Symbol>,
+    imported: Vec<m68k_import::ImportedSymbol>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Analysis {
+    // `INCBIN` files are read relative to `directory`, register aliases depend on `target`:
+    pub fn new(
+        contents: &str,
+        imported: &[m68k_import::ImportedSymbol],
+        directory: &Path,
+        target: Target,
+    ) -> Analysis {
+        let mut diagnostics = Vec::new();
+        let mut tokens = Vec::new();
+        let mut lines = Vec::new();
+        let mut source_tokens = Vec::new();
+
+        let line_length = |line: usize| contents.lines().nth(line).map(str::len).unwrap_or(0);
+
+        // Neither are TEST blocks or code in skipped conditional blocks
+        let (without_tests, _, test_errors) = cases::extract_tests(contents, target);
+        let (active_source, mut preprocessing_errors) =
+            conditionals::active_source(&without_tests, &HashMap::new());
+        preprocessing_errors.extend(test_errors);
+        let (expanded_source, data_errors) = data::expanded_source(&active_source, directory);
+        let (expanded_source, pmset_errors) = pmset::expanded_source(&expanded_source, imported);
+        let (expanded_source, assertions, assertion_errors) =
+            assertions::extract_assertions(&expanded_source);
+        preprocessing_errors.extend(data_errors);
+        preprocessing_errors.extend(pmset_errors);
+        preprocessing_errors.extend(assertion_errors);
+        for (line, message) in preprocessing_errors {
+            diagnostics.push(Diagnostic {
+                location: Location {
+                    line,
+                    start: 0,
+                    end: line_length(line),
+                },
+                severity: Severity::Error,
+                message,
+            });
+        }
+        // Words generated by data and PMSET directives point to the directive's arguments
+        let data_spans: HashMap<usize, (usize, usize)> = active_source
+            .lines()
+            .enumerate()
+            .filter_map(|(line, text)| {
+                data::data_line(text)
+                    .map(|(_, data)| data)
+                    .or_else(|| pmset::pmset_line(text))
+                    .map(|data| {
+                        let start = data.column + data.keyword.len();
+                        let end = data.arguments.as_ptr() as usize - text.as_ptr() as usize
+                            + data.arguments.len();
+                        (line, (start.min(end), end))
+                    })
+            })
+            .collect();
+        let contents = expanded_source.as_str();
+
+        for (line, text) in contents.lines().enumerate() {
+            for (column, raw) in tokens::split_line_with_columns(text) {
+                let resolved = target.resolve(raw);
+                let location = match data_spans.get(&line) {
+                    Some((start, end))
+                        if !matches!(tokens::Token::new(raw), tokens::Token::Label(_)) =>
+                    {
+                        Location {
+                            line,
+                            start: *start,
+                            end: *end,
+                        }
+                    }
+                    _ => Location {
+                        line,
+                        start: column,
+                        end: column + raw.len(),
+                    },
+                };
+
+                match tokens::Token::new(resolved) {
+                    tokens::Token::Invalid(_) => diagnostics.push(Diagnostic {
+                        location,
+                        severity: Severity::Error,
+                        message: format!("Invalid token: {}", raw),
+                    }),
+                    token => {
+                        tokens.push(token);
+                        lines.push(line + 1);
+                        source_tokens.push(SourceToken {
+                            location,
+                            text: raw.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+
+        let (symbol_table, mut equ_table, equb_table) = assembly::extract_tables(&tokens);
+        for (name, value) in target.constants() {
+            equ_table.entry(name).or_insert(*value);
+        }
+        m68k_import::merge_into_tables(imported, &symbol_table, &mut equ_table, &equb_table);
+
+        for (line, message) in
+            assertions::failed_assertions(&assertions, &symbol_table, &equ_table, &equb_table)
+        {
+            diagnostics.push(Diagnostic {
+                location: Location {
+                    line,
+                    start: 0,
+                    end: line_length(line),
+                },
+                severity: Severity::Error,
+                message,
+            });
+        }
+
+        let mut definitions: HashMap<&str, usize> = HashMap::new();
+        let mut undefined: HashSet<usize> = HashSet::new();
+        for (index, token) in tokens.iter().enumerate() {
+            let location = &source_tokens[index].location;
+
+            match token {
+                tokens::Token::Label(label) => {
+                    if let Some(line) = definitions.get(label) {
+                        diagnostics.push(Diagnostic {
+                            location: location.clone(),
+                            severity: Severity::Warning,
+                            message: format!("`{}` is already defined in line {}", label, line),
+                        });
+                    } else {
+                        definitions.insert(label, location.line + 1);
+                    }
+                }
+                tokens::Token::Operator(operators::SspOperator::LabelRef(label))
+                    if !symbol_table.contains_key(label)
+                        && !equ_table.contains_key(label)
+                        && !equb_table.contains_key(label) =>
+                {
+                    undefined.insert(index);
+                    diagnostics.push(Diagnostic {
+                        location: location.clone(),
+                        severity: Severity::Error,
+                        message: format!("Undefined symbol: {}", label),
+                    });
+                }
+                _ => (),
+            }
+        }
+
+        let (spans, binary) = match assembly::assemble(
+            &tokens,
+            &symbol_table,
+            &equ_table,
+            &equb_table,
+            false,
+            None,
+            false,
+            4,
+        ) {
+            Ok(result) => {
+                diagnostics.extend(
+                    result
+                        .errors
+                        .into_iter()
+                        .filter(|(index, _)| !undefined.contains(index))
+                        .map(|(index, error)| Diagnostic {
+                            location: source_tokens[index].location.clone(),
+                            severity: Severity::Error,
+                            message: error.to_string(),
+                        }),
+                );
+                (result.spans, result.binary)
+            }
+            Err(e) => {
+                diagnostics.push(Diagnostic {
+                    location: Location {
+                        line: 0,
+                        start: 0,
+                        end: 0,
+                    },
+                    severity: Severity::Error,
+                    message: e.to_string(),
+                });
+                (Vec::new(), Vec::new())
+            }
+        };
+
+        diagnostics.extend(
+            linter::lint(contents, target)
+                .into_iter()
+                .map(|warning| Diagnostic {
+                    location: Location {
+                        line: warning.line,
+                        start: warning.start,
+                        end: warning.end,
+                    },
+                    severity: Severity::Warning,
+                    message: format!("{} [{}]", warning.message, warning.rule.name()),
+                }),
+        );
+
+        let symbols =
+            symbols::collect_symbols(&tokens, &lines, "", &symbol_table, &equ_table, &equb_table);
+
+        diagnostics.sort_by_key(|d| (d.location.line, d.location.start));
+
+        Analysis {
+            source_tokens,
+            spans,
+            binary,
+            symbols,
+            imported: imported.to_vec(),
+            diagnostics,
+        }
+    }
+
+    pub fn symbol_names(&self) -> Vec<String> {
+        self.symbols
+            .iter()
+            .map(|symbol| symbol.name.clone())
+            .chain(self.imported.iter().map(|symbol| symbol.name.clone()))
+            .collect()
+    }
+
+    pub fn hover(&self, line: usize, column: usize) -> Option<String> {
+        let symbol_info = self
+            .symbol_at(line, column)
+            .and_then(|name| self.describe_symbol(name));
+        let encoding = self.encoding(line);
+
+        match (symbol_info, encoding) {
+            (Some(info), Some(encoding)) => Some(format!("{}\n\n---\n\n{}", info, encoding)),
+            (info, encoding) => info.or(encoding),
+        }
+    }
+
+    pub fn definition(&self, line: usize, column: usize) -> Option<Location> {
+        let name = self.symbol_at(line, column)?;
+
+        self.source_tokens
+            .iter()
+            .find(|token| token.text.strip_suffix(':') == Some(name))
+            .map(|token| token.location.clone())
+    }
+
+    pub fn references(
+        &self,
+        line: usize,
+        column: usize,
+        include_declaration: bool,
+    ) -> Vec<Location> {
+        let name = match self.symbol_at(line, column) {
+            Some(name) => name,
+            None => return Vec::new(),
+        };
+
+        self.source_tokens
+            .iter()
+            .filter(|token| {
+                token.text.strip_prefix('@') == Some(name)
+                    || (include_declaration && token.text.strip_suffix(':') == Some(name))
+            })
+            .map(|token| token.location.clone())
+            .collect()
+    }
+
+    // Name of the symbol (label definition or reference) under the cursor:
+    fn symbol_at(&self, line: usize, column: usize) -> Option<&str> {
+        let token = self.source_tokens.iter().find(|token| {
+            token.location.line == line
+                && token.location.start <= column
+                && column <= token.location.end
+        })?;
+
+        token
+            .text
+            .strip_prefix('@')
+            .or_else(|| token.text.strip_suffix(':'))
+    }
+
+    fn describe_symbol(&self, name: &str) -> Option<String> {
+        if let Some(symbol) = self.symbols.iter().find(|symbol| symbol.name == name) {
+            let description = match symbol.kind {
+                symbols::SymbolKind::Label => format!(
+                    "**{}**: label\n\nAddress: `{:04X}` (M68000: `{:06X}`)",
+                    name,
+                    symbol.value,
+                    symbol.value as u32 * 2
+                ),
+                symbols::SymbolKind::Equ => {
+                    format!(
+                        "**{}**: word constant\n\nValue: `{:04X}`",
+                        name, symbol.value
+                    )
+                }
+                symbols::SymbolKind::Equb => {
+                    format!(
+                        "**{}**: byte constant\n\nValue: `{:02X}`",
+                        name, symbol.value
+                    )
+                }
+            };
+
+            Some(format!(
+                "{}\n\nDefined in line {}",
+                description, symbol.line
+            ))
+        } else {
+            self.imported
+                .iter()
+                .find(|symbol| symbol.name == name)
+                .map(|symbol| match symbol.kind {
+                    m68k_import::ImportedKind::Label => format!(
+                        "**{}**: M68000 label (imported)\n\nAddress: `{:04X}` (M68000: `{:06X}`)",
+                        name,
+                        symbol.value,
+                        symbol.value * 2
+                    ),
+                    m68k_import::ImportedKind::Constant => format!(
+                        "**{}**: M68000 constant (imported)\n\nValue: `{:X}`",
+                        name, symbol.value
+                    ),
+                })
+        }
+    }
+
+    // Opcode words generated by a line, along with the (word) address they were written to:
+    fn encoding(&self, line: usize) -> Option<String> {
+        let (start, end) = self
+            .source_tokens
+            .iter()
+            .zip(self.spans.iter())
+            .filter(|(token, _)| token.location.line == line)
+            .filter_map(|(_, span)| *span)
+            .fold(None, |acc: Option<(u64, u64)>, (start, end)| match acc {
+                Some((first, last)) => Some((first.min(start), last.max(end))),
+                None => Some((start, end)),
+            })?;
+
+        let words = self.binary[start as usize..end as usize]
+            .chunks(2)
+            .map(|word| format!("{:02X}{:02X}", word[0], word[1]))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        Some(format!("`{:04X}`: `{}`", start / 2, words))
+    }
+}
+
+#[cfg(test)]
+mod analysis_tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+value: EQU 1234
+org 0400
+start: ld a, @value
+bra always, @start
+ld x, @missing # comment
+lol
+start: ret
+";
+
+    fn location(line: usize, start: usize, end: usize) -> Location {
+        Location { line, start, end }
+    }
+
+    #[test]
+    fn check_diagnostics() {
+        let analysis = Analysis::new(SOURCE, &[], Path::new(""), Target::Generic);
+        let messages: Vec<(usize, Severity, &str)> = analysis
+            .diagnostics
+            .iter()
+            .map(|d| (d.location.line, d.severity, d.message.as_str()))
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                (4, Severity::Error, "Undefined symbol: missing"),
+                (5, Severity::Error, "Invalid token: lol"),
+                (6, Severity::Warning, "`start` is already defined in line 3"),
+            ]
+        );
+        assert_eq!(analysis.diagnostics[0].location, location(4, 6, 14));
+    }
+
+    #[test]
+    fn check_conditional_blocks() {
+        let analysis = Analysis::new(
+            "ifdef DEBUG\nlol\nendif\nendif\n",
+            &[],
+            Path::new(""),
+            Target::Generic,
+        );
+        let messages: Vec<(usize, Severity, &str)> = analysis
+            .diagnostics
+            .iter()
+            .map(|d| (d.location.line, d.severity, d.message.as_str()))
+            .collect();
+
+        assert_eq!(messages, vec![(3, Severity::Error, "`endif` without `if`")]);
+        assert_eq!(analysis.diagnostics[0].location, location(3, 0, 5));
+    }
+
+    #[test]
+    fn check_data_directives() {
+        let analysis = Analysis::new(
+            "org 0400\ntext: ascii \"Hi\" # greeting\nend: db 100\nld a, @end\n",
+            &[],
+            Path::new(""),
+            Target::Generic,
+        );
+        let messages: Vec<(&Location, &str)> = analysis
+            .diagnostics
+            .iter()
+            .map(|d| (&d.location, d.message.as_str()))
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec![(&location(2, 0, 11), "Value doesn't fit in a byte: 100")]
+        );
+        assert!(analysis.hover(1, 14).unwrap().contains("`0400`: `4869`"));
+    }
+
+    #[test]
+    fn check_assertions_and_limits() {
+        let analysis = Analysis::new(
+            "org 03FF\nlimit 03FF\nstart: ld a, 1234\nend: assert @end <= 0400, \"IRAM overflow\"\n",
+            &[],
+            Path::new(""),
+            Target::Generic,
+        );
+        let messages: Vec<(usize, &str)> = analysis
+            .diagnostics
+            .iter()
+            .map(|d| (d.location.line, d.message.as_str()))
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                (2, "Code placed past the LIMIT of 03FF (ends at 0400)"),
+                (3, "Assertion failed: IRAM overflow"),
+            ]
+        );
+        assert_eq!(analysis.diagnostics[1].location, location(3, 0, 41));
+    }
+
+    #[test]
+    fn check_svp_target() {
+        let analysis = Analysis::new(
+            "org 0400\nld st, @ST_PM\nld a, xst # PM3\nld pmc, al\nld st, 0000\n",
+            &[],
+            Path::new(""),
+            Target::Svp,
+        );
+        let messages: Vec<(&Location, &str)> = analysis
+            .diagnostics
+            .iter()
+            .map(|d| (&d.location, d.message.as_str()))
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                (
+                    &location(2, 6, 9),
+                    "`XST` is used while `ST` bits 5-6 are set, so `EXT3` acts as `PM3` here [xst-in-pm-mode]"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_navigation() {
+        let analysis = Analysis::new(SOURCE, &[], Path::new(""), Target::Generic);
+
+        assert_eq!(analysis.definition(3, 14), Some(location(2, 0, 6)));
+        assert_eq!(analysis.definition(2, 14), Some(location(0, 0, 6)));
+        assert_eq!(analysis.definition(2, 8), None);
+        assert_eq!(analysis.references(0, 2, false), vec![location(2, 13, 19)]);
+        assert_eq!(
+            analysis.references(3, 12, true),
+            vec![location(2, 0, 6), location(3, 12, 18), location(6, 0, 6)]
+        );
+    }
+
+    #[test]
+    fn check_hover() {
+        let analysis = Analysis::new(
+            "value: EQU 1234\norg 0400\nstart: ld a, @value\nbra always, @start\n",
+            &[],
+            Path::new(""),
+            Target::Generic,
+        );
+
+        assert_eq!(
+            analysis.hover(3, 14),
+            Some(
+                "**start**: label\n\nAddress: `0400` (M68000: `000800`)\n\nDefined in line 3\n\n---\n\n`0402`: `4C00 0400`"
+                    .to_string()
+            )
+        );
+
+        let constant = analysis.hover(2, 15).unwrap();
+        assert!(constant.contains("**value**: word constant\n\nValue: `1234`"));
+        assert!(constant.ends_with("`0400`: `0830 1234`"));
+
+        assert_eq!(analysis.hover(1, 0), None);
+    }
+
+    #[test]
+    fn check_imported_symbols() {
+        let imported = vec![m68k_import::ImportedSymbol {
+            name: "padding".to_string(),
+            value: 0x4F8,
+            kind: m68k_import::ImportedKind::Label,
+        }];
+        let analysis = Analysis::new(
+            "ld a, @padding\n",
+            &imported,
+            Path::new(""),
+            Target::Generic,
+        );
+
+        assert!(analysis.diagnostics.is_empty());
+        assert!(analysis
+            .hover(0, 8)
+            .unwrap()
+            .contains("Address: `04F8` (M68000: `0009F0`)"));
+        assert_eq!(analysis.symbol_names(), vec!["padding".to_string()]);
+    }
+}
@@ -0,0 +1,194 @@
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CompletionKind {
+    Mnemonic,
+    Macro,
+    Register,
+    Condition,
+    AccOperation,
+    FlagOperation,
+    Symbol,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: &'static str,
+}
+
+const MNEMONICS: [(&str, &str); 21] = [
+    ("ld", "LD dst, src"),
+    ("ldi", "LDI dst, word"),
+    ("sub", "SUB A, src"),
+    ("subi", "SUBI A, word / SUBI byte"),
+    ("cmp", "CMP A, src"),
+    ("cmpi", "CMPI A, word / CMPI byte"),
+    ("add", "ADD A, src"),
+    ("addi", "ADDI A, word / ADDI byte"),
+    ("and", "AND A, src"),
+    ("andi", "ANDI A, word / ANDI byte"),
+    ("or", "OR A, src"),
+    ("ori", "ORI A, word / ORI byte"),
+    ("eor", "EOR A, src"),
+    ("eori", "EORI A, word / EORI byte"),
+    ("bra", "BRA cond, address"),
+    ("ret", "RET"),
+    ("call", "CALL cond, address"),
+    ("mld", "MLD (rj), (ri)"),
+    ("mpya", "MPYA (rj), (ri)"),
+    ("mpys", "MPYS (rj), (ri)"),
+    ("mod", "MOD cond, acc_op / MOD f, flag_op"),
+];
+
+const MACROS: [(&str, &str); 7] = [
+    ("org", "ORG address"),
+    ("dw", "DW word"),
+    ("equ", "label: EQU word"),
+    ("equb", "label: EQUB byte"),
+    ("export", "EXPORT @symbol"),
+    ("align", "ALIGN words"),
+    ("limit", "LIMIT last_address"),
+];
+
+const REGISTERS: [&str; 16] = [
+    "-", "x", "y", "a", "st", "stack", "pc", "p", "ext0", "ext1", "ext2", "ext3", "ext4", "ext5",
+    "ext6", "ext7",
+];
+
+const POINTER_REGISTERS: [&str; 34] = [
+    "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r0+", "r1+", "r2+", "r4+", "r5+", "r6+",
+    "r0-", "r1-", "r2-", "r4-", "r5-", "r6-", "r0+!", "r1+!", "r2+!", "r4+!", "r5+!", "r6+!",
+    "r3|00", "r3|01", "r3|10", "r3|11", "r7|00", "r7|01", "r7|10", "r7|11",
+];
+
+const CONDITION_FLAGS: [&str; 9] = ["l", "z", "ov", "n", "diof", "gpi0", "gpi1", "gpi2", "gpi3"];
+
+const ACC_OPERATIONS: [&str; 8] = ["ror", "rol", "shr", "shl", "inc", "dec", "neg", "abs"];
+
+const FLAG_OPERATIONS: [&str; 8] = [
+    "resl", "setl", "resie", "setie", "resop", "setop", "res", "set",
+];
+
+// Everything that can be written in a SSP16 source, plus references to the given symbols:
+pub fn completions(symbol_names: &[String]) -> Vec<Completion> {
+    let item = |label: String, kind: CompletionKind, detail: &'static str| Completion {
+        label,
+        kind,
+        detail,
+    };
+
+    MNEMONICS
+        .iter()
+        .map(|(name, detail)| item(name.to_string(), CompletionKind::Mnemonic, detail))
+        .chain(
+            MACROS
+                .iter()
+                .map(|(name, detail)| item(name.to_string(), CompletionKind::Macro, detail)),
+        )
+        .chain(
+            REGISTERS
+                .iter()
+                .map(|name| item(name.to_string(), CompletionKind::Register, "register")),
+        )
+        .chain(POINTER_REGISTERS.iter().map(|name| {
+            item(
+                name.to_string(),
+                CompletionKind::Register,
+                "pointer register",
+            )
+        }))
+        .chain(std::iter::once(item(
+            "always".to_string(),
+            CompletionKind::Condition,
+            "condition",
+        )))
+        .chain(CONDITION_FLAGS.iter().flat_map(|flag| {
+            vec![
+                item(
+                    format!("{}=0", flag),
+                    CompletionKind::Condition,
+                    "condition",
+                ),
+                item(
+                    format!("{}=1", flag),
+                    CompletionKind::Condition,
+                    "condition",
+                ),
+            ]
+        }))
+        .chain(std::iter::once(item(
+            "f".to_string(),
+            CompletionKind::Condition,
+            "MOD f, flag operation",
+        )))
+        .chain(ACC_OPERATIONS.iter().map(|name| {
+            item(
+                name.to_string(),
+                CompletionKind::AccOperation,
+                "accumulator operation",
+            )
+        }))
+        .chain(FLAG_OPERATIONS.iter().map(|name| {
+            item(
+                name.to_string(),
+                CompletionKind::FlagOperation,
+                "flag operation",
+            )
+        }))
+        .chain(
+            symbol_names
+                .iter()
+                .map(|name| item(format!("@{}", name), CompletionKind::Symbol, "symbol")),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod completion_tests {
+    use super::*;
+    use crate::asm::macros;
+    use crate::asm::mnemonics;
+    use crate::asm::operators;
+
+    #[test]
+    fn check_completions_are_valid_tokens() {
+        for completion in completions(&[]) {
+            let is_valid = match completion.kind {
+                CompletionKind::Mnemonic => {
+                    mnemonics::SspMnemonic::new(&completion.label).is_some()
+                }
+                CompletionKind::Macro => macros::SspMacro::new(&completion.label).is_some(),
+                CompletionKind::Register => matches!(
+                    operators::SspOperator::new(&completion.label),
+                    Some(operators::SspOperator::Reg(_)) | Some(operators::SspOperator::Ptr(_))
+                ),
+                CompletionKind::Condition => {
+                    operators::SspMnemonicCondition::new(&completion.label).is_some()
+                        || operators::SspMnemonicConditionFlag::new(&completion.label).is_some()
+                }
+                CompletionKind::AccOperation => {
+                    operators::SspMnemonicAccOperation::new(&completion.label).is_some()
+                }
+                CompletionKind::FlagOperation => {
+                    operators::SspMnemonicFlagOperation::new(&completion.label).is_some()
+                }
+                CompletionKind::Symbol => false,
+            };
+
+            assert!(is_valid, "{:?}", completion);
+        }
+    }
+
+    #[test]
+    fn check_symbol_completions() {
+        let items = completions(&["start".to_string()]);
+
+        assert!(items.contains(&Completion {
+            label: "@start".to_string(),
+            kind: CompletionKind::Symbol,
+            detail: "symbol",
+        }));
+        assert!(items.iter().any(|item| item.label == "gpi0=0"));
+        assert!(items.iter().any(|item| item.label == "resop"));
+    }
+}
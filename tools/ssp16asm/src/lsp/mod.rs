@@ -0,0 +1,3 @@
+pub mod analysis;
+pub mod completion;
+pub mod server;
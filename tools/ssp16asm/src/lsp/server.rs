@@ -0,0 +1,350 @@
+use super::analysis;
+use super::completion;
+use crate::asm::targets::Target;
+use crate::import::m68k as m68k_import;
+use crate::project::manifest;
+use lsp_server::{Connection, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait, PublishDiagnostics,
+};
+use lsp_types::request::Request as RequestTrait;
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, References};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, DiagnosticSeverity,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, MarkupContent, MarkupKind, OneOf, Position, PublishDiagnosticsParams,
+    Range, ReferenceParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+    Url,
+};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+struct Document {
+    text: String,
+    analysis: analysis::Analysis,
+}
+
+// Language server for SSP16 sources, talking LSP over stdin/stdout. Every document is assembled
+// on its own each time it changes. If a project manifest (`svp.toml`) is found in the folder
+// of the document (or any of its parents), the M68000 symbols it imports are available too.
+pub fn run() -> Result<(), Box<dyn Error>> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = serde_json::to_value(ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["@".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        ..ServerCapabilities::default()
+    })?;
+    connection.initialize(capabilities)?;
+
+    main_loop(&connection)?;
+
+    // The writer thread only finishes once the connection is gone:
+    drop(connection);
+    io_threads.join()?;
+
+    Ok(())
+}
+
+fn main_loop(connection: &Connection) -> Result<(), Box<dyn Error>> {
+    let mut documents: HashMap<Url, Document> = HashMap::new();
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                connection
+                    .sender
+                    .send(Message::Response(handle_request(&documents, request)))?;
+            }
+            Message::Notification(notification) => {
+                if let Some(uri) = handle_notification(&mut documents, notification) {
+                    connection
+                        .sender
+                        .send(Message::Notification(diagnostics(&documents, uri)))?;
+                }
+            }
+            Message::Response(_) => (),
+        }
+    }
+
+    Ok(())
+}
+
+// Returns the document that has to be analyzed again (if any):
+fn handle_notification(
+    documents: &mut HashMap<Url, Document>,
+    notification: Notification,
+) -> Option<Url> {
+    match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: lsp_types::DidOpenTextDocumentParams =
+                serde_json::from_value(notification.params).ok()?;
+            update_document(
+                documents,
+                &params.text_document.uri,
+                params.text_document.text,
+            );
+            Some(params.text_document.uri)
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: lsp_types::DidChangeTextDocumentParams =
+                serde_json::from_value(notification.params).ok()?;
+            let text = params.content_changes.into_iter().last()?.text;
+            update_document(documents, &params.text_document.uri, text);
+            Some(params.text_document.uri)
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: lsp_types::DidCloseTextDocumentParams =
+                serde_json::from_value(notification.params).ok()?;
+            documents.remove(&params.text_document.uri);
+            None
+        }
+        _ => None,
+    }
+}
+
+fn update_document(documents: &mut HashMap<Url, Document>, uri: &Url, text: String) {
+    let imported = project_imports(uri);
+    let directory = uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| path.parent().map(|directory| directory.to_path_buf()))
+        .unwrap_or_default();
+    let analysis = analysis::Analysis::new(&text, &imported, &directory, project_target(uri));
+
+    documents.insert(uri.clone(), Document { text, analysis });
+}
+
+fn project_imports(uri: &Url) -> Vec<m68k_import::ImportedSymbol> {
+    let path = match uri.to_file_path() {
+        Ok(path) => path,
+        Err(_) => return Vec::new(),
+    };
+
+    path.ancestors()
+        .skip(1)
+        .map(|directory| directory.join("svp.toml"))
+        .find(|manifest_path| manifest_path.is_file())
+        .and_then(|manifest_path| {
+            let project = manifest::Manifest::from_file(&manifest_path.to_string_lossy()).ok()?;
+            let directory = manifest_path.parent().unwrap_or_else(|| Path::new(""));
+
+            Some(
+                project
+                    .m68k?
+                    .symbols
+                    .iter()
+                    .filter_map(|symbols| {
+                        m68k_import::import_file(&directory.join(symbols).to_string_lossy()).ok()
+                    })
+                    .flatten()
+                    .collect(),
+            )
+        })
+        .unwrap_or_default()
+}
+
+// Target of the manifest entry assembling this document, if any:
+fn project_target(uri: &Url) -> Target {
+    let path = match uri.to_file_path() {
+        Ok(path) => path,
+        Err(_) => return Target::default(),
+    };
+
+    path.ancestors()
+        .skip(1)
+        .map(|directory| directory.join("svp.toml"))
+        .find(|manifest_path| manifest_path.is_file())
+        .and_then(|manifest_path| {
+            let project = manifest::Manifest::from_file(&manifest_path.to_string_lossy()).ok()?;
+            let directory = manifest_path.parent().unwrap_or_else(|| Path::new(""));
+
+            project
+                .svp
+                .iter()
+                .find(|svp| directory.join(&svp.source) == path)
+                .map(|svp| svp.target)
+        })
+        .unwrap_or_default()
+}
+
+fn diagnostics(documents: &HashMap<Url, Document>, uri: Url) -> Notification {
+    let diagnostics = documents
+        .get(&uri)
+        .map(|document| {
+            document
+                .analysis
+                .diagnostics
+                .iter()
+                .map(|diagnostic| lsp_types::Diagnostic {
+                    range: to_range(&document.text, &diagnostic.location),
+                    severity: Some(match diagnostic.severity {
+                        analysis::Severity::Error => DiagnosticSeverity::ERROR,
+                        analysis::Severity::Warning => DiagnosticSeverity::WARNING,
+                    }),
+                    source: Some("ssp16asm".to_string()),
+                    message: diagnostic.message.clone(),
+                    ..lsp_types::Diagnostic::default()
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Notification::new(
+        PublishDiagnostics::METHOD.to_string(),
+        PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        },
+    )
+}
+
+fn handle_request(documents: &HashMap<Url, Document>, request: Request) -> Response {
+    let id = request.id.clone();
+    let result = match request.method.as_str() {
+        HoverRequest::METHOD => serde_json::from_value::<HoverParams>(request.params)
+            .ok()
+            .and_then(|params| {
+                let position = params.text_document_position_params;
+                let (document, line, column) =
+                    locate(documents, &position.text_document.uri, position.position)?;
+
+                document.analysis.hover(line, column).map(|value| Hover {
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value,
+                    }),
+                    range: None,
+                })
+            })
+            .map(serde_json::to_value),
+        GotoDefinition::METHOD => serde_json::from_value::<GotoDefinitionParams>(request.params)
+            .ok()
+            .and_then(|params| {
+                let position = params.text_document_position_params;
+                let uri = position.text_document.uri;
+                let (document, line, column) = locate(documents, &uri, position.position)?;
+
+                document.analysis.definition(line, column).map(|location| {
+                    GotoDefinitionResponse::Scalar(lsp_types::Location {
+                        uri: uri.clone(),
+                        range: to_range(&document.text, &location),
+                    })
+                })
+            })
+            .map(serde_json::to_value),
+        References::METHOD => serde_json::from_value::<ReferenceParams>(request.params)
+            .ok()
+            .and_then(|params| {
+                let position = params.text_document_position;
+                let uri = position.text_document.uri;
+                let (document, line, column) = locate(documents, &uri, position.position)?;
+
+                Some(
+                    document
+                        .analysis
+                        .references(line, column, params.context.include_declaration)
+                        .iter()
+                        .map(|location| lsp_types::Location {
+                            uri: uri.clone(),
+                            range: to_range(&document.text, location),
+                        })
+                        .collect::<Vec<lsp_types::Location>>(),
+                )
+            })
+            .map(serde_json::to_value),
+        Completion::METHOD => serde_json::from_value::<CompletionParams>(request.params)
+            .ok()
+            .map(|params| {
+                let symbol_names = documents
+                    .get(&params.text_document_position.text_document.uri)
+                    .map(|document| document.analysis.symbol_names())
+                    .unwrap_or_default();
+
+                completion::completions(&symbol_names)
+                    .into_iter()
+                    .map(to_completion_item)
+                    .collect::<Vec<CompletionItem>>()
+            })
+            .map(serde_json::to_value),
+        _ => {
+            return Response::new_err(
+                id,
+                lsp_server::ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request: {}", request.method),
+            )
+        }
+    };
+
+    match result {
+        Some(Ok(value)) => Response::new_ok(id, value),
+        _ => Response::new_ok(id, serde_json::Value::Null),
+    }
+}
+
+fn to_completion_item(item: completion::Completion) -> CompletionItem {
+    CompletionItem {
+        kind: Some(match item.kind {
+            completion::CompletionKind::Mnemonic => CompletionItemKind::KEYWORD,
+            completion::CompletionKind::Macro => CompletionItemKind::KEYWORD,
+            completion::CompletionKind::Register => CompletionItemKind::VARIABLE,
+            completion::CompletionKind::Condition => CompletionItemKind::ENUM_MEMBER,
+            completion::CompletionKind::AccOperation => CompletionItemKind::OPERATOR,
+            completion::CompletionKind::FlagOperation => CompletionItemKind::OPERATOR,
+            completion::CompletionKind::Symbol => CompletionItemKind::REFERENCE,
+        }),
+        detail: Some(item.detail.to_string()),
+        label: item.label,
+        ..CompletionItem::default()
+    }
+}
+
+// Finds the document and converts a LSP position (UTF-16 based) to a byte column:
+fn locate<'a>(
+    documents: &'a HashMap<Url, Document>,
+    uri: &Url,
+    position: Position,
+) -> Option<(&'a Document, usize, usize)> {
+    let document = documents.get(uri)?;
+    let line = position.line as usize;
+    let text = document.text.lines().nth(line).unwrap_or("");
+
+    let mut utf16_column = 0;
+    let column = text
+        .char_indices()
+        .find(|(_, c)| {
+            let found = utf16_column >= position.character as usize;
+            utf16_column += c.len_utf16();
+            found
+        })
+        .map(|(index, _)| index)
+        .unwrap_or(text.len());
+
+    Some((document, line, column))
+}
+
+fn to_range(text: &str, location: &analysis::Location) -> Range {
+    let line = text.lines().nth(location.line).unwrap_or("");
+    let utf16_column = |column: usize| {
+        line.get(..column)
+            .map(|prefix| prefix.encode_utf16().count())
+            .unwrap_or(column) as u32
+    };
+
+    Range {
+        start: Position::new(location.line as u32, utf16_column(location.start)),
+        end: Position::new(location.line as u32, utf16_column(location.end)),
+    }
+}
@@ -1,13 +1,31 @@
-use ssp16asm::Config;
+use ssp16asm::Command;
 use std::process;
 
 fn main() {
-    if let Ok(config) = Config::new_from_args() {
-        if let Err(e) = ssp16asm::run(config) {
+    let result = match Command::new_from_args() {
+        Ok(Command::Assemble(config)) => {
+            ssp16asm::run(config).map(|_| "Assembly process complete.")
+        }
+        Ok(Command::Watch(config)) => ssp16asm::watch(config).map(|_| ""),
+        Ok(Command::Build(config)) => ssp16asm::build(config).map(|_| "Build complete."),
+        Ok(Command::Lsp) => ssp16asm::lsp().map(|_| ""),
+        Ok(Command::Format(config)) => ssp16asm::format(config).map(|_| ""),
+        Ok(Command::Convert(config)) => ssp16asm::convert(config).map(|_| "Conversion complete."),
+        Ok(Command::Lint(config)) => ssp16asm::lint(config).map(|_| ""),
+        Ok(Command::Test(config)) => ssp16asm::test(config).map(|_| ""),
+        Ok(Command::Gdb(config)) => ssp16asm::gdb(config).map(|_| ""),
+        Ok(Command::Profile(config)) => ssp16asm::profile(config).map(|_| ""),
+        Ok(Command::Stimulus(config)) => ssp16asm::stimulus(config).map(|_| ""),
+        Ok(Command::Tiles(config)) => ssp16asm::tiles(config).map(|_| ""),
+        Err(_) => return,
+    };
+
+    match result {
+        Ok("") => (),
+        Ok(message) => println!("{}", message),
+        Err(e) => {
             eprintln!("Application error(s): \n\n{}", e);
             process::exit(1);
-        } else {
-            println!("Assembly process complete.");
-        };
+        }
     }
 }
@@ -0,0 +1,384 @@
+use crate::asm::mnemonics::SspMnemonic;
+use crate::asm::operators::SspOperator;
+use crate::asm::registers::SspGeneralRegister;
+use crate::timing::model::{CostModel, Region};
+use ssp16_sim::cpu::Ssp1601;
+use ssp16_sim::debug::DebugInfo;
+use ssp16_sim::errors::SimError;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+// Where instructions with no label before them are counted:
+pub const NO_LABEL: &str = "(no label)";
+
+// Width of the histogram bars in reports (a bar this long is 100% of the cycles):
+const BAR_WIDTH: usize = 20;
+
+// Cycles are split in those spent running from each region (fetching, reading program memory and
+// refilling the pipeline after a branch) and those spent in PM accesses:
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Counters {
+    pub instructions: u64,
+    pub cycles: u64,
+    pub iram: u64,
+    pub rom: u64,
+    pub irom: u64,
+    pub pm: u64,
+    pub pm_accesses: u64,
+}
+
+impl Counters {
+    fn add(&mut self, other: &Counters) {
+        self.instructions += other.instructions;
+        self.cycles += other.cycles;
+        self.iram += other.iram;
+        self.rom += other.rom;
+        self.irom += other.irom;
+        self.pm += other.pm;
+        self.pm_accesses += other.pm_accesses;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum End {
+    // The routine profiled returned (a `RET` with the stack empty)
+    Returned,
+    Limit,
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub start: String,
+    // By closest label before each instruction (`NO_LABEL` if there's none)
+    pub labels: BTreeMap<String, Counters>,
+    pub total: Counters,
+    // Cycles by call stack (routines from the start one on, separated by `;`)
+    pub stacks: BTreeMap<String, u64>,
+    pub end: End,
+}
+
+// Runs the program from the current PC for `max_steps` instructions at most, costing each one with
+// `model` like the timing estimate does (but only counting branches when they're taken). Calls and
+// returns are followed to know the routine each instruction runs in.
+pub fn profile(ssp: &mut Ssp1601, debug: &DebugInfo, model: &CostModel, max_steps: u64) -> Profile {
+    let start = label(debug, ssp.pc);
+    let mut frames = vec![start.clone()];
+    let mut labels: BTreeMap<String, Counters> = BTreeMap::new();
+    let mut total = Counters::default();
+    let mut stacks: BTreeMap<String, u64> = BTreeMap::new();
+    let mut end = End::Limit;
+
+    for _ in 0..max_steps {
+        let pc = ssp.pc;
+        let depth = ssp.stack.len();
+        let mnemonic = ssp
+            .instruction(pc)
+            .and_then(|i| i.operands())
+            .map(|(m, _)| m);
+
+        let counters = if mnemonic == Some(SspMnemonic::Ret) && depth == 0 {
+            end = End::Returned;
+            final_return(ssp, model)
+        } else {
+            match step(ssp, model) {
+                Ok(counters) => counters,
+                Err(error) => {
+                    end = End::Error(error.0);
+                    break;
+                }
+            }
+        };
+        let name = debug.label(pc).map(|(name, _)| name).unwrap_or(NO_LABEL);
+        labels.entry(name.to_string()).or_default().add(&counters);
+        total.add(&counters);
+        *stacks.entry(frames.join(";")).or_default() += counters.cycles;
+
+        if ssp.stack.len() > depth {
+            frames.push(label(debug, ssp.pc));
+        } else if ssp.stack.len() < depth && frames.len() > 1 {
+            frames.pop();
+        }
+        if end == End::Returned {
+            break;
+        }
+    }
+
+    Profile {
+        start,
+        labels,
+        total,
+        stacks,
+        end,
+    }
+}
+
+// Runs an instruction, returning what it cost:
+pub fn step(ssp: &mut Ssp1601, model: &CostModel) -> Result<Counters, SimError> {
+    let pc = ssp.pc;
+    let instruction = ssp.instruction(pc);
+    let size = instruction.as_ref().and_then(|i| i.size()).unwrap_or(1);
+    let operands = instruction
+        .and_then(|i| i.operands())
+        .map(|(_, operands)| operands)
+        .unwrap_or_default();
+
+    ssp.step()?;
+    Ok(cost(pc, size, &operands, ssp.pc, model))
+}
+
+// The `RET` ending the profiled routine has nowhere to return to, so it's costed as the jump it
+// would be without running it:
+fn final_return(ssp: &Ssp1601, model: &CostModel) -> Counters {
+    let pc = ssp.pc;
+    let operands = ssp
+        .instruction(pc)
+        .and_then(|i| i.operands())
+        .map(|(_, operands)| operands)
+        .unwrap_or_default();
+
+    cost(pc, 1, &operands, pc, model)
+}
+
+// Routine names in call stacks (the address for code with no label before it):
+fn label(debug: &DebugInfo, address: u16) -> String {
+    match debug.label(address) {
+        Some((name, _)) => name.to_string(),
+        None => format!("{:04X}", address),
+    }
+}
+
+fn cost(pc: u16, size: u16, operands: &[SspOperator], next: u16, model: &CostModel) -> Counters {
+    let mut cycles = size as u64 * model.cycles_per_word(Region::of(pc)) as u64;
+    let mut pm_accesses = 0;
+    for operand in operands {
+        match operand {
+            SspOperator::Reg(SspGeneralRegister::Ext0)
+            | SspOperator::Reg(SspGeneralRegister::Ext1)
+            | SspOperator::Reg(SspGeneralRegister::Ext2)
+            | SspOperator::Reg(SspGeneralRegister::Ext3)
+            | SspOperator::Reg(SspGeneralRegister::Ext4) => pm_accesses += 1,
+            SspOperator::PtrDoubleRef(_) | SspOperator::PtrAccumulator => {
+                cycles += model.program_memory_read as u64
+            }
+            _ => (),
+        }
+    }
+    if next != pc.wrapping_add(size) {
+        cycles += model.branch as u64;
+    }
+
+    let pm = pm_accesses * model.pm_access as u64;
+    let mut counters = Counters {
+        instructions: 1,
+        cycles: cycles + pm,
+        pm,
+        pm_accesses,
+        ..Counters::default()
+    };
+    match Region::of(pc) {
+        Region::Iram => counters.iram = cycles,
+        Region::Rom => counters.rom = cycles,
+        Region::Irom => counters.irom = cycles,
+    }
+    counters
+}
+
+impl Profile {
+    // Labels by cycles spent, with their share as a histogram bar:
+    pub fn report(&self, model: &CostModel) -> String {
+        let mut report = String::new();
+
+        writeln!(
+            report,
+            "Profile from {}: {} instructions, {} cycles ({:.2} us, {:.2}% of a 60 Hz frame)",
+            self.start,
+            self.total.instructions,
+            self.total.cycles,
+            model.microseconds(self.total.cycles),
+            model.frame_percentage(self.total.cycles)
+        )
+        .unwrap();
+        match self.end {
+            End::Returned => writeln!(report, "{} returned", self.start),
+            End::Limit => writeln!(
+                report,
+                "Stopped after {} instructions",
+                self.total.instructions
+            ),
+            End::Error(ref error) => writeln!(report, "Stopped by an error: {}", error),
+        }
+        .unwrap();
+
+        writeln!(
+            report,
+            "\n\t{:<24} {:>10} {:>10} {:>7} {:>10} {:>10} {:>10} {:>10} {:>9}",
+            "Label", "Instrs", "Cycles", "Share", "IRAM", "ROM", "IROM", "PM", "PM access"
+        )
+        .unwrap();
+        let mut labels: Vec<(&String, &Counters)> = self.labels.iter().collect();
+        labels.sort_by(|(a, a_counters), (b, b_counters)| {
+            b_counters.cycles.cmp(&a_counters.cycles).then(a.cmp(b))
+        });
+        for (name, counters) in labels.iter().copied() {
+            self.row(&mut report, name, counters, true);
+        }
+        self.row(&mut report, "Total", &self.total, false);
+
+        report
+    }
+
+    fn row(&self, report: &mut String, name: &str, counters: &Counters, bar: bool) {
+        let share = match self.total.cycles {
+            0 => 0.0,
+            total => counters.cycles as f64 / total as f64,
+        };
+        let row = format!(
+            "\t{:<24} {:>10} {:>10} {:>6.2}% {:>10} {:>10} {:>10} {:>10} {:>9} {}",
+            name,
+            counters.instructions,
+            counters.cycles,
+            share * 100.0,
+            counters.iram,
+            counters.rom,
+            counters.irom,
+            counters.pm,
+            counters.pm_accesses,
+            if bar {
+                "#".repeat((share * BAR_WIDTH as f64).round() as usize)
+            } else {
+                String::new()
+            }
+        );
+        writeln!(report, "{}", row.trim_end()).unwrap();
+    }
+
+    // Call stacks with their cycles, one per line, as flame graph tools take them:
+    pub fn folded(&self) -> String {
+        self.stacks
+            .iter()
+            .map(|(stack, cycles)| format!("{} {}\n", stack, cycles))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod profiler_tests {
+    use super::*;
+    use ssp16_isa::instructions::Instruction;
+    use ssp16_sim::memory::Memory;
+
+    // Simulator with `code` at 0x400 and labels in it:
+    fn simulator(code: &[&str], labels: &[(&str, u16)]) -> (Ssp1601, DebugInfo) {
+        let mut rom = vec![0; 0x400];
+        for line in code {
+            rom.extend(line.parse::<Instruction>().unwrap().encode().unwrap());
+        }
+        let mut debug = DebugInfo::default();
+        for (name, address) in labels {
+            debug.labels.insert(name.to_string(), *address);
+        }
+        (Ssp1601::new(Memory::new(rom)), debug)
+    }
+
+    #[test]
+    fn check_profile() {
+        // main calls setup, which calls fill: 2 writes to PM4 in a loop
+        let (mut ssp, debug) = simulator(
+            &[
+                "call always, 0408",
+                "ret",
+                "ld ext4, a",
+                "subi 01",
+                "bra z=0, 0403",
+                "ret",
+                "ld a, 0002",
+                "call always, 0403",
+                "ret",
+            ],
+            &[("main", 0x400), ("fill", 0x403), ("setup", 0x408)],
+        );
+        let profile = profile(&mut ssp, &debug, &CostModel::default(), 100);
+
+        assert_eq!(profile.end, End::Returned);
+        // The call and the final RET (2 + 1 taken)
+        assert_eq!(
+            profile.labels["main"],
+            Counters {
+                instructions: 2,
+                cycles: 8,
+                rom: 8,
+                ..Counters::default()
+            }
+        );
+        // Twice (2 + 2 PM) + 2, the branch taken once (4 + 1) and not the other (4), and RET
+        assert_eq!(
+            profile.labels["fill"],
+            Counters {
+                instructions: 7,
+                cycles: 24,
+                rom: 20,
+                pm: 4,
+                pm_accesses: 2,
+                ..Counters::default()
+            }
+        );
+        assert_eq!(profile.labels["setup"].cycles, 12);
+        assert_eq!(profile.total.instructions, 12);
+        assert_eq!(profile.total.cycles, 44);
+        assert_eq!(
+            profile.folded(),
+            "main 8\nmain;setup 12\nmain;setup;fill 24\n"
+        );
+
+        let report = profile.report(&CostModel::default());
+        assert!(report.starts_with("Profile from main: 12 instructions, 44 cycles"));
+        assert!(report.contains("main returned"));
+        let rows: Vec<&str> = report
+            .lines()
+            .skip(4)
+            .map(|line| line.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(rows, vec!["fill", "setup", "main", "Total"]);
+        assert!(report.contains(" 54.55% ") && report.contains(" ###########\n"));
+    }
+
+    #[test]
+    fn check_final_return() {
+        // Clamps A to 0x10, the branch not taken:
+        let (mut ssp, debug) = simulator(
+            &["cmpi 10", "bra n=0, 0403", "ret", "ld a, 0010", "ret"],
+            &[("clamp", 0x400)],
+        );
+        let profile = profile(&mut ssp, &debug, &CostModel::default(), 100);
+
+        assert_eq!(profile.end, End::Returned);
+        assert_eq!(profile.total.instructions, 3);
+        // CMPI (1 word), BRA not taken (2 words) and RET (1 word + 1 taken)
+        assert_eq!(profile.total.cycles, 9);
+        assert_eq!(profile.folded(), "clamp 9\n");
+    }
+
+    #[test]
+    fn check_limits() {
+        let (mut ssp, debug) = simulator(&["bra always, 0400"], &[]);
+        let profile = profile(&mut ssp, &debug, &CostModel::default(), 10);
+
+        assert_eq!(profile.end, End::Limit);
+        assert_eq!(profile.total.cycles, 50);
+        assert_eq!(profile.folded(), "0400 50\n");
+        assert_eq!(
+            profile.labels.keys().collect::<Vec<&String>>(),
+            vec![NO_LABEL]
+        );
+        assert!(profile
+            .report(&CostModel::default())
+            .contains("Stopped after 10 instructions"));
+
+        let (mut ssp, debug) = simulator(&["call always, 0400"], &[]);
+        assert_eq!(
+            super::profile(&mut ssp, &debug, &CostModel::default(), 10).end,
+            End::Error("Stack overflow at 0400".to_string())
+        );
+    }
+}
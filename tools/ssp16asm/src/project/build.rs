@@ -0,0 +1,314 @@
+use super::manifest;
+use crate::assembler::errors;
+use crate::export::m68k;
+use crate::Config;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StageStatus {
+    Built,
+    UpToDate,
+}
+
+// Runs the stages described in a project manifest in order: M68000 build, SVP assembly on top of
+// it and EPROM images. A stage only runs if any of its inputs is newer than its outputs (or
+// if a previous stage had to run). Returns the status of each stage.
+pub fn build(
+    manifest_filename: &str,
+    force: bool,
+) -> Result<Vec<(String, StageStatus)>, Box<dyn Error>> {
+    let manifest =
+        manifest::Manifest::from_file(manifest_filename).map_err(|e| stage_error("manifest", e))?;
+    let root = Path::new(manifest_filename)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    let path = |filename: &str| root.join(filename);
+
+    let mut result = Vec::new();
+    let mut should_build = force;
+
+    if let Some(ref m68k_stage) = manifest.m68k {
+        let inputs: Vec<PathBuf> = m68k_stage
+            .sources
+            .iter()
+            .map(|source| path(source))
+            .chain(std::iter::once(PathBuf::from(manifest_filename)))
+            .collect();
+
+        should_build |=
+            m68k_stage.sources.is_empty() || is_stale(&inputs, &[path(&m68k_stage.output)]);
+        if should_build {
+            run_command(&m68k_stage.command, root).map_err(|e| stage_error("m68k", e))?;
+        }
+        result.push(report_stage("m68k", should_build));
+    }
+
+    let rom_output = path(&manifest.rom.output);
+    let svp_inputs: Vec<PathBuf> = manifest
+        .svp
+        .iter()
+        .flat_map(|svp| {
+            std::iter::once(path(&svp.source)).chain(crate::included_files(&svp_config(
+                &manifest, svp, None, &path,
+            )))
+        })
+        .chain(manifest.m68k.iter().flat_map(|m68k_stage| {
+            std::iter::once(path(&m68k_stage.output))
+                .chain(m68k_stage.symbols.iter().map(|symbols| path(symbols)))
+        }))
+        .chain(std::iter::once(PathBuf::from(manifest_filename)))
+        .collect();
+    let svp_outputs: Vec<PathBuf> = manifest
+        .svp
+        .iter()
+        .flat_map(|svp| {
+            vec![
+                &svp.symbols_json,
+                &svp.symbols_yaml,
+                &svp.export_asm68k,
+                &svp.export_vasm,
+                &svp.export_c,
+            ]
+        })
+        .flatten()
+        .map(|filename| path(filename))
+        .chain(std::iter::once(rom_output.clone()))
+        .collect();
+
+    should_build |= is_stale(&svp_inputs, &svp_outputs);
+    if should_build {
+        for (index, svp) in manifest.svp.iter().enumerate() {
+            let base_rom = if index > 0 {
+                Some(rom_output.clone())
+            } else {
+                manifest
+                    .m68k
+                    .as_ref()
+                    .map(|m68k_stage| path(&m68k_stage.output))
+            };
+
+            crate::run(svp_config(&manifest, svp, base_rom, &path))
+                .map_err(|e| stage_error(&format!("svp ({})", svp.source), e))?;
+        }
+    }
+    result.push(report_stage("svp", should_build));
+
+    if let Some(ref eprom) = manifest.eprom {
+        let inputs = [rom_output.clone(), PathBuf::from(manifest_filename)];
+        let first_eprom = PathBuf::from(format!(
+            "{}_0.o",
+            path(&eprom.output_prefix).to_string_lossy()
+        ));
+
+        should_build |= is_stale(&inputs, &[first_eprom]);
+        if should_build {
+            svptool::run(svptool::Config {
+                input_filename: to_string(&rom_output),
+                output_prefix: to_string(&path(&eprom.output_prefix)),
+                reverse_endianness: eprom.reverse_endianness,
+                split_binary: eprom.split_size_kb.is_some(),
+                split_size_kb: eprom.split_size_kb.unwrap_or(512),
+            })
+            .map_err(|e| stage_error("eprom", e))?;
+        }
+        result.push(report_stage("eprom", should_build));
+    }
+
+    Ok(result)
+}
+
+fn svp_config(
+    manifest: &manifest::Manifest,
+    svp: &manifest::SvpSource,
+    base_rom: Option<PathBuf>,
+    path: &dyn Fn(&str) -> PathBuf,
+) -> Config {
+    let optional_path = |filename: &Option<String>| filename.as_ref().map(|f| to_string(&path(f)));
+
+    Config {
+        input_filename: to_string(&path(&svp.source)),
+        output_filename: to_string(&path(&manifest.rom.output)),
+        is_debug: false,
+        is_hex: manifest.rom.hex,
+        input_base_rom: base_rom.map(|base_rom| to_string(&base_rom)),
+        should_fill: manifest.rom.fill,
+        max_binary_size_in_megs: manifest.rom.size_megs,
+        symbols_json: optional_path(&svp.symbols_json),
+        symbols_yaml: optional_path(&svp.symbols_yaml),
+        exports: [
+            (m68k::ExportFormat::Asm68k, &svp.export_asm68k),
+            (m68k::ExportFormat::Vasm, &svp.export_vasm),
+            (m68k::ExportFormat::CHeader, &svp.export_c),
+        ]
+        .iter()
+        .filter_map(|(format, filename)| optional_path(filename).map(|f| (*format, f)))
+        .collect(),
+        imports: manifest
+            .m68k
+            .iter()
+            .flat_map(|m68k_stage| m68k_stage.symbols.iter())
+            .map(|symbols| to_string(&path(symbols)))
+            .collect(),
+        defines: Vec::new(),
+        target: svp.target,
+        syntax: svp.syntax,
+        write_output: true,
+        flow_report: false,
+        flow_dot: None,
+        timing_report: false,
+        cost_model: None,
+        verbose: false,
+    }
+}
+
+// A stage is stale if any of its inputs or outputs is missing (running it reports the missing
+// input), or if an output is older than the newest of its inputs:
+pub fn is_stale(inputs: &[PathBuf], outputs: &[PathBuf]) -> bool {
+    let modified = |file: &PathBuf| fs::metadata(file).and_then(|m| m.modified()).ok();
+    let times = |files: &[PathBuf]| files.iter().map(modified).collect::<Option<Vec<_>>>();
+
+    match (times(inputs), times(outputs)) {
+        (None, _) | (_, None) => true,
+        (Some(inputs), Some(outputs)) => inputs.into_iter().max() > outputs.into_iter().min(),
+    }
+}
+
+fn run_command(command: &str, directory: &Path) -> Result<(), Box<dyn Error>> {
+    println!("[m68k] {}", command);
+
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+
+    let directory = if directory.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        directory
+    };
+    let status = shell.arg(command).current_dir(directory).status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Box::new(errors::AssemblyError(format!(
+            "Command `{}` failed ({})",
+            command, status
+        ))))
+    }
+}
+
+fn report_stage(stage: &str, was_built: bool) -> (String, StageStatus) {
+    if was_built {
+        println!("[{}] Built", stage);
+        (stage.to_string(), StageStatus::Built)
+    } else {
+        println!("[{}] Up to date", stage);
+        (stage.to_string(), StageStatus::UpToDate)
+    }
+}
+
+fn stage_error(stage: &str, error: Box<dyn Error>) -> Box<dyn Error> {
+    Box::new(errors::AssemblyError(format!(
+        "Stage `{}` failed:\n\n{}",
+        stage, error
+    )))
+}
+
+fn to_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+#[cfg(test)]
+mod build_tests {
+    use super::*;
+
+    fn stages(statuses: &[(&str, StageStatus)]) -> Vec<(String, StageStatus)> {
+        statuses
+            .iter()
+            .map(|(stage, status)| (stage.to_string(), *status))
+            .collect()
+    }
+
+    #[test]
+    fn check_build_only_rebuilds_when_needed() {
+        let directory = std::env::temp_dir().join(format!("ssp16asm_build_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(
+            directory.join("svp.toml"),
+            "[rom]\noutput = \"rom.bin\"\n\n[[svp]]\nsource = \"main.svp\"\n\n[eprom]\noutput_prefix = \"eprom\"\nreverse_endianness = true\n",
+        )
+        .unwrap();
+        fs::write(directory.join("main.svp"), "org 0002\nld a, 1234\nret\n").unwrap();
+        let manifest = to_string(&directory.join("svp.toml"));
+
+        assert_eq!(
+            build(&manifest, false).unwrap(),
+            stages(&[("svp", StageStatus::Built), ("eprom", StageStatus::Built)])
+        );
+        assert_eq!(
+            fs::read(directory.join("eprom_0.o")).unwrap(),
+            vec![0, 0, 0, 0, 0x30, 0x08, 0x34, 0x12, 0x65, 0x00]
+        );
+        assert_eq!(
+            build(&manifest, false).unwrap(),
+            stages(&[
+                ("svp", StageStatus::UpToDate),
+                ("eprom", StageStatus::UpToDate)
+            ])
+        );
+        assert_eq!(
+            build(&manifest, true).unwrap(),
+            stages(&[("svp", StageStatus::Built), ("eprom", StageStatus::Built)])
+        );
+
+        // The included file is an input, so removing it makes the stage run to report it:
+        fs::write(
+            directory.join("main.svp"),
+            "org 0002\nincbin \"data.bin\"\n",
+        )
+        .unwrap();
+        fs::write(directory.join("data.bin"), [0x12, 0x34]).unwrap();
+        build(&manifest, false).unwrap();
+        fs::remove_file(directory.join("data.bin")).unwrap();
+        let error = build(&manifest, false).unwrap_err().to_string();
+        assert!(error.contains("Unable to read data.bin"));
+
+        fs::write(directory.join("main.svp"), "org 0002\nld a, @missing\n").unwrap();
+        let error = build(&manifest, true).unwrap_err().to_string();
+        assert!(error.starts_with("Stage `svp (main.svp)` failed"));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn check_staleness() {
+        let directory = std::env::temp_dir().join(format!("ssp16asm_stale_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let input = directory.join("input");
+        let output = directory.join("output");
+        fs::write(&input, "").unwrap();
+
+        let inputs = std::slice::from_ref(&input);
+        let outputs = std::slice::from_ref(&output);
+
+        assert!(is_stale(inputs, outputs));
+        fs::write(&output, "").unwrap();
+        assert!(!is_stale(inputs, outputs));
+        assert!(!is_stale(&[], outputs));
+        assert!(is_stale(
+            &[input.clone(), directory.join("missing")],
+            outputs
+        ));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
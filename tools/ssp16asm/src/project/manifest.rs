@@ -0,0 +1,206 @@
+use crate::asm::targets::Target;
+use crate::assembler::errors;
+use crate::syntax::Syntax;
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+
+// Project manifest (`svp.toml`) describing how a mixed M68000/SVP ROM is built. All paths are
+// relative to the directory containing the manifest.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub m68k: Option<M68kStage>,
+    pub rom: RomOutput,
+    #[serde(default)]
+    pub svp: Vec<SvpSource>,
+    pub eprom: Option<EpromLayout>,
+}
+
+// External command building the M68000 side of the ROM (i.e.: SNASM68K), its output is used as
+// the base ROM for SVP code. If no sources are listed the command runs on every build.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct M68kStage {
+    pub command: String,
+    pub output: String,
+    #[serde(default)]
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub symbols: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RomOutput {
+    pub output: String,
+    #[serde(default)]
+    pub hex: bool,
+    #[serde(default)]
+    pub fill: bool,
+    #[serde(default = "default_size_megs")]
+    pub size_megs: u8,
+}
+
+// SVP sources are assembled in order, each one on top of the ROM built by the previous one:
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SvpSource {
+    pub source: String,
+    pub symbols_json: Option<String>,
+    pub symbols_yaml: Option<String>,
+    pub export_asm68k: Option<String>,
+    pub export_vasm: Option<String>,
+    pub export_c: Option<String>,
+    #[serde(default)]
+    pub target: Target,
+    #[serde(default)]
+    pub syntax: Syntax,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct EpromLayout {
+    pub output_prefix: String,
+    #[serde(default)]
+    pub reverse_endianness: bool,
+    pub split_size_kb: Option<u16>,
+}
+
+fn default_size_megs() -> u8 {
+    4
+}
+
+impl Manifest {
+    pub fn from_file(filename: &str) -> Result<Manifest, Box<dyn Error>> {
+        Manifest::parse(&fs::read_to_string(filename)?)
+    }
+
+    pub fn parse(contents: &str) -> Result<Manifest, Box<dyn Error>> {
+        let manifest: Manifest = toml::from_str(contents)?;
+
+        if ![1, 2, 4].contains(&manifest.rom.size_megs) {
+            return Err(Box::new(errors::AssemblyError(format!(
+                "Invalid ROM size in manifest: {}MB (valid sizes are 1, 2 or 4)",
+                manifest.rom.size_megs
+            ))));
+        }
+        if manifest.svp.is_empty() {
+            return Err(Box::new(errors::AssemblyError(
+                "No SVP sources found in manifest (missing [[svp]] entries)".to_string(),
+            )));
+        }
+        if let Some(split_size_kb) = manifest.eprom.as_ref().and_then(|e| e.split_size_kb) {
+            if split_size_kb == 0 || split_size_kb > 4096 {
+                return Err(Box::new(errors::AssemblyError(format!(
+                    "Invalid EPROM split size in manifest: {}KB",
+                    split_size_kb
+                ))));
+            }
+        }
+
+        Ok(manifest)
+    }
+}
+
+#[cfg(test)]
+mod manifest_tests {
+    use super::*;
+
+    #[test]
+    fn check_full_manifest() {
+        let manifest = Manifest::parse(
+            r#"
+[m68k]
+command = "SNASM68K.EXE /p main.asm,output.map,output.lst,output.bin"
+sources = ["main.asm", "svp.asm"]
+output = "output.bin"
+symbols = ["output.map"]
+
+[rom]
+output = "rom/rom_svp.bin"
+fill = true
+size_megs = 2
+
+[[svp]]
+source = "svp_gfx.svp"
+symbols_json = "rom/svp_symbols.json"
+export_asm68k = "svp_symbols.asm"
+target = "svp"
+syntax = "disasm"
+
+[eprom]
+output_prefix = "rom/eprom"
+reverse_endianness = true
+split_size_kb = 512
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            manifest.m68k,
+            Some(M68kStage {
+                command: "SNASM68K.EXE /p main.asm,output.map,output.lst,output.bin".to_string(),
+                output: "output.bin".to_string(),
+                sources: vec!["main.asm".to_string(), "svp.asm".to_string()],
+                symbols: vec!["output.map".to_string()],
+            })
+        );
+        assert_eq!(
+            manifest.rom,
+            RomOutput {
+                output: "rom/rom_svp.bin".to_string(),
+                hex: false,
+                fill: true,
+                size_megs: 2,
+            }
+        );
+        assert_eq!(manifest.svp.len(), 1);
+        assert_eq!(manifest.svp[0].source, "svp_gfx.svp");
+        assert_eq!(
+            manifest.svp[0].export_asm68k,
+            Some("svp_symbols.asm".to_string())
+        );
+        assert_eq!(manifest.svp[0].export_c, None);
+        assert_eq!(manifest.svp[0].target, Target::Svp);
+        assert_eq!(manifest.svp[0].syntax, Syntax::Disasm);
+        assert_eq!(
+            manifest.eprom,
+            Some(EpromLayout {
+                output_prefix: "rom/eprom".to_string(),
+                reverse_endianness: true,
+                split_size_kb: Some(512),
+            })
+        );
+    }
+
+    #[test]
+    fn check_minimal_manifest() {
+        let manifest =
+            Manifest::parse("[rom]\noutput = \"rom.bin\"\n\n[[svp]]\nsource = \"a.svp\"\n")
+                .unwrap();
+
+        assert_eq!(manifest.m68k, None);
+        assert_eq!(manifest.eprom, None);
+        assert_eq!(manifest.rom.size_megs, 4);
+        assert!(!manifest.rom.fill);
+        assert_eq!(manifest.svp[0].target, Target::Generic);
+    }
+
+    #[test]
+    fn check_invalid_manifests() {
+        assert!(Manifest::parse("[rom]\noutput = \"rom.bin\"\n").is_err());
+        assert!(Manifest::parse(
+            "[rom]\noutput = \"rom.bin\"\nsize_megs = 3\n\n[[svp]]\nsource = \"a.svp\"\n"
+        )
+        .is_err());
+        assert!(Manifest::parse(
+            "[rom]\noutput = \"rom.bin\"\nunknown = 1\n\n[[svp]]\nsource = \"a.svp\"\n"
+        )
+        .is_err());
+        assert!(Manifest::parse(
+            "[rom]\noutput = \"rom.bin\"\n\n[[svp]]\nsource = \"a.svp\"\n\n[eprom]\noutput_prefix = \"e\"\nsplit_size_kb = 0\n"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::Config;
+use std::error::Error;
+use std::fs;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Editors usually save files in more than one write, so give them a moment before reassembling:
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+// Every file that can change the result of an assembly run, binary files included with `INCBIN`
+// in the source too:
+pub fn watched_files(config: &Config) -> Vec<String> {
+    std::iter::once(&config.input_filename)
+        .chain(config.input_base_rom.iter())
+        .chain(config.imports.iter())
+        .cloned()
+        .chain(
+            crate::included_files(config)
+                .iter()
+                .map(|file| file.to_string_lossy().to_string()),
+        )
+        .collect()
+}
+
+pub fn modification_times(files: &[String]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| fs::metadata(file).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+// Assembles the input file and keeps doing it every time any of the watched files changes.
+// Errors are reported but don't stop the loop, only a Ctrl+C does.
+pub fn watch(config: Config) -> Result<(), Box<dyn Error>> {
+    let mut files = watched_files(&config);
+    let mut last_times = modification_times(&files);
+
+    loop {
+        match crate::run(config.clone()) {
+            Ok(_) => println!("Assembly process complete."),
+            Err(e) => eprintln!("Application error(s): \n\n{}", e),
+        }
+        println!("\nWatching {:?} for changes (Ctrl+C to stop)...", files);
+
+        loop {
+            thread::sleep(POLL_INTERVAL);
+
+            let times = modification_times(&files);
+            if times != last_times {
+                thread::sleep(SETTLE_TIME);
+                // The source may have added or removed INCBIN files:
+                files = watched_files(&config);
+                last_times = modification_times(&files);
+                break;
+            }
+        }
+
+        println!("\nChanges detected, assembling again...\n");
+    }
+}
+
+#[cfg(test)]
+mod watch_tests {
+    use super::*;
+
+    #[test]
+    fn check_watched_files() {
+        let mut config = Config {
+            input_filename: "main.svp".to_string(),
+            output_filename: "rom.bin".to_string(),
+            is_debug: false,
+            is_hex: false,
+            input_base_rom: Some("output.bin".to_string()),
+            should_fill: false,
+            max_binary_size_in_megs: 4,
+            symbols_json: Some("symbols.json".to_string()),
+            symbols_yaml: None,
+            exports: Vec::new(),
+            imports: vec!["output.map".to_string()],
+            defines: Vec::new(),
+            target: Default::default(),
+            syntax: Default::default(),
+            write_output: false,
+            flow_report: false,
+            flow_dot: None,
+            timing_report: false,
+            cost_model: None,
+            verbose: false,
+        };
+
+        assert_eq!(
+            watched_files(&config),
+            vec!["main.svp", "output.bin", "output.map"]
+        );
+
+        let directory =
+            std::env::temp_dir().join(format!("ssp16asm_incbin_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let source = directory.join("main.svp");
+        fs::write(
+            &source,
+            "tiles: incbin \"tiles.bin\"\nifdef OLD\nincbin \"old.bin\"\nendif\n",
+        )
+        .unwrap();
+        config.input_filename = source.to_string_lossy().to_string();
+
+        // Files in skipped blocks aren't part of the assembly:
+        let path = |name: &str| directory.join(name).to_string_lossy().to_string();
+        assert_eq!(watched_files(&config)[3..], [path("tiles.bin")]);
+        config.defines = vec!["OLD".to_string()];
+        assert_eq!(
+            watched_files(&config)[3..],
+            [path("tiles.bin"), path("old.bin")]
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn check_modification_times() {
+        let file = std::env::temp_dir().join(format!("ssp16asm_watch_{}", std::process::id()));
+        let files = vec![file.to_string_lossy().to_string()];
+
+        assert_eq!(modification_times(&files), vec![None]);
+        fs::write(&file, "ret\n").unwrap();
+        assert!(modification_times(&files)[0].is_some());
+
+        fs::remove_file(&file).unwrap();
+    }
+}
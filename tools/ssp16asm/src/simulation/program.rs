@@ -0,0 +1,118 @@
+use crate::assembler::assembly::Assembly;
+use crate::tokenization::tokens::Token;
+use ssp16_sim::debug::{DebugInfo, SourceLine};
+use ssp16_sim::memory::Memory;
+use std::collections::{BTreeMap, HashMap};
+
+// An assembled program as the simulator runs it, with the values tests and debuggers can refer to
+// (labels and constants) and its line information:
+pub struct Program {
+    pub memory: Memory,
+    pub values: HashMap<String, u16>,
+    pub debug: DebugInfo,
+}
+
+impl Program {
+    // `lines` are the (1-based) lines `tokens` were found in, and `source` the file as written (so
+    // debuggers show the lines the way the user wrote them):
+    pub fn new(
+        filename: &str,
+        source: &str,
+        tokens: &[Token],
+        lines: &[usize],
+        assembly: &Assembly,
+        labels: &HashMap<&str, u16>,
+        values: HashMap<String, u16>,
+    ) -> Program {
+        Program {
+            memory: Memory::from_bytes(&assembly.binary),
+            values,
+            debug: DebugInfo {
+                labels: labels
+                    .iter()
+                    .map(|(name, address)| (name.to_string(), *address))
+                    .collect(),
+                lines: line_table(filename, source, tokens, lines, assembly),
+            },
+        }
+    }
+}
+
+// Program memory word address of each instruction, with the line it was assembled from. `DW`
+// words and anything past the first 64K words (which the SSP1601 can't run) are left out.
+pub fn line_table(
+    filename: &str,
+    source: &str,
+    tokens: &[Token],
+    lines: &[usize],
+    assembly: &Assembly,
+) -> BTreeMap<u16, SourceLine> {
+    let source_lines: Vec<&str> = source.lines().collect();
+    let mut table = BTreeMap::new();
+
+    for (index, span) in assembly.spans.iter().enumerate() {
+        let start = match span {
+            Some((start, _)) if start / 2 <= 0xFFFF => start / 2,
+            _ => continue,
+        };
+        // Spans belong to the last token of each instruction (or DW value)
+        let head = (0..=index)
+            .rev()
+            .find(|i| matches!(tokens[*i], Token::Mnemonic(_) | Token::Macro(_)));
+        let line = match head.map(|head| (tokens[head], lines[head])) {
+            Some((Token::Mnemonic(_), line)) => line,
+            _ => continue,
+        };
+
+        table.entry(start as u16).or_insert_with(|| SourceLine {
+            file: filename.to_string(),
+            line,
+            text: source_lines.get(line - 1).unwrap_or(&"").to_string(),
+        });
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod program_tests {
+    use super::*;
+    use crate::assembler::assembly;
+    use crate::tokenization::tokens;
+
+    #[test]
+    fn check_line_table() {
+        let source = "org 400\nstart:\n\tld x, 0001 # one\n\n\tdw 1234\n\tld y, x\n\tret\n";
+        let (tokens, lines) = tokens::tokenize_with_lines(source).unwrap();
+        let (symbols, equs, equbs) = assembly::extract_tables(&tokens);
+        let assembly =
+            assembly::assemble(&tokens, &symbols, &equs, &equbs, false, None, false, 4).unwrap();
+
+        let program = Program::new(
+            "test.svp",
+            source,
+            &tokens,
+            &lines,
+            &assembly,
+            &symbols,
+            HashMap::new(),
+        );
+        let lines: Vec<(u16, usize, &str)> = program
+            .debug
+            .lines
+            .iter()
+            .map(|(address, line)| (*address, line.line, line.text.as_str()))
+            .collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                (0x400, 3, "\tld x, 0001 # one"),
+                (0x403, 6, "\tld y, x"),
+                (0x404, 7, "\tret"),
+            ]
+        );
+        assert_eq!(program.debug.labels["start"], 0x400);
+        assert_eq!(program.memory.program(0x402), 0x1234);
+    }
+}
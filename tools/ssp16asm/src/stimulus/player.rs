@@ -0,0 +1,223 @@
+use super::script::Command;
+use crate::asm::registers::SspGeneralRegister;
+use crate::assembler::conditionals;
+use crate::testing::cases::Location;
+use ssp16_sim::cpu::Ssp1601;
+use ssp16_sim::errors::SimError;
+use ssp16_sim::megadrive::MegaDrive;
+use std::collections::HashMap;
+
+// What a script did, in order: the words it read and the checks that failed (lines are 0-based).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Outcome {
+    pub log: Vec<(usize, String)>,
+    pub checks: usize,
+    pub failures: usize,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.failures == 0
+    }
+}
+
+// Plays the 68000's side of a script against the SVP (values can refer to any label or constant
+// in `values`). It stops at the first simulation error or wait that times out.
+pub fn play<S>(
+    commands: &[(usize, Command)],
+    megadrive: &mut MegaDrive<S>,
+    values: &HashMap<String, u16>,
+) -> Outcome
+where
+    S: FnMut(&mut Ssp1601) -> Result<u64, SimError>,
+{
+    let mut outcome = Outcome::default();
+
+    for (line, command) in commands.iter() {
+        if let Err(error) = execute(*line, command, megadrive, values, &mut outcome) {
+            outcome.failures += 1;
+            outcome.log.push((*line, error));
+            if !matches!(command, Command::Write(..) | Command::Expect(..)) {
+                break;
+            }
+        }
+    }
+
+    outcome
+}
+
+fn execute<S>(
+    line: usize,
+    command: &Command,
+    megadrive: &mut MegaDrive<S>,
+    values: &HashMap<String, u16>,
+    outcome: &mut Outcome,
+) -> Result<(), String>
+where
+    S: FnMut(&mut Ssp1601) -> Result<u64, SimError>,
+{
+    let value = |word: &String| conditionals::value(word, values).map(|value| value as u16);
+    let words = |words: &[String]| {
+        words
+            .iter()
+            .map(value)
+            .collect::<Result<Vec<u16>, String>>()
+    };
+
+    match command {
+        Command::At(cycle) => megadrive.run_until(*cycle).map_err(|error| error.0)?,
+        Command::Run(cycles) => megadrive.run(*cycles).map_err(|error| error.0)?,
+        Command::Write(location, values) => {
+            for (offset, word) in words(values)?.into_iter().enumerate() {
+                write(megadrive, location.offset(offset), word);
+            }
+        }
+        Command::Wait(mask, expected) => {
+            let mask = value(mask)?;
+            let expected = match expected {
+                Some(expected) => value(expected)?,
+                None => mask,
+            };
+            megadrive
+                .wait_state(mask, expected)
+                .map_err(|error| error.0)?;
+        }
+        Command::Read(location, count) => {
+            let words: Vec<String> = (0..*count)
+                .map(|offset| format!("{:04X}", read(megadrive, location.offset(offset))))
+                .collect();
+            outcome
+                .log
+                .push((line, format!("{} = {}", location, words.join(" "))));
+        }
+        Command::Expect(location, values) => {
+            outcome.checks += 1;
+            let mut failed = false;
+            for (offset, word) in words(values)?.into_iter().enumerate() {
+                let location = location.offset(offset);
+                let found = read(megadrive, location);
+                if found != word {
+                    outcome.log.push((
+                        line,
+                        format!("expected {} = {:04X}, found {:04X}", location, word, found),
+                    ));
+                    failed = true;
+                }
+            }
+            outcome.failures += failed as usize;
+        }
+    }
+    Ok(())
+}
+
+// Locations as the 68000 accesses them (reading the XST state clears the bit telling the SSP1601
+// wrote XST, and writing XST sets the one telling the 68000 did):
+fn read<S>(megadrive: &mut MegaDrive<S>, location: Location) -> u16
+where
+    S: FnMut(&mut Ssp1601) -> Result<u64, SimError>,
+{
+    match location {
+        Location::Register(SspGeneralRegister::Ext0) => megadrive.read_state(),
+        Location::Register(SspGeneralRegister::Ext3) => megadrive.read_xst(),
+        Location::Register(ext) => megadrive.ssp.ext[ext.value() as usize - 8],
+        Location::Dram(address) => megadrive.read_dram(address),
+        _ => 0,
+    }
+}
+
+fn write<S>(megadrive: &mut MegaDrive<S>, location: Location, value: u16)
+where
+    S: FnMut(&mut Ssp1601) -> Result<u64, SimError>,
+{
+    match location {
+        Location::Register(SspGeneralRegister::Ext3) => megadrive.write_xst(value),
+        Location::Register(ext) => megadrive.ssp.ext[ext.value() as usize - 8] = value,
+        Location::Dram(address) => megadrive.write_dram(address, value),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod player_tests {
+    use super::*;
+    use crate::stimulus::script;
+    use ssp16_isa::instructions::Instruction;
+    use ssp16_sim::megadrive;
+    use ssp16_sim::memory::Memory;
+
+    // Waits for a color in XST, fills DRAM 1000-1003 with it and answers 1010 in XST:
+    fn megadrive() -> MegaDrive<impl FnMut(&mut Ssp1601) -> Result<u64, SimError>> {
+        let mut rom = vec![0; 0x400];
+        let code = [
+            "ld a, ext0",
+            "andi 02",
+            "bra z=1, 0400",
+            "ld x, ext3",
+            "ld ext6, 0FFF",
+            "ld ext6, 0818",
+            "ld ext4, -",
+            "ld ext4, x",
+            "ld ext4, x",
+            "ld ext4, x",
+            "ld ext4, x",
+            "ld ext3, 1010",
+            "bra always, 0400",
+        ];
+        for line in code.iter() {
+            rom.extend(line.parse::<Instruction>().unwrap().encode().unwrap());
+        }
+        MegaDrive::new(Ssp1601::new(Memory::new(rom)), megadrive::one_cycle)
+    }
+
+    fn play_script(script: &str) -> (Outcome, u64) {
+        let (commands, errors) = script::parse(script);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let values = [("COLOR".to_string(), 0x0005)].iter().cloned().collect();
+        let mut megadrive = megadrive();
+        megadrive.timeout = 1000;
+        let outcome = play(&commands, &mut megadrive, &values);
+        (outcome, megadrive.cycles)
+    }
+
+    #[test]
+    fn check_protocol() {
+        let (outcome, cycles) = play_script(
+            "at 20\nwrite xst, @COLOR\nwait xst_state, 0001\nexpect xst, 1010\n\
+             read DRAM[1000], 4\nexpect DRAM[1000], 0005, 0005, 0005, 0005\n\
+             expect xst_state, 0000\nwrite DRAM[0000], 1234\nexpect DRAM[0000], 1234\nrun 10\n",
+        );
+
+        assert_eq!(
+            outcome,
+            Outcome {
+                log: vec![(4, "DRAM[1000] = 0005 0005 0005 0005".to_string())],
+                checks: 4,
+                failures: 0,
+            }
+        );
+        assert_eq!(cycles, 43);
+    }
+
+    #[test]
+    fn check_failures() {
+        let (outcome, cycles) = play_script(
+            "expect DRAM[1000], 0005, 0000\nwrite xst, @MISSING\nwait xst_state, 0001\n\
+             expect xst, 1010\n",
+        );
+
+        assert_eq!(
+            outcome.log,
+            vec![
+                (0, "expected DRAM[1000] = 0005, found 0000".to_string()),
+                (1, "Undefined symbol in condition: MISSING".to_string()),
+                (
+                    2,
+                    "XST state still 0000 after 1000 cycles (PC at 0401)".to_string()
+                ),
+            ]
+        );
+        assert_eq!((outcome.checks, outcome.failures), (1, 3));
+        assert!(!outcome.passed());
+        assert_eq!(cycles, 1000);
+    }
+}
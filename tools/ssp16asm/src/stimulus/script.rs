@@ -0,0 +1,199 @@
+use crate::asm::registers::SspGeneralRegister;
+use crate::asm::targets::Target;
+use crate::assembler::data;
+use crate::testing::cases::Location;
+
+// Values are kept as written, they can refer to labels and constants. Cycles and word counts are
+// decimal.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    At(u64),
+    Run(u64),
+    Write(Location, Vec<String>),
+    // Mask and value the XST state bits are waited for (the mask if there's no value)
+    Wait(String, Option<String>),
+    Read(Location, usize),
+    Expect(Location, Vec<String>),
+}
+
+// Commands by line (0-based):
+pub type Script = Vec<(usize, Command)>;
+
+// Commands in stimulus scripts, one per line (`#` starts a comment):
+//
+//     AT cycle
+//     RUN cycles
+//     WRITE location, value[, value...]
+//     WAIT xst_state, mask[, value]
+//     READ location[, words]
+//     EXPECT location, value[, value...]
+//
+// Locations are those the 68000 can access: XST (`ext3`), the XST state (`ext0`), the other
+// external registers but the PM ones (`ext1`, `ext2` and `ext5`) and `DRAM[xxxx]`, which can
+// always be called by their SVP names (i.e.: `xst`). Returns the commands and the errors found, by
+// line (0-based).
+pub fn parse(contents: &str) -> (Script, Vec<(usize, String)>) {
+    let mut commands = Vec::new();
+    let mut errors = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let raw = match data::raw_line(line) {
+            Some(raw) => raw,
+            None => continue,
+        };
+        match data::parse_arguments(raw.arguments)
+            .and_then(|arguments| command(&raw.keyword.to_lowercase(), &arguments))
+        {
+            Ok(command) => commands.push((index, command)),
+            Err(error) => errors.push((index, error)),
+        }
+    }
+
+    (commands, errors)
+}
+
+fn command(keyword: &str, arguments: &[data::Argument]) -> Result<Command, String> {
+    let words = arguments
+        .iter()
+        .map(|argument| match argument {
+            data::Argument::Value(value) => Ok(value.split_whitespace().collect::<String>()),
+            data::Argument::Text(_) => Err("Strings can't be used as values".to_string()),
+        })
+        .collect::<Result<Vec<String>, String>>()?;
+    let decimal = |word: &str| {
+        word.parse::<u64>()
+            .map_err(|_| format!("Invalid decimal number: {}", word))
+    };
+    let usage = |arguments: &str| Err(format!("Expected {} {}", keyword.to_uppercase(), arguments));
+
+    match (keyword, words.as_slice()) {
+        ("at", [cycle]) => Ok(Command::At(decimal(cycle)?)),
+        ("at", _) => usage("cycle"),
+        ("run", [cycles]) => Ok(Command::Run(decimal(cycles)?)),
+        ("run", _) => usage("cycles"),
+        ("write", [location, values @ ..]) | ("expect", [location, values @ ..])
+            if !values.is_empty() =>
+        {
+            let location = location_of(location)?;
+            if values.len() > location.size() {
+                return Err(format!("Too many values for {}", location));
+            }
+            match keyword {
+                "write" => Ok(Command::Write(location, values.to_vec())),
+                _ => Ok(Command::Expect(location, values.to_vec())),
+            }
+        }
+        ("write", _) | ("expect", _) => usage("location, value[, value...]"),
+        ("wait", [location, mask, value @ ..]) if value.len() < 2 => {
+            if location_of(location)? != Location::Register(SspGeneralRegister::Ext0) {
+                return Err("Only the XST state (ext0) can be waited for".to_string());
+            }
+            Ok(Command::Wait(mask.clone(), value.first().cloned()))
+        }
+        ("wait", _) => usage("xst_state, mask[, value]"),
+        ("read", [location, count @ ..]) if count.len() < 2 => {
+            let location = location_of(location)?;
+            let count = match count.first() {
+                Some(count) => decimal(count)? as usize,
+                None => 1,
+            };
+            if count == 0 || count > location.size() {
+                return Err(format!("Invalid number of words to read: {}", count));
+            }
+            Ok(Command::Read(location, count))
+        }
+        ("read", _) => usage("location[, words]"),
+        _ => Err(format!(
+            "Unknown stimulus command: {}",
+            keyword.to_uppercase()
+        )),
+    }
+}
+
+fn location_of(location: &str) -> Result<Location, String> {
+    match Location::new(location, Target::Svp) {
+        Some(location)
+            if matches!(
+                location,
+                Location::Dram(_)
+                    | Location::Register(SspGeneralRegister::Ext0)
+                    | Location::Register(SspGeneralRegister::Ext1)
+                    | Location::Register(SspGeneralRegister::Ext2)
+                    | Location::Register(SspGeneralRegister::Ext3)
+                    | Location::Register(SspGeneralRegister::Ext5)
+            ) =>
+        {
+            Ok(location)
+        }
+        Some(location) => Err(format!("The 68000 can't access {}", location)),
+        None => Err(format!("Invalid stimulus location: {}", location)),
+    }
+}
+
+#[cfg(test)]
+mod script_tests {
+    use super::*;
+
+    #[test]
+    fn check_commands() {
+        let script = "# Asks for color 5\nAT 100\n\twrite xst, 0005\n\tWAIT xst_state, 0001\n\
+                      \twait ext0, 0002, 0000\n\tREAD DRAM[1000], 16\n\tread xst\n\
+                      \texpect DRAM[1000], @COLOR, 5555 # both words\nrun 50\n";
+
+        assert_eq!(
+            parse(script),
+            (
+                vec![
+                    (1, Command::At(100)),
+                    (
+                        2,
+                        Command::Write(
+                            Location::Register(SspGeneralRegister::Ext3),
+                            vec!["0005".to_string()]
+                        )
+                    ),
+                    (3, Command::Wait("0001".to_string(), None)),
+                    (
+                        4,
+                        Command::Wait("0002".to_string(), Some("0000".to_string()))
+                    ),
+                    (5, Command::Read(Location::Dram(0x1000), 16)),
+                    (
+                        6,
+                        Command::Read(Location::Register(SspGeneralRegister::Ext3), 1)
+                    ),
+                    (
+                        7,
+                        Command::Expect(
+                            Location::Dram(0x1000),
+                            vec!["@COLOR".to_string(), "5555".to_string()]
+                        )
+                    ),
+                    (8, Command::Run(50)),
+                ],
+                vec![]
+            )
+        );
+    }
+
+    #[test]
+    fn check_errors() {
+        let script = "AT 10h\nwrite x, 0001\nwrite A[00], 0001\nwait xst, 0001\n\
+                      read DRAM[FFFF], 2\nexpect xst, 0001, 0002\nrun\nwrite pm4, 0\njump 0400\n";
+
+        assert_eq!(
+            parse(script).1,
+            vec![
+                (0, "Invalid decimal number: 10h".to_string()),
+                (1, "The 68000 can't access x".to_string()),
+                (2, "The 68000 can't access A[00]".to_string()),
+                (3, "Only the XST state (ext0) can be waited for".to_string()),
+                (4, "Invalid number of words to read: 2".to_string()),
+                (5, "Too many values for ext3".to_string()),
+                (6, "Expected RUN cycles".to_string()),
+                (7, "Invalid stimulus location: pm4".to_string()),
+                (8, "Unknown stimulus command: JUMP".to_string()),
+            ]
+        );
+    }
+}
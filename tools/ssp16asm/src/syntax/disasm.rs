@@ -0,0 +1,390 @@
+use crate::asm::operators::{SspMnemonicCondition, SspOperator};
+use crate::asm::registers::{SspGeneralRegister, SspModifier, SspPointerRegister};
+
+// Disassemblies write arithmetic with immediates without the `i` suffix (the operand tells them
+// apart), conditions as the flag that has to be set (`z`, `!z`), pointer modifiers after the
+// reference (`(r0)+`), RAM bank addresses as plain 9-bit numbers in loads (`ld A, 1F`, bank B
+// from 100), DSP operands with bank A first, `AL` for `ext7` and labels without `@` (a label in
+// arithmetic is an immediate, `[label]` is a RAM address).
+const ARITHMETIC: [&str; 6] = ["sub", "cmp", "add", "and", "or", "eor"];
+const FLAGS: [&str; 9] = ["l", "z", "ov", "n", "gpi0", "gpi1", "gpi2", "gpi3", "diof"];
+
+// Hexadecimal number along with the digits it was written with (`0x`, `$` and `h` are allowed).
+// `A` is the accumulator:
+fn number(word: &str) -> Option<(u32, usize)> {
+    if SspGeneralRegister::new(word).is_some() {
+        return None;
+    }
+    let digits = word
+        .trim_start_matches("0x")
+        .trim_start_matches('$')
+        .trim_end_matches('h');
+    if digits.is_empty() || digits.len() > 4 {
+        return None;
+    }
+    u32::from_str_radix(digits, 16)
+        .ok()
+        .map(|value| (value, digits.len()))
+}
+
+fn is_accumulator(word: &str) -> bool {
+    word.eq_ignore_ascii_case("a")
+}
+
+fn is_pointer(word: &str) -> bool {
+    SspPointerRegister::new(word).is_some()
+}
+
+fn ram_address(value: u32) -> Option<String> {
+    match value {
+        0x000..=0x0FF => Some(format!("A[{:02X}]", value)),
+        0x100..=0x1FF => Some(format!("B[{:02X}]", value & 0xFF)),
+        _ => None,
+    }
+}
+
+// Disassembly style to native:
+
+pub fn to_native(mnemonic: &str, operands: &[&str]) -> String {
+    let lower = mnemonic.to_lowercase();
+
+    let operands: Vec<String> = match (lower.as_str(), operands) {
+        ("nop", []) => return "ld -, -".to_string(),
+
+        (m, [a, value]) if ARITHMETIC.contains(&m) && is_accumulator(a) => {
+            match (number(value), ram_bank(value)) {
+                (Some((value, digits)), _) if digits <= 2 => {
+                    return format!("{}i {:02X}", mnemonic, value)
+                }
+                (Some((value, _)), _) => return format!("{}i {}, {:04X}", mnemonic, a, value),
+                (None, Some(address)) => vec![a.to_string(), format!("{:X}", address)],
+                (None, None) if value.starts_with('[') && value.ends_with(']') => {
+                    vec![a.to_string(), operand_to_native(&value[1..value.len() - 1])]
+                }
+                (None, None) => match operand_to_native(value) {
+                    label if label.starts_with('@') => {
+                        return format!("{}i {}, {}", mnemonic, a, label)
+                    }
+                    value => vec![a.to_string(), value],
+                },
+            }
+        }
+
+        ("ld", [destination, source]) | ("ldi", [destination, source]) => {
+            match (number(destination), number(source)) {
+                (Some((address, digits)), None) if digits <= 3 && is_accumulator(source) => {
+                    match ram_address(address) {
+                        Some(address) => vec![address, source.to_string()],
+                        None => vec![destination.to_string(), source.to_string()],
+                    }
+                }
+                (None, Some((address, digits))) if digits <= 3 && is_accumulator(destination) => {
+                    match ram_address(address) {
+                        Some(address) => vec![destination.to_string(), address],
+                        None => vec![destination.to_string(), source.to_string()],
+                    }
+                }
+                (None, Some((value, _))) if is_pointer(destination) => {
+                    vec![destination.to_string(), format!("{:02X}", value)]
+                }
+                (None, Some((value, _))) => {
+                    vec![operand_to_native(destination), format!("{:04X}", value)]
+                }
+                _ => vec![operand_to_native(destination), operand_to_native(source)],
+            }
+        }
+
+        ("bra", [condition, address]) | ("call", [condition, address]) => vec![
+            condition_to_native(condition),
+            match number(address) {
+                Some((value, _)) => format!("{:04X}", value),
+                None => operand_to_native(address),
+            },
+        ],
+
+        ("mod", [condition, operation]) => {
+            vec![condition_to_native(condition), operation.to_string()]
+        }
+
+        ("mld", [first, second]) | ("mpya", [first, second]) | ("mpys", [first, second]) => {
+            let (first, second) = (operand_to_native(first), operand_to_native(second));
+            match (SspOperator::new(&first), SspOperator::new(&second)) {
+                (Some(SspOperator::PtrRef(a)), Some(SspOperator::PtrRef(b)))
+                    if a.ram_bank() == 0 && b.ram_bank() == 1 =>
+                {
+                    vec![second, first]
+                }
+                _ => vec![first, second],
+            }
+        }
+
+        (_, operands) => operands.iter().map(|op| operand_to_native(op)).collect(),
+    };
+
+    let mnemonic = if lower == "ldi" { "ld" } else { mnemonic };
+    if operands.is_empty() {
+        mnemonic.to_string()
+    } else {
+        format!("{} {}", mnemonic, operands.join(", "))
+    }
+}
+
+// `A[xx]`/`B[xx]` in arithmetic, as a native 9-bit address:
+fn ram_bank(word: &str) -> Option<u32> {
+    match SspOperator::new(&word.replacen("a[", "A[", 1).replacen("b[", "B[", 1)) {
+        Some(SspOperator::RamBankAddressA(address)) => Some(address as u32),
+        Some(SspOperator::RamBankAddressB(address)) => Some(0x100 | address as u32),
+        _ => None,
+    }
+}
+
+fn condition_to_native(word: &str) -> String {
+    let lower = word.to_lowercase();
+    match lower.strip_prefix('!') {
+        Some(flag) if FLAGS.contains(&flag) => format!("{}=0", flag),
+        _ if FLAGS.contains(&lower.as_str()) => format!("{}=1", lower),
+        _ => word.to_string(),
+    }
+}
+
+fn operand_to_native(word: &str) -> String {
+    // Modifiers after the reference: `(r0)+` is `(r0+)`
+    for modifier in ["+!", "+", "-"].iter() {
+        if let Some(reference) = word.strip_suffix(modifier) {
+            if reference.starts_with('(') && reference.ends_with(')') {
+                let parentheses = reference.len() - reference.trim_end_matches(')').len();
+                let (register, closing) = reference.split_at(reference.len() - parentheses);
+                return format!("{}{}{}", register, modifier, closing);
+            }
+        }
+    }
+
+    if word.eq_ignore_ascii_case("al") {
+        "ext7".to_string()
+    } else if SspOperator::new(word).is_none()
+        && word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.')
+    {
+        format!("@{}", word)
+    } else {
+        word.to_string()
+    }
+}
+
+// Native to disassembly style:
+
+pub fn from_native(mnemonic: &str, operands: &[&str]) -> String {
+    let lower = mnemonic.to_lowercase();
+    let parsed: Vec<Option<SspOperator>> = operands.iter().map(|op| SspOperator::new(op)).collect();
+    let dummy = Some(SspOperator::Reg(SspGeneralRegister::Dummy));
+
+    let operands: Vec<String> = match (lower.as_str(), parsed.as_slice()) {
+        ("ld", [first, second]) if *first == dummy && *second == dummy => return "nop".to_string(),
+
+        (m, [Some(SspOperator::Byte(value))]) if is_immediate_arithmetic(m) => {
+            return format!("{} A, {:02X}", &mnemonic[..m.len() - 1], value);
+        }
+        (m, [Some(SspOperator::Reg(SspGeneralRegister::A)), Some(value)])
+            if is_immediate_arithmetic(m) =>
+        {
+            let value = match value {
+                SspOperator::Word(value) => format!("{:04X}", value),
+                SspOperator::Byte(value) => format!("{:04X}", value),
+                _ => operand_from_native(operands[1]),
+            };
+            return format!("{} A, {}", &mnemonic[..m.len() - 1], value);
+        }
+        (m, [Some(SspOperator::Reg(SspGeneralRegister::A)), Some(address)])
+            if ARITHMETIC.contains(&m)
+                && matches!(address, SspOperator::Word(_) | SspOperator::Byte(_)) =>
+        {
+            let address = match address {
+                SspOperator::Word(value) => *value as u32,
+                SspOperator::Byte(value) => *value as u32,
+                _ => unreachable!(),
+            };
+            vec![
+                "A".to_string(),
+                ram_address(address).unwrap_or_else(|| operands[1].to_string()),
+            ]
+        }
+        (m, [Some(SspOperator::Reg(SspGeneralRegister::A)), Some(SspOperator::LabelRef(_))])
+            if ARITHMETIC.contains(&m) =>
+        {
+            vec![
+                "A".to_string(),
+                format!("[{}]", operand_from_native(operands[1])),
+            ]
+        }
+
+        ("ld", [Some(SspOperator::RamBankAddressA(address)), _])
+        | ("ldi", [Some(SspOperator::RamBankAddressA(address)), _]) => {
+            vec![format!("{:02X}", address), operand_from_native(operands[1])]
+        }
+        ("ld", [Some(SspOperator::RamBankAddressB(address)), _])
+        | ("ldi", [Some(SspOperator::RamBankAddressB(address)), _]) => {
+            vec![
+                format!("{:X}", 0x100 | *address as u32),
+                operand_from_native(operands[1]),
+            ]
+        }
+        ("ld", [_, Some(SspOperator::RamBankAddressA(address))])
+        | ("ldi", [_, Some(SspOperator::RamBankAddressA(address))]) => {
+            vec![operand_from_native(operands[0]), format!("{:02X}", address)]
+        }
+        ("ld", [_, Some(SspOperator::RamBankAddressB(address))])
+        | ("ldi", [_, Some(SspOperator::RamBankAddressB(address))]) => {
+            vec![
+                operand_from_native(operands[0]),
+                format!("{:X}", 0x100 | *address as u32),
+            ]
+        }
+        ("ld", [_, Some(SspOperator::Word(value))])
+        | ("ldi", [_, Some(SspOperator::Word(value))]) => {
+            vec![operand_from_native(operands[0]), format!("{:04X}", value)]
+        }
+
+        ("bra", [Some(SspOperator::Condition(condition)), _])
+        | ("call", [Some(SspOperator::Condition(condition)), _])
+        | ("mod", [Some(SspOperator::Condition(condition)), _]) => vec![
+            condition_from_native(*condition, operands[0]),
+            match parsed[1] {
+                Some(SspOperator::Word(value)) => format!("{:04X}", value),
+                _ => operand_from_native(operands[1]),
+            },
+        ],
+
+        ("mld", [Some(SspOperator::PtrRef(first)), Some(SspOperator::PtrRef(second))])
+        | ("mpya", [Some(SspOperator::PtrRef(first)), Some(SspOperator::PtrRef(second))])
+        | ("mpys", [Some(SspOperator::PtrRef(first)), Some(SspOperator::PtrRef(second))])
+            if first.ram_bank() == 1 && second.ram_bank() == 0 =>
+        {
+            vec![
+                operand_from_native(operands[1]),
+                operand_from_native(operands[0]),
+            ]
+        }
+
+        (_, _) => operands.iter().map(|op| operand_from_native(op)).collect(),
+    };
+
+    let mnemonic = if lower == "ldi" { "ld" } else { mnemonic };
+    if operands.is_empty() {
+        mnemonic.to_string()
+    } else {
+        format!("{} {}", mnemonic, operands.join(", "))
+    }
+}
+
+fn is_immediate_arithmetic(mnemonic: &str) -> bool {
+    mnemonic
+        .strip_suffix('i')
+        .map(|base| ARITHMETIC.contains(&base))
+        == Some(true)
+}
+
+fn condition_from_native(condition: SspMnemonicCondition, word: &str) -> String {
+    let flag = word.split('=').next().unwrap_or(word).to_lowercase();
+    match condition {
+        SspMnemonicCondition::Always => "always".to_string(),
+        _ if condition.flag_value() == 1 => flag,
+        _ => format!("!{}", flag),
+    }
+}
+
+fn operand_from_native(word: &str) -> String {
+    match SspOperator::new(word) {
+        Some(SspOperator::Reg(SspGeneralRegister::Ext7)) => "AL".to_string(),
+        Some(SspOperator::Reg(_)) => word.to_uppercase(),
+        Some(SspOperator::PtrRef(register)) | Some(SspOperator::PtrDoubleRef(register)) => {
+            let lower = word.to_lowercase();
+            match modifier(register) {
+                SspModifier::PostIncrement | SspModifier::PostDecrementModulo => {
+                    let inner = lower.trim_end_matches(')');
+                    let (register, modifier) = inner.split_at(inner.len() - 1);
+                    format!("{}{}{}", register, &lower[inner.len()..], modifier)
+                }
+                _ => lower,
+            }
+        }
+        Some(SspOperator::Ptr(_)) => word.to_lowercase(),
+        // Labels keep their `@` if they could be read as anything else
+        Some(SspOperator::LabelRef(label))
+            if SspOperator::new(label).is_none()
+                && !FLAGS.contains(&label.to_lowercase().as_str())
+                && number(label).is_none() =>
+        {
+            label.to_string()
+        }
+        _ => word.to_string(),
+    }
+}
+
+fn modifier(register: SspPointerRegister) -> SspModifier {
+    match register {
+        SspPointerRegister::R0(modifier)
+        | SspPointerRegister::R1(modifier)
+        | SspPointerRegister::R2(modifier)
+        | SspPointerRegister::R3(modifier)
+        | SspPointerRegister::R4(modifier)
+        | SspPointerRegister::R5(modifier)
+        | SspPointerRegister::R6(modifier)
+        | SspPointerRegister::R7(modifier) => modifier,
+    }
+}
+
+#[cfg(test)]
+mod disasm_tests {
+    use super::*;
+
+    #[test]
+    fn check_to_native() {
+        let cases = vec![
+            ("nop", vec![], "ld -, -"),
+            ("ld", vec!["A", "(r0+!)"], "ld A, (r0+!)"),
+            ("ldi", vec!["X", "12"], "ld X, 0012"),
+            ("ld", vec!["r0", "F"], "ld r0, 0F"),
+            ("ld", vec!["A", "1F"], "ld A, A[1F]"),
+            ("ld", vec!["11F", "A"], "ld B[1F], A"),
+            ("ld", vec!["AL", "((r4))-"], "ld ext7, ((r4-))"),
+            ("add", vec!["A", "1234"], "addi A, 1234"),
+            ("and", vec!["A", "$F"], "andi 0F"),
+            ("cmp", vec!["A", "B[10]"], "cmp A, 110"),
+            ("or", vec!["A", "(r1)"], "or A, (r1)"),
+            ("sub", vec!["A", "table"], "subi A, @table"),
+            ("sub", vec!["A", "[counter]"], "sub A, @counter"),
+            ("bra", vec!["z", "4C3"], "bra z=1, 04C3"),
+            ("call", vec!["!n", "routine"], "call n=0, @routine"),
+            ("mod", vec!["ov", "neg"], "mod ov=1, neg"),
+            ("mpya", vec!["(r0)+", "(r4)+"], "mpya (r4+), (r0+)"),
+            ("mld", vec!["(r5)-", "(r2+!)"], "mld (r5-), (r2+!)"),
+        ];
+
+        for (mnemonic, operands, expected) in cases {
+            assert_eq!(to_native(mnemonic, &operands), expected);
+        }
+    }
+
+    #[test]
+    fn check_from_native() {
+        let cases = vec![
+            ("ld", vec!["-", "-"], "nop"),
+            ("ldi", vec!["x", "012"], "ld X, 0012"),
+            ("ld", vec!["a", "A[1F]"], "ld A, 1F"),
+            ("ld", vec!["B[1F]", "a"], "ld 11F, A"),
+            ("ld", vec!["ext7", "((r4-))"], "ld AL, ((r4))-"),
+            ("addi", vec!["a", "1234"], "add A, 1234"),
+            ("andi", vec!["0F"], "and A, 0F"),
+            ("cmp", vec!["a", "110"], "cmp A, B[10]"),
+            ("subi", vec!["a", "@table"], "sub A, table"),
+            ("sub", vec!["a", "@counter"], "sub A, [counter]"),
+            ("bra", vec!["z=1", "04C3"], "bra z, 04C3"),
+            ("call", vec!["n", "@routine"], "call !n, routine"),
+            ("call", vec!["always", "@beef"], "call always, @beef"),
+            ("mpya", vec!["(r4+)", "(r0+)"], "mpya (r0)+, (r4)+"),
+        ];
+
+        for (mnemonic, operands, expected) in cases {
+            assert_eq!(from_native(mnemonic, &operands), expected);
+        }
+    }
+}
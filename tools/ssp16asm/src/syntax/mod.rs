@@ -0,0 +1,173 @@
+pub mod disasm;
+
+use crate::asm::macros::SspMacro;
+use crate::asm::mnemonics::SspMnemonic;
+use crate::assembler::assertions;
+use crate::assembler::conditionals;
+use crate::assembler::data;
+use crate::assembler::pmset;
+use crate::testing::cases;
+use crate::tokenization::tokens;
+use serde::Deserialize;
+
+// Source syntaxes. `Native` is the one used everywhere else in the assembler, `Disasm` is the
+// style of the notaz/MAME SSP1601 disassemblies (i.e.: Virtua Racing research).
+#[derive(Debug, Copy, Clone, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Syntax {
+    #[default]
+    Native,
+    Disasm,
+}
+
+impl Syntax {
+    pub fn new(name: &str) -> Option<Syntax> {
+        match name.to_lowercase().as_str() {
+            "native" => Some(Syntax::Native),
+            "disasm" => Some(Syntax::Disasm),
+            _ => None,
+        }
+    }
+
+    pub fn to_native(self, contents: &str) -> String {
+        match self {
+            Syntax::Native => contents.to_string(),
+            Syntax::Disasm => convert_lines(contents, disasm::to_native),
+        }
+    }
+
+    // Native source written in this syntax:
+    pub fn rewrite_native(self, contents: &str) -> String {
+        match self {
+            Syntax::Native => contents.to_string(),
+            Syntax::Disasm => convert_lines(contents, disasm::from_native),
+        }
+    }
+}
+
+pub fn convert(contents: &str, from: Syntax, to: Syntax) -> String {
+    to.rewrite_native(&from.to_native(contents))
+}
+
+// Rewrites the instructions of every line (one statement per mnemonic) with `statement`, keeping
+// labels, comments and spacing around them. Directive lines and TEST blocks (always in native
+// syntax) are left as they are.
+fn convert_lines(contents: &str, statement: fn(&str, &[&str]) -> String) -> String {
+    let mut source = contents
+        .lines()
+        .zip(cases::test_block_lines(contents))
+        .map(|(line, is_test)| {
+            if is_test {
+                line.to_string()
+            } else {
+                convert_line(line, statement)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    if contents.ends_with('\n') {
+        source.push('\n');
+    }
+    source
+}
+
+fn convert_line(line: &str, statement: fn(&str, &[&str]) -> String) -> String {
+    if conditionals::directive(line).is_some()
+        || data::data_line(line).is_some()
+        || pmset::pmset_line(line).is_some()
+        || assertions::assertion_line(line).is_some()
+    {
+        return line.to_string();
+    }
+
+    let words = tokens::split_line_with_columns(line);
+    let labels = words
+        .iter()
+        .take_while(|(_, word)| matches!(tokens::Token::new(word), tokens::Token::Label(_)))
+        .count();
+    let code = &words[labels..];
+    let (start, end) = match (code.first(), code.last()) {
+        (Some((start, head)), Some((last_column, last))) if SspMacro::new(head).is_none() => {
+            (*start, last_column + last.len())
+        }
+        _ => return line.to_string(),
+    };
+
+    let mut statements: Vec<Vec<&str>> = Vec::new();
+    for (_, word) in code.iter() {
+        match statements.last_mut() {
+            Some(current) if !is_mnemonic(word) => current.push(word),
+            _ => statements.push(vec![word]),
+        }
+    }
+    let converted = statements
+        .iter()
+        .map(|words| statement(words[0], &words[1..]))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    format!("{}{}{}", &line[..start], converted, &line[end..])
+}
+
+fn is_mnemonic(word: &str) -> bool {
+    SspMnemonic::new(word).is_some() || word.eq_ignore_ascii_case("nop")
+}
+
+#[cfg(test)]
+mod syntax_tests {
+    use super::*;
+    use crate::assembler::assembly;
+    use std::fs;
+    use std::path::Path;
+
+    fn assemble(contents: &str) -> Vec<u8> {
+        let (tokens, _) = tokens::tokenize_with_lines(contents).unwrap();
+        let (symbols, equs, equbs) = assembly::extract_tables(&tokens);
+        let result =
+            assembly::assemble(&tokens, &symbols, &equs, &equbs, false, None, false, 4).unwrap();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        result.binary
+    }
+
+    #[test]
+    fn check_lines_are_kept() {
+        assert_eq!(
+            Syntax::Disasm.to_native(
+                "loop:   mpya (r0)+, (r4)+   # multiply\n\tdw 1234\nifdef X\nbra z, loop\nendif\n"
+            ),
+            "loop:   mpya (r4+), (r0+)   # multiply\n\tdw 1234\nifdef X\nbra z=1, @loop\nendif\n"
+        );
+    }
+
+    #[test]
+    fn check_samples_round_trip() {
+        let samples = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../samples");
+        let mut checked = 0;
+
+        for sample in fs::read_dir(samples).unwrap() {
+            let sample = sample.unwrap().path();
+            if !sample.is_dir() {
+                continue;
+            }
+
+            for file in fs::read_dir(sample).unwrap() {
+                let path = file.unwrap().path();
+                if path.extension().map(|e| e == "svp") != Some(true) {
+                    continue;
+                }
+                let native = fs::read_to_string(&path).unwrap();
+                let disasm = convert(&native, Syntax::Native, Syntax::Disasm);
+
+                assert_ne!(native, disasm);
+                assert_eq!(
+                    assemble(&Syntax::Disasm.to_native(&disasm)),
+                    assemble(&native),
+                    "{:?}",
+                    path
+                );
+                checked += 1;
+            }
+        }
+        assert!(checked > 0);
+    }
+}
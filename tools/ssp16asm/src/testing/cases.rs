@@ -0,0 +1,372 @@
+use crate::asm::operators::SspOperator;
+use crate::asm::registers::SspGeneralRegister;
+use crate::asm::targets::Target;
+use crate::assembler::data;
+use ssp16_sim::memory::{DRAM_SIZE, IRAM_SIZE};
+use std::fmt;
+
+// Something a test can set up or check. Registers and pointer registers take a single word, memory
+// locations one word per value (starting at the given address).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Location {
+    Register(SspGeneralRegister),
+    Pointer(usize),
+    Ram(usize, usize),
+    Dram(usize),
+    Iram(usize),
+}
+
+impl Location {
+    // `x`, `y`, `a`, `st`, `p` (read only), `ext0`-`ext7` (or their target aliases) except the PM
+    // ones, `r0`-`r7`, `A[xx]`, `B[xx]`, `DRAM[xxxx]` and `IRAM[xxx]`:
+    pub fn new(location: &str, target: Target) -> Option<Location> {
+        let memory = |prefix: &str, size: usize| {
+            let address = location
+                .get(..prefix.len())
+                .filter(|start| start.eq_ignore_ascii_case(prefix))
+                .and_then(|_| location[prefix.len()..].strip_suffix(']'))
+                .and_then(|address| data::number(address).ok())?;
+            Some(address as usize).filter(|address| *address < size)
+        };
+        if let Some(address) = memory("DRAM[", DRAM_SIZE) {
+            return Some(Location::Dram(address));
+        }
+        if let Some(address) = memory("IRAM[", IRAM_SIZE) {
+            return Some(Location::Iram(address));
+        }
+
+        match SspOperator::new(target.resolve(location))? {
+            SspOperator::Reg(register) => match register {
+                SspGeneralRegister::Dummy
+                | SspGeneralRegister::Stack
+                | SspGeneralRegister::Pc
+                | SspGeneralRegister::Ext4
+                | SspGeneralRegister::Ext6 => None,
+                register => Some(Location::Register(register)),
+            },
+            SspOperator::Ptr(preg) => Some(Location::Pointer(ssp16_sim::cpu::number(preg))),
+            SspOperator::RamBankAddressA(address) => Some(Location::Ram(0, address as usize)),
+            SspOperator::RamBankAddressB(address) => Some(Location::Ram(1, address as usize)),
+            _ => None,
+        }
+    }
+
+    // Number of consecutive words that can be accessed from the location:
+    pub fn size(&self) -> usize {
+        match self {
+            Location::Register(_) | Location::Pointer(_) => 1,
+            Location::Ram(_, address) => 0x100 - address,
+            Location::Dram(address) => DRAM_SIZE - address,
+            Location::Iram(address) => IRAM_SIZE - address,
+        }
+    }
+
+    // The location `words` words after this one:
+    pub fn offset(&self, words: usize) -> Location {
+        match *self {
+            Location::Ram(bank, address) => Location::Ram(bank, address + words),
+            Location::Dram(address) => Location::Dram(address + words),
+            Location::Iram(address) => Location::Iram(address + words),
+            location => location,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Location::Register(register) => write!(f, "{}", register),
+            Location::Pointer(number) => write!(f, "r{}", number),
+            Location::Ram(0, address) => write!(f, "A[{:02X}]", address),
+            Location::Ram(_, address) => write!(f, "B[{:02X}]", address),
+            Location::Dram(address) => write!(f, "DRAM[{:04X}]", address),
+            Location::Iram(address) => write!(f, "IRAM[{:03X}]", address),
+        }
+    }
+}
+
+// Values are kept as written, they can refer to labels and constants:
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Set(Location, Vec<String>),
+    Call(String),
+    Expect(Location, Vec<String>),
+}
+
+// Lines are 0-based:
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub line: usize,
+    pub steps: Vec<(usize, Step)>,
+}
+
+// Directives in test blocks:
+//
+//     TEST "name"
+//         SET location, value[, value...]
+//         CALL @label
+//         EXPECT location, value[, value...]
+//     ENDTEST
+pub fn test_line(line: &str) -> Option<data::RawLine<'_>> {
+    data::raw_line(line).filter(|raw| {
+        ["test", "endtest", "set", "call", "expect"]
+            .iter()
+            .any(|keyword| raw.keyword.eq_ignore_ascii_case(keyword))
+    })
+}
+
+// Lines (0-based) belonging to TEST blocks, from TEST to ENDTEST (or the end of the source):
+pub fn test_block_lines(contents: &str) -> Vec<bool> {
+    let mut in_test = false;
+
+    contents
+        .lines()
+        .map(|line| {
+            let keyword = data::raw_line(line).map(|raw| raw.keyword.to_lowercase());
+            match keyword.as_deref() {
+                Some("test") => in_test = true,
+                Some("endtest") if in_test => {
+                    in_test = false;
+                    return true;
+                }
+                _ => (),
+            }
+            in_test
+        })
+        .collect()
+}
+
+// Takes TEST blocks out of the source (their lines are left blank, so the rest keeps its line
+// numbers). Register aliases in locations depend on `target`.
+pub fn extract_tests(
+    contents: &str,
+    target: Target,
+) -> (String, Vec<TestCase>, Vec<(usize, String)>) {
+    let mut tests: Vec<TestCase> = Vec::new();
+    let mut errors = Vec::new();
+    let mut output: Vec<&str> = Vec::new();
+    let blocks = test_block_lines(contents);
+    // Line of the TEST block being read, and whether it's valid
+    let mut open: Option<(usize, bool)> = None;
+
+    for (index, line) in contents.lines().enumerate() {
+        if !blocks[index] {
+            match test_line(line) {
+                Some(raw) if raw.keyword.eq_ignore_ascii_case("endtest") => {
+                    errors.push((index, "ENDTEST without TEST".to_string()))
+                }
+                Some(raw) if !raw.keyword.eq_ignore_ascii_case("call") => errors.push((
+                    index,
+                    format!("{} outside of a TEST block", raw.keyword.to_uppercase()),
+                )),
+                _ => (),
+            }
+            output.push(line);
+            continue;
+        }
+        output.push("");
+
+        let raw = match data::raw_line(line) {
+            Some(raw) => raw,
+            None => continue,
+        };
+        let arguments = match data::parse_arguments(raw.arguments) {
+            Ok(arguments) => arguments,
+            Err(error) => {
+                errors.push((index, error));
+                continue;
+            }
+        };
+
+        match raw.keyword.to_lowercase().as_str() {
+            "test" => {
+                if let Some((line, _)) = open {
+                    errors.push((line, "TEST without ENDTEST".to_string()));
+                }
+                match arguments.as_slice() {
+                    [data::Argument::Text(name)] => tests.push(TestCase {
+                        name: name.clone(),
+                        line: index,
+                        steps: Vec::new(),
+                    }),
+                    _ => errors.push((index, "Expected TEST \"name\"".to_string())),
+                }
+                open = Some((
+                    index,
+                    matches!(arguments.as_slice(), [data::Argument::Text(_)]),
+                ));
+            }
+            "endtest" => {
+                if !arguments.is_empty() {
+                    errors.push((index, "ENDTEST takes no arguments".to_string()));
+                }
+                open = None;
+            }
+            keyword => match step(keyword, &arguments, target) {
+                Ok(step) if open.map(|(_, valid)| valid) == Some(true) => {
+                    if let Some(test) = tests.last_mut() {
+                        test.steps.push((index, step));
+                    }
+                }
+                Ok(_) => (),
+                Err(error) => errors.push((index, error)),
+            },
+        }
+    }
+
+    if let Some((line, _)) = open {
+        errors.push((line, "TEST without ENDTEST".to_string()));
+    }
+
+    let mut source = output.join("\n");
+    if contents.ends_with('\n') {
+        source.push('\n');
+    }
+    (source, tests, errors)
+}
+
+fn step(keyword: &str, arguments: &[data::Argument], target: Target) -> Result<Step, String> {
+    let values = |arguments: &[data::Argument]| {
+        arguments
+            .iter()
+            .map(|argument| match argument {
+                data::Argument::Value(value) => Ok(value.split_whitespace().collect()),
+                data::Argument::Text(_) => Err("Strings can't be used as values".to_string()),
+            })
+            .collect::<Result<Vec<String>, String>>()
+    };
+    let location_and_values = |arguments: &[data::Argument]| match arguments {
+        [data::Argument::Value(location), rest @ ..] if !rest.is_empty() => {
+            let location = Location::new(location.trim(), target)
+                .ok_or_else(|| format!("Invalid test location: {}", location.trim()))?;
+            if rest.len() > location.size() {
+                return Err(format!("Too many values for {}", location));
+            }
+            Ok((location, values(rest)?))
+        }
+        _ => Err(format!(
+            "Expected {} location, value[, value...]",
+            keyword.to_uppercase()
+        )),
+    };
+
+    match keyword {
+        "set" => {
+            let (location, values) = location_and_values(arguments)?;
+            if location == Location::Register(SspGeneralRegister::P) {
+                return Err("P can't be set, set X and Y instead".to_string());
+            }
+            Ok(Step::Set(location, values))
+        }
+        "expect" => {
+            let (location, values) = location_and_values(arguments)?;
+            Ok(Step::Expect(location, values))
+        }
+        "call" => match arguments {
+            [data::Argument::Value(label)] => Ok(Step::Call(label.trim().to_string())),
+            _ => Err("Expected CALL @label".to_string()),
+        },
+        _ => Err(format!(
+            "{} can't be used in a TEST block",
+            keyword.to_uppercase()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod cases_tests {
+    use super::*;
+
+    #[test]
+    fn check_locations() {
+        let location = |text| Location::new(text, Target::Svp);
+
+        assert_eq!(
+            location("X"),
+            Some(Location::Register(SspGeneralRegister::X))
+        );
+        assert_eq!(
+            location("al"),
+            Some(Location::Register(SspGeneralRegister::Ext7))
+        );
+        assert_eq!(location("r6"), Some(Location::Pointer(6)));
+        assert_eq!(location("B[1F]"), Some(Location::Ram(1, 0x1F)));
+        assert_eq!(location("dram[8000]"), Some(Location::Dram(0x8000)));
+        assert_eq!(location("IRAM[3FF]"), Some(Location::Iram(0x3FF)));
+        assert_eq!(location("IRAM[400]"), None);
+        assert_eq!(location("pm4"), None);
+        assert_eq!(location("(r0)"), None);
+        assert_eq!(Location::Ram(0, 0xFE).size(), 2);
+        assert_eq!(Location::Dram(0x10).offset(2).to_string(), "DRAM[0012]");
+    }
+
+    #[test]
+    fn check_extract_tests() {
+        let (source, tests, errors) = extract_tests(
+            "clamp: ld a, x # clamp\n\tret\n\nTEST \"clamp, # and all\" # comment\n\tSET x, 0009\n\
+             \n\tset DRAM[0010], 0001, @clamp+1\n\tCALL @clamp\n\texpect a, 0009\nENDTEST\n",
+            Target::Generic,
+        );
+
+        assert_eq!(source, "clamp: ld a, x # clamp\n\tret\n\n\n\n\n\n\n\n\n");
+        assert_eq!(
+            tests,
+            vec![TestCase {
+                name: "clamp, # and all".to_string(),
+                line: 3,
+                steps: vec![
+                    (
+                        4,
+                        Step::Set(
+                            Location::Register(SspGeneralRegister::X),
+                            vec!["0009".to_string()]
+                        )
+                    ),
+                    (
+                        6,
+                        Step::Set(
+                            Location::Dram(0x10),
+                            vec!["0001".to_string(), "@clamp+1".to_string()]
+                        )
+                    ),
+                    (7, Step::Call("@clamp".to_string())),
+                    (
+                        8,
+                        Step::Expect(
+                            Location::Register(SspGeneralRegister::A),
+                            vec!["0009".to_string()]
+                        )
+                    ),
+                ],
+            }]
+        );
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn check_test_errors() {
+        let (_, tests, errors) = extract_tests(
+            "expect a, 0001\nendtest\ncall always, 0400\ntest\n\
+             test \"a\"\n\tset p, 0001\n\tset r0, 01, 02\n\texpect (r0), 0001\n\tld a, x\n\tcall\n",
+            Target::Generic,
+        );
+
+        assert_eq!(tests.len(), 1);
+        assert_eq!(
+            errors,
+            vec![
+                (0, "EXPECT outside of a TEST block".to_string()),
+                (1, "ENDTEST without TEST".to_string()),
+                (3, "Expected TEST \"name\"".to_string()),
+                (3, "TEST without ENDTEST".to_string()),
+                (5, "P can't be set, set X and Y instead".to_string()),
+                (6, "Too many values for r0".to_string()),
+                (7, "Invalid test location: (r0)".to_string()),
+                (8, "LD can't be used in a TEST block".to_string()),
+                (9, "Expected CALL @label".to_string()),
+                (4, "TEST without ENDTEST".to_string()),
+            ]
+        );
+    }
+}
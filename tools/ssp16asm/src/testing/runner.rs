@@ -0,0 +1,204 @@
+use super::cases::{Location, Step, TestCase};
+use crate::asm::registers::SspGeneralRegister;
+use crate::assembler::conditionals;
+use ssp16_sim::coverage::Coverage;
+use ssp16_sim::cpu::Ssp1601;
+use ssp16_sim::memory::Memory;
+use std::collections::HashMap;
+
+pub const DEFAULT_MAX_STEPS: u64 = 1_000_000;
+
+// Lines are 0-based:
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub steps: u64,
+    pub failures: Vec<(usize, String)>,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+// Runs a test on a fresh SSP1601 with `memory` loaded (values can refer to any label or constant
+// in `values`). Every step is run even after a failure, unless a call doesn't return. Instructions
+// run are added to `coverage`.
+pub fn run_test(
+    test: &TestCase,
+    memory: &Memory,
+    values: &HashMap<String, u16>,
+    max_steps: u64,
+    coverage: &mut Coverage,
+) -> TestResult {
+    let mut ssp = Ssp1601::new(memory.clone());
+    let mut failures = Vec::new();
+
+    for (line, step) in test.steps.iter() {
+        let words = match step {
+            Step::Set(_, words) | Step::Expect(_, words) => words.as_slice(),
+            Step::Call(label) => std::slice::from_ref(label),
+        };
+        let words = match words
+            .iter()
+            .map(|word| conditionals::value(word, values).map(|value| value as u16))
+            .collect::<Result<Vec<u16>, String>>()
+        {
+            Ok(words) => words,
+            Err(error) => {
+                failures.push((*line, error));
+                continue;
+            }
+        };
+
+        match step {
+            Step::Set(location, _) => {
+                for (offset, word) in words.iter().enumerate() {
+                    write(&mut ssp, location.offset(offset), *word);
+                }
+            }
+            Step::Expect(location, _) => {
+                for (offset, word) in words.iter().enumerate() {
+                    let location = location.offset(offset);
+                    let found = read(&ssp, location);
+                    if found != *word {
+                        failures.push((
+                            *line,
+                            format!("expected {} = {:04X}, found {:04X}", location, word, found),
+                        ));
+                    }
+                }
+            }
+            Step::Call(_) => {
+                if let Err(error) = ssp.call_with(words[0], max_steps, |ssp| coverage.step(ssp)) {
+                    failures.push((*line, error.0));
+                    break;
+                }
+            }
+        }
+    }
+
+    TestResult {
+        steps: ssp.steps,
+        failures,
+    }
+}
+
+// Locations are accessed directly, without the side effects instructions have:
+fn read(ssp: &Ssp1601, location: Location) -> u16 {
+    match location {
+        Location::Register(register) => match register {
+            SspGeneralRegister::X => ssp.x,
+            SspGeneralRegister::Y => ssp.y,
+            SspGeneralRegister::A => (ssp.a >> 16) as u16,
+            SspGeneralRegister::Ext7 => ssp.a as u16,
+            SspGeneralRegister::St => ssp.st,
+            SspGeneralRegister::P => (ssp.p() >> 16) as u16,
+            ext => ssp.ext[ext.value() as usize - 8],
+        },
+        Location::Pointer(number) => ssp.r[number] as u16,
+        Location::Ram(bank, address) => ssp.ram[bank][address],
+        Location::Dram(address) => ssp.memory.dram[address],
+        Location::Iram(address) => ssp.memory.iram[address],
+    }
+}
+
+fn write(ssp: &mut Ssp1601, location: Location, value: u16) {
+    match location {
+        Location::Register(register) => match register {
+            SspGeneralRegister::X => ssp.x = value,
+            SspGeneralRegister::Y => ssp.y = value,
+            SspGeneralRegister::A => ssp.a = (value as u32) << 16 | ssp.a & 0xFFFF,
+            SspGeneralRegister::Ext7 => ssp.a = ssp.a & 0xFFFF_0000 | value as u32,
+            SspGeneralRegister::St => ssp.st = value,
+            SspGeneralRegister::P => (),
+            ext => ssp.ext[ext.value() as usize - 8] = value,
+        },
+        Location::Pointer(number) => ssp.r[number] = value as u8,
+        Location::Ram(bank, address) => ssp.ram[bank][address] = value,
+        Location::Dram(address) => ssp.memory.dram[address] = value,
+        Location::Iram(address) => ssp.memory.iram[address] = value,
+    }
+}
+
+#[cfg(test)]
+mod runner_tests {
+    use super::*;
+    use crate::asm::targets::Target;
+    use crate::testing::cases;
+    use ssp16_isa::instructions::Instruction;
+
+    // ROM with `code` at 0x400:
+    fn memory(code: &[&str]) -> Memory {
+        let mut rom = vec![0; 0x400];
+        for line in code {
+            rom.extend(line.parse::<Instruction>().unwrap().encode().unwrap());
+        }
+        Memory::new(rom)
+    }
+
+    fn test_case(source: &str) -> TestCase {
+        let (_, mut tests, errors) = cases::extract_tests(source, Target::Svp);
+        assert!(errors.is_empty(), "{:?}", errors);
+        tests.remove(0)
+    }
+
+    #[test]
+    fn check_passing_test() {
+        // double: A = X * 2, stored at DRAM 0 through PM4
+        let memory = memory(&[
+            "ld a, x",
+            "add a, x",
+            "ld ext6, FFFF",
+            "ld ext6, 0817",
+            "ld ext4, -",
+            "ld ext4, a",
+            "ret",
+        ]);
+        let values = [("double".to_string(), 0x400), ("OFFSET".to_string(), 1)]
+            .iter()
+            .cloned()
+            .collect();
+        let test = test_case(
+            "TEST \"double\"\n\tset x, 0021\n\tset al, 1234\n\tcall @double\n\
+             \texpect a, 0042\n\texpect al, 1234\n\texpect DRAM[0000], 0042, 0000\nENDTEST\n",
+        );
+
+        let mut coverage = Coverage::default();
+        let result = run_test(&test, &memory, &values, DEFAULT_MAX_STEPS, &mut coverage);
+        assert_eq!(result.failures, vec![]);
+        assert_eq!(result.steps, 7);
+        assert_eq!(coverage.hits.len(), 7);
+
+        let test = test_case(
+            "TEST \"memory\"\n\tset A[10], 0001, @double+OFFSET\n\tset r0, 10\n\
+             \texpect B[10], 0000\n\texpect A[11], 0401\n\texpect r0, 0010\nENDTEST\n",
+        );
+        assert!(run_test(&test, &memory, &values, DEFAULT_MAX_STEPS, &mut coverage).passed());
+        assert_eq!(coverage.hits[&0x400], 1);
+    }
+
+    #[test]
+    fn check_failing_test() {
+        let memory = memory(&["ld y, 0002", "bra always, 0402"]);
+        let values = [("loop".to_string(), 0x400)].iter().cloned().collect();
+        let test = test_case(
+            "TEST \"fail\"\n\texpect y, 0001\n\texpect A[00], @missing\n\
+             \tcall @loop\n\texpect y, 0002\nENDTEST\n",
+        );
+
+        let result = run_test(&test, &memory, &values, 100, &mut Coverage::default());
+        assert_eq!(
+            result.failures,
+            vec![
+                (1, "expected y = 0001, found 0000".to_string()),
+                (2, "Undefined symbol in condition: missing".to_string()),
+                (
+                    3,
+                    "No return after 100 instructions (PC at 0402)".to_string()
+                ),
+            ]
+        );
+        assert_eq!(result.steps, 100);
+    }
+}
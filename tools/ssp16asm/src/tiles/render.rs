@@ -0,0 +1,308 @@
+use super::image::Image;
+
+// Words in a 4bpp 8x8 tile, two per row with the leftmost pixel in the top nibble:
+pub const TILE_WORDS: usize = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Order {
+    #[default]
+    Rows,
+    Columns,
+}
+
+impl Order {
+    pub fn new(name: &str) -> Option<Order> {
+        match name.to_lowercase().as_str() {
+            "rows" => Some(Order::Rows),
+            "columns" => Some(Order::Columns),
+            _ => None,
+        }
+    }
+}
+
+// How tiles are placed in the image: `columns` tiles wide, consecutive ones going across rows
+// (like a tilemap with increasing tile numbers) or down columns.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Layout {
+    pub tiles: usize,
+    pub columns: usize,
+    pub order: Order,
+}
+
+impl Layout {
+    // Layouts with no tiles or no columns have nothing to render:
+    pub fn check(&self) -> Result<(), String> {
+        match (self.tiles, self.columns) {
+            (0, _) => Err("--tiles must be at least 1".to_string()),
+            (_, 0) => Err("--columns must be at least 1".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.tiles.div_ceil(self.columns)
+    }
+
+    // Image size in pixels:
+    pub fn size(&self) -> (usize, usize) {
+        (self.columns * 8, self.rows() * 8)
+    }
+
+    // Column and row of a tile in the image:
+    pub fn position(&self, tile: usize) -> (usize, usize) {
+        match self.order {
+            Order::Rows => (tile % self.columns, tile / self.columns),
+            Order::Columns => (tile / self.rows(), tile % self.rows()),
+        }
+    }
+
+    // Tile at a column and row of the image (none past the last one):
+    pub fn tile(&self, column: usize, row: usize) -> Option<usize> {
+        let tile = match self.order {
+            Order::Rows => row * self.columns + column,
+            Order::Columns => column * self.rows() + row,
+        };
+        Some(tile).filter(|tile| *tile < self.tiles)
+    }
+}
+
+// The 16 colors tile pixels index (grays from black to white by default):
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Palette(pub [[u8; 3]; 16]);
+
+impl Default for Palette {
+    fn default() -> Palette {
+        let mut colors = [[0; 3]; 16];
+        for (index, color) in colors.iter_mut().enumerate() {
+            *color = [index as u8 * 17; 3];
+        }
+        Palette(colors)
+    }
+}
+
+impl Palette {
+    // One of the four palettes in a CRAM dump (big endian words):
+    pub fn from_cram(cram: &[u8], line: usize) -> Result<Palette, String> {
+        let start = line * 32;
+        if line > 3 || cram.len() < start + 32 {
+            return Err(format!(
+                "No palette line {} in a {} byte CRAM dump",
+                line,
+                cram.len()
+            ));
+        }
+
+        let mut colors = [[0; 3]; 16];
+        for (color, word) in colors.iter_mut().zip(cram[start..start + 32].chunks(2)) {
+            *color = color_of((word[0] as u16) << 8 | word[1] as u16);
+        }
+        Ok(Palette(colors))
+    }
+}
+
+// Mega Drive color (0000BBB0GGG0RRR0) as RGB, each 3-bit level scaled to 0-255:
+pub fn color_of(word: u16) -> [u8; 3] {
+    let level = |shift: u16| (((word >> shift) & 0x0E) >> 1) * 255 / 7;
+    [level(0) as u8, level(4) as u8, level(8) as u8]
+}
+
+// Renders the tiles in `words` from `offset` on, leaving whatever the layout doesn't fill black.
+pub fn render(
+    words: &[u16],
+    offset: usize,
+    layout: &Layout,
+    palette: &Palette,
+) -> Result<Image, String> {
+    layout.check()?;
+    let end = offset + layout.tiles * TILE_WORDS;
+    if end > words.len() {
+        return Err(format!(
+            "{} tiles from {:04X} end at {:04X}, past the end of the dump ({:04X} words)",
+            layout.tiles,
+            offset,
+            end,
+            words.len()
+        ));
+    }
+
+    let (width, height) = layout.size();
+    let mut image = Image::new(width, height);
+    for tile in 0..layout.tiles {
+        let (column, row) = layout.position(tile);
+        for y in 0..8 {
+            for x in 0..8 {
+                let pixel = nibble(words, offset + tile * TILE_WORDS, x, y);
+                image.set_pixel(column * 8 + x, row * 8 + y, palette.0[pixel]);
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+// Checks a rendered image against a reference, telling which tile and word the first different
+// pixel comes from.
+pub fn compare(
+    image: &Image,
+    reference: &Image,
+    offset: usize,
+    layout: &Layout,
+) -> Result<(), String> {
+    if (image.width, image.height) != (reference.width, reference.height) {
+        return Err(format!(
+            "The reference is {}x{} pixels, the rendered image {}x{}",
+            reference.width, reference.height, image.width, image.height
+        ));
+    }
+
+    let mut differences = (0..image.height)
+        .flat_map(|y| (0..image.width).map(move |x| (x, y)))
+        .filter(|(x, y)| image.pixel(*x, *y) != reference.pixel(*x, *y));
+    let (x, y) = match differences.next() {
+        Some(first) => first,
+        None => return Ok(()),
+    };
+
+    let source = match layout.tile(x / 8, y / 8) {
+        Some(tile) => format!(
+            "tile {}, DRAM[{:04X}]",
+            tile,
+            offset + tile * TILE_WORDS + (y % 8) * 2 + (x % 8) / 4
+        ),
+        None => "no tile".to_string(),
+    };
+    let hex = |color: [u8; 3]| format!("#{:02X}{:02X}{:02X}", color[0], color[1], color[2]);
+    Err(format!(
+        "{} pixel(s) differ from the reference, the first at {},{} ({}): {} instead of {}",
+        differences.count() + 1,
+        x,
+        y,
+        source,
+        hex(image.pixel(x, y)),
+        hex(reference.pixel(x, y))
+    ))
+}
+
+fn nibble(words: &[u16], start: usize, x: usize, y: usize) -> usize {
+    let word = words[start + y * 2 + x / 4];
+    ((word >> (12 - (x % 4) * 4)) & 0x0F) as usize
+}
+
+#[cfg(test)]
+mod render_tests {
+    use super::*;
+
+    fn layout(tiles: usize, columns: usize, order: Order) -> Layout {
+        Layout {
+            tiles,
+            columns,
+            order,
+        }
+    }
+
+    #[test]
+    fn check_layouts() {
+        let rows = layout(5, 2, Order::Rows);
+        assert_eq!((rows.rows(), rows.size()), (3, (16, 24)));
+        assert_eq!(
+            (0..5).map(|tile| rows.position(tile)).collect::<Vec<_>>(),
+            vec![(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)]
+        );
+        assert_eq!((rows.tile(1, 1), rows.tile(1, 2)), (Some(3), None));
+
+        let columns = layout(5, 2, Order::Columns);
+        assert_eq!(
+            (0..5)
+                .map(|tile| columns.position(tile))
+                .collect::<Vec<_>>(),
+            vec![(0, 0), (0, 1), (0, 2), (1, 0), (1, 1)]
+        );
+        assert_eq!((columns.tile(1, 1), columns.tile(1, 2)), (Some(4), None));
+        assert_eq!(Order::new("Columns"), Some(Order::Columns));
+
+        assert_eq!(rows.check(), Ok(()));
+        assert_eq!(
+            layout(0, 2, Order::Rows).check(),
+            Err("--tiles must be at least 1".to_string())
+        );
+        assert_eq!(
+            layout(5, 0, Order::Rows).check(),
+            Err("--columns must be at least 1".to_string())
+        );
+    }
+
+    #[test]
+    fn check_palettes() {
+        assert_eq!(color_of(0x0000), [0, 0, 0]);
+        assert_eq!(color_of(0x0EEE), [255, 255, 255]);
+        assert_eq!(color_of(0x0E20), [0, 36, 255]);
+        assert_eq!(Palette::default().0[15], [255; 3]);
+
+        let mut cram = vec![0; 128];
+        cram[34..36].copy_from_slice(&[0x00, 0x0E]);
+        assert_eq!(Palette::from_cram(&cram, 1).unwrap().0[1], [255, 0, 0]);
+        assert_eq!(
+            Palette::from_cram(&cram[..32], 1),
+            Err("No palette line 1 in a 32 byte CRAM dump".to_string())
+        );
+        assert!(Palette::from_cram(&cram, 4).is_err());
+    }
+
+    #[test]
+    fn check_render() {
+        let mut words = vec![0; 0x30];
+        words[0x10] = 0x0123;
+        words[0x1F] = 0x456F;
+        words[0x20] = 0xFFFF;
+        let layout = layout(2, 1, Order::Rows);
+
+        let image = render(&words, 0x10, &layout, &Palette::default()).unwrap();
+        assert_eq!((image.width, image.height), (8, 16));
+        assert_eq!(
+            (0..4).map(|x| image.pixel(x, 0)).collect::<Vec<_>>(),
+            vec![[0x00; 3], [0x11; 3], [0x22; 3], [0x33; 3]]
+        );
+        assert_eq!(image.pixel(7, 7), [0xFF; 3]);
+        assert_eq!(image.pixel(0, 8), [0xFF; 3]);
+        assert_eq!(image.pixel(0, 9), [0x00; 3]);
+
+        assert!(render(
+            &words,
+            0,
+            &Layout { tiles: 0, ..layout },
+            &Palette::default()
+        )
+        .is_err());
+        assert_eq!(
+            render(&words, 0x20, &layout, &Palette::default()),
+            Err("2 tiles from 0020 end at 0040, past the end of the dump (0030 words)".to_string())
+        );
+    }
+
+    #[test]
+    fn check_compare() {
+        let words = vec![0x1111; 0x30];
+        let layout = layout(3, 2, Order::Rows);
+        let image = render(&words, 0, &layout, &Palette::default()).unwrap();
+        let mut reference = image.clone();
+        assert_eq!(compare(&image, &reference, 0x1000, &layout), Ok(()));
+
+        reference.set_pixel(13, 10, [0x12, 0x34, 0x56]);
+        reference.set_pixel(14, 10, [0x12, 0x34, 0x56]);
+        assert_eq!(
+            compare(&image, &reference, 0x1000, &layout),
+            Err("2 pixel(s) differ from the reference, the first at 13,10 \
+                 (no tile): #000000 instead of #123456"
+                .to_string())
+        );
+
+        reference.set_pixel(5, 9, [0; 3]);
+        assert_eq!(
+            compare(&image, &reference, 0x1000, &layout),
+            Err("3 pixel(s) differ from the reference, the first at 5,9 \
+                 (tile 2, DRAM[1023]): #111111 instead of #000000"
+                .to_string())
+        );
+        assert!(compare(&image, &Image::new(16, 8), 0, &layout).is_err());
+    }
+}
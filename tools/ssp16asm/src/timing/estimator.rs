@@ -0,0 +1,435 @@
+use super::model::{CostModel, Region};
+use crate::asm::macros::SspMacro;
+use crate::asm::operators::SspOperator;
+use crate::asm::registers::SspGeneralRegister;
+use crate::flow::cfg;
+use crate::tokenization::tokens;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstructionTiming {
+    pub address: u16,
+    pub line: usize,
+    pub text: String,
+    pub region: Region,
+    pub cycles: u32,
+    // Extra cycles if a conditional branch/call is taken
+    pub taken_penalty: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopTiming {
+    pub line: usize,
+    pub start: u16,
+    pub end: u32,
+    pub count: u64,
+    pub iteration: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelTiming {
+    pub names: Vec<String>,
+    pub address: u16,
+    pub cycles: u64,
+}
+
+// Cycle estimate for every instruction of a program, along with totals per label and per
+// annotated loop. Loops are annotated in a comment of the branch closing them
+// (`bra z=0, @loop # @loop 64`), and code copied somewhere else before running (i.e.: to IRAM)
+// can be given the region it runs from in a comment (`org C000 # @region iram`), which applies
+// until the next `ORG`.
+pub struct Timing {
+    pub instructions: Vec<InstructionTiming>,
+    pub labels: Vec<LabelTiming>,
+    pub loops: Vec<LoopTiming>,
+    pub warnings: Vec<String>,
+}
+
+pub fn estimate(contents: &str, graph: &cfg::ControlFlowGraph, model: &CostModel) -> Timing {
+    let mut warnings = Vec::new();
+    let mut regions: Vec<Option<Region>> = Vec::new();
+    let mut loop_counts: Vec<(usize, u64)> = Vec::new();
+    let mut region = None;
+
+    for (index, line) in contents.lines().enumerate() {
+        let is_org = tokens::split_line_with_columns(line)
+            .iter()
+            .any(|(_, word)| SspMacro::new(word) == Some(SspMacro::Org));
+        if is_org {
+            region = None;
+        }
+
+        for (keyword, argument) in annotations(line) {
+            match (keyword, argument) {
+                ("region", Some(name)) if Region::new(name).is_some() => region = Region::new(name),
+                ("loop", Some(count)) if count.parse::<u64>().map(|c| c > 0) == Ok(true) => {
+                    loop_counts.push((index + 1, count.parse().unwrap()))
+                }
+                ("region", _) | ("loop", _) => warnings.push(format!(
+                    "Line {}: invalid timing annotation `@{} {}`",
+                    index + 1,
+                    keyword,
+                    argument.unwrap_or("")
+                )),
+                _ => (),
+            }
+        }
+        regions.push(region);
+    }
+
+    let instructions: Vec<InstructionTiming> = graph
+        .instructions()
+        .map(|instruction| {
+            let region = regions
+                .get(instruction.line - 1)
+                .copied()
+                .flatten()
+                .unwrap_or_else(|| Region::of(instruction.address));
+            instruction_timing(instruction, region, model)
+        })
+        .collect();
+
+    let mut timing = Timing {
+        instructions,
+        labels: Vec::new(),
+        loops: Vec::new(),
+        warnings,
+    };
+
+    for (line, count) in loop_counts {
+        let branch = graph.instructions().find(|instruction| {
+            instruction.line == line
+                && instruction.jump.map(|to| to <= instruction.address) == Some(true)
+        });
+
+        match branch {
+            Some(branch) => timing.loops.push(LoopTiming {
+                line,
+                start: branch.jump.unwrap(),
+                end: branch.address as u32 + branch.size as u32,
+                count,
+                iteration: 0,
+                total: 0,
+            }),
+            None => timing.warnings.push(format!(
+                "Line {}: `@loop` annotations go in the line of the branch closing the loop",
+                line
+            )),
+        }
+    }
+    // Inner loops first, so outer ones can include them
+    timing
+        .loops
+        .sort_by_key(|l| (l.end - l.start as u32, l.start));
+    for index in 0..timing.loops.len() {
+        let (start, end, count) = {
+            let l = &timing.loops[index];
+            (l.start as u32, l.end, l.count)
+        };
+        let penalty = timing.taken_penalty_at(end);
+        let body = timing.cycles(start, end, Some(index));
+
+        timing.loops[index].iteration = body + penalty as u64;
+        timing.loops[index].total = body * count + penalty as u64 * (count - 1);
+    }
+
+    let mut labelled: Vec<(u16, Vec<String>)> = graph
+        .labels
+        .iter()
+        .map(|(address, names)| (*address, names.clone()))
+        .collect();
+    labelled.sort();
+    for (index, (address, names)) in labelled.iter().enumerate() {
+        if !timing.instructions.iter().any(|i| i.address == *address) {
+            continue;
+        }
+        let end = labelled
+            .get(index + 1)
+            .map(|(next, _)| *next as u32)
+            .unwrap_or(0x10000);
+
+        timing.labels.push(LabelTiming {
+            names: names.clone(),
+            address: *address,
+            cycles: timing.cycles(*address as u32, end, None),
+        });
+    }
+
+    timing.loops.sort_by_key(|l| l.line);
+    timing
+}
+
+impl Timing {
+    // Cycles for the instructions in a range (run once), with annotated loops fully inside it
+    // expanded to their iteration count:
+    fn cycles(&self, start: u32, end: u32, skip: Option<usize>) -> u64 {
+        let inside = |l: &LoopTiming| start <= l.start as u32 && l.end <= end;
+        let mut cycles: u64 = self
+            .instructions
+            .iter()
+            .filter(|i| start <= i.address as u32 && (i.address as u32) < end)
+            .map(|i| i.cycles as u64)
+            .sum();
+
+        for (index, inner) in self.loops.iter().enumerate() {
+            if Some(index) == skip || !inside(inner) {
+                continue;
+            }
+            let nested = self.loops.iter().enumerate().any(|(other_index, other)| {
+                other_index != index
+                    && Some(other_index) != skip
+                    && inside(other)
+                    && other.start <= inner.start
+                    && inner.end <= other.end
+                    && (other.end - other.start as u32) > (inner.end - inner.start as u32)
+            });
+            if !nested {
+                let body = self.cycles(inner.start as u32, inner.end, Some(index));
+                let penalty = self.taken_penalty_at(inner.end) as u64;
+                cycles += (body + penalty) * (inner.count - 1);
+            }
+        }
+
+        cycles
+    }
+
+    fn taken_penalty_at(&self, end: u32) -> u32 {
+        self.instructions
+            .iter()
+            .filter(|i| (i.address as u32) < end)
+            .max_by_key(|i| i.address)
+            .map(|i| i.taken_penalty)
+            .unwrap_or(0)
+    }
+
+    pub fn report(&self, model: &CostModel) -> String {
+        let mut report = String::new();
+        let describe = |cycles: u64| {
+            format!(
+                "{} cycles ({:.2} us, {:.2}% of a 60 Hz frame)",
+                cycles,
+                model.microseconds(cycles),
+                model.frame_percentage(cycles)
+            )
+        };
+
+        writeln!(report, "\tAddress  Cycles  Region  Line  Instruction").unwrap();
+        for i in self.instructions.iter() {
+            let cycles = if i.taken_penalty > 0 {
+                format!("{} (+{})", i.cycles, i.taken_penalty)
+            } else {
+                i.cycles.to_string()
+            };
+            writeln!(
+                report,
+                "\t{:04X}     {:<7} {:<7} {:<5} {}",
+                i.address,
+                cycles,
+                i.region.to_string(),
+                i.line,
+                i.text
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            report,
+            "\nCycles per label (run once, with annotated loops):"
+        )
+        .unwrap();
+        for label in self.labels.iter() {
+            writeln!(
+                report,
+                "\t{}: {}",
+                label.names.join(", "),
+                describe(label.cycles)
+            )
+            .unwrap();
+        }
+
+        if !self.loops.is_empty() {
+            writeln!(report, "\nLoops:").unwrap();
+        }
+        for l in self.loops.iter() {
+            writeln!(
+                report,
+                "\tLine {} ({:04X}-{:04X}): {} x {} cycles = {}",
+                l.line,
+                l.start,
+                l.end - 1,
+                l.count,
+                l.iteration,
+                describe(l.total)
+            )
+            .unwrap();
+        }
+
+        for warning in self.warnings.iter() {
+            writeln!(report, "\n{}", warning).unwrap();
+        }
+
+        report
+    }
+}
+
+fn instruction_timing(
+    instruction: &cfg::Instruction,
+    region: Region,
+    model: &CostModel,
+) -> InstructionTiming {
+    let mut cycles = instruction.size as u32 * model.cycles_per_word(region);
+    for operand in instruction.operands.iter() {
+        cycles += match SspOperator::new(operand) {
+            Some(SspOperator::Reg(SspGeneralRegister::Ext0))
+            | Some(SspOperator::Reg(SspGeneralRegister::Ext1))
+            | Some(SspOperator::Reg(SspGeneralRegister::Ext2))
+            | Some(SspOperator::Reg(SspGeneralRegister::Ext3))
+            | Some(SspOperator::Reg(SspGeneralRegister::Ext4)) => model.pm_access,
+            Some(SspOperator::PtrDoubleRef(_)) | Some(SspOperator::PtrAccumulator) => {
+                model.program_memory_read
+            }
+            _ => 0,
+        };
+    }
+
+    let changes_flow = instruction.jump.is_some()
+        || instruction.call.is_some()
+        || instruction.returns
+        || instruction.computed;
+    let taken_penalty = if changes_flow && instruction.condition.is_some() {
+        model.branch
+    } else {
+        if changes_flow {
+            cycles += model.branch;
+        }
+        0
+    };
+
+    InstructionTiming {
+        address: instruction.address,
+        line: instruction.line,
+        text: instruction.text.clone(),
+        region,
+        cycles,
+        taken_penalty,
+    }
+}
+
+// `@keyword argument` pairs found in the comment of a line:
+fn annotations(line: &str) -> Vec<(&str, Option<&str>)> {
+    let comment = match line.find('#') {
+        Some(start) => line[start..].trim_start_matches('#'),
+        None => return Vec::new(),
+    };
+    let words: Vec<&str> = comment.split_whitespace().collect();
+
+    words
+        .iter()
+        .enumerate()
+        .filter_map(|(index, word)| {
+            word.strip_prefix('@')
+                .map(|keyword| (keyword, words.get(index + 1).copied()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod estimator_tests {
+    use super::*;
+    use crate::assembler::assembly;
+
+    const SOURCE: &str = "\
+org 400
+start: ld x, 0000
+ld a, ext0
+loop: ld ext4, ((r0))
+ld y, a
+inner: subi 1
+bra z=0, @inner # @loop 4
+subi 1
+bra z=0, @loop # @loop 10
+ret
+org C000 # @region iram
+copied: ld a, x
+ret
+";
+
+    fn timing(contents: &str) -> Timing {
+        let (tokens, _) = tokens::tokenize_with_lines(contents).unwrap();
+        let (symbol_table, equ_table, equb_table) = assembly::extract_tables(&tokens);
+        let graph =
+            cfg::ControlFlowGraph::new(contents, &symbol_table, &equ_table, &equb_table).unwrap();
+        estimate(contents, &graph, &CostModel::default())
+    }
+
+    #[test]
+    fn check_instruction_costs() {
+        let timing = timing(SOURCE);
+        let costs: Vec<(u16, u32, u32, Region)> = timing
+            .instructions
+            .iter()
+            .map(|i| (i.address, i.cycles, i.taken_penalty, i.region))
+            .collect();
+
+        assert_eq!(
+            costs,
+            vec![
+                (0x400, 4, 0, Region::Rom),
+                (0x402, 4, 0, Region::Rom),
+                (0x403, 5, 0, Region::Rom),
+                (0x404, 2, 0, Region::Rom),
+                (0x405, 2, 0, Region::Rom),
+                (0x406, 4, 1, Region::Rom),
+                (0x408, 2, 0, Region::Rom),
+                (0x409, 4, 1, Region::Rom),
+                (0x40B, 3, 0, Region::Rom),
+                (0xC000, 1, 0, Region::Iram),
+                (0xC001, 2, 0, Region::Iram),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_loops_and_labels() {
+        let timing = timing(SOURCE);
+        let loops: Vec<(usize, u64, u64)> = timing
+            .loops
+            .iter()
+            .map(|l| (l.line, l.iteration, l.total))
+            .collect();
+        // inner: (2 + 4) + 1 per taken branch => 4 * 6 + 3 = 27
+        // loop: 5 + 2 + 27 + 2 + 4 = 40 (+1 when taken) => 10 * 40 + 9 = 409
+        assert_eq!(loops, vec![(7, 7, 27), (9, 41, 409)]);
+
+        let labels: Vec<(&str, u64)> = timing
+            .labels
+            .iter()
+            .map(|l| (l.names[0].as_str(), l.cycles))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                ("start", 8),
+                ("loop", 7),
+                ("inner", 27 + 2 + 4 + 3),
+                ("copied", 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_invalid_annotations() {
+        let timing = timing("org 400\nstart: ld a, x # @loop 3\nret # @region dram\n");
+
+        assert_eq!(timing.loops, vec![]);
+        assert_eq!(
+            timing.warnings,
+            vec![
+                "Line 3: invalid timing annotation `@region dram`".to_string(),
+                "Line 2: `@loop` annotations go in the line of the branch closing the loop"
+                    .to_string(),
+            ]
+        );
+    }
+}
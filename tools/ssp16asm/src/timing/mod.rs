@@ -0,0 +1,2 @@
+pub mod estimator;
+pub mod model;
@@ -0,0 +1,139 @@
+use crate::assembler::errors;
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Region {
+    Iram,
+    Irom,
+    Rom,
+}
+
+impl Region {
+    // Program memory map (word addresses): IRAM at 0000-03FF, internal ROM at FC00-FFFF and the
+    // cartridge ROM everywhere else:
+    pub fn of(address: u16) -> Region {
+        match address {
+            0x0000..=0x03FF => Region::Iram,
+            0xFC00..=0xFFFF => Region::Irom,
+            _ => Region::Rom,
+        }
+    }
+
+    pub fn new(name: &str) -> Option<Region> {
+        match name.to_lowercase().as_str() {
+            "iram" => Some(Region::Iram),
+            "irom" => Some(Region::Irom),
+            "rom" => Some(Region::Rom),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Region::Iram => write!(f, "IRAM"),
+            Region::Irom => write!(f, "IROM"),
+            Region::Rom => write!(f, "ROM"),
+        }
+    }
+}
+
+// Cycle costs used by the timing estimator. Instructions cost a number of cycles per word
+// depending on the memory they run from, plus penalties for some accesses. The defaults are a
+// rough estimate: measuring with the `SVP_speed_test` sample and writing the results to a
+// model file gives better numbers.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct CostModel {
+    pub clock_mhz: f64,
+    pub iram: u32,
+    pub irom: u32,
+    pub rom: u32,
+    // Accesses to PM0-PM4 (`ext0`-`ext4`), going through the external memory interface
+    pub pm_access: u32,
+    // Program memory reads through `((ri))` and `(a)`
+    pub program_memory_read: u32,
+    // Taken branches, calls, returns and writes to `PC` (refilling the pipeline)
+    pub branch: u32,
+}
+
+impl Default for CostModel {
+    fn default() -> CostModel {
+        CostModel {
+            clock_mhz: 23.01,
+            iram: 1,
+            irom: 1,
+            rom: 2,
+            pm_access: 2,
+            program_memory_read: 1,
+            branch: 1,
+        }
+    }
+}
+
+impl CostModel {
+    pub fn from_file(filename: &str) -> Result<CostModel, Box<dyn Error>> {
+        CostModel::parse(&fs::read_to_string(filename)?)
+    }
+
+    pub fn parse(contents: &str) -> Result<CostModel, Box<dyn Error>> {
+        let model: CostModel = toml::from_str(contents)?;
+
+        if model.clock_mhz <= 0.0 {
+            return Err(Box::new(errors::AssemblyError(format!(
+                "Invalid clock in cost model: {} MHz",
+                model.clock_mhz
+            ))));
+        }
+
+        Ok(model)
+    }
+
+    pub fn cycles_per_word(&self, region: Region) -> u32 {
+        match region {
+            Region::Iram => self.iram,
+            Region::Irom => self.irom,
+            Region::Rom => self.rom,
+        }
+    }
+
+    pub fn microseconds(&self, cycles: u64) -> f64 {
+        cycles as f64 / self.clock_mhz
+    }
+
+    // Share of a 60 Hz (NTSC) frame:
+    pub fn frame_percentage(&self, cycles: u64) -> f64 {
+        self.microseconds(cycles) / (1_000_000.0 / 60.0) * 100.0
+    }
+}
+
+#[cfg(test)]
+mod model_tests {
+    use super::*;
+
+    #[test]
+    fn check_regions() {
+        assert_eq!(Region::of(0x0000), Region::Iram);
+        assert_eq!(Region::of(0x03FF), Region::Iram);
+        assert_eq!(Region::of(0x0400), Region::Rom);
+        assert_eq!(Region::of(0xFC00), Region::Irom);
+        assert_eq!(Region::new("IRAM"), Some(Region::Iram));
+        assert_eq!(Region::new("dram"), None);
+    }
+
+    #[test]
+    fn check_model_file() {
+        let model = CostModel::parse("rom = 3\npm_access = 4\n").unwrap();
+
+        assert_eq!(model.rom, 3);
+        assert_eq!(model.pm_access, 4);
+        assert_eq!(model.iram, CostModel::default().iram);
+        assert!(CostModel::parse("dram = 1\n").is_err());
+        assert!(CostModel::parse("clock_mhz = 0.0\n").is_err());
+    }
+}
@@ -15,6 +15,22 @@ pub enum Token<'a> {
 }
 
 impl<'a> Token<'a> {
+    pub fn new(token: &'a str) -> Token<'a> {
+        match token {
+            _ if mnemonics::SspMnemonic::new(token).is_some() => {
+                Token::Mnemonic(mnemonics::SspMnemonic::new(token).unwrap())
+            }
+            _ if macros::SspMacro::new(token).is_some() => {
+                Token::Macro(macros::SspMacro::new(token).unwrap())
+            }
+            _ if operators::SspOperator::new(token).is_some() => {
+                Token::Operator(operators::SspOperator::new(token).unwrap())
+            }
+            _ if labels::is_label(token) => Token::Label(token.trim_end_matches(":")),
+            _ => Token::Invalid(token),
+        }
+    }
+
     pub fn invalid_token(&self) -> Option<&'a str> {
         match self {
             Token::Invalid(s) => Some(s),
@@ -23,32 +39,26 @@ impl<'a> Token<'a> {
     }
 }
 
-pub fn tokenize(tokens: &str) -> Result<Vec<Token>, Box<dyn Error>> {
-    let (tokens, errors) = split_raw_tokens(tokens).iter().fold(
-        (Vec::new(), Vec::<Token>::new()),
-        |(mut tokens, mut errors), token| {
-            match token {
-                _ if mnemonics::SspMnemonic::new(token).is_some() => {
-                    tokens.push(Token::Mnemonic(mnemonics::SspMnemonic::new(token).unwrap()))
-                }
-                _ if macros::SspMacro::new(token).is_some() => {
-                    tokens.push(Token::Macro(macros::SspMacro::new(token).unwrap()))
+// Along with the tokens, the (1-based) source line each one was found in is returned,
+// so later stages can point back to the original source:
+pub fn tokenize_with_lines(contents: &str) -> Result<(Vec<Token<'_>>, Vec<usize>), Box<dyn Error>> {
+    let (tokens, lines, errors) = split_raw_tokens(contents).iter().fold(
+        (Vec::new(), Vec::new(), Vec::<Token>::new()),
+        |(mut tokens, mut lines, mut errors), (line, token)| {
+            match Token::new(token) {
+                Token::Invalid(invalid) => errors.push(Token::Invalid(invalid)),
+                valid => {
+                    tokens.push(valid);
+                    lines.push(*line);
                 }
-                _ if operators::SspOperator::new(token).is_some() => {
-                    tokens.push(Token::Operator(operators::SspOperator::new(token).unwrap()))
-                }
-                _ if labels::is_label(token) => {
-                    tokens.push(Token::Label(token.trim_end_matches(":")))
-                }
-                _ => errors.push(Token::Invalid(token)),
             }
 
-            (tokens, errors)
+            (tokens, lines, errors)
         },
     );
 
     if errors.is_empty() {
-        Ok(tokens)
+        Ok((tokens, lines))
     } else {
         Err(Box::new(errors::TokenizationError(format!(
             "Invalid tokens found: {:?}",
@@ -60,11 +70,15 @@ pub fn tokenize(tokens: &str) -> Result<Vec<Token>, Box<dyn Error>> {
     }
 }
 
-fn split_raw_tokens(raw_contents: &str) -> Vec<&str> {
+fn split_raw_tokens(raw_contents: &str) -> Vec<(usize, &str)> {
     raw_contents
         .lines()
-        .map(|line| remove_comments(split_line(line)))
-        .flatten()
+        .enumerate()
+        .flat_map(|(index, line)| {
+            remove_comments(split_line(line))
+                .into_iter()
+                .map(move |token| (index + 1, token))
+        })
         .collect()
 }
 
@@ -83,6 +97,14 @@ pub fn split_line(line: &str) -> Vec<&str> {
         })
 }
 
+// Tokens of a single line (without comments) along with the byte column each one starts at:
+pub fn split_line_with_columns(line: &str) -> Vec<(usize, &str)> {
+    remove_comments(split_line(line))
+        .into_iter()
+        .map(|token| (token.as_ptr() as usize - line.as_ptr() as usize, token))
+        .collect()
+}
+
 pub fn remove_comments(tokens: Vec<&str>) -> Vec<&str> {
     let mut reached_comment: bool = false;
 